@@ -0,0 +1,232 @@
+//! Activity manager state, parsed from `dumpsys activity activities` (the
+//! resumed activity and every task's back stack) and `dumpsys activity top`
+//! (the top activity's fragments).
+
+use serde::Serialize;
+
+/// The resumed activity's component, e.g. `com.foo/.MainActivity`.
+///
+/// Android 10 and later report it as `topResumedActivity=` (or per stack as
+/// `ResumedActivity:`); older releases as `mResumedActivity:`.
+pub fn parse_resumed_activity(dump: &str) -> Option<String> {
+    dump.lines()
+        .map(str::trim)
+        .filter(|l| {
+            ["topResumedActivity", "mResumedActivity", "ResumedActivity"]
+                .iter()
+                .any(|prefix| l.starts_with(prefix))
+        })
+        .find_map(|l| l.split_whitespace().find(|w| w.contains('/')))
+        .map(|component| component.trim_end_matches('}').to_string())
+}
+
+/// The package part of a `package/activity` component.
+pub fn component_package(component: &str) -> &str {
+    component.split('/').next().unwrap_or(component)
+}
+
+/// Whether two components name the same activity, expanding the
+/// `com.foo/.Main` shorthand.
+pub fn same_component(a: &str, b: &str) -> bool {
+    fn expand(component: &str) -> String {
+        match component.split_once('/') {
+            Some((package, class)) if class.starts_with('.') => {
+                format!("{}/{}{}", package, package, class)
+            }
+            _ => component.to_string(),
+        }
+    }
+    expand(a) == expand(b)
+}
+
+/// One task and its back stack.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Task {
+    pub id: u32,
+    /// Activity components, top of the stack first.
+    pub activities: Vec<String>,
+}
+
+/// What's on screen and what's behind it, for `dab top`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActivityStack {
+    pub device: String,
+    /// The resumed activity; `None` with the screen off or the keyguard up.
+    pub resumed: Option<String>,
+    pub package: Option<String>,
+    pub task_id: Option<u32>,
+    /// The resumed activity's added fragments, outermost first. Empty when
+    /// it has none or doesn't use the fragment library.
+    pub fragments: Vec<String>,
+    /// Every task, most recent first.
+    pub tasks: Vec<Task>,
+}
+
+/// Every task's back stack, from the `Hist #N:` (and, before Android 10,
+/// `Run #N:`) lines, each naming an `ActivityRecord{hash u0 component tID}`.
+pub fn parse_tasks(dump: &str) -> Vec<Task> {
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut seen: Vec<&str> = Vec::new();
+    for line in dump.lines().map(str::trim) {
+        let line = line.trim_start_matches("* ");
+        if !(line.starts_with("Hist ") || line.starts_with("Run ")) {
+            continue;
+        }
+        let Some(record) = line
+            .split_once("ActivityRecord{")
+            .map(|(_, r)| r.trim_end_matches('}'))
+        else {
+            continue;
+        };
+        let words: Vec<&str> = record.split_whitespace().collect();
+        let (Some(hash), Some(component)) = (words.first(), words.iter().find(|w| w.contains('/')))
+        else {
+            continue;
+        };
+        let Some(id) = words
+            .iter()
+            .find_map(|w| w.strip_prefix('t').and_then(|id| id.parse().ok()))
+        else {
+            continue;
+        };
+        if seen.contains(hash) {
+            continue;
+        }
+        seen.push(hash);
+        match tasks.iter_mut().find(|t| t.id == id) {
+            Some(task) => task.activities.push(component.to_string()),
+            None => tasks.push(Task {
+                id,
+                activities: vec![component.to_string()],
+            }),
+        }
+    }
+    tasks
+}
+
+/// The added fragments of `component`'s block in `dumpsys activity top`,
+/// including those of nested fragment managers, without duplicates.
+pub fn parse_fragments(dump: &str, component: &str) -> Vec<String> {
+    let mut fragments: Vec<String> = Vec::new();
+    let mut in_activity = false;
+    // Indent of the `Added Fragments:` header being read
+    let mut added: Option<usize> = None;
+    for line in dump.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        if let Some(rest) = trimmed.strip_prefix("ACTIVITY ") {
+            let name = rest.split_whitespace().next().unwrap_or_default();
+            in_activity = same_component(name, component);
+            added = None;
+            continue;
+        }
+        if !in_activity {
+            continue;
+        }
+        if added.is_some_and(|header| indent <= header) {
+            added = None;
+        }
+        if trimmed == "Added Fragments:" {
+            added = Some(indent);
+        } else if added.is_some() {
+            // `#0: HomeFragment{7a2b3c (1f2e…) id=0x7f0a00b2}`
+            let name = trimmed
+                .split_once(": ")
+                .map_or(trimmed, |(_, f)| f)
+                .split('{')
+                .next()
+                .unwrap_or_default();
+            if !name.is_empty() && !fragments.iter().any(|f| f == name) {
+                fragments.push(name.to_string());
+            }
+        }
+    }
+    fragments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_resumed_activity_across_releases() {
+        let android_14 = "\
+Display #0 (activities from top to bottom):
+  * Task{5b2a1f2 #31 type=standard A=10123:com.example.app}
+    topResumedActivity=ActivityRecord{15f1f3c u0 com.example.app/.MainActivity t31}
+";
+        let android_9 = "\
+  Stack #1:
+    mResumedActivity: ActivityRecord{a1b2c3 u0 com.android.chrome/com.google.android.apps.chrome.Main t12}
+";
+        assert_eq!(
+            parse_resumed_activity(android_14).as_deref(),
+            Some("com.example.app/.MainActivity")
+        );
+        let chrome = parse_resumed_activity(android_9).unwrap();
+        assert_eq!(component_package(&chrome), "com.android.chrome");
+        assert_eq!(parse_resumed_activity("  mFocusedApp=null"), None);
+    }
+
+    #[test]
+    fn parses_back_stacks_across_releases() {
+        let android_14 = "\
+Display #0 (activities from top to bottom):
+  * Task{5b2a1f2 #31 type=standard A=10123:com.example.app U=0 visible=true sz=2}
+    topResumedActivity=ActivityRecord{15f1f3c u0 com.example.app/.DetailActivity t31}
+    * Hist  #1: ActivityRecord{15f1f3c u0 com.example.app/.DetailActivity t31}
+    * Hist  #0: ActivityRecord{a1b2c3d u0 com.example.app/.MainActivity t31}
+  * Task{9f8e7d6 #1 type=home A=10045:com.google.android.apps.nexuslauncher U=0 sz=1}
+    * Hist  #0: ActivityRecord{4d5e6f7 u0 com.google.android.apps.nexuslauncher/.NexusLauncherActivity t1}
+";
+        let tasks = parse_tasks(android_14);
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(
+            tasks[0],
+            Task {
+                id: 31,
+                activities: vec![
+                    "com.example.app/.DetailActivity".into(),
+                    "com.example.app/.MainActivity".into()
+                ]
+            }
+        );
+
+        let android_9 = "\
+  Stack #1: type=standard mode=fullscreen
+    Task id #12
+      * Hist #0: ActivityRecord{def u0 com.example.app/.MainActivity t12}
+    Running activities (most recent first):
+      TaskRecord{abc #12 A=com.example.app U=0 StackId=1 sz=1}
+        Run #0: ActivityRecord{def u0 com.example.app/.MainActivity t12}
+";
+        assert_eq!(parse_tasks(android_9)[0].activities.len(), 1);
+    }
+
+    #[test]
+    fn parses_the_top_activitys_fragments() {
+        let top = "\
+TASK com.example.app id=31 userId=0
+  ACTIVITY com.example.app/.MainActivity 15f1f3c pid=4242
+    Local FragmentActivity 9a8b7c State:
+    Active Fragments:
+      NavHostFragment{3c4d (e1c4) id=0x7f0a00b2}
+    Added Fragments:
+      #0: NavHostFragment{3c4d (e1c4) id=0x7f0a00b2}
+    Child FragmentManager{5e6f in NavHostFragment{3c4d}}:
+      Added Fragments:
+        #0: HomeFragment{7a8b (f2d5) id=0x7f0a00b2}
+    FragmentManager misc state:
+      mHost=androidx.fragment.app.FragmentActivity$HostCallbacks@1a2b
+TASK com.other.app id=40 userId=0
+  ACTIVITY com.other.app/.Main 1a2b3c pid=5151
+    Added Fragments:
+      #0: OtherFragment{1a (2b) id=0x1}
+";
+        assert_eq!(
+            parse_fragments(top, "com.example.app/com.example.app.MainActivity"),
+            ["NavHostFragment", "HomeFragment"]
+        );
+        assert!(parse_fragments(top, "com.example.app/.Missing").is_empty());
+    }
+}
@@ -1,73 +1,420 @@
-//! `AdbClient` — dab's presentation layer over the shared `androkit` toolkit.
+//! `AdbClient` — dab's device API over the shared `androkit` toolkit.
 //!
-//! The ADB/APK plumbing that used to live here now lives in `androkit`
-//! (`androkit::adb`, `androkit::apk`), so dab and `adev` share one
-//! implementation. This file keeps dab's public method surface, colored output,
-//! and exact JSON shapes intact — it just delegates the real work to androkit
-//! and reshapes the results.
-
-use super::app::App;
-use androkit::adb::Adb;
+//! The ADB/APK plumbing lives in `androkit` (`androkit::adb`, `androkit::apk`),
+//! so dab and `adev` share one implementation. This file adds dab's own
+//! queries and reshapes androkit's results into the typed [`model`] values.
+//! Nothing here prints: the `dab` binary renders every result as text or JSON.
+//!
+//! Every adb call goes through an [`AdbBackend`]: the real `adb` by default,
+//! or a [`Recorder`](crate::backend::Recorder) /
+//! [`Replayer`](crate::backend::Replayer) pair for testing without devices.
+//!
+//! [`model`]: crate::model
+
+use super::activity::{
+    component_package, parse_fragments, parse_resumed_activity, parse_tasks, same_component,
+    ActivityStack,
+};
+use super::backend::{AdbBackend, Lines, RealAdb};
+use super::crash::{CrashCollector, Incident};
+use super::deeplink::{self, DeepLink, DeepLinks, Verification};
+use super::device::{parse_battery_level, parse_device_list, Device};
+use super::error::{DabError, ErrorCode};
+use super::gesture::{self, Gesture, GestureRecorder, Macro, Screen, Step, Touchscreen};
+use super::input::{self, TextPart};
+use super::instrumentation::{
+    parse_instrumentation_list, Instrumentation, StatusParser, TestCase, TestRun,
+};
+use super::intent::{self, BroadcastResult, Intent, Target};
+use super::logcat::{parse_pids, started_pid, LogEntry, LogFilter};
+use super::model::{
+    ApkInfo, AppInfo, CrashReport, DeviceHealth, DeviceInfo, DeviceList, InstalledApps,
+    NetworkInfo, SavedIncident,
+};
+use super::perf::{parse_gfxinfo, parse_meminfo, parse_proc_stat, FrameStats, MemInfo, ProcStat};
+use super::startup::{parse_start_timing, StartTiming};
+use super::ui::{self, Hierarchy, Match, Query};
 use androkit::apk;
 use anyhow::{anyhow, Result};
-use colored::*;
-use serde_json::{json, Value};
+use serde_json::json;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-pub struct AdbClient {
-    adb: Adb,
+/// Log buffers that carry Java crashes, tombstone summaries and ANR reports.
+const CRASH_BUFFERS: &str = "main,system,crash";
+/// How long a live log must stay quiet before the block in progress counts as
+/// complete; a report is logged in one burst, so this only delays the last one.
+const CRASH_FLUSH_AFTER: Duration = Duration::from_millis(500);
+/// Where `uiautomator dump` writes; `/data/local/tmp` is writable by the shell user.
+const UI_DUMP_PATH: &str = "/data/local/tmp/dab_ui.xml";
+
+/// What [`AdbClient::stream_logcat`] reports while following an app.
+#[derive(Debug)]
+pub enum LogEvent<'a> {
+    /// The app isn't running yet; the stream starts once it does.
+    Waiting,
+    /// The app is running as this process, newly seen.
+    Following(u32),
+    /// An entry from one of the app's processes that passed the filter.
+    Entry(&'a LogEntry),
+}
+
+/// Clones share one backend, so a clone can be handed to another thread.
+pub struct AdbClient<B = RealAdb> {
+    backend: Arc<B>,
+}
+
+impl<B> Clone for AdbClient<B> {
+    fn clone(&self) -> Self {
+        Self {
+            backend: Arc::clone(&self.backend),
+        }
+    }
 }
 
 impl AdbClient {
+    /// A client for the `adb` on `PATH`.
     pub fn new() -> Result<Self> {
-        Ok(Self { adb: Adb::new()? })
+        Ok(Self::with_backend(RealAdb::new()?))
     }
+}
 
-    // ── devices ──────────────────────────────────────────────────────────
+impl<B: AdbBackend> AdbClient<B> {
+    pub fn with_backend(backend: B) -> Self {
+        Self {
+            backend: Arc::new(backend),
+        }
+    }
 
-    pub fn get_device_list(&self) -> Result<Vec<String>> {
-        self.adb.devices()
+    pub fn backend(&self) -> &B {
+        &self.backend
     }
 
-    pub fn get_device_list_json(&self) -> Value {
-        match self.adb.devices() {
-            Ok(devices) => json!({ "devices": devices }),
-            Err(e) => json!({ "error": e.to_string(), "devices": [] }),
+    // ── devices ──────────────────────────────────────────────────────────
+
+    /// Every attached device with its state and `adb devices -l` attributes.
+    pub fn get_devices(&self) -> Result<Vec<Device>> {
+        let output = self.backend.run(&["devices", "-l"])?;
+        let devices = parse_device_list(&output.stdout);
+        if devices.is_empty() {
+            return Err(anyhow!(
+                "No connected devices found. Connect an Android device via USB (with USB debugging) or start an emulator."
+            ));
         }
+        Ok(devices)
     }
 
-    // ── apps ─────────────────────────────────────────────────────────────
+    /// [`get_devices`](Self::get_devices) plus Android version and battery
+    /// level for every ready device, queried in parallel.
+    pub fn get_devices_detailed(&self) -> Result<Vec<Device>> {
+        let mut devices = self.get_devices()?;
+        std::thread::scope(|scope| {
+            for device in devices.iter_mut().filter(|d| d.is_ready()) {
+                scope.spawn(move || {
+                    let serial = device.serial.as_str();
+                    if let Ok(o) = self.backend.run(&[
+                        "-s",
+                        serial,
+                        "shell",
+                        "getprop",
+                        "ro.build.version.release",
+                    ]) {
+                        let version = o.stdout.trim().to_string();
+                        device.android_version = Some(version).filter(|v| !v.is_empty());
+                    }
+                    if let Ok(o) = self
+                        .backend
+                        .run(&["-s", serial, "shell", "dumpsys", "battery"])
+                    {
+                        device.battery_level = parse_battery_level(&o.stdout);
+                    }
+                });
+            }
+        });
+        Ok(devices)
+    }
 
-    pub fn get_installed_apps(&self, device: &str) -> Result<Vec<App>> {
-        Ok(self
-            .adb
-            .list_packages(device)?
-            .into_iter()
-            .map(|p| App::new(&p))
-            .collect())
+    pub fn get_device_list(&self) -> Result<DeviceList> {
+        Ok(DeviceList {
+            devices: self.get_devices_detailed()?,
+        })
     }
 
-    pub fn get_installed_apps_json(&self, device: &str) -> Value {
-        match self.adb.list_packages(device) {
-            Ok(apps) => json!({ "device": device, "apps": apps }),
-            Err(e) => json!({ "error": e.to_string(), "apps": [] }),
+    /// Make sure `device` is attached and usable, turning adb's `get-state`
+    /// complaints (unknown serial, unauthorized, offline) into coded errors.
+    pub fn check_device(&self, device: &str) -> Result<()> {
+        let output = self.backend.run(&["-s", device, "get-state"])?;
+        let state = output.stdout.trim().to_string();
+        if state == "device" {
+            return Ok(());
         }
+        let stderr = output.stderr.to_lowercase();
+        let err = if stderr.contains("unauthorized") || state == "unauthorized" {
+            DabError::new(
+                ErrorCode::DeviceUnauthorized,
+                format!("Device {} is unauthorized", device),
+            )
+        } else if stderr.contains("offline") || state == "offline" {
+            DabError::new(
+                ErrorCode::DeviceOffline,
+                format!("Device {} is offline", device),
+            )
+        } else {
+            DabError::new(
+                ErrorCode::DeviceNotFound,
+                format!("Device {} not found", device),
+            )
+        };
+        Err(err.with_detail("device", device).into())
+    }
+
+    // ── apps ─────────────────────────────────────────────────────────────
+
+    pub fn get_installed_apps(&self, device: &str) -> Result<InstalledApps> {
+        Ok(InstalledApps {
+            device: device.to_string(),
+            apps: self.backend.list_packages(device)?,
+        })
     }
 
     pub fn open_app(&self, device: &str, package_name: &str) -> Result<()> {
-        self.adb.launch_package(device, package_name)
+        self.backend.launch_package(device, package_name)
     }
 
     pub fn uninstall_app(&self, device: &str, package_name: &str) -> Result<()> {
-        self.adb.uninstall(device, package_name)
+        self.backend.uninstall(device, package_name)
     }
 
     pub fn clear_app_data(&self, device: &str, package_name: &str) -> Result<()> {
-        self.adb.clear_data(device, package_name)
+        self.backend.clear_data(device, package_name)
     }
 
     pub fn force_kill_app(&self, device: &str, package_name: &str) -> Result<()> {
-        self.adb.stop_app(device, package_name)
+        self.backend.stop_app(device, package_name)
+    }
+
+    /// Packages that are installed but disabled (`pm list packages -d`).
+    pub fn get_disabled_packages(&self, device: &str) -> Result<Vec<String>> {
+        let output = self
+            .backend
+            .run(&["-s", device, "shell", "pm", "list", "packages", "-d"])?;
+        Ok(output
+            .stdout
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("package:"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Disable an app for the default user, as Settings › Apps › Disable does.
+    pub fn disable_app(&self, device: &str, package_name: &str) -> Result<()> {
+        let output = self.backend.run(&[
+            "-s",
+            device,
+            "shell",
+            "pm",
+            "disable-user",
+            "--user",
+            "0",
+            package_name,
+        ])?;
+        if output.stdout.contains("new state: disabled") {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Failed to disable {}: {}",
+                package_name,
+                format!("{}{}", output.stdout, output.stderr).trim()
+            ))
+        }
+    }
+
+    /// The launcher activity component, e.g. `com.foo/.MainActivity`.
+    pub fn launcher_activity(&self, device: &str, package_name: &str) -> Result<String> {
+        let output = self.backend.run(&[
+            "-s",
+            device,
+            "shell",
+            "cmd",
+            "package",
+            "resolve-activity",
+            "--brief",
+            package_name,
+        ])?;
+        output
+            .stdout
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|l| l.starts_with(package_name) && l.contains('/'))
+            .map(str::to_string)
+            .ok_or_else(|| {
+                DabError::new(
+                    ErrorCode::PackageNotFound,
+                    format!("{} has no launcher activity", package_name),
+                )
+                .into()
+            })
+    }
+
+    /// Start `component` with `am start -W` and return the reported timing.
+    pub fn start_activity_timed(&self, device: &str, component: &str) -> Result<StartTiming> {
+        let output = self
+            .backend
+            .run(&["-s", device, "shell", "am", "start", "-W", "-n", component])?;
+        let stdout = output.stdout;
+        parse_start_timing(&stdout).ok_or_else(|| {
+            anyhow!(
+                "Failed to start {}: {}",
+                component,
+                stdout
+                    .lines()
+                    .find(|l| l.starts_with("Error"))
+                    .unwrap_or("no TotalTime reported")
+            )
+        })
+    }
+
+    /// Send a key event such as `KEYCODE_HOME`.
+    pub fn press_key(&self, device: &str, keycode: &str) -> Result<()> {
+        self.input(device, &["keyevent", keycode])
+    }
+
+    /// Hold a key, as a long press of `KEYCODE_POWER` brings up the power menu.
+    pub fn long_press_key(&self, device: &str, keycode: &str) -> Result<()> {
+        self.input(device, &["keyevent", "--longpress", keycode])
+    }
+
+    /// Tap the screen at `(x, y)` in pixels.
+    pub fn tap(&self, device: &str, x: u32, y: u32) -> Result<()> {
+        self.input(device, &["tap", &x.to_string(), &y.to_string()])
+    }
+
+    /// Press and hold at `(x, y)` for `duration`.
+    pub fn long_press(&self, device: &str, x: u32, y: u32, duration: Duration) -> Result<()> {
+        self.swipe(device, (x, y), (x, y), duration)
+    }
+
+    /// Drag from `from` to `to` over `duration`.
+    pub fn swipe(
+        &self,
+        device: &str,
+        from: (u32, u32),
+        to: (u32, u32),
+        duration: Duration,
+    ) -> Result<()> {
+        self.input(
+            device,
+            &[
+                "swipe",
+                &from.0.to_string(),
+                &from.1.to_string(),
+                &to.0.to_string(),
+                &to.1.to_string(),
+                &duration.as_millis().to_string(),
+            ],
+        )
+    }
+
+    /// Type `text` into the focused field. Spaces, shell characters, `%s`,
+    /// newlines and tabs are sent so they arrive as written.
+    pub fn type_text(&self, device: &str, text: &str) -> Result<()> {
+        for part in input::text_parts(text) {
+            match part {
+                TextPart::Text(text) => {
+                    self.input(device, &["text", &intent::shell_quote(&text)])?
+                }
+                TextPart::Key(keycode) => self.press_key(device, keycode)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `input` with `args`, failing with `INPUT_FAILED` when it prints
+    /// an error.
+    fn input(&self, device: &str, args: &[&str]) -> Result<()> {
+        let mut command = vec!["-s", device, "shell", "input"];
+        command.extend_from_slice(args);
+        let output = self.backend.run(&command)?;
+        let printed = format!("{}{}", output.stdout, output.stderr);
+        match input::input_error(&printed) {
+            Some(error) => Err(DabError::new(
+                ErrorCode::InputFailed,
+                format!("`input {}` failed: {}", args[0], error),
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// The resumed activity's component, e.g. `com.foo/.MainActivity`, or
+    /// `None` when nothing is resumed (screen off, keyguard up).
+    pub fn get_foreground_activity(&self, device: &str) -> Result<Option<String>> {
+        let output =
+            self.backend
+                .run(&["-s", device, "shell", "dumpsys", "activity", "activities"])?;
+        Ok(parse_resumed_activity(&output.stdout))
+    }
+
+    /// The resumed activity with its task and fragments, and every task's
+    /// back stack.
+    pub fn get_activity_stack(&self, device: &str) -> Result<ActivityStack> {
+        let output =
+            self.backend
+                .run(&["-s", device, "shell", "dumpsys", "activity", "activities"])?;
+        let resumed = parse_resumed_activity(&output.stdout);
+        let tasks = parse_tasks(&output.stdout);
+        let fragments = match &resumed {
+            Some(component) => {
+                let top = self
+                    .backend
+                    .run(&["-s", device, "shell", "dumpsys", "activity", "top"])?;
+                parse_fragments(&top.stdout, component)
+            }
+            None => Vec::new(),
+        };
+        let task_id = resumed.as_deref().and_then(|component| {
+            tasks
+                .iter()
+                .find(|t| t.activities.iter().any(|a| same_component(a, component)))
+                .map(|t| t.id)
+        });
+        Ok(ActivityStack {
+            device: device.to_string(),
+            package: resumed.as_deref().map(|c| component_package(c).to_string()),
+            resumed,
+            task_id,
+            fragments,
+            tasks,
+        })
+    }
+
+    /// Wait up to `timeout` for one of `package_name`'s activities to resume,
+    /// and return it; `None` if it didn't come up in time.
+    pub fn wait_for_foreground(
+        &self,
+        device: &str,
+        package_name: &str,
+        timeout: Duration,
+    ) -> Result<Option<String>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let activity = self.get_foreground_activity(device)?;
+            if activity
+                .as_deref()
+                .is_some_and(|a| component_package(a) == package_name)
+            {
+                return Ok(activity);
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            std::thread::sleep(Duration::from_millis(250));
+        }
     }
 
     pub fn download_apk(
@@ -76,7 +423,7 @@ impl AdbClient {
         package_name: &str,
         output_path: Option<PathBuf>,
     ) -> Result<PathBuf> {
-        self.adb.download_apk(device, package_name, output_path)
+        self.backend.download_apk(device, package_name, output_path)
     }
 
     // ── app info (pm dump — dab-specific introspection) ──────────────────
@@ -85,227 +432,609 @@ impl AdbClient {
         &self,
         device: &str,
         package_name: &str,
-        show_permissions: bool,
-    ) -> Result<()> {
+        include_permissions: bool,
+    ) -> Result<AppInfo> {
         let output = self
-            .adb
+            .backend
             .run(&["-s", device, "shell", "pm", "dump", package_name])?;
-        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stdout = output.stdout;
         let (version_code, version_name) = parse_versions(&stdout);
-        println!("{}", "\nApp Info".bold().underline().yellow());
-        println!("{}: {}", "Package Name".cyan(), package_name.green());
-        println!("{}: {}", "Version Code".cyan(), version_code.green());
-        println!("{}: {}", "Version Name".cyan(), version_name.green());
-        if show_permissions {
-            let granted = parse_granted_permissions(&stdout);
-            println!("{}:", "Granted Permissions".cyan());
-            if granted.is_empty() {
-                println!("  {}", "None".red());
-            } else {
-                for perm in granted {
-                    println!("  {}", perm.blue());
-                }
-            }
-        }
-        Ok(())
+        Ok(AppInfo {
+            package_name: package_name.to_string(),
+            version_code,
+            version_name,
+            granted_permissions: include_permissions.then(|| parse_granted_permissions(&stdout)),
+        })
     }
 
-    pub fn get_app_info_json(
-        &self,
-        device: &str,
-        package_name: &str,
-        include_permissions: bool,
-    ) -> Value {
-        let output = match self
-            .adb
-            .run(&["-s", device, "shell", "pm", "dump", package_name])
-        {
-            Ok(o) => o,
-            Err(e) => return json!({ "error": e.to_string() }),
-        };
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let (version_code, version_name) = parse_versions(&stdout);
-        let mut result = json!({
-            "package_name": package_name,
-            "version_code": version_code,
-            "version_name": version_name,
-        });
-        if include_permissions {
-            result["granted_permissions"] = json!(parse_granted_permissions(&stdout));
+    // ── device info ──────────────────────────────────────────────────────
+
+    pub fn get_device_info(&self, device: &str) -> Result<DeviceInfo> {
+        self.backend.device_info(device)
+    }
+
+    // ── network ──────────────────────────────────────────────────────────
+
+    pub fn get_network_info(&self, device: &str) -> Result<NetworkInfo> {
+        Ok(self.backend.network_info(device)?.into())
+    }
+
+    // ── health ───────────────────────────────────────────────────────────
+
+    pub fn get_device_health(&self, device: &str) -> Result<DeviceHealth> {
+        Ok(self.backend.device_health(device)?.into())
+    }
+
+    // ── settings ─────────────────────────────────────────────────────────
+
+    /// `settings get <namespace> <key>`; `None` when the key is unset.
+    pub fn get_setting(&self, device: &str, namespace: &str, key: &str) -> Result<Option<String>> {
+        let output = self
+            .backend
+            .run(&["-s", device, "shell", "settings", "get", namespace, key])?;
+        let value = output.stdout.trim();
+        Ok((value != "null").then(|| value.to_string()))
+    }
+
+    pub fn put_setting(&self, device: &str, namespace: &str, key: &str, value: &str) -> Result<()> {
+        // `adb shell` re-splits the command line on the device.
+        let quoted = format!("'{}'", value.replace('\'', "'\\''"));
+        let output = self.backend.run(&[
+            "-s", device, "shell", "settings", "put", namespace, key, &quoted,
+        ])?;
+        // `settings` exits 0 even on bad input, so failures only show as text.
+        let complaint = format!("{}{}", output.stdout, output.stderr);
+        if complaint.trim().is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Failed to set {} {}: {}",
+                namespace,
+                key,
+                complaint.trim()
+            ))
         }
-        result
     }
 
-    // ── device info ──────────────────────────────────────────────────────
+    // ── media ────────────────────────────────────────────────────────────
 
-    pub fn get_device_info(&self, device: &str) -> Result<()> {
-        let info = self.adb.device_info(device)?;
-        println!("\n{}", "Device Info".bold().underline().yellow());
-        let rows: [(&str, &Option<String>); 13] = [
-            ("Model", &info.model),
-            ("Manufacturer", &info.manufacturer),
-            ("Brand", &info.brand),
-            ("Device", &info.device),
-            ("Name", &info.name),
-            ("Android Version", &info.android_version),
-            ("SDK", &info.sdk),
-            ("Codename", &info.codename),
-            ("Board", &info.board),
-            ("CPU ABI", &info.cpu_abi),
-            ("Locale", &info.locale),
-            ("Build ID", &info.build_id),
-            ("Security Patch", &info.security_patch),
-        ];
-        for (label, value) in rows {
-            if let Some(v) = value {
-                println!("{:<18}: {}", label.cyan(), v.green());
-            }
+    pub fn take_screenshot(&self, device: &str, output_path: Option<PathBuf>) -> Result<PathBuf> {
+        self.backend.screenshot(device, output_path)
+    }
+
+    /// Record until Ctrl+C, then pull the video.
+    pub fn record_screen(&self, device: &str, output_path: Option<PathBuf>) -> Result<PathBuf> {
+        self.backend.record_screen(device, output_path)
+    }
+
+    // ── UI hierarchy ─────────────────────────────────────────────────────
+
+    /// The raw `uiautomator dump` XML of what's on screen.
+    pub fn dump_ui_xml(&self, device: &str) -> Result<String> {
+        let dumped =
+            self.backend
+                .run(&["-s", device, "shell", "uiautomator", "dump", UI_DUMP_PATH])?;
+        let printed = format!("{}{}", dumped.stdout, dumped.stderr);
+        if !printed.contains("dumped to") {
+            let reason = printed
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty())
+                .unwrap_or("uiautomator printed nothing");
+            return Err(DabError::new(
+                ErrorCode::UiDumpFailed,
+                format!("Could not dump the UI: {}", reason),
+            )
+            .into());
         }
-        Ok(())
+        let xml = self
+            .backend
+            .run(&["-s", device, "shell", "cat", UI_DUMP_PATH])?;
+        let _ = self
+            .backend
+            .run(&["-s", device, "shell", "rm", "-f", UI_DUMP_PATH]);
+        Ok(xml.stdout)
     }
 
-    pub fn get_device_info_json(&self, device: &str) -> Value {
-        match self.adb.device_info(device) {
-            Ok(info) => {
-                serde_json::to_value(info).unwrap_or_else(|e| json!({ "error": e.to_string() }))
+    /// The view hierarchy of what's on screen.
+    pub fn get_ui(&self, device: &str) -> Result<Hierarchy> {
+        let xml = self.dump_ui_xml(device)?;
+        ui::parse_hierarchy(&xml).map_err(|e| {
+            DabError::new(
+                ErrorCode::UiDumpFailed,
+                format!("Could not read the UI dump: {}", e),
+            )
+            .into()
+        })
+    }
+
+    /// The on-screen nodes `query` matches. With a `wait`, re-read the
+    /// screen until something matches or the time is up; empty if nothing
+    /// ever did.
+    pub fn find_ui(&self, device: &str, query: &Query, wait: Duration) -> Result<Vec<Match>> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let found = self.get_ui(device)?.find(query);
+            if !found.is_empty() || Instant::now() >= deadline {
+                return Ok(found);
             }
-            Err(e) => json!({ "error": e.to_string() }),
+            std::thread::sleep(Duration::from_millis(500));
         }
     }
 
-    // ── network ──────────────────────────────────────────────────────────
+    // ── macros ───────────────────────────────────────────────────────────
 
-    pub fn get_network_info(&self, device: &str) -> Result<()> {
-        let info = self.adb.network_info(device)?;
-        println!(
-            "\n{}",
-            "Network Interfaces (IP addresses)"
-                .bold()
-                .underline()
-                .yellow()
-        );
-        for ip in &info.ip_addresses {
-            println!("{} {}", "IP Address:".cyan(), ip.green());
+    /// The screen size `input` coordinates refer to, in the display's current
+    /// orientation.
+    pub fn screen_size(&self, device: &str) -> Result<Screen> {
+        Ok(self.display(device)?.0)
+    }
+
+    /// The screen size in the current orientation, and that orientation's
+    /// rotation in quarter turns. A rotation `dumpsys input` doesn't show is
+    /// taken as the natural one.
+    fn display(&self, device: &str) -> Result<(Screen, u8)> {
+        let output = self.backend.run(&["-s", device, "shell", "wm", "size"])?;
+        let screen = gesture::parse_screen_size(&output.stdout)
+            .ok_or_else(|| anyhow!("Could not read the screen size from `wm size`"))?;
+        let output = self
+            .backend
+            .run(&["-s", device, "shell", "dumpsys", "input"])?;
+        let rotation = gesture::parse_rotation(&output.stdout).unwrap_or(0);
+        Ok((screen.rotated(rotation), rotation))
+    }
+
+    /// The input device that reports touches.
+    pub fn touchscreen(&self, device: &str) -> Result<Touchscreen> {
+        let output = self
+            .backend
+            .run(&["-s", device, "shell", "getevent", "-lp"])?;
+        gesture::parse_touchscreen(&output.stdout).ok_or_else(|| {
+            DabError::new(
+                ErrorCode::InputFailed,
+                "No touchscreen found in `getevent -lp`",
+            )
+            .with_hint("Record on a device or emulator with a touchscreen.")
+            .into()
+        })
+    }
+
+    /// Record touches and key presses until Ctrl+C or adb exits, calling
+    /// `on_step` as each one completes. A gesture still in progress when
+    /// the recording stops is dropped.
+    pub fn record_gestures(
+        &self,
+        device: &str,
+        mut on_step: impl FnMut(&Step) -> Result<()>,
+    ) -> Result<Macro> {
+        let (screen, rotation) = self.display(device)?;
+        let mut recorder = GestureRecorder::new(screen, rotation, self.touchscreen(device)?);
+        // `-tt` gives getevent a terminal so it flushes every line.
+        let lines = self
+            .backend
+            .stream(&["-s", device, "shell", "-tt", "getevent", "-lt"])?;
+        for line in lines {
+            let Ok(line) = line else { break };
+            if let Some(step) = recorder.feed(&line) {
+                on_step(step)?;
+            }
         }
-        println!("\n{}", "WiFi Info".bold().underline().yellow());
-        println!(
-            "{} {}",
-            "SSID:".cyan(),
-            info.ssid.unwrap_or_else(|| "N/A".to_string()).green()
-        );
-        Ok(())
+        Ok(recorder.finish())
     }
 
-    pub fn get_network_info_json(&self, device: &str) -> Value {
-        match self.adb.network_info(device) {
-            Ok(info) => json!({
-                "device": info.device,
-                "ip_addresses": info.ip_addresses,
-                "ssid": info.ssid.map(Value::from).unwrap_or(Value::Null),
-            }),
-            Err(e) => json!({ "error": e.to_string() }),
+    /// Perform one recorded gesture.
+    pub fn perform_gesture(&self, device: &str, gesture: &Gesture) -> Result<()> {
+        match gesture {
+            Gesture::Tap { x, y } => self.tap(device, *x, *y),
+            Gesture::LongPress { x, y, duration_ms } => {
+                self.long_press(device, *x, *y, Duration::from_millis(*duration_ms))
+            }
+            Gesture::Swipe {
+                x1,
+                y1,
+                x2,
+                y2,
+                duration_ms,
+            } => self.swipe(
+                device,
+                (*x1, *y1),
+                (*x2, *y2),
+                Duration::from_millis(*duration_ms),
+            ),
+            Gesture::Key { key, long: false } => self.press_key(device, key),
+            Gesture::Key { key, long: true } => self.long_press_key(device, key),
         }
     }
 
-    // ── health ───────────────────────────────────────────────────────────
+    // ── logs ─────────────────────────────────────────────────────────────
 
-    pub fn get_device_health(&self, device: &str) -> Result<()> {
-        let h = self.adb.device_health(device)?;
-        println!("\n{}", "Device Health Check".bold().underline().yellow());
-        println!(
-            "{} {}% (Status: {})",
-            "Battery:".cyan(),
-            h.battery.level.unwrap_or_else(|| "N/A".to_string()).green(),
-            h.battery
-                .status
-                .unwrap_or_else(|| "N/A".to_string())
-                .green()
-        );
-        if let Some(s) = h.storage {
-            println!(
-                "{} Used: {:.2} GB ({:.1}%) / Total: {:.2} GB | Free: {:.2} GB",
-                "Storage:".cyan(),
-                s.used_gb,
-                s.percent_used,
-                s.total_gb,
-                s.free_gb
-            );
+    /// Stream `package`'s logcat until Ctrl+C, adb exits or `on_event`
+    /// fails.
+    ///
+    /// The app's PIDs are re-read with `pidof` every second and picked up
+    /// from ActivityManager's `Start proc` lines, so the stream follows the
+    /// app across restarts. Only new entries are streamed: an old `Start proc`
+    /// line would name a PID that may since belong to another process.
+    /// `on_event` is called from two threads; errors it returns for
+    /// [`LogEvent::Entry`] end the stream and are returned.
+    pub fn stream_logcat(
+        &self,
+        device: &str,
+        package: &str,
+        filter: &LogFilter,
+        on_event: impl Fn(LogEvent) -> Result<()> + Sync,
+    ) -> Result<()> {
+        let pids = Mutex::new(HashSet::new());
+        let follow = |found: &[u32]| {
+            let new: Vec<u32> = {
+                let mut known = pids.lock().unwrap();
+                found.iter().copied().filter(|p| known.insert(*p)).collect()
+            };
+            for pid in new {
+                let _ = on_event(LogEvent::Following(pid));
+            }
+        };
+        let current = parse_pids(&self.backend.pid_of(device, package)?.unwrap_or_default());
+        if current.is_empty() {
+            on_event(LogEvent::Waiting)?;
         }
-        println!(
-            "{} {:.2} GB free / {:.2} GB total",
-            "RAM:".cyan(),
-            h.ram.free_gb,
-            h.ram.total_gb
-        );
-        println!(
-            "{} {} (SSID: {})",
-            "Network:".cyan(),
-            h.network.ip.unwrap_or_else(|| "N/A".to_string()).green(),
-            h.network.ssid.unwrap_or_else(|| "N/A".to_string()).green()
-        );
-        Ok(())
+        follow(&current);
+
+        let lines = self.stream_logcat_lines(device, &["-T", "1"])?;
+        let done = AtomicBool::new(false);
+        let streamed: Result<()> = std::thread::scope(|scope| {
+            scope.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_secs(1));
+                    if let Ok(Some(found)) = self.backend.pid_of(device, package) {
+                        follow(&parse_pids(&found));
+                    }
+                }
+            });
+            let result = (|| {
+                for line in lines {
+                    let Some(entry) = LogEntry::parse(&line?) else {
+                        continue;
+                    };
+                    if let Some(pid) = started_pid(&entry, package) {
+                        follow(&[pid]);
+                    }
+                    if pids.lock().unwrap().contains(&entry.pid) && filter.matches(&entry) {
+                        on_event(LogEvent::Entry(&entry))?;
+                    }
+                }
+                Ok(())
+            })();
+            done.store(true, Ordering::Relaxed);
+            result
+        });
+        streamed
     }
 
-    pub fn get_device_health_json(&self, device: &str) -> Value {
-        match self.adb.device_health(device) {
-            Ok(h) => json!({
-                "device": h.device,
-                "battery": {
-                    "level": h.battery.level.map(Value::from).unwrap_or(Value::Null),
-                    "status": h.battery.status.map(Value::from).unwrap_or(Value::Null),
-                },
-                "storage": h.storage.map(|s| json!({
-                    "total_gb": s.total_gb,
-                    "used_gb": s.used_gb,
-                    "free_gb": s.free_gb,
-                    "percent_used": s.percent_used,
-                })).unwrap_or(Value::Null),
-                "ram": { "total_gb": h.ram.total_gb, "free_gb": h.ram.free_gb },
-                "network": {
-                    "ip": h.network.ip.map(Value::from).unwrap_or(Value::Null),
-                    "ssid": h.network.ssid.map(Value::from).unwrap_or(Value::Null),
+    /// The entries already logged, without following: the last `count`, or
+    /// all of them since `since` (a [`LogEntry::time`], inclusive).
+    pub fn dump_logcat(
+        &self,
+        device: &str,
+        since: Option<&str>,
+        count: usize,
+    ) -> Result<Vec<LogEntry>> {
+        let count = count.to_string();
+        let output = self.backend.run(&[
+            "-s",
+            device,
+            "logcat",
+            "-v",
+            "threadtime",
+            "-d",
+            "-t",
+            since.unwrap_or(&count),
+        ])?;
+        Ok(output.stdout.lines().filter_map(LogEntry::parse).collect())
+    }
+
+    /// Lines of `adb logcat -v threadtime <args>` as they are logged.
+    fn stream_logcat_lines(&self, device: &str, args: &[&str]) -> Result<Lines> {
+        let mut command = vec!["-s", device, "logcat", "-v", "threadtime"];
+        command.extend(args);
+        self.backend.stream(&command)
+    }
+
+    // ── crashes ──────────────────────────────────────────────────────────
+
+    /// Crashes and ANRs of `package` still in the device's log buffers.
+    pub fn get_crash_incidents(&self, device: &str, package: &str) -> Result<Vec<Incident>> {
+        let output = self.backend.run(&[
+            "-s",
+            device,
+            "logcat",
+            "-d",
+            "-v",
+            "threadtime",
+            "-b",
+            CRASH_BUFFERS,
+        ])?;
+        let mut collector = CrashCollector::new(package);
+        let mut incidents: Vec<Incident> = output
+            .stdout
+            .lines()
+            .filter_map(LogEntry::parse)
+            .filter_map(|entry| collector.feed(&entry))
+            .collect();
+        incidents.extend(collector.finish());
+        Ok(incidents)
+    }
+
+    /// [`get_crash_incidents`](Self::get_crash_incidents), each written to
+    /// `save_dir` when given.
+    pub fn get_crashes(
+        &self,
+        device: &str,
+        package: &str,
+        save_dir: Option<&Path>,
+    ) -> Result<CrashReport> {
+        let incidents = self
+            .get_crash_incidents(device, package)?
+            .into_iter()
+            .map(|incident| self.saved_incident(device, package, incident, save_dir))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(CrashReport {
+            device: device.to_string(),
+            package: package.to_string(),
+            incidents,
+        })
+    }
+
+    /// Call `on_incident` with each new crash or ANR of `package` as it
+    /// happens, until Ctrl+C or `on_incident` fails.
+    pub fn watch_crashes(
+        &self,
+        device: &str,
+        package: &str,
+        save_dir: Option<&Path>,
+        mut on_incident: impl FnMut(SavedIncident) -> Result<()>,
+    ) -> Result<()> {
+        let lines = self.stream_logcat_lines(device, &["-b", CRASH_BUFFERS, "-T", "1"])?;
+        let mut collector = CrashCollector::new(package);
+        let mut report =
+            |incident| on_incident(self.saved_incident(device, package, incident, save_dir)?);
+        // Read on a thread so a quiet log can flush the incident in progress
+        // instead of holding it until some other line arrives.
+        let (sender, received) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in lines {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut result = Ok(());
+        loop {
+            let incident = match received.recv_timeout(CRASH_FLUSH_AFTER) {
+                Ok(line) => match LogEntry::parse(&line) {
+                    Some(entry) => collector.feed(&entry),
+                    None => continue,
                 },
-            }),
-            Err(e) => json!({ "error": e.to_string() }),
+                Err(RecvTimeoutError::Timeout) => collector.finish(),
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if let Some(incident) = incident {
+                result = report(incident);
+                if result.is_err() {
+                    break;
+                }
+            }
+        }
+        if let (Ok(()), Some(incident)) = (&result, collector.finish()) {
+            result = report(incident);
         }
+        result
     }
 
-    // ── media ────────────────────────────────────────────────────────────
+    fn saved_incident(
+        &self,
+        device: &str,
+        package: &str,
+        incident: Incident,
+        save_dir: Option<&Path>,
+    ) -> Result<SavedIncident> {
+        let saved = match save_dir {
+            Some(dir) => Some(self.save_incident(device, package, &incident, dir)?),
+            None => None,
+        };
+        Ok(SavedIncident { incident, saved })
+    }
 
-    pub fn take_screenshot(&self, device: &str, output_path: Option<PathBuf>) -> Result<PathBuf> {
-        let path = self.adb.screenshot(device, output_path)?;
-        println!("Screenshot saved to {}", path.display());
+    /// Write the incident to `dir` with the save time and the app's
+    /// `app-info` attached, so a QA report carries the exact build.
+    pub fn save_incident(
+        &self,
+        device: &str,
+        package: &str,
+        incident: &Incident,
+        dir: &Path,
+    ) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let mut record = serde_json::to_value(incident)?;
+        record["device"] = json!(device);
+        record["saved_at"] = json!(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default());
+        record["app_info"] = serde_json::to_value(self.get_app_info(device, package, true)?)?;
+        let path = dir.join(incident.file_name());
+        std::fs::write(&path, serde_json::to_string_pretty(&record)?)?;
         Ok(path)
     }
 
-    pub fn record_screen(&self, device: &str, output_path: Option<PathBuf>) -> Result<PathBuf> {
-        println!("Recording... Press Ctrl+C to stop.");
-        let path = self.adb.record_screen(device, output_path)?;
-        println!("Screen recording saved to {}", path.display());
-        Ok(path)
+    // ── app performance ──────────────────────────────────────────────────
+
+    /// The app's main process id, if it is running.
+    pub fn app_pid(&self, device: &str, package_name: &str) -> Result<Option<u32>> {
+        Ok(self
+            .backend
+            .pid_of(device, package_name)?
+            .and_then(|pids| parse_pids(&pids).first().copied()))
+    }
+
+    /// `dumpsys meminfo` App Summary, or `None` when the app isn't running.
+    pub fn app_meminfo(&self, device: &str, package_name: &str) -> Result<Option<MemInfo>> {
+        let output =
+            self.backend
+                .run(&["-s", device, "shell", "dumpsys", "meminfo", package_name])?;
+        Ok(parse_meminfo(&output.stdout))
+    }
+
+    pub fn app_proc_stat(&self, device: &str, pid: u32) -> Result<Option<ProcStat>> {
+        let path = format!("/proc/{}/stat", pid);
+        let output = self.backend.run(&["-s", device, "shell", "cat", &path])?;
+        Ok(parse_proc_stat(&output.stdout))
+    }
+
+    /// Cumulative frame counters; `reset` starts them over first.
+    pub fn app_frame_stats(
+        &self,
+        device: &str,
+        package_name: &str,
+        reset: bool,
+    ) -> Result<FrameStats> {
+        let mut args = vec!["-s", device, "shell", "dumpsys", "gfxinfo", package_name];
+        if reset {
+            args.push("reset");
+        }
+        let output = self.backend.run(&args)?;
+        Ok(parse_gfxinfo(&output.stdout))
+    }
+
+    // ── instrumentation ──────────────────────────────────────────────────
+
+    pub fn list_instrumentations(&self, device: &str) -> Result<Vec<Instrumentation>> {
+        let output = self
+            .backend
+            .run(&["-s", device, "shell", "pm", "list", "instrumentation"])?;
+        Ok(parse_instrumentation_list(&output.stdout))
+    }
+
+    /// Run `am instrument -w -r package/runner`, passing each `(key, value)`
+    /// as `-e key value` and calling `on_test` as every test finishes.
+    pub fn run_instrumentation(
+        &self,
+        device: &str,
+        package: &str,
+        runner: &str,
+        extras: &[(String, String)],
+        mut on_test: impl FnMut(&TestCase),
+    ) -> Result<TestRun> {
+        let mut args: Vec<String> = ["-s", device, "shell", "am", "instrument", "-w", "-r"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        for (key, value) in extras {
+            // `adb shell` re-splits the command line on the device.
+            args.extend([
+                "-e".to_string(),
+                key.clone(),
+                format!("'{}'", value.replace('\'', "'\\''")),
+            ]);
+        }
+        args.push(format!("{}/{}", package, runner));
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let started = Instant::now();
+        let mut parser = StatusParser::default();
+        for line in self.backend.stream(&args)? {
+            if let Some(test) = parser.feed(&line?, started.elapsed().as_secs_f64()) {
+                on_test(&test);
+            }
+        }
+        Ok(parser.finish(started.elapsed().as_secs_f64()))
     }
 
     // ── connectivity ─────────────────────────────────────────────────────
 
-    pub fn enable_wifi(&self, device: &str) -> Result<()> {
-        println!("Enabling ADB over Wi-Fi (TCP/IP 5555)...");
-        let addr = self.adb.enable_wifi(device)?;
-        println!("Connected to {}", addr.green());
-        println!("\nYou can now disconnect the USB cable and use ADB over Wi-Fi!");
-        Ok(())
+    /// Switch adb to TCP/IP on port 5555 and connect; returns the address.
+    pub fn enable_wifi(&self, device: &str) -> Result<String> {
+        self.backend.enable_wifi(device)
     }
 
     pub fn enable_usb(&self, device: &str) -> Result<()> {
-        println!("Switching ADB back to USB mode...");
-        self.adb.enable_usb(device)?;
-        println!("ADB is now in USB mode.");
-        Ok(())
+        self.backend.enable_usb(device)
     }
 
     // ── launch & permissions ─────────────────────────────────────────────
 
     pub fn launch_url(&self, device: &str, url: &str) -> Result<()> {
-        self.adb.launch_url(device, url)
+        self.backend.launch_url(device, url)
+    }
+
+    /// Send `intent` to an activity, broadcast receivers or a service through
+    /// `am`. Broadcasts return what the receivers answered.
+    pub fn send_intent(
+        &self,
+        device: &str,
+        target: Target,
+        intent: &Intent,
+    ) -> Result<Option<BroadcastResult>> {
+        let am_args = intent.am_args();
+        let mut args = vec!["-s", device, "shell", "am", target.am_command()];
+        args.extend(am_args.iter().map(String::as_str));
+        let output = self.backend.run(&args)?;
+        let printed = format!("{}{}", output.stdout, output.stderr);
+        if let Some(error) = intent::am_error(&printed) {
+            let mut err = DabError::new(ErrorCode::IntentFailed, error);
+            if let Some(component) = &intent.component {
+                err = err.with_detail("component", component.as_str());
+            }
+            return Err(err.into());
+        }
+        Ok(match target {
+            Target::Broadcast => intent::parse_broadcast_result(&printed),
+            _ => None,
+        })
+    }
+
+    /// The deep links an installed app declares, from `dumpsys package`.
+    pub fn get_deeplinks(&self, device: &str, package_name: &str) -> Result<DeepLinks> {
+        let output =
+            self.backend
+                .run(&["-s", device, "shell", "dumpsys", "package", package_name])?;
+        Ok(DeepLinks {
+            package: package_name.to_string(),
+            links: deeplink::links(&deeplink::parse_dumpsys(&output.stdout)),
+        })
+    }
+
+    /// The activity `url` opens through [`launch_url`](Self::launch_url), or
+    /// `None` when no activity handles it.
+    pub fn resolve_url(&self, device: &str, url: &str) -> Result<Option<String>> {
+        let output = self.backend.run(&[
+            "-s",
+            device,
+            "shell",
+            "cmd",
+            "package",
+            "resolve-activity",
+            "--brief",
+            "-a",
+            "android.intent.action.VIEW",
+            "-c",
+            "android.intent.category.DEFAULT",
+            "-d",
+            &intent::shell_quote(url),
+        ])?;
+        Ok(deeplink::parse_resolved(&output.stdout))
+    }
+
+    /// Check that `url` opens `link`'s own activity rather than a chooser,
+    /// a browser or nothing at all.
+    pub fn verify_deeplink(
+        &self,
+        device: &str,
+        link: &DeepLink,
+        url: &str,
+    ) -> Result<Verification> {
+        let resolved = self.resolve_url(device, url)?;
+        Ok(Verification::new(url, &link.activity, resolved))
     }
 
     pub fn grant_permissions(
@@ -314,7 +1043,7 @@ impl AdbClient {
         package_name: &str,
         permissions: &[&str],
     ) -> Result<()> {
-        self.adb.grant(device, package_name, permissions)
+        self.backend.grant(device, package_name, permissions)
     }
 
     pub fn revoke_permissions(
@@ -323,12 +1052,14 @@ impl AdbClient {
         package_name: &str,
         permissions: &[&str],
     ) -> Result<()> {
-        self.adb.revoke(device, package_name, permissions)
+        self.backend.revoke(device, package_name, permissions)
     }
 
     // ── install ──────────────────────────────────────────────────────────
 
-    pub fn install_file(&self, device: &str, file_path: &Path) -> Result<()> {
+    /// Install an APK, or every APK in an XAPK/APKM bundle. Returns how
+    /// many APKs were installed.
+    pub fn install_file(&self, device: &str, file_path: &Path) -> Result<usize> {
         if !file_path.exists() {
             return Err(anyhow!("File does not exist: {}", file_path.display()));
         }
@@ -338,33 +1069,13 @@ impl AdbClient {
             .map(|e| e.to_lowercase());
         match extension.as_deref() {
             Some("apk") => {
-                println!("{} {}", "Installing APK:".green(), file_path.display());
-                self.adb.install_apk(device, file_path)?;
-                println!("{}", "APK installed successfully!".green());
-                Ok(())
-            }
-            Some("xapk") | Some("apkm") => {
-                println!(
-                    "{} {}",
-                    "Installing XAPK/APKM:".green(),
-                    file_path.display()
-                );
-                let temp_dir =
-                    std::env::temp_dir().join(format!("dab_xapk_{}", std::process::id()));
-                std::fs::create_dir_all(&temp_dir)?;
-                let result = (|| {
-                    let apks = apk::extract_apks(file_path, &temp_dir)?;
-                    if apks.is_empty() {
-                        return Err(anyhow!("No APK files found in XAPK"));
-                    }
-                    println!("{} {} APK files", "Installing".green(), apks.len());
-                    self.adb.install_multiple(device, &apks)?;
-                    println!("{}", "XAPK installed successfully!".green());
-                    Ok(())
-                })();
-                let _ = std::fs::remove_dir_all(&temp_dir);
-                result
+                self.backend.install_apk(device, file_path)?;
+                Ok(1)
             }
+            Some("xapk") | Some("apkm") => with_extracted_apks(file_path, "xapk", |apks| {
+                self.backend.install_multiple(device, apks)?;
+                Ok(apks.len())
+            }),
             _ => Err(anyhow!(
                 "Unsupported file type. Only APK, XAPK, and APKM files are supported."
             )),
@@ -373,32 +1084,100 @@ impl AdbClient {
 
     // ── local file analysis ──────────────────────────────────────────────
 
-    pub fn analyze_local_file(&self, file_path: &Path) -> Result<()> {
-        let info = apk::analyze(file_path)?;
-        println!("{}", "\nAPK File Analysis".bold().underline().yellow());
-        println!("{}: {}", "Package Name".cyan(), info.package_name.green());
-        println!("{}: {}", "App Name".cyan(), info.app_name.green());
-        println!("{}: {}", "Version Code".cyan(), info.version_code.green());
-        println!("{}: {}", "Version Name".cyan(), info.version_name.green());
-        println!("{}:", "Permissions Requested".cyan());
-        if info.permissions.is_empty() {
-            println!("  {}", "None".red());
-        } else {
-            for perm in &info.permissions {
-                println!("  {}", perm.blue());
-            }
+    pub fn analyze_local_file(&self, file_path: &Path) -> Result<ApkInfo> {
+        if !file_path.exists() {
+            return Err(anyhow!("File does not exist: {}", file_path.display()));
         }
-        Ok(())
+        apk::analyze(file_path)
+    }
+
+    /// The deep links a local APK, XAPK or APKM declares, read from its
+    /// base APK's manifest with `aapt` or `aapt2`.
+    pub fn get_file_deeplinks(&self, file_path: &Path) -> Result<DeepLinks> {
+        if !file_path.exists() {
+            return Err(anyhow!("File does not exist: {}", file_path.display()));
+        }
+        let extension = file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        let manifest = match extension.as_deref() {
+            Some("apk") => dump_manifest(file_path)?,
+            Some("xapk") | Some("apkm") => with_extracted_apks(file_path, "deeplinks", |apks| {
+                dump_manifest(&apk::find_base_apk(apks))
+            })?,
+            _ => {
+                return Err(anyhow!(
+                    "Unsupported file type. Only APK, XAPK, and APKM files are supported."
+                ))
+            }
+        };
+        let (package, filters) = deeplink::parse_xmltree(&manifest);
+        Ok(DeepLinks {
+            package: package.unwrap_or_default(),
+            links: deeplink::links(&filters),
+        })
     }
+}
+
+/// Bundles extracted so far by this process, numbering their directories.
+static EXTRACTIONS: AtomicU64 = AtomicU64::new(0);
 
-    pub fn analyze_local_file_json(&self, file_path: &Path) -> Value {
-        match apk::analyze(file_path) {
-            Ok(info) => {
-                serde_json::to_value(info).unwrap_or_else(|e| json!({ "error": e.to_string() }))
+/// Extract the APKs in an XAPK/APKM `bundle` and call `f` with them. Each call
+/// gets its own directory, removed afterwards, so fan-out threads and server
+/// workers extracting at the same time don't delete each other's files.
+fn with_extracted_apks<T>(
+    bundle: &Path,
+    purpose: &str,
+    f: impl FnOnce(&[PathBuf]) -> Result<T>,
+) -> Result<T> {
+    let n = EXTRACTIONS.fetch_add(1, Ordering::Relaxed);
+    let temp_dir =
+        std::env::temp_dir().join(format!("dab_{}_{}_{}", purpose, std::process::id(), n));
+    std::fs::create_dir_all(&temp_dir)?;
+    let result = apk::extract_apks(bundle, &temp_dir).and_then(|apks| {
+        if apks.is_empty() {
+            return Err(anyhow!("No APK files found in XAPK"));
+        }
+        f(&apks)
+    });
+    let _ = std::fs::remove_dir_all(&temp_dir);
+    result
+}
+
+/// `AndroidManifest.xml` as an element tree, from whichever of `aapt` and
+/// `aapt2` is on `PATH`.
+fn dump_manifest(apk: &Path) -> Result<String> {
+    let apk = apk.to_string_lossy();
+    let attempts: [(&str, Vec<&str>); 2] = [
+        ("aapt", vec!["dump", "xmltree", &apk, "AndroidManifest.xml"]),
+        (
+            "aapt2",
+            vec!["dump", "xmltree", "--file", "AndroidManifest.xml", &apk],
+        ),
+    ];
+    for (tool, args) in attempts {
+        match std::process::Command::new(tool).args(&args).output() {
+            Ok(output) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+            }
+            Ok(output) => {
+                return Err(anyhow!(
+                    "{} could not read the manifest of {}: {}",
+                    tool,
+                    apk,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ))
             }
-            Err(e) => json!({ "error": e.to_string() }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
         }
     }
+    Err(DabError::new(
+        ErrorCode::AaptMissing,
+        "Reading an APK's intent filters needs `aapt` or `aapt2`",
+    )
+    .into())
 }
 
 /// Extract `versionCode` / `versionName` from `pm dump` output.
@@ -0,0 +1,696 @@
+//! Where [`AdbClient`](crate::adb_client::AdbClient) sends its adb calls.
+//!
+//! [`AdbBackend`] is the seam between dab's command logic and the `adb`
+//! binary. [`RealAdb`] runs adb through androkit. [`Recorder`] wraps another
+//! backend and keeps a [`Transcript`] of every call and its response, and
+//! [`Replayer`] answers from such a transcript, so whole command flows can run
+//! in tests and on CI without a device.
+//!
+//! Transcripts are JSON: one exchange per call, with the call name, its
+//! arguments and either the `ok` value or the `error` message.
+//!
+//! ```json
+//! { "exchanges": [
+//!   { "call": "list_packages", "args": ["emulator-5554"], "ok": ["com.foo"] },
+//!   { "call": "uninstall", "args": ["emulator-5554", "com.bar"], "error": "Failure [DELETE_FAILED_INTERNAL_ERROR]" }
+//! ] }
+//! ```
+
+use androkit::adb::Adb;
+use androkit::exec;
+use androkit::model::{DeviceHealth, DeviceInfo, NetworkInfo};
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Captured result of `adb <args>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Output lines of a long-running adb command. Dropping it stops the command.
+pub type Lines = Box<dyn Iterator<Item = std::io::Result<String>> + Send>;
+
+/// Every adb operation `AdbClient` performs. The typed methods mirror
+/// `androkit::adb::Adb`; `run` and `stream` cover everything else.
+pub trait AdbBackend: Send + Sync + 'static {
+    /// Run `adb <args>` and capture its output.
+    fn run(&self, args: &[&str]) -> Result<CommandOutput>;
+    /// Start `adb <args>` and read its stdout line by line as it arrives.
+    fn stream(&self, args: &[&str]) -> Result<Lines>;
+
+    fn list_packages(&self, device: &str) -> Result<Vec<String>>;
+    fn launch_package(&self, device: &str, package: &str) -> Result<()>;
+    fn uninstall(&self, device: &str, package: &str) -> Result<()>;
+    fn clear_data(&self, device: &str, package: &str) -> Result<()>;
+    fn stop_app(&self, device: &str, package: &str) -> Result<()>;
+    fn pid_of(&self, device: &str, package: &str) -> Result<Option<String>>;
+    fn download_apk(&self, device: &str, package: &str, dest: Option<PathBuf>) -> Result<PathBuf>;
+    fn install_apk(&self, device: &str, apk: &Path) -> Result<()>;
+    fn install_multiple(&self, device: &str, apks: &[PathBuf]) -> Result<()>;
+    fn grant(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()>;
+    fn revoke(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()>;
+    fn launch_url(&self, device: &str, url: &str) -> Result<()>;
+    fn device_info(&self, device: &str) -> Result<DeviceInfo>;
+    fn network_info(&self, device: &str) -> Result<NetworkInfo>;
+    fn device_health(&self, device: &str) -> Result<DeviceHealth>;
+    fn screenshot(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf>;
+    fn record_screen(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf>;
+    /// Switch to TCP/IP and connect; returns the address.
+    fn enable_wifi(&self, device: &str) -> Result<String>;
+    fn enable_usb(&self, device: &str) -> Result<()>;
+}
+
+// ── real adb ─────────────────────────────────────────────────────────────
+
+/// The `adb` on `PATH`, driven through androkit.
+pub struct RealAdb {
+    adb: Adb,
+    /// Kept alongside `adb` for the commands androkit can't stream.
+    adb_path: PathBuf,
+}
+
+impl RealAdb {
+    pub fn new() -> Result<Self> {
+        let adb_path = exec::find_program("adb")?;
+        Ok(Self {
+            adb: Adb::with_path(adb_path.clone()),
+            adb_path,
+        })
+    }
+}
+
+/// A spawned adb process whose stdout is read as lines; killed on drop.
+struct ChildLines {
+    child: Child,
+    lines: std::io::Lines<BufReader<std::process::ChildStdout>>,
+}
+
+impl Iterator for ChildLines {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.lines.next()
+    }
+}
+
+impl Drop for ChildLines {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl AdbBackend for RealAdb {
+    fn run(&self, args: &[&str]) -> Result<CommandOutput> {
+        let output = self.adb.run(args)?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    fn stream(&self, args: &[&str]) -> Result<Lines> {
+        let mut child = Command::new(&self.adb_path)
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        Ok(Box::new(ChildLines {
+            child,
+            lines: BufReader::new(stdout).lines(),
+        }))
+    }
+
+    fn list_packages(&self, device: &str) -> Result<Vec<String>> {
+        self.adb.list_packages(device)
+    }
+
+    fn launch_package(&self, device: &str, package: &str) -> Result<()> {
+        self.adb.launch_package(device, package)
+    }
+
+    fn uninstall(&self, device: &str, package: &str) -> Result<()> {
+        self.adb.uninstall(device, package)
+    }
+
+    fn clear_data(&self, device: &str, package: &str) -> Result<()> {
+        self.adb.clear_data(device, package)
+    }
+
+    fn stop_app(&self, device: &str, package: &str) -> Result<()> {
+        self.adb.stop_app(device, package)
+    }
+
+    fn pid_of(&self, device: &str, package: &str) -> Result<Option<String>> {
+        self.adb.pid_of(device, package)
+    }
+
+    fn download_apk(&self, device: &str, package: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        self.adb.download_apk(device, package, dest)
+    }
+
+    fn install_apk(&self, device: &str, apk: &Path) -> Result<()> {
+        self.adb.install_apk(device, apk)
+    }
+
+    fn install_multiple(&self, device: &str, apks: &[PathBuf]) -> Result<()> {
+        self.adb.install_multiple(device, apks)
+    }
+
+    fn grant(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()> {
+        self.adb.grant(device, package, permissions)
+    }
+
+    fn revoke(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()> {
+        self.adb.revoke(device, package, permissions)
+    }
+
+    fn launch_url(&self, device: &str, url: &str) -> Result<()> {
+        self.adb.launch_url(device, url)
+    }
+
+    fn device_info(&self, device: &str) -> Result<DeviceInfo> {
+        self.adb.device_info(device)
+    }
+
+    fn network_info(&self, device: &str) -> Result<NetworkInfo> {
+        self.adb.network_info(device)
+    }
+
+    fn device_health(&self, device: &str) -> Result<DeviceHealth> {
+        self.adb.device_health(device)
+    }
+
+    fn screenshot(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        self.adb.screenshot(device, dest)
+    }
+
+    fn record_screen(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        self.adb.record_screen(device, dest)
+    }
+
+    fn enable_wifi(&self, device: &str) -> Result<String> {
+        self.adb.enable_wifi(device)
+    }
+
+    fn enable_usb(&self, device: &str) -> Result<()> {
+        self.adb.enable_usb(device)
+    }
+}
+
+// ── transcripts ──────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub exchanges: Vec<Exchange>,
+}
+
+impl Transcript {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read transcript {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+}
+
+/// One backend call and what it returned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub call: String,
+    pub args: Vec<String>,
+    #[serde(flatten)]
+    pub response: Response,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    /// The serialized return value; for `stream`, the array of lines.
+    #[serde(rename = "ok")]
+    Ok(Value),
+    /// The error message, as `{:#}` formats it.
+    #[serde(rename = "error")]
+    Err(String),
+}
+
+fn owned(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn path_arg(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default()
+}
+
+/// Installed APKs are recorded by file name: bundles are extracted to a
+/// fresh temporary directory on every run.
+fn file_names(apks: &[PathBuf]) -> Vec<String> {
+    apks.iter()
+        .map(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Forwards to `inner` and records every exchange.
+pub struct Recorder<B> {
+    inner: B,
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl<B: AdbBackend> Recorder<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            exchanges: Arc::default(),
+        }
+    }
+
+    /// Everything recorded so far.
+    pub fn transcript(&self) -> Transcript {
+        Transcript {
+            exchanges: self.exchanges.lock().unwrap().clone(),
+        }
+    }
+
+    fn record<T: Serialize>(&self, call: &str, args: Vec<String>, result: Result<T>) -> Result<T> {
+        let response = match &result {
+            Ok(value) => Response::Ok(serde_json::to_value(value)?),
+            Err(e) => Response::Err(format!("{:#}", e)),
+        };
+        self.exchanges.lock().unwrap().push(Exchange {
+            call: call.to_string(),
+            args,
+            response,
+        });
+        result
+    }
+}
+
+/// Appends each line it yields to the recorded `stream` exchange.
+struct RecordedLines {
+    inner: Lines,
+    exchanges: Arc<Mutex<Vec<Exchange>>>,
+    index: usize,
+}
+
+impl Iterator for RecordedLines {
+    type Item = std::io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.inner.next()?;
+        if let Ok(text) = &line {
+            if let Response::Ok(Value::Array(lines)) =
+                &mut self.exchanges.lock().unwrap()[self.index].response
+            {
+                lines.push(Value::from(text.as_str()));
+            }
+        }
+        Some(line)
+    }
+}
+
+impl<B: AdbBackend> AdbBackend for Recorder<B> {
+    fn run(&self, args: &[&str]) -> Result<CommandOutput> {
+        self.record("run", owned(args), self.inner.run(args))
+    }
+
+    fn stream(&self, args: &[&str]) -> Result<Lines> {
+        let result = self.inner.stream(args);
+        let index = {
+            let mut exchanges = self.exchanges.lock().unwrap();
+            exchanges.push(Exchange {
+                call: "stream".to_string(),
+                args: owned(args),
+                response: match &result {
+                    Ok(_) => Response::Ok(Value::Array(Vec::new())),
+                    Err(e) => Response::Err(format!("{:#}", e)),
+                },
+            });
+            exchanges.len() - 1
+        };
+        let inner = result?;
+        Ok(Box::new(RecordedLines {
+            inner,
+            exchanges: self.exchanges.clone(),
+            index,
+        }))
+    }
+
+    fn list_packages(&self, device: &str) -> Result<Vec<String>> {
+        self.record(
+            "list_packages",
+            owned(&[device]),
+            self.inner.list_packages(device),
+        )
+    }
+
+    fn launch_package(&self, device: &str, package: &str) -> Result<()> {
+        let result = self.inner.launch_package(device, package);
+        self.record("launch_package", owned(&[device, package]), result)
+    }
+
+    fn uninstall(&self, device: &str, package: &str) -> Result<()> {
+        let result = self.inner.uninstall(device, package);
+        self.record("uninstall", owned(&[device, package]), result)
+    }
+
+    fn clear_data(&self, device: &str, package: &str) -> Result<()> {
+        let result = self.inner.clear_data(device, package);
+        self.record("clear_data", owned(&[device, package]), result)
+    }
+
+    fn stop_app(&self, device: &str, package: &str) -> Result<()> {
+        let result = self.inner.stop_app(device, package);
+        self.record("stop_app", owned(&[device, package]), result)
+    }
+
+    fn pid_of(&self, device: &str, package: &str) -> Result<Option<String>> {
+        let result = self.inner.pid_of(device, package);
+        self.record("pid_of", owned(&[device, package]), result)
+    }
+
+    fn download_apk(&self, device: &str, package: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        let args = vec![device.to_string(), package.to_string(), path_arg(&dest)];
+        let result = self.inner.download_apk(device, package, dest);
+        self.record("download_apk", args, result)
+    }
+
+    fn install_apk(&self, device: &str, apk: &Path) -> Result<()> {
+        let args = vec![device.to_string(), apk.display().to_string()];
+        self.record("install_apk", args, self.inner.install_apk(device, apk))
+    }
+
+    fn install_multiple(&self, device: &str, apks: &[PathBuf]) -> Result<()> {
+        let mut args = vec![device.to_string()];
+        args.extend(file_names(apks));
+        self.record(
+            "install_multiple",
+            args,
+            self.inner.install_multiple(device, apks),
+        )
+    }
+
+    fn grant(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()> {
+        let mut args = owned(&[device, package]);
+        args.extend(owned(permissions));
+        self.record(
+            "grant",
+            args,
+            self.inner.grant(device, package, permissions),
+        )
+    }
+
+    fn revoke(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()> {
+        let mut args = owned(&[device, package]);
+        args.extend(owned(permissions));
+        self.record(
+            "revoke",
+            args,
+            self.inner.revoke(device, package, permissions),
+        )
+    }
+
+    fn launch_url(&self, device: &str, url: &str) -> Result<()> {
+        let result = self.inner.launch_url(device, url);
+        self.record("launch_url", owned(&[device, url]), result)
+    }
+
+    fn device_info(&self, device: &str) -> Result<DeviceInfo> {
+        self.record(
+            "device_info",
+            owned(&[device]),
+            self.inner.device_info(device),
+        )
+    }
+
+    fn network_info(&self, device: &str) -> Result<NetworkInfo> {
+        self.record(
+            "network_info",
+            owned(&[device]),
+            self.inner.network_info(device),
+        )
+    }
+
+    fn device_health(&self, device: &str) -> Result<DeviceHealth> {
+        self.record(
+            "device_health",
+            owned(&[device]),
+            self.inner.device_health(device),
+        )
+    }
+
+    fn screenshot(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        let args = vec![device.to_string(), path_arg(&dest)];
+        self.record("screenshot", args, self.inner.screenshot(device, dest))
+    }
+
+    fn record_screen(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        let args = vec![device.to_string(), path_arg(&dest)];
+        self.record(
+            "record_screen",
+            args,
+            self.inner.record_screen(device, dest),
+        )
+    }
+
+    fn enable_wifi(&self, device: &str) -> Result<String> {
+        self.record(
+            "enable_wifi",
+            owned(&[device]),
+            self.inner.enable_wifi(device),
+        )
+    }
+
+    fn enable_usb(&self, device: &str) -> Result<()> {
+        self.record(
+            "enable_usb",
+            owned(&[device]),
+            self.inner.enable_usb(device),
+        )
+    }
+}
+
+/// Answers every call from a transcript. Each exchange is served once, in
+/// recorded order among calls with the same name and arguments; a call with
+/// no exchange left fails with an error naming it.
+pub struct Replayer {
+    exchanges: Mutex<Vec<(Exchange, bool)>>,
+}
+
+impl Replayer {
+    pub fn new(transcript: Transcript) -> Self {
+        Self {
+            exchanges: Mutex::new(
+                transcript
+                    .exchanges
+                    .into_iter()
+                    .map(|e| (e, false))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self::new(Transcript::load(path)?))
+    }
+
+    /// Exchanges that were never asked for, for asserting a flow made every
+    /// expected call.
+    pub fn unused(&self) -> Vec<Exchange> {
+        self.exchanges
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, used)| !used)
+            .map(|(e, _)| e.clone())
+            .collect()
+    }
+
+    fn replay<T: DeserializeOwned>(&self, call: &str, args: Vec<String>) -> Result<T> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        let (exchange, used) = exchanges
+            .iter_mut()
+            .find(|(e, used)| !used && e.call == call && e.args == args)
+            .ok_or_else(|| anyhow!("No recorded response for {} {}", call, args.join(" ")))?;
+        *used = true;
+        match &exchange.response {
+            Response::Ok(value) => Ok(serde_json::from_value(value.clone())?),
+            Response::Err(message) => Err(anyhow!("{}", message)),
+        }
+    }
+}
+
+impl AdbBackend for Replayer {
+    fn run(&self, args: &[&str]) -> Result<CommandOutput> {
+        self.replay("run", owned(args))
+    }
+
+    fn stream(&self, args: &[&str]) -> Result<Lines> {
+        let lines: Vec<String> = self.replay("stream", owned(args))?;
+        Ok(Box::new(lines.into_iter().map(Ok)))
+    }
+
+    fn list_packages(&self, device: &str) -> Result<Vec<String>> {
+        self.replay("list_packages", owned(&[device]))
+    }
+
+    fn launch_package(&self, device: &str, package: &str) -> Result<()> {
+        self.replay("launch_package", owned(&[device, package]))
+    }
+
+    fn uninstall(&self, device: &str, package: &str) -> Result<()> {
+        self.replay("uninstall", owned(&[device, package]))
+    }
+
+    fn clear_data(&self, device: &str, package: &str) -> Result<()> {
+        self.replay("clear_data", owned(&[device, package]))
+    }
+
+    fn stop_app(&self, device: &str, package: &str) -> Result<()> {
+        self.replay("stop_app", owned(&[device, package]))
+    }
+
+    fn pid_of(&self, device: &str, package: &str) -> Result<Option<String>> {
+        self.replay("pid_of", owned(&[device, package]))
+    }
+
+    fn download_apk(&self, device: &str, package: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        let args = vec![device.to_string(), package.to_string(), path_arg(&dest)];
+        self.replay("download_apk", args)
+    }
+
+    fn install_apk(&self, device: &str, apk: &Path) -> Result<()> {
+        self.replay(
+            "install_apk",
+            vec![device.to_string(), apk.display().to_string()],
+        )
+    }
+
+    fn install_multiple(&self, device: &str, apks: &[PathBuf]) -> Result<()> {
+        let mut args = vec![device.to_string()];
+        args.extend(file_names(apks));
+        self.replay("install_multiple", args)
+    }
+
+    fn grant(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()> {
+        let mut args = owned(&[device, package]);
+        args.extend(owned(permissions));
+        self.replay("grant", args)
+    }
+
+    fn revoke(&self, device: &str, package: &str, permissions: &[&str]) -> Result<()> {
+        let mut args = owned(&[device, package]);
+        args.extend(owned(permissions));
+        self.replay("revoke", args)
+    }
+
+    fn launch_url(&self, device: &str, url: &str) -> Result<()> {
+        self.replay("launch_url", owned(&[device, url]))
+    }
+
+    fn device_info(&self, device: &str) -> Result<DeviceInfo> {
+        self.replay("device_info", owned(&[device]))
+    }
+
+    fn network_info(&self, device: &str) -> Result<NetworkInfo> {
+        self.replay("network_info", owned(&[device]))
+    }
+
+    fn device_health(&self, device: &str) -> Result<DeviceHealth> {
+        self.replay("device_health", owned(&[device]))
+    }
+
+    fn screenshot(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        self.replay("screenshot", vec![device.to_string(), path_arg(&dest)])
+    }
+
+    fn record_screen(&self, device: &str, dest: Option<PathBuf>) -> Result<PathBuf> {
+        self.replay("record_screen", vec![device.to_string(), path_arg(&dest)])
+    }
+
+    fn enable_wifi(&self, device: &str) -> Result<String> {
+        self.replay("enable_wifi", owned(&[device]))
+    }
+
+    fn enable_usb(&self, device: &str) -> Result<()> {
+        self.replay("enable_usb", owned(&[device]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn transcript(exchanges: Value) -> Transcript {
+        serde_json::from_value(json!({ "exchanges": exchanges })).unwrap()
+    }
+
+    #[test]
+    fn replays_responses_in_order_and_errors() {
+        let replayer = Replayer::new(transcript(json!([
+            { "call": "pid_of", "args": ["emu", "com.foo"], "ok": null },
+            { "call": "pid_of", "args": ["emu", "com.foo"], "ok": "1234" },
+            { "call": "uninstall", "args": ["emu", "com.foo"], "error": "Failure [DELETE_FAILED_INTERNAL_ERROR]" },
+            { "call": "stream", "args": ["-s", "emu", "logcat"], "ok": ["a", "b"] },
+        ])));
+        assert_eq!(replayer.pid_of("emu", "com.foo").unwrap(), None);
+        assert_eq!(
+            replayer.pid_of("emu", "com.foo").unwrap().as_deref(),
+            Some("1234")
+        );
+        let err = replayer.pid_of("emu", "com.foo").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No recorded response for pid_of emu com.foo"
+        );
+        let err = replayer.uninstall("emu", "com.foo").unwrap_err();
+        assert!(err.to_string().contains("DELETE_FAILED_INTERNAL_ERROR"));
+        let lines: Vec<String> = replayer
+            .stream(&["-s", "emu", "logcat"])
+            .unwrap()
+            .map(|l| l.unwrap())
+            .collect();
+        assert_eq!(lines, ["a", "b"]);
+        assert!(replayer.unused().is_empty());
+    }
+
+    #[test]
+    fn recording_a_replay_reproduces_the_transcript() {
+        let original = transcript(json!([
+            { "call": "run", "args": ["-s", "emu", "get-state"],
+              "ok": { "success": true, "stdout": "device\n", "stderr": "" } },
+            { "call": "grant", "args": ["emu", "com.foo", "android.permission.CAMERA"], "ok": null },
+            { "call": "stream", "args": ["-s", "emu", "logcat"], "ok": ["x"] },
+            { "call": "install_apk", "args": ["emu", "app.apk"], "error": "Failure [INSTALL_FAILED_VERSION_DOWNGRADE]" },
+        ]));
+        let recorder = Recorder::new(Replayer::new(original.clone()));
+        recorder.run(&["-s", "emu", "get-state"]).unwrap();
+        recorder
+            .grant("emu", "com.foo", &["android.permission.CAMERA"])
+            .unwrap();
+        assert_eq!(
+            recorder.stream(&["-s", "emu", "logcat"]).unwrap().count(),
+            1
+        );
+        assert!(recorder.install_apk("emu", Path::new("app.apk")).is_err());
+        assert_eq!(recorder.transcript(), original);
+    }
+}
@@ -1,6 +1,13 @@
 //! Contains CLI argument parsing structs and enums.
-use clap::{Parser, Subcommand};
+use super::health::Threshold;
+use super::input;
+use super::intent::{Extra, IntentFlag};
+use super::logcat::LogLevel;
+use super::ui::Query;
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -12,12 +19,21 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub json: bool,
 
-    /// Target a specific device by serial number, skipping interactive selection
+    /// Target a device by serial, skipping interactive selection. `all` or a
+    /// comma-separated list runs the command on several devices in parallel
     #[arg(long, global = true, value_name = "SERIAL")]
     pub device: Option<String>,
+
+    /// Run on every ready device whose serial, model or product contains TAG
+    #[arg(long, global = true, value_name = "TAG")]
+    pub device_tag: Option<String>,
+
+    /// Never prompt — fail with an error naming the missing flag (implied when stdin is not a TTY)
+    #[arg(long, global = true)]
+    pub no_input: bool,
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Clone)]
 pub enum Commands {
     /// List connected ADB devices
     Devices,
@@ -25,32 +41,32 @@ pub enum Commands {
     Apps,
     /// Open an app
     Open {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
     },
     /// Uninstall an app
     Uninstall {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
     },
     /// Clear app data
     Clear {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
     },
     /// Force kill an app
     #[command(name = "force-kill")]
     ForceKill {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
     },
     /// Download APK
     Download {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
         #[arg(short, long)]
@@ -59,7 +75,7 @@ pub enum Commands {
     /// Show app info (version, etc)
     #[command(name = "app-info")]
     AppInfo {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
         /// Include permissions and other details
@@ -78,6 +94,112 @@ pub enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
+    /// Read the on-screen view hierarchy, find nodes by text or id, and tap them
+    Ui {
+        #[command(subcommand)]
+        command: UiCommand,
+    },
+    /// Send input to the device: taps, swipes, text and key events
+    Input {
+        #[command(subcommand)]
+        command: InputCommand,
+    },
+    /// Record touches and key presses to a file, and play them back on any device
+    Macro {
+        #[command(subcommand)]
+        command: MacroCommand,
+    },
+    /// Stream an app's logcat, following it across restarts
+    Logcat {
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
+        #[arg(long, value_name = "PACKAGE")]
+        package: Option<String>,
+        /// Minimum level to show
+        #[arg(short, long, value_parser = level_parser())]
+        level: Option<LogLevel>,
+        /// Only show these tags (comma-separated or repeated)
+        #[arg(short, long, value_name = "TAG", value_delimiter = ',')]
+        tag: Vec<String>,
+        /// Only show entries whose message or tag matches this regex
+        #[arg(short, long, value_name = "REGEX")]
+        grep: Option<String>,
+        /// Also write matching entries to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Show an app's crashes and ANRs with their stack traces
+    Crashes {
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
+        #[arg(long, value_name = "PACKAGE")]
+        package: Option<String>,
+        /// Keep watching and report new incidents as they happen
+        #[arg(short, long)]
+        watch: bool,
+        /// Save every incident, with app info attached, as JSON in this directory
+        #[arg(long, value_name = "DIR")]
+        save_dir: Option<PathBuf>,
+    },
+    /// Run on-device instrumentation tests and write a JUnit XML report
+    Test {
+        /// Test package, full or partial (e.g. `myapp.test`) — skips interactive selection
+        #[arg(long, value_name = "PACKAGE")]
+        package: Option<String>,
+        /// Instrumentation runner (defaults to the one the test APK declares)
+        #[arg(long, value_name = "RUNNER")]
+        runner: Option<String>,
+        /// Only run this class, or `Class#method` (repeatable)
+        #[arg(long = "class", value_name = "CLASS")]
+        class: Vec<String>,
+        /// Extra instrumentation argument, passed as `-e KEY VALUE` (repeatable)
+        #[arg(long = "arg", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        arg: Vec<(String, String)>,
+        /// Install these APKs (e.g. the app and its test APK) before running
+        #[arg(long, value_name = "APK")]
+        install: Vec<PathBuf>,
+        /// Directory for `junit.xml` and `summary.json`
+        #[arg(short, long, value_name = "DIR", default_value = "test-results")]
+        output: PathBuf,
+    },
+    /// Benchmark app start time over several launches
+    #[command(group(ArgGroup::new("start_mode").args(["cold", "warm", "hot"])))]
+    Startup {
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
+        #[arg(long, value_name = "PACKAGE")]
+        package: Option<String>,
+        /// Number of measured launches
+        #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        runs: u32,
+        /// Kill the process before every launch (default)
+        #[arg(long)]
+        cold: bool,
+        /// Keep the process, finish the activity with Back before every launch
+        #[arg(long)]
+        warm: bool,
+        /// Send the app to the background with Home before every launch
+        #[arg(long)]
+        hot: bool,
+        /// Compare against a previous `dab startup --json` result
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+        /// Fail when the median TotalTime regresses by more than this percentage
+        #[arg(long, value_name = "PERCENT", default_value_t = 10.0)]
+        threshold: f64,
+    },
+    /// Sample an app's memory, CPU, threads and jank over time
+    Perf {
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
+        #[arg(long, value_name = "PACKAGE")]
+        package: Option<String>,
+        /// Time between samples, e.g. `500ms`, `1s`
+        #[arg(short, long, default_value = "1s", value_parser = parse_duration)]
+        interval: Duration,
+        /// How long to sample for, e.g. `60s`, `5m` (Ctrl+C stops early)
+        #[arg(short, long, default_value = "60s", value_parser = parse_duration)]
+        duration: Duration,
+        /// Write the time series to this file: CSV for `.csv`, JSON otherwise
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
     /// Show network info (IP, WiFi, etc)
     Network,
     /// Enable ADB over Wi-Fi
@@ -85,15 +207,84 @@ pub enum Commands {
     /// Switch ADB back to USB mode
     Usb,
     /// Device health check (battery, storage, RAM, network)
-    Health,
+    Health {
+        /// Keep refreshing every INTERVAL (e.g. `5s`) until Ctrl+C
+        #[arg(short, long, value_name = "INTERVAL", value_parser = parse_duration)]
+        watch: Option<Duration>,
+        /// Fail when any threshold is crossed, e.g. `battery<20,storage>90,ram_free<0.5`
+        #[arg(long, value_name = "THRESHOLDS", value_delimiter = ',')]
+        fail_if: Vec<Threshold>,
+        /// Only warn when any threshold is crossed (same syntax as --fail-if)
+        #[arg(long, value_name = "THRESHOLDS", value_delimiter = ',')]
+        warn_if: Vec<Threshold>,
+        /// Append every sample to this CSV file
+        #[arg(long, value_name = "FILE")]
+        log: Option<PathBuf>,
+    },
+    /// Show the resumed activity with its task and fragments, and every task's back stack
+    Top {
+        /// Print each change until Ctrl+C, checking every INTERVAL (default `1s`)
+        #[arg(
+            short,
+            long,
+            value_name = "INTERVAL",
+            value_parser = parse_duration,
+            num_args = 0..=1,
+            default_missing_value = "1s"
+        )]
+        watch: Option<Duration>,
+    },
     /// Launch a URL or deep link in the Android device
     Launch {
         /// The URL or deep link to launch
         url: String,
     },
+    /// List the deep links an app declares, or pick one, fill it in and open it
+    Deeplinks {
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
+        #[arg(long, value_name = "PACKAGE", conflicts_with = "file")]
+        package: Option<String>,
+        /// Read the links from a local APK, XAPK or APKM file instead (needs aapt or aapt2)
+        #[arg(long, value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// Pick a link, fill in its placeholders and open it
+        #[arg(long)]
+        open: bool,
+        /// Check that each link opens its own activity rather than a chooser or browser
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Start an activity with an explicit intent: extras, flags, action and categories
+    Start {
+        /// Activity class, `.Relative` to the package or fully qualified (default: the launcher activity)
+        #[arg(long, value_name = "ACTIVITY")]
+        activity: Option<String>,
+        #[command(flatten)]
+        intent: IntentArgs,
+    },
+    /// Send a broadcast, with the same intent options as `start`
+    Broadcast {
+        /// Receiver class, `.Relative` to the package or fully qualified
+        #[arg(long, value_name = "RECEIVER")]
+        receiver: Option<String>,
+        #[command(flatten)]
+        intent: IntentArgs,
+    },
+    /// Start a service, with the same intent options as `start`
+    #[command(name = "start-service")]
+    StartService {
+        /// Service class, `.Relative` to the package or fully qualified
+        #[arg(long, value_name = "SERVICE")]
+        service: String,
+        /// Start it as a foreground service
+        #[arg(long)]
+        foreground: bool,
+        #[command(flatten)]
+        intent: IntentArgs,
+    },
     /// Grant permissions to an app
     Grant {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
         /// Comma-separated list of permissions — skips interactive selection
@@ -102,7 +293,7 @@ pub enum Commands {
     },
     /// Revoke permissions from an app
     Revoke {
-        /// Package name — skips interactive selection
+        /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
         #[arg(long, value_name = "PACKAGE")]
         package: Option<String>,
         /// Comma-separated list of permissions — skips interactive selection
@@ -114,6 +305,41 @@ pub enum Commands {
         /// Path to the APK, XAPK, or APKM file to install
         file: PathBuf,
     },
+    /// Apply a YAML or TOML device profile: installs, permissions, settings and more
+    Provision {
+        /// Path to the profile (`.yaml`, `.yml` or `.toml`)
+        profile: PathBuf,
+        /// Print the plan without changing the device
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Run the steps of a YAML scenario against the device, with a step-by-step report
+    Run {
+        /// Path to the scenario file
+        scenario: PathBuf,
+        /// Set or override a scenario variable, used as `${KEY}` (repeatable)
+        #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        var: Vec<(String, String)>,
+    },
+    /// Interactive shell that keeps the device and app in context between commands
+    #[command(name = "shell-mode")]
+    ShellMode,
+    /// Full-screen dashboard: live health, apps with actions, logcat and the foreground activity
+    Tui,
+    /// Run a Model Context Protocol server over stdio, exposing dab commands as agent tools
+    Mcp,
+    /// Serve a local HTTP/JSON API for device control
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 7070)]
+        port: u16,
+        /// Address to bind; anything but localhost exposes your devices to the network
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Require `Authorization: Bearer <TOKEN>` on every request
+        #[arg(long, env = "DAB_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+    },
     /// Show info for a local APK, XAPK, or APKM file
     Info {
         /// Path to the APK, XAPK, or APKM file to analyze
@@ -121,6 +347,215 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand, Clone)]
+pub enum UiCommand {
+    /// Capture the view hierarchy: an indented tree, JSON with --json, or the raw XML
+    Dump {
+        /// Print uiautomator's XML as is
+        #[arg(long)]
+        xml: bool,
+        /// Write it to FILE instead of printing it
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// List the nodes that match, with their bounds and center
+    Find {
+        #[command(flatten)]
+        query: NodeQuery,
+    },
+    /// Tap the center of the first matching node that's on screen
+    Tap {
+        #[command(flatten)]
+        query: NodeQuery,
+        /// Tap the INDEX-th match instead, counting from 0 in `ui find` order
+        #[arg(long, value_name = "INDEX")]
+        index: Option<usize>,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum InputCommand {
+    /// Tap at X,Y in pixels
+    Tap { x: u32, y: u32 },
+    /// Press and hold at X,Y
+    #[command(name = "long-press")]
+    LongPress {
+        x: u32,
+        y: u32,
+        /// How long to hold
+        #[arg(short, long, default_value = "1s", value_parser = parse_duration)]
+        duration: Duration,
+    },
+    /// Swipe from X1,Y1 to X2,Y2
+    Swipe {
+        x1: u32,
+        y1: u32,
+        x2: u32,
+        y2: u32,
+        /// How long the swipe takes
+        #[arg(short, long, default_value = "300ms", value_parser = parse_duration)]
+        duration: Duration,
+    },
+    /// Type text into the focused field, spaces and special characters included
+    Text { text: String },
+    /// Press a key: BACK, HOME, ENTER, VOLUME_UP, ..., a KEYCODE_ constant or a number
+    Key {
+        #[arg(value_parser = parse_key)]
+        key: String,
+        /// Hold the key instead of tapping it
+        #[arg(long)]
+        long: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+pub enum MacroCommand {
+    /// Record touches and key presses on the device until Ctrl+C
+    Record {
+        /// Where to save the macro (JSON)
+        file: PathBuf,
+    },
+    /// Replay a macro with its original timing, scaled to this device's screen
+    Play {
+        /// The macro to play
+        file: PathBuf,
+        /// Play faster or slower: 2x halves every pause and gesture
+        #[arg(long, default_value = "1x", value_parser = parse_speed)]
+        speed: f64,
+    },
+}
+
+/// What `ui find` and `ui tap` look for; every option given must match.
+#[derive(Args, Clone)]
+#[command(group(ArgGroup::new("selector").required(true).multiple(true).args(["text", "id", "desc"])))]
+pub struct NodeQuery {
+    /// Text the node shows (case-insensitive substring unless --exact)
+    #[arg(long, value_name = "TEXT")]
+    pub text: Option<String>,
+    /// Resource id, full (`com.app:id/login`) or just its name (`login`)
+    #[arg(long, value_name = "ID")]
+    pub id: Option<String>,
+    /// Content description (case-insensitive substring unless --exact)
+    #[arg(long, value_name = "DESC")]
+    pub desc: Option<String>,
+    /// Match --text and --desc whole and case-sensitively
+    #[arg(long)]
+    pub exact: bool,
+    /// Keep re-reading the screen for up to DURATION until a node matches
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub wait: Option<Duration>,
+}
+
+impl NodeQuery {
+    pub fn query(&self) -> Query {
+        Query {
+            text: self.text.clone(),
+            id: self.id.clone(),
+            desc: self.desc.clone(),
+            exact: self.exact,
+        }
+    }
+}
+
+/// The intent options `start`, `broadcast` and `start-service` share.
+#[derive(Args, Clone)]
+pub struct IntentArgs {
+    /// Package name, full or partial (e.g. `youtube`) — skips interactive selection
+    #[arg(long, value_name = "PACKAGE")]
+    pub package: Option<String>,
+    /// Intent action, e.g. `com.example.action.DEBUG`
+    #[arg(long, value_name = "ACTION")]
+    pub action: Option<String>,
+    /// Data URI
+    #[arg(long, value_name = "URI")]
+    pub data: Option<String>,
+    /// MIME type
+    #[arg(long = "type", value_name = "MIME")]
+    pub mime_type: Option<String>,
+    /// Intent category (repeatable)
+    #[arg(long = "category", value_name = "CATEGORY")]
+    pub categories: Vec<String>,
+    /// Extra as KEY:TYPE=VALUE, e.g. `n:int=3` (repeatable). Types: string (the default
+    /// for KEY=VALUE), int, long, float, double, bool, uri, component, int[], long[],
+    /// float[], string[]; `KEY:null` sends a null string
+    #[arg(long = "extra", value_name = "KEY:TYPE=VALUE")]
+    pub extras: Vec<Extra>,
+    /// Intent flags by name, e.g. `clear-task,new-task`, or as a hex value
+    #[arg(long, value_name = "FLAGS", value_delimiter = ',')]
+    pub flags: Vec<IntentFlag>,
+}
+
+impl IntentArgs {
+    /// Whether the intent names what it's for, rather than only where it goes.
+    pub fn implicit(&self) -> bool {
+        self.action.is_some() || self.data.is_some()
+    }
+}
+
+/// Parse a key name into its `KEYCODE_` constant.
+fn parse_key(s: &str) -> Result<String, String> {
+    input::keycode(s)
+}
+
+/// Parse a `KEY=VALUE` argument.
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got `{}`", s)),
+    }
+}
+
+/// Parse a duration such as `500ms`, `10s`, `5m` or a bare number of seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("expected a duration like 500ms, 10s or 5m, got `{}`", s))?;
+    let secs = match unit {
+        "ms" => value / 1000.0,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => {
+            return Err(format!(
+                "unknown duration unit `{}` (use ms, s, m or h)",
+                unit
+            ))
+        }
+    };
+    if secs <= 0.0 {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("duration `{}` is too long", s))
+}
+
+/// `--level`: logcat's priorities by name, or by their first letter.
+fn level_parser() -> impl TypedValueParser<Value = LogLevel> {
+    PossibleValuesParser::new(
+        ["verbose", "debug", "info", "warn", "error", "fatal"]
+            .map(|name| PossibleValue::new(name).alias(&name[..1])),
+    )
+    .try_map(|s| s.parse::<LogLevel>())
+}
+
+/// Parse a playback speed like `2x`, `0.5x` or `2`.
+fn parse_speed(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let speed: f64 = s
+        .strip_suffix(['x', 'X'])
+        .unwrap_or(s)
+        .parse()
+        .map_err(|_| format!("expected a speed like 2x or 0.5x, got `{}`", s))?;
+    if !(speed > 0.0 && speed.is_finite()) {
+        return Err("speed must be greater than zero".to_string());
+    }
+    Ok(speed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,6 +574,22 @@ mod tests {
         assert!(matches!(cli.command, Some(Commands::Devices)));
     }
 
+    #[test]
+    fn parses_global_no_input_flag_after_subcommand() {
+        let cli = Cli::try_parse_from(["dab", "open", "--no-input"]).unwrap();
+        assert!(cli.no_input);
+        assert!(matches!(cli.command, Some(Commands::Open { .. })));
+    }
+
+    #[test]
+    fn parses_device_list_and_tag() {
+        let cli =
+            Cli::try_parse_from(["dab", "health", "--device", "a,b", "--device-tag", "pixel"])
+                .unwrap();
+        assert_eq!(cli.device.as_deref(), Some("a,b"));
+        assert_eq!(cli.device_tag.as_deref(), Some("pixel"));
+    }
+
     #[test]
     fn parses_uninstall_package() {
         let cli = Cli::try_parse_from(["dab", "uninstall", "--package", "com.foo"]).unwrap();
@@ -150,6 +601,81 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parses_logcat_filters() {
+        let cli = Cli::try_parse_from([
+            "dab",
+            "logcat",
+            "--package",
+            "foo",
+            "-l",
+            "w",
+            "--tag",
+            "A,B",
+            "--tag",
+            "C",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Logcat { level, tag, .. }) => {
+                assert_eq!(level, Some(LogLevel::Warn));
+                assert_eq!(tag, vec!["A", "B", "C"]);
+            }
+            _ => panic!("expected logcat command"),
+        }
+    }
+
+    #[test]
+    fn parses_test_classes_and_args() {
+        let cli = Cli::try_parse_from([
+            "dab",
+            "test",
+            "--class",
+            "com.foo.A",
+            "--class",
+            "com.foo.B#b",
+            "--arg",
+            "size=large",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Commands::Test { class, arg, .. }) => {
+                assert_eq!(class, vec!["com.foo.A", "com.foo.B#b"]);
+                assert_eq!(arg, vec![("size".to_string(), "large".to_string())]);
+            }
+            _ => panic!("expected test command"),
+        }
+        assert!(Cli::try_parse_from(["dab", "test", "--arg", "nope"]).is_err());
+    }
+
+    #[test]
+    fn startup_modes_are_exclusive() {
+        let cli = Cli::try_parse_from(["dab", "startup", "--warm", "-r", "5"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Startup {
+                warm: true,
+                runs: 5,
+                ..
+            })
+        ));
+        assert!(Cli::try_parse_from(["dab", "startup", "--cold", "--hot"]).is_err());
+        assert!(Cli::try_parse_from(["dab", "startup", "--runs", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("10 parsecs").is_err());
+        assert!(parse_duration("99999999999999999999h").is_err());
+        assert_eq!(parse_speed("2x"), Ok(2.0));
+        assert_eq!(parse_speed("0.5"), Ok(0.5));
+        assert!(parse_speed("0x").is_err());
+    }
+
     #[test]
     fn parses_grant_package_and_permissions() {
         let cli = Cli::try_parse_from([
@@ -0,0 +1,351 @@
+//! Crash and ANR extraction from logcat.
+//!
+//! [`CrashCollector`] is fed parsed [`LogEntry`] values and assembles the
+//! multi-line blocks Android logs for an incident — the `AndroidRuntime`
+//! `FATAL EXCEPTION` stack trace, the `DEBUG` tombstone summary of a native
+//! crash, and ActivityManager's `ANR in` report — into [`Incident`] records.
+
+use super::logcat::LogEntry;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentKind {
+    Crash,
+    NativeCrash,
+    Anr,
+}
+
+impl IncidentKind {
+    /// Heading for the terminal.
+    pub fn label(self) -> &'static str {
+        match self {
+            IncidentKind::Crash => "FATAL EXCEPTION",
+            IncidentKind::NativeCrash => "NATIVE CRASH",
+            IncidentKind::Anr => "ANR",
+        }
+    }
+}
+
+/// One crash or ANR, as printed under `--json` and saved by `--save-dir`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    pub package: String,
+    pub pid: Option<u32>,
+    pub time: String,
+    /// Exception line, signal or ANR reason.
+    pub summary: String,
+    /// Stack frames (or the rest of the ANR report).
+    pub trace: Vec<String>,
+}
+
+impl Incident {
+    fn start(kind: IncidentKind, entry: &LogEntry) -> Self {
+        Incident {
+            kind,
+            package: String::new(),
+            pid: None,
+            time: entry.time.clone(),
+            summary: String::new(),
+            trace: Vec::new(),
+        }
+    }
+
+    /// Whether this incident happened in `package` or one of its `:process`es.
+    pub fn belongs_to(&self, package: &str) -> bool {
+        self.package
+            .strip_prefix(package)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
+    }
+
+    fn absorb(&mut self, message: &str) {
+        let line = message.trim();
+        match self.kind {
+            IncidentKind::Crash => {
+                if let Some(rest) = line.strip_prefix("Process: ") {
+                    if let Some((package, pid)) = rest.split_once(", PID: ") {
+                        self.package = package.to_string();
+                        self.pid = pid.trim().parse().ok();
+                    }
+                } else if self.summary.is_empty() {
+                    self.summary = line.to_string();
+                } else {
+                    self.trace.push(line.to_string());
+                }
+            }
+            IncidentKind::NativeCrash => {
+                if let Some((_, rest)) = line.split_once(">>> ") {
+                    if let Some((package, _)) = rest.split_once(" <<<") {
+                        self.package = package.to_string();
+                    }
+                    self.pid = line
+                        .strip_prefix("pid: ")
+                        .and_then(|p| p.split(',').next())
+                        .and_then(|p| p.trim().parse().ok());
+                } else if let Some(cmdline) = line.strip_prefix("Cmdline: ") {
+                    if self.package.is_empty() {
+                        self.package = cmdline.trim().to_string();
+                    }
+                } else if line.starts_with("signal ") {
+                    self.summary = line.to_string();
+                } else if line.starts_with("Abort message:") || line.starts_with('#') {
+                    self.trace.push(line.to_string());
+                }
+            }
+            IncidentKind::Anr => {
+                if let Some(pid) = line.strip_prefix("PID: ") {
+                    self.pid = pid.trim().parse().ok();
+                } else if let Some(reason) = line.strip_prefix("Reason: ") {
+                    self.summary = reason.to_string();
+                } else if !line.is_empty() {
+                    self.trace.push(line.to_string());
+                }
+            }
+        }
+    }
+
+    /// File name for `--save-dir`: `<time>-<kind>-<package>.json`.
+    pub fn file_name(&self) -> String {
+        let safe = |s: &str| -> String {
+            s.chars()
+                .map(|c| match c {
+                    ' ' => '_',
+                    ':' | '/' | '\\' => '-',
+                    c => c,
+                })
+                .collect()
+        };
+        let kind = serde_json::to_value(self.kind)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default();
+        format!("{}-{}-{}.json", safe(&self.time), kind, safe(&self.package))
+    }
+}
+
+/// Assembles incidents from a logcat stream, keeping those of one package.
+pub struct CrashCollector {
+    package: String,
+    current: Option<Block>,
+}
+
+/// The incident being assembled and the line that opened it.
+struct Block {
+    incident: Incident,
+    pid: u32,
+    tid: u32,
+    tag: String,
+}
+
+impl Block {
+    /// Whether `entry` is the next line of this block.
+    ///
+    /// A Java crash is logged by the crashing thread, so its lines share the
+    /// opening line's pid and tid. ActivityManager logs the whole ANR report in
+    /// one call, so its lines also share the opening line's timestamp; anything
+    /// else it logs afterwards, even from the same process, is not part of the
+    /// report.
+    fn continues(&self, entry: &LogEntry) -> bool {
+        let same = self.pid == entry.pid && self.tag == entry.tag;
+        match self.incident.kind {
+            IncidentKind::Crash => same && self.tid == entry.tid,
+            IncidentKind::NativeCrash => same,
+            IncidentKind::Anr => same && self.tid == entry.tid && self.incident.time == entry.time,
+        }
+    }
+
+    /// Whether `entry` is another process or thread's line that the merged
+    /// buffers interleaved into a Java stack trace, and should be skipped
+    /// rather than end it.
+    fn interleaves(&self, entry: &LogEntry) -> bool {
+        self.incident.kind == IncidentKind::Crash
+            && (self.pid != entry.pid || self.tid != entry.tid)
+    }
+}
+
+impl CrashCollector {
+    pub fn new(package: &str) -> Self {
+        CrashCollector {
+            package: package.to_string(),
+            current: None,
+        }
+    }
+
+    /// Feed the next entry. Returns an incident once its block has ended,
+    /// which is when a line that does not continue it arrives. Lines that
+    /// only interleave with a Java stack trace are skipped, and the next
+    /// incident's opening line always ends the block.
+    pub fn feed(&mut self, entry: &LogEntry) -> Option<Incident> {
+        let kind = match entry.tag.as_str() {
+            "AndroidRuntime" if entry.message.starts_with("FATAL EXCEPTION") => {
+                Some(IncidentKind::Crash)
+            }
+            "DEBUG" if entry.message.contains("*** *** ***") => Some(IncidentKind::NativeCrash),
+            "ActivityManager" if entry.message.starts_with("ANR in ") => Some(IncidentKind::Anr),
+            _ => None,
+        };
+        if let Some(block) = &mut self.current {
+            if block.continues(entry) {
+                block.incident.absorb(&entry.message);
+                return None;
+            }
+            if kind.is_none() && block.interleaves(entry) {
+                return None;
+            }
+        }
+        let finished = self.finish();
+        if let Some(kind) = kind {
+            let mut incident = Incident::start(kind, entry);
+            if kind == IncidentKind::Anr {
+                let rest = entry.message.trim_start_matches("ANR in ");
+                incident.package = rest.split([' ', '(']).next().unwrap_or("").to_string();
+            }
+            self.current = Some(Block {
+                incident,
+                pid: entry.pid,
+                tid: entry.tid,
+                tag: entry.tag.clone(),
+            });
+        }
+        finished
+    }
+
+    /// Flush the block in progress, e.g. at the end of a `logcat -d` dump or
+    /// when a live stream has gone quiet.
+    pub fn finish(&mut self) -> Option<Incident> {
+        self.current
+            .take()
+            .map(|block| block.incident)
+            .filter(|incident| incident.belongs_to(&self.package))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(log: &str, package: &str) -> Vec<Incident> {
+        let mut collector = CrashCollector::new(package);
+        let mut incidents: Vec<Incident> = log
+            .lines()
+            .filter_map(LogEntry::parse)
+            .filter_map(|e| collector.feed(&e))
+            .collect();
+        incidents.extend(collector.finish());
+        incidents
+    }
+
+    const LOG: &str = "\
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: FATAL EXCEPTION: main
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: Process: com.foo, PID: 1000
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: java.lang.IllegalStateException: boom
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: \tat com.foo.Main.onCreate(Main.java:10)
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: \tat android.app.Activity.performCreate(Activity.java:8000)
+10-16 12:00:00.100   500   600 W ActivityManager:   Force finishing activity com.foo/.Main
+10-16 12:00:01.000  2000  2000 E AndroidRuntime: FATAL EXCEPTION: main
+10-16 12:00:01.000  2000  2000 E AndroidRuntime: Process: com.other, PID: 2000
+10-16 12:00:01.000  2000  2000 E AndroidRuntime: java.lang.NullPointerException
+10-16 12:00:02.000  3000  3000 F DEBUG   : *** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***
+10-16 12:00:02.000  3000  3000 F DEBUG   : pid: 1100, tid: 1100, name: com.foo  >>> com.foo <<<
+10-16 12:00:02.000  3000  3000 F DEBUG   : signal 11 (SIGSEGV), code 1 (SEGV_MAPERR), fault addr 0x0
+10-16 12:00:02.000  3000  3000 F DEBUG   :     x0  0000000000000000  x1  0000007fc0a1b2c8
+10-16 12:00:02.000  3000  3000 F DEBUG   : backtrace:
+10-16 12:00:02.000  3000  3000 F DEBUG   :       #00 pc 000000000001a2b4  /data/app/com.foo/lib/arm64/libfoo.so (crash+20)
+10-16 12:00:03.000   500   600 E ActivityManager: ANR in com.foo (com.foo/.Main)
+10-16 12:00:03.000   500   600 E ActivityManager: PID: 1100
+10-16 12:00:03.000   500   600 E ActivityManager: Reason: Input dispatching timed out
+10-16 12:00:03.000   500   600 E ActivityManager: Load: 5.2 / 4.1 / 3.0
+";
+
+    #[test]
+    fn extracts_java_crash_with_stack_trace() {
+        let incidents = collect(LOG, "com.foo");
+        let crash = &incidents[0];
+        assert_eq!(crash.kind, IncidentKind::Crash);
+        assert_eq!(crash.package, "com.foo");
+        assert_eq!(crash.pid, Some(1000));
+        assert_eq!(crash.summary, "java.lang.IllegalStateException: boom");
+        assert_eq!(crash.trace.len(), 2);
+        assert!(crash.trace[0].starts_with("at com.foo.Main.onCreate"));
+    }
+
+    #[test]
+    fn extracts_native_crash_and_anr() {
+        let incidents = collect(LOG, "com.foo");
+        assert_eq!(incidents.len(), 3);
+        let native = &incidents[1];
+        assert_eq!(native.kind, IncidentKind::NativeCrash);
+        assert_eq!(native.pid, Some(1100));
+        assert!(native.summary.starts_with("signal 11 (SIGSEGV)"));
+        assert_eq!(native.trace.len(), 1);
+        let anr = &incidents[2];
+        assert_eq!(anr.kind, IncidentKind::Anr);
+        assert_eq!(anr.summary, "Input dispatching timed out");
+        assert_eq!(anr.trace, vec!["Load: 5.2 / 4.1 / 3.0"]);
+    }
+
+    #[test]
+    fn keeps_only_the_requested_package() {
+        let incidents = collect(LOG, "com.other");
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(incidents[0].summary, "java.lang.NullPointerException");
+        assert!(collect(LOG, "com.fo").is_empty());
+    }
+
+    #[test]
+    fn anr_report_ends_at_the_next_activity_manager_message() {
+        let log = "\
+10-16 12:00:03.000   500   600 E ActivityManager: ANR in com.foo (com.foo/.Main)
+10-16 12:00:03.000   500   600 E ActivityManager: PID: 1100
+10-16 12:00:03.000   500   600 E ActivityManager: Reason: Input dispatching timed out
+10-16 12:00:03.200   500   600 I ActivityManager: Killing 1100:com.foo/u0a123 (adj 0): bg anr
+10-16 12:00:03.300   500   610 I ActivityManager: Start proc 1200:com.foo/u0a123 for activity
+";
+        let mut collector = CrashCollector::new("com.foo");
+        let mut entries = log.lines().filter_map(LogEntry::parse);
+        for entry in entries.by_ref().take(3) {
+            assert!(collector.feed(&entry).is_none());
+        }
+        let anr = collector.feed(&entries.next().unwrap()).unwrap();
+        assert_eq!(anr.summary, "Input dispatching timed out");
+        assert!(anr.trace.is_empty());
+        assert!(collector.feed(&entries.next().unwrap()).is_none());
+        assert!(collector.finish().is_none());
+    }
+
+    #[test]
+    fn stack_trace_survives_interleaved_lines() {
+        let log = "\
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: FATAL EXCEPTION: main
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: Process: com.foo, PID: 1000
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: java.lang.IllegalStateException: boom
+10-16 12:00:00.001   500   600 W ActivityManager:   Force finishing activity com.foo/.Main
+10-16 12:00:00.001  1000  1010 I OkHttp  : <-- HTTP FAILED
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: \tat com.foo.Main.onCreate(Main.java:10)
+10-16 12:00:00.000  1000  1000 E AndroidRuntime: \tat android.app.Activity.performCreate(Activity.java:8000)
+10-16 12:00:00.100  1000  1000 I Process : Sending signal. PID: 1000 SIG: 9
+";
+        let incidents = collect(log, "com.foo");
+        assert_eq!(incidents.len(), 1);
+        assert_eq!(
+            incidents[0].summary,
+            "java.lang.IllegalStateException: boom"
+        );
+        assert_eq!(incidents[0].trace.len(), 2);
+    }
+
+    #[test]
+    fn file_name_is_filesystem_safe() {
+        let mut incidents = collect(LOG, "com.foo");
+        assert_eq!(
+            incidents[2].file_name(),
+            "10-16_12-00-03.000-anr-com.foo.json"
+        );
+        incidents[0].package = "com.foo:remote".to_string();
+        assert_eq!(
+            incidents[0].file_name(),
+            "10-16_12-00-00.000-crash-com.foo-remote.json"
+        );
+    }
+}
@@ -0,0 +1,686 @@
+//! Deep links an app declares: its `android.intent.action.VIEW` intent
+//! filters with a URI scheme, read from `dumpsys package` for an installed
+//! app or from `aapt dump xmltree` for a local APK.
+//!
+//! Every scheme/host/path combination a filter accepts becomes a
+//! [`DeepLink`] whose URL marks the parts the pattern leaves open as
+//! `{placeholders}`, e.g. `https://example.com/product/{any}`.
+
+use super::activity::same_component;
+use serde::Serialize;
+
+const VIEW: &str = "android.intent.action.VIEW";
+
+/// How an intent filter's path matches, as in Android's `PatternMatcher`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathKind {
+    /// `android:path`
+    Literal,
+    /// `android:pathPrefix`
+    Prefix,
+    /// `android:pathPattern`
+    Glob,
+    /// `android:pathAdvancedPattern`
+    AdvancedGlob,
+    /// `android:pathSuffix`
+    Suffix,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathPattern {
+    pub kind: PathKind,
+    pub pattern: String,
+}
+
+/// One `<intent-filter>` that accepts `VIEW` intents for some URI scheme.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntentFilter {
+    /// The declaring activity's component, e.g. `com.foo/.LinkActivity`.
+    pub activity: String,
+    pub schemes: Vec<String>,
+    /// Hosts, with `:port` when the filter names one.
+    pub hosts: Vec<String>,
+    pub paths: Vec<PathPattern>,
+    pub auto_verify: bool,
+}
+
+/// One scheme/host/path combination an activity accepts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeepLink {
+    pub activity: String,
+    pub scheme: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathPattern>,
+    /// `android:autoVerify`: an App Link Android may open without asking.
+    pub auto_verify: bool,
+    /// The link, with a `{placeholder}` for every part the filter leaves open.
+    pub url: String,
+    /// The placeholders in `url`, in order.
+    pub placeholders: Vec<String>,
+    /// Set by `--verify`: where the link's sample URL actually resolves.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+    #[serde(skip)]
+    samples: Vec<String>,
+}
+
+impl DeepLink {
+    /// `url` with each placeholder replaced by the matching entry of `values`.
+    pub fn fill(&self, values: &[String]) -> String {
+        self.placeholders
+            .iter()
+            .zip(values)
+            .fold(self.url.clone(), |url, (name, value)| {
+                url.replacen(&format!("{{{}}}", name), value, 1)
+            })
+    }
+
+    /// A concrete URL the filter accepts, for checking where it resolves.
+    pub fn sample_url(&self) -> String {
+        self.fill(&self.samples)
+    }
+
+    /// A value for the placeholder at `index` that the pattern accepts.
+    pub fn sample(&self, index: usize) -> &str {
+        self.samples.get(index).map_or("", String::as_str)
+    }
+}
+
+/// The deep links of one app.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeepLinks {
+    pub package: String,
+    pub links: Vec<DeepLink>,
+}
+
+/// Where a deep link resolves, compared with the activity that declares it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Verification {
+    pub url: String,
+    pub expected: String,
+    /// The activity `url` opens; `None` when nothing handles it.
+    pub resolved: Option<String>,
+    pub verified: bool,
+}
+
+impl Verification {
+    pub fn new(url: &str, expected: &str, resolved: Option<String>) -> Self {
+        let verified = resolved
+            .as_deref()
+            .is_some_and(|r| same_component(r, expected));
+        Verification {
+            url: url.to_string(),
+            expected: expected.to_string(),
+            resolved,
+            verified,
+        }
+    }
+
+    /// What happens instead of the expected activity opening.
+    pub fn problem(&self) -> Option<String> {
+        if self.verified {
+            return None;
+        }
+        Some(match &self.resolved {
+            None => "no activity handles it".to_string(),
+            Some(r) if r.contains("ResolverActivity") || r.contains("ChooserActivity") => {
+                "Android asks which app should open it".to_string()
+            }
+            Some(r) => format!("it opens {}", r),
+        })
+    }
+}
+
+/// The component `cmd package resolve-activity --brief` printed, if any.
+pub fn parse_resolved(output: &str) -> Option<String> {
+    output
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|l| l.contains('/') && !l.contains(' '))
+        .map(str::to_string)
+}
+
+/// Expand filters into one link per scheme, host and path they accept.
+pub fn links(filters: &[IntentFilter]) -> Vec<DeepLink> {
+    let mut links = Vec::new();
+    for filter in filters {
+        for scheme in &filter.schemes {
+            let hosts: Vec<Option<&String>> = if filter.hosts.is_empty() {
+                vec![None]
+            } else {
+                filter.hosts.iter().map(Some).collect()
+            };
+            for host in hosts {
+                // Android ignores paths on a filter without an authority
+                let paths: Vec<Option<&PathPattern>> = if host.is_none() || filter.paths.is_empty()
+                {
+                    vec![None]
+                } else {
+                    filter.paths.iter().map(Some).collect()
+                };
+                for path in paths {
+                    let template = template(scheme, host.map(String::as_str), path);
+                    links.push(DeepLink {
+                        activity: filter.activity.clone(),
+                        scheme: scheme.clone(),
+                        host: host.cloned(),
+                        path: path.cloned(),
+                        auto_verify: filter.auto_verify,
+                        url: template.url,
+                        placeholders: template.names,
+                        verification: None,
+                        samples: template.samples,
+                    });
+                }
+            }
+        }
+    }
+    links
+}
+
+#[derive(Default)]
+struct Template {
+    url: String,
+    names: Vec<String>,
+    samples: Vec<String>,
+}
+
+impl Template {
+    fn text(&mut self, text: &str) {
+        self.url.push_str(text);
+    }
+
+    /// Add `{name}`, numbered from the second use on: `{any}`, `{any2}`.
+    fn placeholder(&mut self, name: &str, sample: &str) {
+        let uses = self
+            .names
+            .iter()
+            .filter(|n| n.trim_end_matches(|c: char| c.is_ascii_digit()) == name)
+            .count();
+        let name = match uses {
+            0 => name.to_string(),
+            n => format!("{}{}", name, n + 1),
+        };
+        self.url.push_str(&format!("{{{}}}", name));
+        self.names.push(name);
+        self.samples.push(sample.to_string());
+    }
+}
+
+fn template(scheme: &str, host: Option<&str>, path: Option<&PathPattern>) -> Template {
+    let mut t = Template::default();
+    t.text(&format!("{}://", scheme));
+    let Some(host) = host else {
+        t.placeholder("path", "x");
+        return t;
+    };
+    if host == "*" {
+        t.placeholder("host", "example.com");
+    } else if let Some(domain) = host.strip_prefix('*') {
+        t.placeholder("subdomain", "www");
+        t.text(domain);
+    } else {
+        t.text(host);
+    }
+    let Some(path) = path else {
+        return t;
+    };
+    if path.kind == PathKind::Suffix {
+        t.text("/");
+        t.placeholder("path", "x");
+        t.text(&path.pattern);
+        return t;
+    }
+    if !path.pattern.starts_with('/') {
+        t.text("/");
+    }
+    match path.kind {
+        PathKind::Literal => t.text(&path.pattern),
+        PathKind::Prefix => {
+            t.text(&path.pattern);
+            t.placeholder("rest", "");
+        }
+        PathKind::Glob => glob(&mut t, &path.pattern, false),
+        PathKind::AdvancedGlob => glob(&mut t, &path.pattern, true),
+        PathKind::Suffix => unreachable!("handled above"),
+    }
+    t
+}
+
+/// Turn a path pattern into text and placeholders. Simple globs know `.`
+/// (any character), `*` (repeat) and `\` escapes; advanced ones add
+/// `[classes]`, `+` and `{n,m}`.
+fn glob(t: &mut Template, pattern: &str, advanced: bool) {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        // The atom, a sample character it matches, and whether it's literal
+        let (sample, literal, mut next) = match chars[i] {
+            '\\' if i + 1 < chars.len() => (chars[i + 1], true, i + 2),
+            '.' => ('x', false, i + 1),
+            '[' if advanced => match chars[i..].iter().position(|&c| c == ']') {
+                Some(len) => (class_sample(&chars[i + 1..i + len]), false, i + len + 1),
+                None => ('[', true, i + 1),
+            },
+            c => (c, true, i + 1),
+        };
+        let mut repeat = 1;
+        match chars.get(next) {
+            Some('*') | Some('+') => next += 1,
+            Some('{') if advanced => {
+                if let Some(len) = chars[next..].iter().position(|&c| c == '}') {
+                    let bounds: String = chars[next + 1..next + len].iter().collect();
+                    let min = bounds.split(',').next().unwrap_or_default().trim();
+                    repeat = min.parse().unwrap_or(1).max(1);
+                    next += len + 1;
+                }
+            }
+            _ => {}
+        }
+        let sample = sample.to_string().repeat(repeat);
+        if literal {
+            t.text(&sample);
+        } else {
+            t.placeholder("any", &sample);
+        }
+        i = next;
+    }
+}
+
+/// A character a `[...]` class body accepts.
+fn class_sample(class: &[char]) -> char {
+    match class.first() {
+        None | Some('^') => 'x',
+        Some('\\') => class.get(1).copied().unwrap_or('x'),
+        Some(&c) => c,
+    }
+}
+
+/// Intent filters from `dumpsys package <pkg>`'s Activity Resolver Table.
+///
+/// The table lists a filter once per scheme it handles, so filters are
+/// deduplicated by their id.
+pub fn parse_dumpsys(dump: &str) -> Vec<IntentFilter> {
+    let mut filters = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    let mut current: Option<(IntentFilter, bool)> = None;
+    let mut in_table = false;
+    let mut flush = |current: &mut Option<(IntentFilter, bool)>| {
+        if let Some((filter, true)) = current.take() {
+            if !filter.schemes.is_empty() {
+                filters.push(filter);
+            }
+        }
+    };
+    for line in dump.lines() {
+        let trimmed = line.trim();
+        if !line.starts_with(' ') && trimmed.ends_with(':') {
+            flush(&mut current);
+            in_table = trimmed == "Activity Resolver Table:";
+            continue;
+        }
+        if !in_table {
+            continue;
+        }
+        let words: Vec<&str> = trimmed.split_whitespace().collect();
+        if let [_, component, "filter", id, ..] = words[..] {
+            flush(&mut current);
+            let key = format!("{} {}", component, id);
+            if !seen.contains(&key) {
+                seen.push(key);
+                let filter = IntentFilter {
+                    activity: component.to_string(),
+                    ..Default::default()
+                };
+                current = Some((filter, false));
+            }
+            continue;
+        }
+        if trimmed.ends_with(':') && !trimmed.contains(' ') {
+            flush(&mut current);
+            continue;
+        }
+        let Some((filter, is_view)) = current.as_mut() else {
+            continue;
+        };
+        if let Some(action) = trimmed.strip_prefix("Action: ") {
+            *is_view |= unquote(action) == VIEW;
+        } else if let Some(scheme) = trimmed.strip_prefix("Scheme: ") {
+            filter.schemes.push(unquote(scheme).to_string());
+        } else if let Some(authority) = trimmed.strip_prefix("Authority: ") {
+            // `"example.com": -1`, the port being -1 when unset
+            let (host, port) = authority.rsplit_once(": ").unwrap_or((authority, "-1"));
+            filter.hosts.push(match port.trim() {
+                "-1" => unquote(host).to_string(),
+                port => format!("{}:{}", unquote(host), port),
+            });
+        } else if let Some(path) = trimmed.strip_prefix("Path: ") {
+            // `"PatternMatcher{PREFIX: /product}"`
+            let inner = unquote(path)
+                .trim_start_matches("PatternMatcher{")
+                .trim_end_matches('}');
+            if let Some((kind, pattern)) = inner.split_once(": ") {
+                let kind = match kind {
+                    "LITERAL" => Some(PathKind::Literal),
+                    "PREFIX" => Some(PathKind::Prefix),
+                    "GLOB" => Some(PathKind::Glob),
+                    "ADVANCED_GLOB" => Some(PathKind::AdvancedGlob),
+                    "SUFFIX" => Some(PathKind::Suffix),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    filter.paths.push(PathPattern {
+                        kind,
+                        pattern: pattern.to_string(),
+                    });
+                }
+            }
+        } else if trimmed.contains("AutoVerify=true") {
+            filter.auto_verify = true;
+        }
+    }
+    flush(&mut current);
+    filters
+}
+
+fn unquote(s: &str) -> &str {
+    s.trim().trim_matches('"')
+}
+
+/// The package name and intent filters in `aapt dump xmltree <apk>
+/// AndroidManifest.xml` (or the `aapt2` equivalent).
+pub fn parse_xmltree(dump: &str) -> (Option<String>, Vec<IntentFilter>) {
+    let mut package = None;
+    let mut filters = Vec::new();
+    // Open elements as (indent, name)
+    let mut open: Vec<(usize, String)> = Vec::new();
+    let mut activity: Option<String> = None;
+    let mut current: Option<(IntentFilter, bool)> = None;
+    // Index into `hosts` of the `<data>` element being read, for its port
+    let mut data_host: Option<usize> = None;
+    let mut flush = |current: &mut Option<(IntentFilter, bool)>| {
+        if let Some((filter, true)) = current.take() {
+            if !filter.schemes.is_empty() {
+                filters.push(filter);
+            }
+        }
+    };
+    for line in dump.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim();
+        if let Some(element) = trimmed.strip_prefix("E: ") {
+            while open.last().is_some_and(|(i, _)| *i >= indent) {
+                if let Some((_, name)) = open.pop() {
+                    if name == "intent-filter" {
+                        flush(&mut current);
+                    }
+                }
+            }
+            let name = element.split_whitespace().next().unwrap_or_default();
+            match name {
+                "activity" | "activity-alias" => activity = None,
+                "intent-filter" => {
+                    current = activity.as_ref().map(|a| {
+                        let filter = IntentFilter {
+                            activity: a.clone(),
+                            ..Default::default()
+                        };
+                        (filter, false)
+                    })
+                }
+                _ => {}
+            }
+            data_host = None;
+            open.push((indent, name.to_string()));
+            continue;
+        }
+        let Some((attr, value)) = trimmed.strip_prefix("A: ").and_then(parse_attribute) else {
+            continue;
+        };
+        let element = open.last().map_or("", |(_, name)| name.as_str());
+        match (element, attr) {
+            ("manifest", "package") => package = Some(value),
+            ("activity" | "activity-alias", "name") => {
+                let package = package.as_deref().unwrap_or_default();
+                activity = Some(if value.contains('.') {
+                    format!("{}/{}", package, value)
+                } else {
+                    format!("{}/.{}", package, value)
+                });
+            }
+            (_, _) if current.is_none() => {}
+            ("intent-filter", "autoVerify") => {
+                if let Some((filter, _)) = current.as_mut() {
+                    filter.auto_verify = value == "true" || value.ends_with("0xffffffff");
+                }
+            }
+            ("action", "name") => {
+                if let Some((_, is_view)) = current.as_mut() {
+                    *is_view |= value == VIEW;
+                }
+            }
+            ("data", attr) => {
+                let Some((filter, _)) = current.as_mut() else {
+                    continue;
+                };
+                let kind = match attr {
+                    "scheme" => {
+                        filter.schemes.push(value);
+                        continue;
+                    }
+                    "host" => {
+                        data_host = Some(filter.hosts.len());
+                        filter.hosts.push(value);
+                        continue;
+                    }
+                    "port" => {
+                        if let Some(host) = data_host.and_then(|i| filter.hosts.get_mut(i)) {
+                            host.push_str(&format!(":{}", value));
+                        }
+                        continue;
+                    }
+                    "path" => PathKind::Literal,
+                    "pathPrefix" => PathKind::Prefix,
+                    "pathPattern" => PathKind::Glob,
+                    "pathAdvancedPattern" => PathKind::AdvancedGlob,
+                    "pathSuffix" => PathKind::Suffix,
+                    _ => continue,
+                };
+                filter.paths.push(PathPattern {
+                    kind,
+                    pattern: value,
+                });
+            }
+            _ => {}
+        }
+    }
+    flush(&mut current);
+    (package, filters)
+}
+
+/// `android:scheme(0x01010027)="https" (Raw: "https")` as `("scheme",
+/// "https")`. aapt2 spells the namespace out as a URI, and non-string values
+/// come through as written, e.g. `(type 0x12)0xffffffff`.
+fn parse_attribute(attr: &str) -> Option<(&str, String)> {
+    let end = attr.find(['(', '='])?;
+    let name = attr[..end].rsplit(':').next()?;
+    let value = attr[attr.find('=')? + 1..].trim();
+    let value = match value.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+        None => value.split(" (Raw").next().unwrap_or(value),
+    };
+    Some((name, value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMPSYS: &str = r#"Activity Resolver Table:
+  Schemes:
+      https:
+        e2ccd3a com.example.app/.LinkActivity filter 7c7b8f5
+          Action: "android.intent.action.VIEW"
+          Category: "android.intent.category.DEFAULT"
+          Category: "android.intent.category.BROWSABLE"
+          Scheme: "https"
+          Authority: "example.com": -1
+          Path: "PatternMatcher{PREFIX: /product/}"
+          Path: "PatternMatcher{GLOB: /item/.*/reviews}"
+          AutoVerify=true
+      myapp:
+        e2ccd3a com.example.app/.LinkActivity filter 1d2e3f4
+          Action: "android.intent.action.VIEW"
+          Scheme: "myapp"
+        e2ccd3a com.example.app/.MainActivity filter 9a8b7c6
+          Action: "android.intent.action.SEND"
+          Scheme: "myapp"
+  Non-Data Actions:
+      android.intent.action.MAIN:
+        5f6a7b8 com.example.app/.MainActivity filter 2b3c4d5
+          Action: "android.intent.action.MAIN"
+          Category: "android.intent.category.LAUNCHER"
+
+Receiver Resolver Table:
+  Schemes:
+      https:
+        0a1b2c3 com.example.app/.Receiver filter 4d5e6f7
+          Action: "android.intent.action.VIEW"
+          Scheme: "https"
+"#;
+
+    #[test]
+    fn parses_view_filters_from_dumpsys() {
+        let filters = parse_dumpsys(DUMPSYS);
+        assert_eq!(filters.len(), 2, "{:#?}", filters);
+        let web = &filters[0];
+        assert_eq!(web.activity, "com.example.app/.LinkActivity");
+        assert_eq!(web.schemes, ["https"]);
+        assert_eq!(web.hosts, ["example.com"]);
+        assert_eq!(web.paths[1].kind, PathKind::Glob);
+        assert!(web.auto_verify);
+        assert_eq!(filters[1].schemes, ["myapp"]);
+
+        let urls: Vec<String> = links(&filters).into_iter().map(|l| l.url).collect();
+        assert_eq!(
+            urls,
+            [
+                "https://example.com/product/{rest}",
+                "https://example.com/item/{any}/reviews",
+                "myapp://{path}"
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_filters_from_aapt_and_aapt2_xmltree() {
+        let aapt = r#"N: android=http://schemas.android.com/apk/res/android
+  E: manifest (line=2)
+    A: android:versionCode(0x0101021b)=(type 0x10)0x1
+    A: package="com.example.app" (Raw: "com.example.app")
+    E: application (line=10)
+      E: activity (line=12)
+        A: android:name(0x01010003)="com.example.app.LinkActivity" (Raw: "com.example.app.LinkActivity")
+        E: intent-filter (line=14)
+          A: android:autoVerify(0x010104ee)=(type 0x12)0xffffffff
+          E: action (line=15)
+            A: android:name(0x01010003)="android.intent.action.VIEW" (Raw: "android.intent.action.VIEW")
+          E: data (line=17)
+            A: android:scheme(0x01010027)="https" (Raw: "https")
+            A: android:host(0x01010028)="*.example.com" (Raw: "*.example.com")
+            A: android:port(0x01010029)="8443" (Raw: "8443")
+          E: data (line=18)
+            A: android:pathAdvancedPattern(0x0101062f)="/order/[0-9]+" (Raw: "/order/[0-9]+")
+      E: activity (line=20)
+        A: android:name(0x01010003)="com.example.app.MainActivity" (Raw: "com.example.app.MainActivity")
+        E: intent-filter (line=21)
+          E: action (line=22)
+            A: android:name(0x01010003)="android.intent.action.MAIN" (Raw: "android.intent.action.MAIN")
+"#;
+        let (package, filters) = parse_xmltree(aapt);
+        assert_eq!(package.as_deref(), Some("com.example.app"));
+        assert_eq!(filters.len(), 1);
+        assert_eq!(
+            filters[0].activity,
+            "com.example.app/com.example.app.LinkActivity"
+        );
+        assert_eq!(filters[0].hosts, ["*.example.com:8443"]);
+        assert!(filters[0].auto_verify);
+
+        let link = &links(&filters)[0];
+        assert_eq!(link.url, "https://{subdomain}.example.com:8443/order/{any}");
+        assert_eq!(link.sample_url(), "https://www.example.com:8443/order/0");
+        assert_eq!(
+            link.fill(&["m".into(), "42".into()]),
+            "https://m.example.com:8443/order/42"
+        );
+
+        let aapt2 = r#"E: manifest (line=2)
+  A: package="com.example.app" (Raw: "com.example.app")
+    E: activity-alias (line=9)
+      A: http://schemas.android.com/apk/res/android:name(0x01010003)="Share" (Raw: "Share")
+      E: intent-filter (line=10)
+        E: action (line=11)
+          A: http://schemas.android.com/apk/res/android:name(0x01010003)="android.intent.action.VIEW" (Raw: "android.intent.action.VIEW")
+        E: data (line=12)
+          A: http://schemas.android.com/apk/res/android:scheme(0x01010027)="myapp" (Raw: "myapp")
+          A: http://schemas.android.com/apk/res/android:host(0x01010028)="share" (Raw: "share")
+          A: http://schemas.android.com/apk/res/android:pathSuffix(0x01010667)=".pdf" (Raw: ".pdf")
+"#;
+        let (_, filters) = parse_xmltree(aapt2);
+        assert_eq!(filters[0].activity, "com.example.app/.Share");
+        assert_eq!(links(&filters)[0].url, "myapp://share/{path}.pdf");
+    }
+
+    #[test]
+    fn templates_name_repeated_placeholders_apart() {
+        let filter = IntentFilter {
+            activity: "com.example.app/.LinkActivity".into(),
+            schemes: vec!["https".into()],
+            hosts: vec!["example.com".into()],
+            paths: vec![PathPattern {
+                kind: PathKind::Glob,
+                pattern: "/u/.*/p/.*".into(),
+            }],
+            auto_verify: false,
+        };
+        let link = &links(&[filter])[0];
+        assert_eq!(link.url, "https://example.com/u/{any}/p/{any2}");
+        assert_eq!(link.placeholders, ["any", "any2"]);
+        assert_eq!(link.sample_url(), "https://example.com/u/x/p/x");
+    }
+
+    #[test]
+    fn verification_spots_choosers_and_other_apps() {
+        let expected = "com.example.app/.LinkActivity";
+        let ok = Verification::new(
+            "myapp://x",
+            expected,
+            parse_resolved("priority=0 preferredOrder=0 match=0x200000\ncom.example.app/com.example.app.LinkActivity\n"),
+        );
+        assert!(ok.verified);
+        assert_eq!(ok.problem(), None);
+
+        let chooser = Verification::new(
+            "https://example.com",
+            expected,
+            parse_resolved("android/com.android.internal.app.ResolverActivity"),
+        );
+        assert_eq!(
+            chooser.problem().as_deref(),
+            Some("Android asks which app should open it")
+        );
+        let browser = Verification::new(
+            "https://example.com",
+            expected,
+            Some("com.android.chrome/com.google.android.apps.chrome.IntentDispatcher".into()),
+        );
+        assert!(browser.problem().unwrap().contains("com.android.chrome"));
+        let none = Verification::new("nope://x", expected, parse_resolved("No activity found"));
+        assert_eq!(none.resolved, None);
+    }
+}
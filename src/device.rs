@@ -0,0 +1,215 @@
+//! Contains the Device struct, parsed from one line of `adb devices -l`.
+use super::error::ErrorCode;
+use serde::Serialize;
+use std::fmt;
+
+/// How the device is connected to adb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Usb,
+    Tcp,
+    Emulator,
+    Unknown,
+}
+
+impl fmt::Display for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Transport::Usb => "USB",
+            Transport::Tcp => "TCP",
+            Transport::Emulator => "emulator",
+            Transport::Unknown => "unknown",
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Device {
+    pub serial: String,
+    /// adb connection state: `device`, `unauthorized`, `offline`, ...
+    pub state: String,
+    pub model: Option<String>,
+    pub product: Option<String>,
+    pub device: Option<String>,
+    pub transport: Transport,
+    #[serde(skip)]
+    pub usb: Option<String>,
+    pub transport_id: Option<String>,
+    /// Filled in by `AdbClient::get_devices_detailed` for ready devices.
+    pub android_version: Option<String>,
+    /// Battery percentage, filled in like `android_version`.
+    pub battery_level: Option<u8>,
+    /// How to fix an unauthorized or offline device.
+    pub hint: Option<&'static str>,
+}
+
+impl Device {
+    /// Parse a line such as
+    /// `emulator-5554 device product:sdk_gphone64 model:Pixel_7 device:emu64a transport_id:1`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let serial = parts.next()?.to_string();
+        let state = parts.next()?.to_string();
+        let mut device = Device {
+            serial,
+            state,
+            model: None,
+            product: None,
+            device: None,
+            transport: Transport::Unknown,
+            usb: None,
+            transport_id: None,
+            android_version: None,
+            battery_level: None,
+            hint: None,
+        };
+        for part in parts {
+            if let Some((key, value)) = part.split_once(':') {
+                let value = Some(value.to_string());
+                match key {
+                    "product" => device.product = value,
+                    "model" => device.model = value,
+                    "device" => device.device = value,
+                    "usb" => device.usb = value,
+                    "transport_id" => device.transport_id = value,
+                    _ => {}
+                }
+            }
+        }
+        device.transport = device.detect_transport();
+        device.hint = match device.state.as_str() {
+            "unauthorized" => ErrorCode::DeviceUnauthorized.hint(),
+            "offline" => ErrorCode::DeviceOffline.hint(),
+            _ => None,
+        };
+        Some(device)
+    }
+
+    fn detect_transport(&self) -> Transport {
+        if self.serial.starts_with("emulator-") {
+            Transport::Emulator
+        } else if self.serial.contains(':') || self.serial.contains("._adb-tls-connect.") {
+            Transport::Tcp
+        } else if self.usb.is_some() {
+            Transport::Usb
+        } else {
+            Transport::Unknown
+        }
+    }
+
+    /// Whether adb can run commands on it right now.
+    pub fn is_ready(&self) -> bool {
+        self.state == "device"
+    }
+
+    /// Case-insensitive match of `tag` against the serial, model, product and
+    /// device name, so `pixel` selects every `model:Pixel_*` phone.
+    pub fn matches_tag(&self, tag: &str) -> bool {
+        let tag = tag.to_lowercase();
+        [
+            Some(&self.serial),
+            self.model.as_ref(),
+            self.product.as_ref(),
+            self.device.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|v| v.to_lowercase().contains(&tag))
+    }
+
+    /// `Pixel 7 Pro` for `model:Pixel_7_Pro`.
+    pub fn display_model(&self) -> Option<String> {
+        self.model.as_ref().map(|m| m.replace('_', " "))
+    }
+}
+
+/// Parse the full output of `adb devices -l`, skipping the header and daemon noise.
+pub fn parse_device_list(output: &str) -> Vec<Device> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| !line.starts_with("List of devices attached"))
+        .filter(|line| !line.starts_with('*'))
+        .filter_map(Device::parse)
+        .collect()
+}
+
+/// Battery percentage from `dumpsys battery` output.
+pub fn parse_battery_level(dumpsys: &str) -> Option<u8> {
+    dumpsys.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("level:")
+            .and_then(|v| v.trim().parse().ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICES_L: &str = "\
+* daemon not running; starting now at tcp:5037
+* daemon started successfully
+List of devices attached
+emulator-5554          device product:sdk_gphone64_arm64 model:sdk_gphone64_arm64 device:emu64a transport_id:1
+R38M3049YJH            unauthorized usb:1-1 transport_id:2
+2A191FDH2000A3         device usb:1-2 product:cheetah model:Pixel_7_Pro device:cheetah transport_id:3
+192.168.1.20:5555      offline product:oriole model:Pixel_6 device:oriole transport_id:4
+
+";
+
+    #[test]
+    fn parses_devices_with_attributes() {
+        let devices = parse_device_list(DEVICES_L);
+        assert_eq!(devices.len(), 4);
+        assert_eq!(devices[0].serial, "emulator-5554");
+        assert!(devices[0].is_ready());
+        assert_eq!(devices[1].state, "unauthorized");
+        assert!(!devices[1].is_ready());
+        assert_eq!(devices[1].model, None);
+        assert_eq!(devices[2].model.as_deref(), Some("Pixel_7_Pro"));
+        assert_eq!(devices[2].display_model().as_deref(), Some("Pixel 7 Pro"));
+        assert_eq!(devices[2].transport_id.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn detects_transport() {
+        let devices = parse_device_list(DEVICES_L);
+        let transports: Vec<Transport> = devices.iter().map(|d| d.transport).collect();
+        assert_eq!(
+            transports,
+            vec![
+                Transport::Emulator,
+                Transport::Usb,
+                Transport::Usb,
+                Transport::Tcp
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_unusable_devices_with_hints() {
+        let devices = parse_device_list(DEVICES_L);
+        assert!(devices[0].hint.is_none());
+        assert!(devices[1].hint.unwrap().contains("USB debugging"));
+        assert!(devices[3].hint.is_some());
+    }
+
+    #[test]
+    fn matches_tag_case_insensitively() {
+        let devices = parse_device_list(DEVICES_L);
+        assert!(devices[2].matches_tag("pixel"));
+        assert!(devices[2].matches_tag("CHEETAH"));
+        assert!(!devices[0].matches_tag("pixel"));
+        assert!(devices[0].matches_tag("emulator"));
+    }
+
+    #[test]
+    fn parses_battery_level() {
+        let dumpsys =
+            "Current Battery Service state:\n  AC powered: false\n  level: 85\n  scale: 100";
+        assert_eq!(parse_battery_level(dumpsys), Some(85));
+        assert_eq!(parse_battery_level("nothing"), None);
+    }
+}
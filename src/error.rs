@@ -0,0 +1,329 @@
+//! `DabError` — the one error type every command reports through.
+//!
+//! Each error carries a stable machine-readable [`ErrorCode`], a human message
+//! and an optional hint. `main` renders it either as text or as a flat JSON
+//! envelope on stderr, and exits with the code's documented process exit code.
+//! Errors bubbling up from androkit as plain `anyhow` strings are classified
+//! into codes by [`DabError::classify`].
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+/// Declares [`ErrorCode`] with each code's exit code, plus
+/// [`ErrorCode::ALL`] listing every code in order.
+macro_rules! error_codes {
+    ($($code:ident = $exit:literal,)*) => {
+        /// Stable error codes. The serialized names and exit codes are part of
+        /// dab's scripting contract — never renumber, only append.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+        #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+        pub enum ErrorCode {
+            $($code,)*
+        }
+
+        impl ErrorCode {
+            /// Every error code, in exit-code order.
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$code,)*];
+
+            /// Process exit code for this error.
+            pub fn exit_code(self) -> i32 {
+                match self {
+                    $(ErrorCode::$code => $exit,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    Unknown = 1,
+    Usage = 2,
+    AdbMissing = 3,
+    NoDevices = 4,
+    DeviceNotFound = 5,
+    DeviceUnauthorized = 6,
+    DeviceOffline = 7,
+    PackageNotFound = 8,
+    AmbiguousPackage = 9,
+    InputRequired = 10,
+    FileNotFound = 11,
+    UnsupportedFile = 12,
+    InstallFailed = 13,
+    InstallFailedVersionDowngrade = 14,
+    InstallFailedInsufficientStorage = 15,
+    PartialFailure = 16,
+    TestsFailed = 17,
+    StartupRegression = 18,
+    Unauthorized = 19,
+    RouteNotFound = 20,
+    InvalidProfile = 21,
+    ScenarioFailed = 22,
+    InvalidScenario = 23,
+    HealthCheckFailed = 24,
+    DeeplinkMismatch = 25,
+    AaptMissing = 26,
+    IntentFailed = 27,
+    UiNodeNotFound = 28,
+    UiDumpFailed = 29,
+    InputFailed = 30,
+    InvalidMacro = 31,
+    PayloadTooLarge = 32,
+}
+
+impl ErrorCode {
+    /// Suggested next step, used when an error doesn't supply its own hint.
+    pub fn hint(self) -> Option<&'static str> {
+        match self {
+            ErrorCode::Unknown => None,
+            ErrorCode::Usage => Some("Run `dab --help` for usage."),
+            ErrorCode::AdbMissing => {
+                Some("Install Android SDK platform-tools and make sure `adb` is on your PATH.")
+            }
+            ErrorCode::NoDevices => {
+                Some("Connect a device via USB with USB debugging enabled, or start an emulator.")
+            }
+            ErrorCode::DeviceNotFound => Some("Run `dab devices` to list connected serials."),
+            ErrorCode::DeviceUnauthorized => {
+                Some("Unlock the device and accept the \"Allow USB debugging\" prompt.")
+            }
+            ErrorCode::DeviceOffline => {
+                Some("Reconnect the cable or run `adb reconnect`, then try again.")
+            }
+            ErrorCode::PackageNotFound => Some("Run `dab apps` to list installed packages."),
+            ErrorCode::AmbiguousPackage => {
+                Some("Pass one of the candidates as the full --package name.")
+            }
+            ErrorCode::InputRequired => None,
+            ErrorCode::FileNotFound => Some("Check the file path."),
+            ErrorCode::UnsupportedFile => Some("Pass an .apk, .xapk or .apkm file."),
+            ErrorCode::InstallFailed => None,
+            ErrorCode::InstallFailedVersionDowngrade => Some(
+                "Uninstall the existing app first, or install a build with a higher versionCode.",
+            ),
+            ErrorCode::InstallFailedInsufficientStorage => {
+                Some("Free up space on the device; `dab health` shows storage usage.")
+            }
+            ErrorCode::PartialFailure => Some("See the per-device results for what failed where."),
+            ErrorCode::TestsFailed => {
+                Some("See the JUnit report or summary.json for failing tests and stack traces.")
+            }
+            ErrorCode::StartupRegression => Some(
+                "Profile the start with `dab logcat` or Android Studio, or raise --threshold if the change is expected.",
+            ),
+            ErrorCode::Unauthorized => {
+                Some("Send the server's token as `Authorization: Bearer <token>`.")
+            }
+            ErrorCode::RouteNotFound => {
+                Some("See the `dab serve` section of the README for the available routes.")
+            }
+            ErrorCode::InvalidProfile => {
+                Some("See the `dab provision` section of the README for the profile format.")
+            }
+            ErrorCode::ScenarioFailed => {
+                Some("See `steps` for the failing step's error; the steps after it were skipped.")
+            }
+            ErrorCode::InvalidScenario => {
+                Some("See the `dab run` section of the README for the scenario format.")
+            }
+            ErrorCode::HealthCheckFailed => Some(
+                "See `breaches` for the thresholds crossed; free space, charge or reboot the device before retrying.",
+            ),
+            ErrorCode::DeeplinkMismatch => Some(
+                "Another app or the chooser claims the link; check the intent filter and, for App Links, the site's assetlinks.json.",
+            ),
+            ErrorCode::AaptMissing => Some(
+                "Install the Android SDK build-tools and put `aapt2` on PATH, or install the app and use --package.",
+            ),
+            ErrorCode::IntentFailed => Some(
+                "Check the class name, and that the component is exported or the app is debuggable.",
+            ),
+            ErrorCode::UiNodeNotFound => Some(
+                "Run `dab ui dump` to see the screen's text, ids and descriptions; pass --wait if it's still loading.",
+            ),
+            ErrorCode::UiDumpFailed => Some(
+                "Wait for animations to settle and try again; screens marked secure or showing video can't be dumped.",
+            ),
+            ErrorCode::InputFailed => Some(
+                "Check the key name; `input text` can't type most characters outside ASCII.",
+            ),
+            ErrorCode::InvalidMacro => {
+                Some("Record the macro again with `dab macro record <file>`.")
+            }
+            ErrorCode::PayloadTooLarge => Some(
+                "Install a bundle this large with `dab install` on the machine the device is attached to.",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DabError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub hint: Option<String>,
+    /// Extra structured fields merged into the JSON envelope.
+    pub details: Map<String, Value>,
+}
+
+impl DabError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            hint: code.hint().map(str::to_string),
+            details: Map::new(),
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn with_detail(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// Turn any error into a `DabError`, recognising androkit and adb failure
+    /// messages. Errors that already are `DabError`s pass through unchanged.
+    pub fn classify(err: &anyhow::Error) -> Self {
+        if let Some(e) = err.downcast_ref::<DabError>() {
+            return e.clone();
+        }
+        let message = format!("{:#}", err);
+        let code = classify_message(&message);
+        Self::new(code, message)
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.code.exit_code()
+    }
+
+    /// The JSON envelope written to stderr under `--json`.
+    pub fn to_json(&self) -> Value {
+        let mut envelope = json!({
+            "error": self.message,
+            "code": self.code,
+            "exit_code": self.exit_code(),
+            "hint": self.hint,
+        });
+        if let Value::Object(map) = &mut envelope {
+            for (k, v) in &self.details {
+                map.entry(k.clone()).or_insert_with(|| v.clone());
+            }
+        }
+        envelope
+    }
+}
+
+impl fmt::Display for DabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DabError {}
+
+/// Map an error message (usually adb's own stderr wrapped by androkit) to a code.
+fn classify_message(message: &str) -> ErrorCode {
+    let m = message.to_lowercase();
+    if m.contains("`adb` not found") {
+        ErrorCode::AdbMissing
+    } else if m.contains("no connected devices") {
+        ErrorCode::NoDevices
+    } else if m.contains("unauthorized") {
+        ErrorCode::DeviceUnauthorized
+    } else if m.contains("device offline") {
+        ErrorCode::DeviceOffline
+    } else if m.contains("device '") && m.contains("not found") {
+        ErrorCode::DeviceNotFound
+    } else if m.contains("install_failed_version_downgrade") {
+        ErrorCode::InstallFailedVersionDowngrade
+    } else if m.contains("install_failed_insufficient_storage") {
+        ErrorCode::InstallFailedInsufficientStorage
+    } else if m.contains("install_failed_")
+        || m.contains("install_parse_failed_")
+        || m.contains("failed to install")
+    {
+        ErrorCode::InstallFailed
+    } else if m.contains("file does not exist") {
+        ErrorCode::FileNotFound
+    } else if m.contains("unsupported file type") {
+        ErrorCode::UnsupportedFile
+    } else if m.contains("unknown package") || m.contains("could not find apk path") {
+        ErrorCode::PackageNotFound
+    } else {
+        ErrorCode::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn classifies_androkit_and_adb_messages() {
+        let cases = [
+            (
+                "`adb` not found in PATH. Please install the Android SDK",
+                ErrorCode::AdbMissing,
+            ),
+            ("No connected devices found.", ErrorCode::NoDevices),
+            ("error: device 'abc' not found", ErrorCode::DeviceNotFound),
+            ("error: device unauthorized.", ErrorCode::DeviceUnauthorized),
+            ("error: device offline", ErrorCode::DeviceOffline),
+            (
+                "Failed to install APK: adb: failed to install app.apk: Failure [INSTALL_FAILED_VERSION_DOWNGRADE]",
+                ErrorCode::InstallFailedVersionDowngrade,
+            ),
+            (
+                "Failed to install APK: Failure [INSTALL_FAILED_INSUFFICIENT_STORAGE]",
+                ErrorCode::InstallFailedInsufficientStorage,
+            ),
+            (
+                "Failed to install APKs: Failure [INSTALL_FAILED_UPDATE_INCOMPATIBLE]",
+                ErrorCode::InstallFailed,
+            ),
+            ("File does not exist: app.apk", ErrorCode::FileNotFound),
+            ("Could not find APK path for com.foo", ErrorCode::PackageNotFound),
+            ("something else", ErrorCode::Unknown),
+        ];
+        for (message, code) in cases {
+            assert_eq!(
+                DabError::classify(&anyhow!(message)).code,
+                code,
+                "{message}"
+            );
+        }
+    }
+
+    #[test]
+    fn classify_passes_dab_errors_through() {
+        let err: anyhow::Error = DabError::new(ErrorCode::InputRequired, "nope").into();
+        assert_eq!(DabError::classify(&err).code, ErrorCode::InputRequired);
+    }
+
+    #[test]
+    fn json_envelope_is_flat_with_details() {
+        let err = DabError::new(ErrorCode::AmbiguousPackage, "'foo' is ambiguous")
+            .with_detail("candidates", vec!["a", "b"]);
+        let v = err.to_json();
+        assert_eq!(v["error"], "'foo' is ambiguous");
+        assert_eq!(v["code"], "AMBIGUOUS_PACKAGE");
+        assert_eq!(v["exit_code"], 9);
+        assert_eq!(v["candidates"], json!(["a", "b"]));
+        assert!(v["hint"].is_string());
+    }
+
+    #[test]
+    fn exit_codes_are_unique() {
+        let codes = ErrorCode::ALL;
+        let mut seen: Vec<i32> = codes.iter().map(|c| c.exit_code()).collect();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), codes.len());
+    }
+}
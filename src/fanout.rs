@@ -0,0 +1,419 @@
+//! Fan-out: run one command on several devices in parallel.
+//!
+//! `--device all`, `--device a,b,c` and `--device-tag <tag>` select the
+//! targets; each device runs on its own thread and reports the same JSON its
+//! single-device `--json` run would print. Results are aggregated in target
+//! order with a summary, and any failed device makes the whole run fail.
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::cli::{Commands, MacroCommand, UiCommand};
+use super::error::{DabError, ErrorCode};
+use super::prompt;
+use anyhow::Result;
+use colored::*;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Resolve `--device` / `--device-tag` into a fan-out target list.
+///
+/// Returns `None` for the ordinary single-device case (no flag, or one plain
+/// serial). `all` and tags select ready devices only; explicit serials are
+/// taken as given so unreachable ones show up as failures.
+pub fn targets(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: Option<&str>,
+    tag: Option<&str>,
+) -> Result<Option<Vec<String>>> {
+    let explicit: Option<Vec<String>> = match device {
+        Some("all") => None,
+        Some(d) if d.contains(',') || tag.is_some() => Some(
+            d.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect(),
+        ),
+        Some(_) => return Ok(None),
+        None if tag.is_none() => return Ok(None),
+        None => None,
+    };
+    let serials: Vec<String> = match explicit {
+        Some(serials) if tag.is_none() => serials,
+        explicit => adb_client
+            .get_devices()?
+            .into_iter()
+            .filter(|d| d.is_ready())
+            .filter(|d| tag.is_none_or(|t| d.matches_tag(t)))
+            .filter(|d| explicit.as_ref().is_none_or(|e| e.contains(&d.serial)))
+            .map(|d| d.serial)
+            .collect(),
+    };
+    if serials.is_empty() {
+        let err = match tag {
+            Some(t) => DabError::new(
+                ErrorCode::NoDevices,
+                format!("No ready devices match tag '{}'", t),
+            )
+            .with_hint("Run `dab devices` to see each device's serial, model and product."),
+            None => DabError::new(ErrorCode::NoDevices, "No ready devices to run on"),
+        };
+        return Err(err.into());
+    }
+    Ok(Some(serials))
+}
+
+/// Reject commands that can't run unattended on several devices at once,
+/// before any device is touched.
+pub fn preflight(command: &Commands) -> Result<()> {
+    let missing = match command {
+        Commands::Run { .. } => {
+            return Err(DabError::new(
+                ErrorCode::Usage,
+                "`run` drives a single device step by step; target one with --device",
+            )
+            .into())
+        }
+        Commands::ShellMode | Commands::Tui => {
+            return Err(DabError::new(
+                ErrorCode::Usage,
+                "`shell-mode` and `tui` are interactive and work on one device at a time; target one with --device",
+            )
+            .into())
+        }
+        Commands::Deeplinks { open: true, .. } => {
+            return Err(DabError::new(
+                ErrorCode::Usage,
+                "`deeplinks --open` prompts for the link to open; target a single device",
+            )
+            .into())
+        }
+        Commands::Record { .. }
+        | Commands::Logcat { .. }
+        | Commands::Crashes { watch: true, .. }
+        | Commands::Health { watch: Some(_), .. }
+        | Commands::Top { watch: Some(_) }
+        | Commands::Macro {
+            command: MacroCommand::Record { .. },
+        } => {
+            return Err(DabError::new(
+                ErrorCode::Usage,
+                "`record`, `logcat`, `crashes --watch`, `health --watch`, `top --watch` and `macro record` run until Ctrl+C and can't fan out; target a single device",
+            )
+            .into())
+        }
+        Commands::Open { package }
+        | Commands::Uninstall { package }
+        | Commands::Clear { package }
+        | Commands::ForceKill { package }
+        | Commands::Download { package, .. }
+        | Commands::AppInfo { package, .. }
+        | Commands::Crashes { package, .. }
+        | Commands::Test { package, .. }
+        | Commands::Startup { package, .. }
+        | Commands::Perf { package, .. } => package.is_none().then_some(("app", "--package")),
+        Commands::Deeplinks { package, file, .. } => {
+            (package.is_none() && file.is_none()).then_some(("app", "--package"))
+        }
+        Commands::Start { activity, intent } => (intent.package.is_none()
+            && (activity.is_some() || !intent.implicit()))
+        .then_some(("app", "--package")),
+        Commands::Broadcast { receiver, intent } => (intent.package.is_none()
+            && receiver.is_some())
+        .then_some(("app", "--package")),
+        Commands::StartService { intent, .. } => {
+            intent.package.is_none().then_some(("app", "--package"))
+        }
+        Commands::Grant {
+            package,
+            permissions,
+        }
+        | Commands::Revoke {
+            package,
+            permissions,
+        } => {
+            if package.is_none() {
+                Some(("app", "--package"))
+            } else {
+                permissions
+                    .is_none()
+                    .then_some(("permission", "--permissions"))
+            }
+        }
+        _ => None,
+    };
+    match missing {
+        Some((prompt, flag)) => Err(prompt::input_required(prompt, flag)
+            .with_hint(format!("Pass {} when targeting several devices.", flag))
+            .into()),
+        None => Ok(()),
+    }
+}
+
+/// Give each device its own output file so parallel runs don't overwrite
+/// each other: `shot.png` becomes `shot-<serial>.png`, and a directory (or
+/// no path) gets `<default_stem>-<serial>.<ext>` inside it.
+pub fn per_device_output(output: &Option<PathBuf>, serial: &str, default_name: &str) -> PathBuf {
+    let safe_serial = safe_serial(serial);
+    let suffixed = |name: &Path| -> String {
+        let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+        match name.extension() {
+            Some(ext) => format!("{}-{}.{}", stem, safe_serial, ext.to_string_lossy()),
+            None => format!("{}-{}", stem, safe_serial),
+        }
+    };
+    match output {
+        Some(p) if p.is_dir() => p.join(suffixed(Path::new(default_name))),
+        Some(p) => p.with_file_name(suffixed(p)),
+        None => PathBuf::from(suffixed(Path::new(default_name))),
+    }
+}
+
+/// `serial` with the characters that can't appear in file names replaced.
+fn safe_serial(serial: &str) -> String {
+    serial.replace([':', '/', '\\'], "_")
+}
+
+/// The command as it should run on `serial`, with output paths made unique.
+pub fn for_device(command: &Commands, serial: &str) -> Commands {
+    match command {
+        Commands::Screenshot { output } => Commands::Screenshot {
+            output: Some(per_device_output(output, serial, "screen.png")),
+        },
+        Commands::Ui {
+            command:
+                UiCommand::Dump {
+                    xml,
+                    output: output @ Some(_),
+                },
+        } => Commands::Ui {
+            command: UiCommand::Dump {
+                xml: *xml,
+                output: Some(per_device_output(
+                    output,
+                    serial,
+                    if *xml { "ui.xml" } else { "ui.json" },
+                )),
+            },
+        },
+        Commands::Download { package, output } => Commands::Download {
+            package: package.clone(),
+            output: Some(per_device_output(
+                output,
+                serial,
+                &format!("{}.apk", package.as_deref().unwrap_or("app")),
+            )),
+        },
+        Commands::Perf {
+            package,
+            interval,
+            duration,
+            output: output @ Some(_),
+        } => Commands::Perf {
+            package: package.clone(),
+            interval: *interval,
+            duration: *duration,
+            output: Some(per_device_output(output, serial, "perf.json")),
+        },
+        Commands::Test {
+            package,
+            runner,
+            class,
+            arg,
+            install,
+            output,
+        } => Commands::Test {
+            package: package.clone(),
+            runner: runner.clone(),
+            class: class.clone(),
+            arg: arg.clone(),
+            install: install.clone(),
+            output: output.join(safe_serial(serial)),
+        },
+        Commands::Crashes {
+            package,
+            watch,
+            save_dir: Some(dir),
+        } => Commands::Crashes {
+            package: package.clone(),
+            watch: *watch,
+            save_dir: Some(dir.join(safe_serial(serial))),
+        },
+        other => other.clone(),
+    }
+}
+
+/// Outcome of the command on one device.
+pub struct DeviceResult {
+    pub device: String,
+    pub result: Result<Value, DabError>,
+}
+
+impl DeviceResult {
+    fn to_json(&self) -> Value {
+        match &self.result {
+            Ok(value) => json!({ "device": self.device, "ok": true, "result": value }),
+            Err(err) => json!({ "device": self.device, "ok": false, "error": err.to_json() }),
+        }
+    }
+}
+
+/// Run `task` for every serial on its own thread, keeping target order.
+pub fn run<F>(serials: &[String], task: F) -> Vec<DeviceResult>
+where
+    F: Fn(&str) -> Result<Value> + Sync,
+{
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = serials
+            .iter()
+            .map(|serial| {
+                let task = &task;
+                scope.spawn(move || task(serial))
+            })
+            .collect();
+        serials
+            .iter()
+            .zip(handles)
+            .map(|(serial, handle)| DeviceResult {
+                device: serial.clone(),
+                result: match handle.join() {
+                    Ok(result) => result.map_err(|e| DabError::classify(&e)),
+                    Err(_) => Err(DabError::new(
+                        ErrorCode::Unknown,
+                        format!("Worker for {} panicked", serial),
+                    )),
+                },
+            })
+            .collect()
+    })
+}
+
+/// Print the aggregated results and summary, failing when any device failed.
+pub fn report(results: &[DeviceResult], json: bool) -> Result<()> {
+    let total = results.len();
+    let failed = results.iter().filter(|r| r.result.is_err()).count();
+    let succeeded = total - failed;
+    if json {
+        let doc = json!({
+            "results": results.iter().map(DeviceResult::to_json).collect::<Vec<_>>(),
+            "summary": { "total": total, "succeeded": succeeded, "failed": failed },
+        });
+        println!("{}", serde_json::to_string_pretty(&doc)?);
+    } else {
+        for r in results {
+            match &r.result {
+                Ok(value) => {
+                    println!("{} {}", "✔".green(), r.device.bold());
+                    for line in serde_json::to_string_pretty(value)?.lines() {
+                        println!("    {}", line);
+                    }
+                }
+                Err(err) => {
+                    println!("{} {}: {}", "✘".red(), r.device.bold(), err.message.red());
+                    if let Some(hint) = &err.hint {
+                        println!("    {} {}", "Hint:".yellow(), hint);
+                    }
+                }
+            }
+        }
+        println!(
+            "\n{} {} device(s): {} succeeded, {} failed",
+            "Ran on".bold(),
+            total,
+            succeeded.to_string().green(),
+            if failed > 0 {
+                failed.to_string().red()
+            } else {
+                failed.to_string().normal()
+            }
+        );
+    }
+    if failed > 0 {
+        return Err(DabError::new(
+            ErrorCode::PartialFailure,
+            format!("{} of {} devices failed", failed, total),
+        )
+        .with_detail(
+            "failed_devices",
+            results
+                .iter()
+                .filter(|r| r.result.is_err())
+                .map(|r| r.device.clone())
+                .collect::<Vec<_>>(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_device_output_suffixes_file_names() {
+        assert_eq!(
+            per_device_output(
+                &Some(PathBuf::from("/tmp/shots/home.png")),
+                "emulator-5554",
+                "screen.png"
+            ),
+            PathBuf::from("/tmp/shots/home-emulator-5554.png")
+        );
+        assert_eq!(
+            per_device_output(&None, "192.168.1.5:5555", "screen.png"),
+            PathBuf::from("screen-192.168.1.5_5555.png")
+        );
+    }
+
+    #[test]
+    fn crash_save_dir_gets_a_subdirectory_per_device() {
+        let command = Commands::Crashes {
+            package: Some("com.foo".into()),
+            watch: false,
+            save_dir: Some(PathBuf::from("qa-crashes")),
+        };
+        match for_device(&command, "192.168.1.5:5555") {
+            Commands::Crashes { save_dir, .. } => {
+                assert_eq!(save_dir, Some(PathBuf::from("qa-crashes/192.168.1.5_5555")))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn preflight_requires_package_for_app_commands() {
+        let err = preflight(&Commands::Open { package: None }).unwrap_err();
+        let err = DabError::classify(&err);
+        assert_eq!(err.code, ErrorCode::InputRequired);
+        assert_eq!(err.details["flag"], "--package");
+        assert!(preflight(&Commands::Open {
+            package: Some("com.foo".into())
+        })
+        .is_ok());
+        assert!(preflight(&Commands::Health {
+            watch: None,
+            fail_if: Vec::new(),
+            warn_if: Vec::new(),
+            log: None,
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn run_keeps_order_and_classifies_failures() {
+        let serials = vec!["a".to_string(), "b".to_string()];
+        let results = run(&serials, |s| {
+            if s == "b" {
+                Err(anyhow::anyhow!("error: device offline"))
+            } else {
+                Ok(json!({ "ok": s }))
+            }
+        });
+        assert_eq!(results[0].device, "a");
+        assert!(results[0].result.is_ok());
+        assert_eq!(
+            results[1].result.as_ref().unwrap_err().code,
+            ErrorCode::DeviceOffline
+        );
+    }
+}
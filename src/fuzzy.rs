@@ -0,0 +1,201 @@
+//! Fuzzy package-name matching shared by `--package` resolution and the app picker.
+
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+
+/// How many candidates to report when a query is ambiguous.
+const MAX_CANDIDATES: usize = 10;
+
+/// Outcome of resolving a `--package` query against the installed packages.
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// One clear winner.
+    Found(String),
+    /// Several equally plausible packages, best first.
+    Ambiguous(Vec<String>),
+    /// No package matched well enough to pick on its own; the weaker
+    /// substring or subsequence hits, best first.
+    NotFound(Vec<String>),
+}
+
+/// How strongly a package matches a query. Higher tiers always win over
+/// lower ones, regardless of the fuzzy score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Tier {
+    Fuzzy,
+    Substring,
+    Segment,
+    LastSegment,
+    Exact,
+}
+
+fn tier(query: &str, package: &str) -> Tier {
+    let (q, p) = (query.to_lowercase(), package.to_lowercase());
+    if p == q {
+        Tier::Exact
+    } else if p.rsplit('.').next() == Some(q.as_str()) {
+        Tier::LastSegment
+    } else if p.split('.').any(|s| s == q) {
+        Tier::Segment
+    } else if p.contains(&q) {
+        Tier::Substring
+    } else {
+        Tier::Fuzzy
+    }
+}
+
+/// Score `package` against `query`, or `None` when it doesn't match at all.
+///
+/// Exact, segment and substring hits are boosted above plain subsequence
+/// hits, so `youtube` ranks `com.google.android.youtube` first.
+pub fn score(query: &str, package: &str) -> Option<i64> {
+    let q = query.trim();
+    if q.is_empty() {
+        return Some(0);
+    }
+    let base = SkimMatcherV2::default()
+        .ignore_case()
+        .fuzzy_match(package, q)?;
+    Some(base + tier(q, package) as i64 * 10_000)
+}
+
+/// Packages that match `query`, best match first. Ties keep the input order.
+pub fn rank<'a>(query: &str, packages: &'a [String]) -> Vec<&'a str> {
+    let mut scored: Vec<(i64, &str)> = packages
+        .iter()
+        .filter_map(|p| score(query, p).map(|s| (s, p.as_str())))
+        .collect();
+    scored.sort_by_key(|(s, _)| std::cmp::Reverse(*s));
+    scored.into_iter().map(|(_, p)| p).collect()
+}
+
+/// Resolve a partial or fuzzy `query` to a single package.
+///
+/// The query resolves when exactly one package sits in the strongest match
+/// tier, and that tier is an exact name or a whole segment. Several packages
+/// sharing it are reported as ambiguous. Substring and subsequence hits are
+/// only ever suggestions, so `com.example.myapp` never picks
+/// `com.example.myapp.debug`; a query with a `.` can't match a segment and so
+/// only resolves exactly.
+pub fn resolve(query: &str, packages: &[String]) -> Resolution {
+    let q = query.trim();
+    let ranked = rank(q, packages);
+    let Some(best) = ranked.iter().map(|p| tier(q, p)).max() else {
+        return Resolution::NotFound(Vec::new());
+    };
+    if best < Tier::Segment {
+        return Resolution::NotFound(candidates(ranked));
+    }
+    let top: Vec<&str> = ranked.into_iter().filter(|p| tier(q, p) == best).collect();
+    match top.as_slice() {
+        [only] => Resolution::Found(only.to_string()),
+        _ => Resolution::Ambiguous(candidates(top)),
+    }
+}
+
+fn candidates(packages: Vec<&str>) -> Vec<String> {
+    packages
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packages() -> Vec<String> {
+        [
+            "com.android.chrome",
+            "com.google.android.youtube",
+            "com.google.android.apps.youtube.music",
+            "com.google.android.apps.maps",
+            "com.example.myapp",
+            "com.example.myapp.debug",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn exact_name_wins_over_longer_matches() {
+        assert_eq!(
+            resolve("com.example.myapp", &packages()),
+            Resolution::Found("com.example.myapp".to_string())
+        );
+    }
+
+    #[test]
+    fn last_segment_is_a_clear_winner() {
+        assert_eq!(
+            resolve("youtube", &packages()),
+            Resolution::Found("com.google.android.youtube".to_string())
+        );
+        assert_eq!(
+            resolve("Chrome", &packages()),
+            Resolution::Found("com.android.chrome".to_string())
+        );
+    }
+
+    #[test]
+    fn last_segment_beats_inner_segment() {
+        assert_eq!(
+            resolve("myapp", &packages()),
+            Resolution::Found("com.example.myapp".to_string())
+        );
+    }
+
+    #[test]
+    fn shared_segment_is_ambiguous() {
+        match resolve("example", &packages()) {
+            Resolution::Ambiguous(mut c) => {
+                c.sort();
+                assert_eq!(c, vec!["com.example.myapp", "com.example.myapp.debug"]);
+            }
+            other => panic!("expected ambiguous, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn unique_subsequence_match_is_only_a_suggestion() {
+        assert_eq!(
+            resolve("gmaps", &packages()),
+            Resolution::NotFound(vec!["com.google.android.apps.maps".to_string()])
+        );
+    }
+
+    #[test]
+    fn full_name_never_resolves_to_a_longer_package() {
+        let installed = vec!["com.example.myapp.debug".to_string()];
+        assert_eq!(
+            resolve("com.example.myapp", &installed),
+            Resolution::NotFound(installed.clone())
+        );
+        assert_eq!(
+            resolve("example.myapp", &installed),
+            Resolution::NotFound(installed.clone())
+        );
+        assert_eq!(
+            resolve("debug", &installed),
+            Resolution::Found("com.example.myapp.debug".to_string())
+        );
+    }
+
+    #[test]
+    fn unmatched_query_is_not_found() {
+        assert_eq!(
+            resolve("zzzz", &packages()),
+            Resolution::NotFound(Vec::new())
+        );
+    }
+
+    #[test]
+    fn rank_puts_segment_hits_first() {
+        let pkgs = packages();
+        let ranked = rank("youtube", &pkgs);
+        assert_eq!(ranked[0], "com.google.android.youtube");
+        assert_eq!(ranked[1], "com.google.android.apps.youtube.music");
+    }
+}
@@ -0,0 +1,710 @@
+//! Touch and key input recorded from `getevent`, as the macros `dab macro`
+//! saves and plays back.
+//!
+//! `getevent -lt` reports raw kernel events: touchscreen positions in the
+//! panel's own units and key codes by their Linux names. [`GestureRecorder`]
+//! folds each touch from finger down to finger up into a tap, long press or
+//! swipe in screen pixels, and each key press into its Android key code, so a
+//! macro replays through `input` and scales to another screen size. The panel
+//! keeps its own axes when the display rotates, so positions are turned by the
+//! rotation `dumpsys input` reports, and the macro's screen is the size in that
+//! orientation. Only the first finger is followed; multi-touch gestures are
+//! recorded as one-finger swipes.
+
+use super::error::{DabError, ErrorCode};
+use super::input;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// A touch held at least this long without moving is a long press.
+const LONG_PRESS_SECS: f64 = 0.5;
+
+/// A display size in pixels, as `wm size` reports it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Screen {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The size `input` coordinates refer to: the override size when one is set
+/// (`wm size 720x1600`), otherwise the physical size.
+pub fn parse_screen_size(output: &str) -> Option<Screen> {
+    let size = |prefix: &str| {
+        output.lines().find_map(|l| {
+            let (w, h) = l.trim().strip_prefix(prefix)?.trim().split_once('x')?;
+            Some(Screen {
+                width: w.trim().parse().ok()?,
+                height: h.trim().parse().ok()?,
+            })
+        })
+    };
+    size("Override size:").or_else(|| size("Physical size:"))
+}
+
+impl Screen {
+    /// The size with the display turned `rotation` quarter turns.
+    pub fn rotated(self, rotation: u8) -> Screen {
+        if rotation % 2 == 1 {
+            Screen {
+                width: self.height,
+                height: self.width,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+/// The display's rotation in quarter turns (0 to 3) from `dumpsys input`: the
+/// internal viewport's `orientation=`, or the touch device's
+/// `SurfaceOrientation:` on older releases.
+pub fn parse_rotation(output: &str) -> Option<u8> {
+    let lines = || output.lines().map(str::trim);
+    let viewport = |prefix: &str| {
+        lines().filter(|l| l.starts_with(prefix)).find_map(|l| {
+            l.split([',', ' '])
+                .find_map(|part| part.strip_prefix("orientation=")?.parse().ok())
+        })
+    };
+    viewport("Viewport INTERNAL")
+        .or_else(|| viewport("Viewport"))
+        .or_else(|| {
+            lines().find_map(|l| l.strip_prefix("SurfaceOrientation:")?.trim().parse().ok())
+        })
+        .filter(|rotation| *rotation < 4)
+}
+
+/// One axis of a touchscreen's raw range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Axis {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl Axis {
+    /// How far along the axis a raw value lies, from 0 to 1.
+    fn fraction(self, value: i64) -> f64 {
+        let span = (self.max - self.min).max(1) as f64;
+        ((value - self.min) as f64 / span).clamp(0.0, 1.0)
+    }
+}
+
+/// A fraction of a screen side `size` pixels long, as a pixel.
+fn to_pixel(fraction: f64, size: u32) -> u32 {
+    (fraction * (size.saturating_sub(1)) as f64).round() as u32
+}
+
+/// The input device that reports touches, and its axis ranges.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Touchscreen {
+    pub path: String,
+    pub x: Axis,
+    pub y: Axis,
+}
+
+/// The first touchscreen in `getevent -lp` output: the first device with
+/// multi-touch (or, failing that, single-touch) X and Y axes.
+pub fn parse_touchscreen(output: &str) -> Option<Touchscreen> {
+    let mut devices: Vec<(String, HashMap<String, Axis>)> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("add device") {
+            let path = rest.split_once(':').map_or("", |(_, p)| p.trim());
+            devices.push((path.to_string(), HashMap::new()));
+            continue;
+        }
+        let Some((_, axes)) = devices.last_mut() else {
+            continue;
+        };
+        // `ABS (0003): ABS_MT_POSITION_X : value 0, min 0, max 32767, ...`
+        let line = line.rsplit_once("): ").map_or(line, |(_, l)| l);
+        let Some((name, ranges)) = line.split_once(':') else {
+            continue;
+        };
+        let number = |key: &str| {
+            ranges
+                .split(',')
+                .find_map(|part| part.trim().strip_prefix(key)?.trim().parse().ok())
+        };
+        if let (Some(min), Some(max)) = (number("min"), number("max")) {
+            axes.insert(name.trim().to_string(), Axis { min, max });
+        }
+    }
+    ["ABS_MT_POSITION", "ABS"].iter().find_map(|prefix| {
+        devices.iter().find_map(|(path, axes)| {
+            Some(Touchscreen {
+                path: path.clone(),
+                x: *axes.get(&format!("{}_X", prefix))?,
+                y: *axes.get(&format!("{}_Y", prefix))?,
+            })
+        })
+    })
+}
+
+/// One action of a macro, in screen pixels.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Gesture {
+    Tap {
+        x: u32,
+        y: u32,
+    },
+    LongPress {
+        x: u32,
+        y: u32,
+        duration_ms: u64,
+    },
+    Swipe {
+        x1: u32,
+        y1: u32,
+        x2: u32,
+        y2: u32,
+        duration_ms: u64,
+    },
+    Key {
+        /// An `input keyevent` key code, e.g. `KEYCODE_BACK`.
+        key: String,
+        #[serde(default)]
+        long: bool,
+    },
+}
+
+impl Gesture {
+    /// The gesture on a screen `sx` and `sy` times the size, played `speed`
+    /// times as fast.
+    pub fn scaled(&self, sx: f64, sy: f64, speed: f64) -> Gesture {
+        let x = |v: u32| (v as f64 * sx).round() as u32;
+        let y = |v: u32| (v as f64 * sy).round() as u32;
+        let ms = |v: u64| (v as f64 / speed).round().max(1.0) as u64;
+        match self {
+            Gesture::Tap { x: px, y: py } => Gesture::Tap {
+                x: x(*px),
+                y: y(*py),
+            },
+            Gesture::LongPress {
+                x: px,
+                y: py,
+                duration_ms,
+            } => Gesture::LongPress {
+                x: x(*px),
+                y: y(*py),
+                duration_ms: ms(*duration_ms),
+            },
+            Gesture::Swipe {
+                x1,
+                y1,
+                x2,
+                y2,
+                duration_ms,
+            } => Gesture::Swipe {
+                x1: x(*x1),
+                y1: y(*y1),
+                x2: x(*x2),
+                y2: y(*y2),
+                duration_ms: ms(*duration_ms),
+            },
+            Gesture::Key { .. } => self.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Gesture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gesture::Tap { x, y } => write!(f, "tap {},{}", x, y),
+            Gesture::LongPress { x, y, duration_ms } => {
+                write!(f, "long-press {},{} for {}ms", x, y, duration_ms)
+            }
+            Gesture::Swipe {
+                x1,
+                y1,
+                x2,
+                y2,
+                duration_ms,
+            } => write!(
+                f,
+                "swipe {},{} → {},{} in {}ms",
+                x1, y1, x2, y2, duration_ms
+            ),
+            Gesture::Key { key, long: false } => write!(f, "key {}", key),
+            Gesture::Key { key, long: true } => write!(f, "long-press key {}", key),
+        }
+    }
+}
+
+/// A gesture and when it started, in seconds from the macro's first one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub t: f64,
+    #[serde(flatten)]
+    pub gesture: Gesture,
+}
+
+/// A recorded macro, as saved to its JSON file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Macro {
+    /// The screen it was recorded on; playback scales from this.
+    pub screen: Screen,
+    pub steps: Vec<Step>,
+}
+
+impl Macro {
+    /// Read a macro saved by `dab macro record`.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(anyhow!("File does not exist: {}", path.display()));
+        }
+        let text = std::fs::read_to_string(path)?;
+        let invalid = |reason: String| -> anyhow::Error {
+            DabError::new(
+                ErrorCode::InvalidMacro,
+                format!("Invalid macro {}: {}", path.display(), reason),
+            )
+            .into()
+        };
+        let mut recorded: Macro =
+            serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+        if recorded.screen.width == 0 || recorded.screen.height == 0 {
+            return Err(invalid("the screen size must be greater than zero".into()));
+        }
+        // Keys go to `input keyevent` through the device's shell, so only
+        // key codes `dab input key` would accept get that far.
+        for (i, step) in recorded.steps.iter_mut().enumerate() {
+            if step.t < 0.0 {
+                return Err(invalid(format!("step {}: `t` is negative", i + 1)));
+            }
+            if let Gesture::Key { key, .. } = &mut step.gesture {
+                *key =
+                    input::keycode(key).map_err(|e| invalid(format!("step {}: {}", i + 1, e)))?;
+            }
+        }
+        Ok(recorded)
+    }
+
+    /// Seconds from the first gesture's start to the last one's end.
+    pub fn duration_secs(&self) -> f64 {
+        self.steps
+            .iter()
+            .map(|s| {
+                let held = match s.gesture {
+                    Gesture::LongPress { duration_ms, .. } | Gesture::Swipe { duration_ms, .. } => {
+                        duration_ms as f64 / 1000.0
+                    }
+                    _ => 0.0,
+                };
+                s.t + held
+            })
+            .fold(0.0, f64::max)
+    }
+}
+
+/// The Android key code for a Linux `KEY_` name from `getevent -l`.
+fn android_key(linux: &str) -> Option<String> {
+    const KEYS: [(&str, &str); 14] = [
+        ("KEY_HOMEPAGE", "HOME"),
+        ("KEY_HOME", "MOVE_HOME"),
+        ("KEY_APPSELECT", "APP_SWITCH"),
+        ("KEY_VOLUMEUP", "VOLUME_UP"),
+        ("KEY_VOLUMEDOWN", "VOLUME_DOWN"),
+        ("KEY_MUTE", "VOLUME_MUTE"),
+        ("KEY_BACKSPACE", "DEL"),
+        ("KEY_ESC", "ESCAPE"),
+        ("KEY_LEFT", "DPAD_LEFT"),
+        ("KEY_RIGHT", "DPAD_RIGHT"),
+        ("KEY_UP", "DPAD_UP"),
+        ("KEY_DOWN", "DPAD_DOWN"),
+        ("KEY_PLAYPAUSE", "MEDIA_PLAY_PAUSE"),
+        ("KEY_NEXTSONG", "MEDIA_NEXT"),
+    ];
+    let name = KEYS
+        .iter()
+        .find(|(from, _)| *from == linux)
+        .map(|(_, to)| *to)
+        .or_else(|| linux.strip_prefix("KEY_"))?;
+    input::keycode(name).ok()
+}
+
+/// A finger on the screen, from down to up.
+struct Touch {
+    start: f64,
+    points: Vec<(f64, u32, u32)>,
+}
+
+/// Turns `getevent -lt` lines into [`Step`]s as they complete.
+pub struct GestureRecorder {
+    screen: Screen,
+    rotation: u8,
+    touchscreen: Touchscreen,
+    origin: Option<f64>,
+    slot: i64,
+    x: Option<i64>,
+    y: Option<i64>,
+    pending_down: bool,
+    pending_up: bool,
+    touch: Option<Touch>,
+    keys_down: HashMap<String, f64>,
+    steps: Vec<Step>,
+}
+
+impl GestureRecorder {
+    /// `screen` is the size in the display's current orientation, which is
+    /// `rotation` quarter turns from the panel's own.
+    pub fn new(screen: Screen, rotation: u8, touchscreen: Touchscreen) -> Self {
+        GestureRecorder {
+            screen,
+            rotation,
+            touchscreen,
+            origin: None,
+            slot: 0,
+            x: None,
+            y: None,
+            pending_down: false,
+            pending_up: false,
+            touch: None,
+            keys_down: HashMap::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Feed one line: `[   51742.467254] /dev/input/event2: EV_ABS
+    /// ABS_MT_POSITION_X 00003a6c`. Returns the step it completed, if any.
+    pub fn feed(&mut self, line: &str) -> Option<&Step> {
+        let (time, rest) = line.trim().strip_prefix('[')?.split_once(']')?;
+        let time: f64 = time.trim().parse().ok()?;
+        let (path, event) = rest.split_once(": ")?;
+        let mut fields = event.split_whitespace();
+        let (kind, code, value) = (fields.next()?, fields.next()?, fields.next()?);
+        let completed = if path.trim() == self.touchscreen.path {
+            self.touch_event(time, kind, code, value)
+        } else if kind == "EV_KEY" && code.starts_with("KEY_") {
+            self.key_event(time, code, value)
+        } else {
+            None
+        };
+        let step = completed?;
+        self.steps.push(step);
+        self.steps.last()
+    }
+
+    /// The macro recorded so far.
+    pub fn finish(self) -> Macro {
+        Macro {
+            screen: self.screen,
+            steps: self.steps,
+        }
+    }
+
+    fn touch_event(&mut self, time: f64, kind: &str, code: &str, value: &str) -> Option<Step> {
+        let number = || i64::from_str_radix(value, 16).ok();
+        match (kind, code) {
+            ("EV_ABS", "ABS_MT_SLOT") => self.slot = number()?,
+            _ if self.slot != 0 => {}
+            ("EV_ABS", "ABS_MT_POSITION_X" | "ABS_X") => self.x = number(),
+            ("EV_ABS", "ABS_MT_POSITION_Y" | "ABS_Y") => self.y = number(),
+            ("EV_ABS", "ABS_MT_TRACKING_ID") if value == "ffffffff" => self.pending_up = true,
+            ("EV_ABS", "ABS_MT_TRACKING_ID") => self.pending_down = true,
+            ("EV_KEY", "BTN_TOUCH") if value == "DOWN" => self.pending_down = true,
+            ("EV_KEY", "BTN_TOUCH") if value == "UP" => self.pending_up = true,
+            ("EV_SYN", "SYN_REPORT") => return self.sync(time),
+            _ => {}
+        }
+        None
+    }
+
+    fn sync(&mut self, time: f64) -> Option<Step> {
+        if std::mem::take(&mut self.pending_down) && self.touch.is_none() {
+            self.touch = Some(Touch {
+                start: time,
+                points: Vec::new(),
+            });
+        }
+        if let (Some(touch), Some(x), Some(y)) = (&mut self.touch, self.x, self.y) {
+            let (fx, fy) = (
+                self.touchscreen.x.fraction(x),
+                self.touchscreen.y.fraction(y),
+            );
+            // As Android's touch mapper turns the panel's axes.
+            let (fx, fy) = match self.rotation {
+                1 => (fy, 1.0 - fx),
+                2 => (1.0 - fx, 1.0 - fy),
+                3 => (1.0 - fy, fx),
+                _ => (fx, fy),
+            };
+            let x = to_pixel(fx, self.screen.width);
+            let y = to_pixel(fy, self.screen.height);
+            if touch
+                .points
+                .last()
+                .is_none_or(|&(_, px, py)| (px, py) != (x, y))
+            {
+                touch.points.push((time, x, y));
+            }
+        }
+        if !std::mem::take(&mut self.pending_up) {
+            return None;
+        }
+        let touch = self.touch.take()?;
+        let &(_, x1, y1) = touch.points.first()?;
+        let &(_, x2, y2) = touch.points.last()?;
+        let held = time - touch.start;
+        let duration_ms = (held * 1000.0).round().max(1.0) as u64;
+        // Movement under 3% of the screen width is a finger wobbling in place.
+        let slop = (self.screen.width / 33).max(1) as f64;
+        let moved = touch
+            .points
+            .iter()
+            .any(|&(_, x, y)| (x as f64 - x1 as f64).hypot(y as f64 - y1 as f64) >= slop);
+        let gesture = if moved {
+            Gesture::Swipe {
+                x1,
+                y1,
+                x2,
+                y2,
+                duration_ms,
+            }
+        } else if held >= LONG_PRESS_SECS {
+            Gesture::LongPress {
+                x: x1,
+                y: y1,
+                duration_ms,
+            }
+        } else {
+            Gesture::Tap { x: x1, y: y1 }
+        };
+        Some(self.step(touch.start, gesture))
+    }
+
+    fn key_event(&mut self, time: f64, code: &str, value: &str) -> Option<Step> {
+        match value {
+            "DOWN" => {
+                self.keys_down.entry(code.to_string()).or_insert(time);
+                None
+            }
+            "UP" => {
+                let down = self.keys_down.remove(code)?;
+                let key = android_key(code)?;
+                let long = time - down >= LONG_PRESS_SECS;
+                Some(self.step(down, Gesture::Key { key, long }))
+            }
+            _ => None,
+        }
+    }
+
+    fn step(&mut self, start: f64, gesture: Gesture) -> Step {
+        let origin = *self.origin.get_or_insert(start);
+        Step {
+            t: ((start - origin) * 1000.0).round() / 1000.0,
+            gesture,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GETEVENT_LP: &str = "\
+add device 1: /dev/input/event1
+  name:     \"gpio-keys\"
+  events:
+    KEY (0001): KEY_VOLUMEDOWN        KEY_VOLUMEUP          KEY_POWER
+add device 2: /dev/input/event2
+  name:     \"virtio_input_multi_touch\"
+  events:
+    ABS (0003): ABS_MT_SLOT           : value 0, min 0, max 9, fuzz 0, flat 0, resolution 0
+                ABS_MT_POSITION_X     : value 0, min 0, max 32767, fuzz 0, flat 0, resolution 0
+                ABS_MT_POSITION_Y     : value 0, min 0, max 32767, fuzz 0, flat 0, resolution 0
+                ABS_MT_TRACKING_ID    : value 0, min 0, max 65535, fuzz 0, flat 0, resolution 0
+";
+
+    const PORTRAIT: Screen = Screen {
+        width: 1080,
+        height: 2400,
+    };
+
+    fn recorder() -> GestureRecorder {
+        GestureRecorder::new(PORTRAIT, 0, parse_touchscreen(GETEVENT_LP).unwrap())
+    }
+
+    fn touch(recorder: &mut GestureRecorder, path: &[(f64, i64, i64)]) -> Vec<Step> {
+        let mut lines = Vec::new();
+        let event = |t: f64, kind: &str, code: &str, value: String| {
+            format!(
+                "[{:>12.6}] /dev/input/event2: {:<12} {:<20} {}",
+                t, kind, code, value
+            )
+        };
+        for (i, &(t, x, y)) in path.iter().enumerate() {
+            if i == 0 {
+                lines.push(event(t, "EV_ABS", "ABS_MT_TRACKING_ID", "00000001".into()));
+            }
+            lines.push(event(
+                t,
+                "EV_ABS",
+                "ABS_MT_POSITION_X",
+                format!("{:08x}", x),
+            ));
+            lines.push(event(
+                t,
+                "EV_ABS",
+                "ABS_MT_POSITION_Y",
+                format!("{:08x}", y),
+            ));
+            lines.push(event(t, "EV_SYN", "SYN_REPORT", "00000000".into()));
+        }
+        let end = path.last().unwrap().0 + 0.01;
+        lines.push(event(
+            end,
+            "EV_ABS",
+            "ABS_MT_TRACKING_ID",
+            "ffffffff".into(),
+        ));
+        lines.push(event(end, "EV_SYN", "SYN_REPORT", "00000000".into()));
+        lines
+            .iter()
+            .filter_map(|l| recorder.feed(l).cloned())
+            .collect()
+    }
+
+    #[test]
+    fn parses_screen_and_touchscreen() {
+        assert_eq!(
+            parse_screen_size("Physical size: 1080x2400\nOverride size: 720x1600\n"),
+            Some(Screen {
+                width: 720,
+                height: 1600
+            })
+        );
+        assert_eq!(
+            parse_screen_size("Physical size: 1080x2400\n").map(|s| s.width),
+            Some(1080)
+        );
+        let touchscreen = parse_touchscreen(GETEVENT_LP).unwrap();
+        assert_eq!(touchscreen.path, "/dev/input/event2");
+        assert_eq!(touchscreen.x, Axis { min: 0, max: 32767 });
+        assert!(parse_touchscreen("add device 1: /dev/input/event1\n").is_none());
+    }
+
+    #[test]
+    fn records_taps_long_presses_swipes_and_keys() {
+        let mut recorder = recorder();
+        let tap = touch(
+            &mut recorder,
+            &[(100.0, 16384, 16384), (100.05, 16390, 16380)],
+        );
+        assert_eq!(
+            tap,
+            [Step {
+                t: 0.0,
+                gesture: Gesture::Tap { x: 540, y: 1200 }
+            }]
+        );
+        let held = touch(
+            &mut recorder,
+            &[(101.0, 16384, 16384), (101.8, 16384, 16384)],
+        );
+        assert!(matches!(
+            held[0].gesture,
+            Gesture::LongPress {
+                duration_ms: 810,
+                ..
+            }
+        ));
+        let swipe = touch(
+            &mut recorder,
+            &[
+                (102.5, 16384, 24000),
+                (102.6, 16384, 16000),
+                (102.7, 16384, 8000),
+            ],
+        );
+        assert_eq!(swipe[0].t, 2.5);
+        assert_eq!(
+            swipe[0].gesture,
+            Gesture::Swipe {
+                x1: 540,
+                y1: 1757,
+                x2: 540,
+                y2: 586,
+                duration_ms: 210
+            }
+        );
+
+        let key = |t: f64, value: &str| {
+            format!(
+                "[{:>12.6}] /dev/input/event1: EV_KEY       KEY_BACK             {}",
+                t, value
+            )
+        };
+        assert!(recorder.feed(&key(104.0, "DOWN")).is_none());
+        assert_eq!(
+            recorder.feed(&key(104.1, "UP")).map(|s| &s.gesture),
+            Some(&Gesture::Key {
+                key: "KEYCODE_BACK".into(),
+                long: false
+            })
+        );
+        assert!(recorder.feed("add device 3: /dev/input/event3").is_none());
+
+        let recorded = recorder.finish();
+        assert_eq!(recorded.steps.len(), 4);
+        assert_eq!(recorded.duration_secs(), 4.0);
+    }
+
+    #[test]
+    fn turns_touches_with_the_display() {
+        assert_eq!(
+            parse_rotation(
+                "Viewport INTERNAL: displayId=0, uniqueId=local:0, port=128, orientation=1, \
+                 logicalFrame=[0, 0, 2400, 1080], deviceSize=[1080, 2400]\n"
+            ),
+            Some(1)
+        );
+        assert_eq!(parse_rotation("    SurfaceOrientation: 3\n"), Some(3));
+        assert_eq!(parse_rotation("Input Manager State:\n"), None);
+
+        // Near the panel's top left corner: the bottom left of the screen
+        // turned one way, the top right turned the other.
+        let corner = |rotation: u8| {
+            let mut recorder = GestureRecorder::new(
+                PORTRAIT.rotated(rotation),
+                rotation,
+                parse_touchscreen(GETEVENT_LP).unwrap(),
+            );
+            touch(&mut recorder, &[(100.0, 3277, 3277)])
+                .remove(0)
+                .gesture
+        };
+        assert_eq!(corner(1), Gesture::Tap { x: 240, y: 971 });
+        assert_eq!(corner(2), Gesture::Tap { x: 971, y: 2159 });
+        assert_eq!(corner(3), Gesture::Tap { x: 2159, y: 108 });
+        assert_eq!(PORTRAIT.rotated(1).width, 2400);
+    }
+
+    #[test]
+    fn scales_to_another_screen_and_speed() {
+        let swipe = Gesture::Swipe {
+            x1: 540,
+            y1: 1800,
+            x2: 540,
+            y2: 600,
+            duration_ms: 300,
+        };
+        assert_eq!(
+            swipe.scaled(720.0 / 1080.0, 1600.0 / 2400.0, 2.0),
+            Gesture::Swipe {
+                x1: 360,
+                y1: 1200,
+                x2: 360,
+                y2: 400,
+                duration_ms: 150
+            }
+        );
+        let step: Step =
+            serde_json::from_str(r#"{"t":1.5,"action":"key","key":"KEYCODE_HOME"}"#).unwrap();
+        assert_eq!(step.gesture.to_string(), "key KEYCODE_HOME");
+    }
+}
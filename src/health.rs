@@ -0,0 +1,274 @@
+//! Thresholds for `dab health --fail-if` / `--warn-if`, and the CSV time
+//! series `--log` appends to.
+//!
+//! A threshold reads `<metric><op><value>`, e.g. `battery<20`, `storage>90`
+//! or `ram_free<0.5`. Percentages are 0–100 and sizes are in gigabytes.
+
+use super::model::DeviceHealth;
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// A health value a threshold can test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Battery level, in percent.
+    Battery,
+    /// `/data` usage, in percent.
+    Storage,
+    /// Free space on `/data`, in GB.
+    StorageFree,
+    /// RAM in use, in percent.
+    Ram,
+    /// Free RAM, in GB.
+    RamFree,
+}
+
+impl Metric {
+    const ALL: [Metric; 5] = [
+        Metric::Battery,
+        Metric::Storage,
+        Metric::StorageFree,
+        Metric::Ram,
+        Metric::RamFree,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Battery => "battery",
+            Metric::Storage => "storage",
+            Metric::StorageFree => "storage_free",
+            Metric::Ram => "ram",
+            Metric::RamFree => "ram_free",
+        }
+    }
+
+    /// The metric's current value, or `None` when the device didn't report it.
+    pub fn value(self, health: &DeviceHealth) -> Option<f64> {
+        let ram = &health.ram;
+        match self {
+            Metric::Battery => health.battery.level.as_deref()?.trim().parse().ok(),
+            Metric::Storage => health.storage.as_ref().map(|s| s.percent_used),
+            Metric::StorageFree => health.storage.as_ref().map(|s| s.free_gb),
+            Metric::Ram => (ram.total_gb > 0.0)
+                .then(|| round1((ram.total_gb - ram.free_gb) / ram.total_gb * 100.0)),
+            Metric::RamFree => (ram.total_gb > 0.0).then_some(ram.free_gb),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+
+    fn holds(self, left: f64, right: f64) -> bool {
+        match self {
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Gt => left > right,
+            Op::Ge => left >= right,
+        }
+    }
+}
+
+/// A condition that counts as a breach when it holds, e.g. `storage>90`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Threshold {
+    pub metric: Metric,
+    pub op: Op,
+    pub value: f64,
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let at = s
+            .find(['<', '>'])
+            .ok_or_else(|| format!("expected METRIC<VALUE or METRIC>VALUE, got `{}`", s))?;
+        let (name, rest) = s.split_at(at);
+        let (op, value) = match rest.split_at(1) {
+            ("<", v) => match v.strip_prefix('=') {
+                Some(v) => (Op::Le, v),
+                None => (Op::Lt, v),
+            },
+            (_, v) => match v.strip_prefix('=') {
+                Some(v) => (Op::Ge, v),
+                None => (Op::Gt, v),
+            },
+        };
+        let metric = Metric::ALL
+            .into_iter()
+            .find(|m| m.name() == name.trim())
+            .ok_or_else(|| {
+                let names: Vec<&str> = Metric::ALL.iter().map(|m| m.name()).collect();
+                format!(
+                    "unknown metric `{}` (use {})",
+                    name.trim(),
+                    names.join(", ")
+                )
+            })?;
+        let value =
+            value.trim().trim_end_matches('%').parse().map_err(|_| {
+                format!("expected a number after `{}`, got `{}`", op.symbol(), value)
+            })?;
+        Ok(Threshold { metric, op, value })
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.metric.name(),
+            self.op.symbol(),
+            self.value
+        )
+    }
+}
+
+/// A threshold that held, with the value that crossed it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Breach {
+    pub threshold: String,
+    pub value: f64,
+}
+
+impl fmt::Display for Breach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {} ({})",
+            self.threshold.split(['<', '>']).next().unwrap_or_default(),
+            self.value,
+            self.threshold
+        )
+    }
+}
+
+/// The thresholds `health` crosses. Metrics the device didn't report never
+/// count as breaches.
+pub fn breaches(health: &DeviceHealth, thresholds: &[Threshold]) -> Vec<Breach> {
+    thresholds
+        .iter()
+        .filter_map(|t| {
+            let value = t.metric.value(health)?;
+            t.op.holds(value, t.value).then(|| Breach {
+                threshold: t.to_string(),
+                value,
+            })
+        })
+        .collect()
+}
+
+pub const CSV_HEADER: &str =
+    "time,device,battery,battery_status,storage_percent,storage_free_gb,ram_percent,ram_free_gb";
+
+/// One `--log` row: `time` in Unix seconds, then every [`Metric`]. Values
+/// the device didn't report are left empty.
+pub fn csv_row(time: u64, health: &DeviceHealth) -> String {
+    let value = |metric: Metric| {
+        metric
+            .value(health)
+            .map(|v| v.to_string())
+            .unwrap_or_default()
+    };
+    format!(
+        "{},{},{},{},{},{},{},{}",
+        time,
+        health.device,
+        value(Metric::Battery),
+        health.battery.status.as_deref().unwrap_or_default(),
+        value(Metric::Storage),
+        value(Metric::StorageFree),
+        value(Metric::Ram),
+        value(Metric::RamFree)
+    )
+}
+
+fn round1(v: f64) -> f64 {
+    (v * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health() -> DeviceHealth {
+        let mut health = DeviceHealth::from(androkit::model::DeviceHealth {
+            device: "emu".into(),
+            storage: Some(androkit::model::Storage {
+                total_gb: 64.0,
+                used_gb: 60.0,
+                free_gb: 4.0,
+                percent_used: 93.8,
+            }),
+            ram: androkit::model::Ram {
+                total_gb: 4.0,
+                free_gb: 0.4,
+            },
+            ..Default::default()
+        });
+        health.battery.level = Some("18".into());
+        health.battery.status = Some("Discharging".into());
+        health
+    }
+
+    #[test]
+    fn parses_thresholds() {
+        let t: Threshold = "battery<20".parse().unwrap();
+        assert_eq!((t.metric, t.op, t.value), (Metric::Battery, Op::Lt, 20.0));
+        let t: Threshold = " storage >= 90% ".parse().unwrap();
+        assert_eq!((t.metric, t.op, t.value), (Metric::Storage, Op::Ge, 90.0));
+        assert_eq!(t.to_string(), "storage>=90");
+        assert!("battery=20".parse::<Threshold>().is_err());
+        assert!("disk>90".parse::<Threshold>().is_err());
+        assert!("ram_free<lots".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn reports_only_crossed_thresholds() {
+        let thresholds: Vec<Threshold> = ["battery<20", "storage>90", "ram_free<0.5", "ram>95"]
+            .iter()
+            .map(|t| t.parse().unwrap())
+            .collect();
+        let found: Vec<String> = breaches(&health(), &thresholds)
+            .iter()
+            .map(|b| b.to_string())
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "battery is 18 (battery<20)",
+                "storage is 93.8 (storage>90)",
+                "ram_free is 0.4 (ram_free<0.5)"
+            ]
+        );
+
+        let unknown = DeviceHealth::from(androkit::model::DeviceHealth::default());
+        assert!(breaches(&unknown, &thresholds).is_empty());
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let row = csv_row(1_700_000_000, &health());
+        assert_eq!(row, "1700000000,emu,18,Discharging,93.8,4,90,0.4");
+        assert_eq!(row.split(',').count(), CSV_HEADER.split(',').count());
+    }
+}
@@ -0,0 +1,130 @@
+//! Input injection through the device's `input` tool: key names, and text
+//! split into what `input text` can actually type.
+//!
+//! `input text` reads `%s` as a space and can't type a newline, and `adb
+//! shell` passes the text through the device's `sh` unquoted, so typing
+//! `hello world` or `a&b` with raw adb goes wrong. [`text_parts`] encodes the
+//! spaces, turns newlines and tabs into key events, and keeps a literal `%s`
+//! from being read as a space; the caller still quotes each part for `sh`.
+
+/// One `input` call that types part of a text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextPart {
+    /// Argument for `input text`, spaces already encoded as `%s`.
+    Text(String),
+    /// Key for `input keyevent`, for characters `input text` can't type.
+    Key(&'static str),
+}
+
+/// Split `text` into `input text` arguments and key events.
+pub fn text_parts(text: &str) -> Vec<TextPart> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => current.push_str("%s"),
+            '\n' | '\t' => {
+                if !current.is_empty() {
+                    parts.push(TextPart::Text(std::mem::take(&mut current)));
+                }
+                parts.push(TextPart::Key(if c == '\n' {
+                    "KEYCODE_ENTER"
+                } else {
+                    "KEYCODE_TAB"
+                }));
+            }
+            '\r' => {}
+            // A literal `%s` would be typed as a space; end the part after
+            // the `%` so the `s` starts the next one.
+            '%' if chars.peek() == Some(&'s') => {
+                current.push('%');
+                parts.push(TextPart::Text(std::mem::take(&mut current)));
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        parts.push(TextPart::Text(current));
+    }
+    parts
+}
+
+/// Other names for keys whose `KEYCODE_` constant isn't the obvious one.
+const KEY_ALIASES: [(&str, &str); 6] = [
+    ("BACKSPACE", "DEL"),
+    ("DELETE", "FORWARD_DEL"),
+    ("ESC", "ESCAPE"),
+    ("RETURN", "ENTER"),
+    ("RECENTS", "APP_SWITCH"),
+    ("LOCK", "SLEEP"),
+];
+
+/// The `input keyevent` argument for a key given as a name (`back`,
+/// `volume-up`), a `KEYCODE_` constant or a numeric key code.
+pub fn keycode(key: &str) -> Result<String, String> {
+    let key = key.trim();
+    if !key.is_empty() && key.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(key.to_string());
+    }
+    let name = key.to_ascii_uppercase().replace('-', "_");
+    let name = name.strip_prefix("KEYCODE_").unwrap_or(&name);
+    let name = KEY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, key)| key);
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!(
+            "expected a key name such as BACK, HOME or ENTER, a KEYCODE_ constant or a number, got `{}`",
+            key
+        ));
+    }
+    Ok(format!("KEYCODE_{}", name))
+}
+
+/// The error `input` printed, if any. It exits 0 even when it throws.
+pub fn input_error(output: &str) -> Option<&str> {
+    output.lines().map(str::trim).find(|l| {
+        l.starts_with("Error")
+            || l.contains("Exception")
+            || l.starts_with("Usage: input")
+            || l.starts_with("Unknown")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_text_into_what_input_can_type() {
+        use TextPart::{Key, Text};
+        assert_eq!(text_parts("hello world"), [Text("hello%sworld".into())]);
+        assert_eq!(
+            text_parts("a & b\nnext\tfield"),
+            [
+                Text("a%s&%sb".into()),
+                Key("KEYCODE_ENTER"),
+                Text("next".into()),
+                Key("KEYCODE_TAB"),
+                Text("field".into())
+            ]
+        );
+        assert_eq!(
+            text_parts("100%sure 5%"),
+            [Text("100%".into()), Text("sure%s5%".into())]
+        );
+        assert!(text_parts("").is_empty());
+    }
+
+    #[test]
+    fn normalizes_key_names() {
+        assert_eq!(keycode("back").unwrap(), "KEYCODE_BACK");
+        assert_eq!(keycode("volume-up").unwrap(), "KEYCODE_VOLUME_UP");
+        assert_eq!(keycode("KEYCODE_HOME").unwrap(), "KEYCODE_HOME");
+        assert_eq!(keycode("Backspace").unwrap(), "KEYCODE_DEL");
+        assert_eq!(keycode("66").unwrap(), "66");
+        assert!(keycode("").is_err());
+        assert!(keycode("back; reboot").is_err());
+    }
+}
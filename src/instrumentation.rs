@@ -0,0 +1,472 @@
+//! On-device instrumentation tests: parsing `am instrument -r` output and
+//! rendering the results as JUnit XML.
+//!
+//! Raw mode prints a status bundle (`INSTRUMENTATION_STATUS: key=value`
+//! lines, values may span lines) followed by `INSTRUMENTATION_STATUS_CODE`
+//! for every test start and finish, then a final `INSTRUMENTATION_RESULT`
+//! bundle and `INSTRUMENTATION_CODE`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    /// `@Ignore`d, so never run.
+    Ignored,
+    /// Stopped by a failed `assume…()`; the stack says which.
+    AssumptionFailed,
+}
+
+impl TestStatus {
+    /// The outcome a finished test's `INSTRUMENTATION_STATUS_CODE` reports,
+    /// or `None` for start (1), in-progress (2) and runner-specific codes.
+    fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "0" => Some(TestStatus::Passed),
+            "-1" | "-2" => Some(TestStatus::Failed),
+            "-3" => Some(TestStatus::Ignored),
+            "-4" => Some(TestStatus::AssumptionFailed),
+            _ => None,
+        }
+    }
+
+    /// Ignored and assumption-failed tests, reported as JUnit `<skipped/>`.
+    pub fn skipped(self) -> bool {
+        matches!(self, TestStatus::Ignored | TestStatus::AssumptionFailed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestCase {
+    pub class: String,
+    pub name: String,
+    pub status: TestStatus,
+    pub duration_secs: f64,
+    pub stack: Option<String>,
+}
+
+/// An instrumentation declared by an installed test APK.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrumentation {
+    pub package: String,
+    pub runner: String,
+    pub target: Option<String>,
+}
+
+/// Parse `pm list instrumentation`, e.g.
+/// `instrumentation:com.foo.test/androidx.test.runner.AndroidJUnitRunner (target=com.foo)`.
+pub fn parse_instrumentation_list(output: &str) -> Vec<Instrumentation> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("instrumentation:"))
+        .filter_map(|rest| {
+            let (component, target) = match rest.split_once(" (target=") {
+                Some((c, t)) => (c, Some(t.trim_end_matches(')').to_string())),
+                None => (rest, None),
+            };
+            let (package, runner) = component.split_once('/')?;
+            Some(Instrumentation {
+                package: package.to_string(),
+                runner: runner.to_string(),
+                target,
+            })
+        })
+        .collect()
+}
+
+/// The outcome of one `am instrument` run.
+#[derive(Debug, Clone, Default)]
+pub struct TestRun {
+    pub tests: Vec<TestCase>,
+    pub duration_secs: f64,
+    /// Set when the run itself broke (crashed process, missing runner, ...).
+    pub run_error: Option<String>,
+}
+
+impl TestRun {
+    pub fn count(&self, status: TestStatus) -> usize {
+        self.tests.iter().filter(|t| t.status == status).count()
+    }
+
+    /// Tests that didn't run to a pass or fail.
+    pub fn skipped(&self) -> usize {
+        self.tests.iter().filter(|t| t.status.skipped()).count()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.run_error.is_none() && self.count(TestStatus::Failed) == 0
+    }
+
+    /// The JSON summary written next to the JUnit report.
+    pub fn summary_json(&self, package: &str, runner: &str) -> Value {
+        json!({
+            "package": package,
+            "runner": runner,
+            "success": self.passed(),
+            "total": self.tests.len(),
+            "passed": self.count(TestStatus::Passed),
+            "failed": self.count(TestStatus::Failed),
+            "skipped": self.skipped(),
+            "duration_secs": round_ms(self.duration_secs),
+            "run_error": self.run_error,
+            "tests": self.tests,
+        })
+    }
+
+    /// JUnit XML with one `<testsuite>` per test class, in run order.
+    pub fn to_junit_xml(&self, package: &str) -> String {
+        let mut classes: Vec<&str> = Vec::new();
+        for t in &self.tests {
+            if !classes.contains(&t.class.as_str()) {
+                classes.push(&t.class);
+            }
+        }
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(package),
+            self.tests.len(),
+            self.count(TestStatus::Failed),
+            usize::from(self.run_error.is_some()),
+            self.skipped(),
+            self.duration_secs
+        ));
+        for class in classes {
+            let tests: Vec<&TestCase> = self.tests.iter().filter(|t| t.class == class).collect();
+            let failures = tests
+                .iter()
+                .filter(|t| t.status == TestStatus::Failed)
+                .count();
+            let skipped = tests.iter().filter(|t| t.status.skipped()).count();
+            let time: f64 = tests.iter().map(|t| t.duration_secs).sum();
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" time=\"{:.3}\">\n",
+                xml_escape(class),
+                tests.len(),
+                failures,
+                skipped,
+                time
+            ));
+            for t in tests {
+                xml.push_str(&format!(
+                    "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                    xml_escape(&t.class),
+                    xml_escape(&t.name),
+                    t.duration_secs
+                ));
+                match (t.status, &t.stack) {
+                    (TestStatus::Passed, _) => xml.push_str("/>\n"),
+                    (TestStatus::Ignored, _) => {
+                        xml.push_str(">\n      <skipped/>\n    </testcase>\n")
+                    }
+                    (TestStatus::AssumptionFailed, stack) => {
+                        let message = stack
+                            .as_deref()
+                            .and_then(|s| s.lines().next())
+                            .unwrap_or("assumption failed");
+                        xml.push_str(&format!(
+                            ">\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                            xml_escape(message)
+                        ));
+                    }
+                    (TestStatus::Failed, stack) => {
+                        let stack = stack.as_deref().unwrap_or_default();
+                        let message = stack.lines().next().unwrap_or("failed");
+                        xml.push_str(&format!(
+                            ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+                            xml_escape(message),
+                            xml_escape(stack)
+                        ));
+                    }
+                }
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        if let Some(err) = &self.run_error {
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"1\" failures=\"0\" errors=\"1\" skipped=\"0\" time=\"0\">\n    <testcase classname=\"{}\" name=\"instrumentation\" time=\"0\">\n      <error message=\"{}\"/>\n    </testcase>\n  </testsuite>\n",
+                xml_escape(package),
+                xml_escape(package),
+                xml_escape(err)
+            ));
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+}
+
+fn round_ms(secs: f64) -> f64 {
+    (secs * 1000.0).round() / 1000.0
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if c.is_control() && c != '\n' && c != '\t' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Incremental parser for the raw status stream. Feed it every line with the
+/// time elapsed since the run started; it yields each test as it finishes.
+#[derive(Default)]
+pub struct StatusParser {
+    bundle: HashMap<String, String>,
+    last_key: Option<String>,
+    started: HashMap<(String, String), f64>,
+    result: HashMap<String, String>,
+    in_result: bool,
+    run: TestRun,
+}
+
+impl StatusParser {
+    pub fn feed(&mut self, line: &str, elapsed_secs: f64) -> Option<TestCase> {
+        let line = line.trim_end_matches('\r');
+        if let Some(kv) = line.strip_prefix("INSTRUMENTATION_STATUS: ") {
+            self.in_result = false;
+            self.store(kv);
+        } else if let Some(kv) = line.strip_prefix("INSTRUMENTATION_RESULT: ") {
+            self.in_result = true;
+            self.store(kv);
+        } else if let Some(code) = line.strip_prefix("INSTRUMENTATION_STATUS_CODE: ") {
+            self.last_key = None;
+            let bundle = std::mem::take(&mut self.bundle);
+            return self.finish_status(code.trim(), bundle, elapsed_secs);
+        } else if let Some(code) = line.strip_prefix("INSTRUMENTATION_CODE: ") {
+            self.last_key = None;
+            // -1 is Activity.RESULT_OK; anything else means the run broke.
+            if code.trim() != "-1" && self.run.run_error.is_none() {
+                self.run.run_error = Some(format!("Instrumentation exited with code {}", code));
+            }
+        } else if let Some(reason) = line.strip_prefix("INSTRUMENTATION_FAILED: ") {
+            self.run.run_error = Some(format!("Instrumentation failed: {}", reason));
+        } else if let Some(key) = &self.last_key {
+            let map = if self.in_result {
+                &mut self.result
+            } else {
+                &mut self.bundle
+            };
+            if let Some(value) = map.get_mut(key) {
+                value.push('\n');
+                value.push_str(line);
+            }
+        }
+        None
+    }
+
+    fn store(&mut self, kv: &str) {
+        let (key, value) = kv.split_once('=').unwrap_or((kv, ""));
+        let map = if self.in_result {
+            &mut self.result
+        } else {
+            &mut self.bundle
+        };
+        map.insert(key.to_string(), value.to_string());
+        self.last_key = Some(key.to_string());
+    }
+
+    fn finish_status(
+        &mut self,
+        code: &str,
+        bundle: HashMap<String, String>,
+        elapsed_secs: f64,
+    ) -> Option<TestCase> {
+        let class = bundle.get("class")?.clone();
+        let name = bundle.get("test")?.clone();
+        let key = (class.clone(), name.clone());
+        if code == "1" {
+            self.started.insert(key, elapsed_secs);
+            return None;
+        }
+        let status = TestStatus::from_code(code)?;
+        let start = self.started.remove(&key).unwrap_or(elapsed_secs);
+        let test = TestCase {
+            class,
+            name,
+            status,
+            duration_secs: round_ms(elapsed_secs - start),
+            stack: bundle
+                .get("stack")
+                .map(|s| s.trim_end().to_string())
+                .filter(|s| !s.is_empty()),
+        };
+        self.run.tests.push(test.clone());
+        Some(test)
+    }
+
+    /// The completed run; a test that started but never finished means the
+    /// process died under it.
+    pub fn finish(mut self, elapsed_secs: f64) -> TestRun {
+        if let Some(msg) = self.result.get("shortMsg") {
+            self.run.run_error = Some(msg.trim().to_string());
+        }
+        let mut unfinished: Vec<_> = self.started.drain().collect();
+        unfinished.sort_by(|a, b| a.1.total_cmp(&b.1));
+        for ((class, name), start) in unfinished {
+            self.run.tests.push(TestCase {
+                class,
+                name,
+                status: TestStatus::Failed,
+                duration_secs: round_ms(elapsed_secs - start),
+                stack: self.run.run_error.clone(),
+            });
+        }
+        self.run.duration_secs = elapsed_secs;
+        self.run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &str = "\
+INSTRUMENTATION_STATUS: class=com.foo.LoginTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: id=AndroidJUnitRunner
+INSTRUMENTATION_STATUS: numtests=3
+INSTRUMENTATION_STATUS: stream=
+com.foo.LoginTest:
+INSTRUMENTATION_STATUS: test=logsIn
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.foo.LoginTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: test=logsIn
+INSTRUMENTATION_STATUS_CODE: 0
+INSTRUMENTATION_STATUS: class=com.foo.LoginTest
+INSTRUMENTATION_STATUS: test=rejectsBadPassword
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.foo.LoginTest
+INSTRUMENTATION_STATUS: stack=java.lang.AssertionError: expected <error> but was <home>
+\tat org.junit.Assert.fail(Assert.java:89)
+\tat com.foo.LoginTest.rejectsBadPassword(LoginTest.kt:42)
+
+INSTRUMENTATION_STATUS: test=rejectsBadPassword
+INSTRUMENTATION_STATUS_CODE: -2
+INSTRUMENTATION_STATUS: class=com.foo.CartTest
+INSTRUMENTATION_STATUS: test=ignored
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.foo.CartTest
+INSTRUMENTATION_STATUS: test=ignored
+INSTRUMENTATION_STATUS_CODE: -3
+INSTRUMENTATION_RESULT: stream=
+
+Time: 1.5
+
+FAILURES!!!
+Tests run: 3,  Failures: 1
+
+INSTRUMENTATION_CODE: -1
+";
+
+    fn parse(raw: &str) -> TestRun {
+        let mut parser = StatusParser::default();
+        for (i, line) in raw.lines().enumerate() {
+            parser.feed(line, i as f64 * 0.1);
+        }
+        parser.finish(3.0)
+    }
+
+    #[test]
+    fn parses_pass_fail_and_skip() {
+        let run = parse(RAW);
+        assert_eq!(run.tests.len(), 3);
+        assert_eq!(run.tests[0].name, "logsIn");
+        assert_eq!(run.tests[0].status, TestStatus::Passed);
+        assert!(run.tests[0].duration_secs > 0.0);
+        assert_eq!(run.tests[1].status, TestStatus::Failed);
+        let stack = run.tests[1].stack.as_deref().unwrap();
+        assert!(stack.starts_with("java.lang.AssertionError"));
+        assert!(stack.contains("LoginTest.kt:42"));
+        assert_eq!(run.tests[2].status, TestStatus::Ignored);
+        assert!(run.run_error.is_none());
+        assert!(!run.passed());
+    }
+
+    #[test]
+    fn in_progress_and_custom_codes_do_not_end_a_test() {
+        let raw = "\
+INSTRUMENTATION_STATUS: class=com.foo.A
+INSTRUMENTATION_STATUS: test=slow
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.foo.A
+INSTRUMENTATION_STATUS: test=slow
+INSTRUMENTATION_STATUS: stream=still going
+INSTRUMENTATION_STATUS_CODE: 2
+INSTRUMENTATION_STATUS: class=com.foo.A
+INSTRUMENTATION_STATUS: test=slow
+INSTRUMENTATION_STATUS_CODE: 42
+INSTRUMENTATION_STATUS: class=com.foo.A
+INSTRUMENTATION_STATUS: test=slow
+INSTRUMENTATION_STATUS_CODE: 0
+INSTRUMENTATION_STATUS: class=com.foo.A
+INSTRUMENTATION_STATUS: test=assumes
+INSTRUMENTATION_STATUS: stack=org.junit.AssumptionViolatedException: got: <false>
+INSTRUMENTATION_STATUS_CODE: -4
+INSTRUMENTATION_CODE: -1
+";
+        let run = parse(raw);
+        assert_eq!(run.tests.len(), 2);
+        assert_eq!(run.tests[0].status, TestStatus::Passed);
+        assert_eq!(run.tests[0].duration_secs, 1.0);
+        assert_eq!(run.tests[1].status, TestStatus::AssumptionFailed);
+        assert!(run.passed());
+        assert_eq!(run.skipped(), 1);
+        let xml = run.to_junit_xml("com.foo.test");
+        assert!(xml.contains(
+            "<skipped message=\"org.junit.AssumptionViolatedException: got: &lt;false&gt;\"/>"
+        ));
+    }
+
+    #[test]
+    fn reports_crashed_runs() {
+        let raw = "\
+INSTRUMENTATION_STATUS: class=com.foo.A
+INSTRUMENTATION_STATUS: test=boom
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_RESULT: shortMsg=Process crashed.
+INSTRUMENTATION_CODE: 0
+";
+        let run = parse(raw);
+        assert_eq!(run.run_error.as_deref(), Some("Process crashed."));
+        assert_eq!(run.tests.len(), 1);
+        assert_eq!(run.tests[0].status, TestStatus::Failed);
+    }
+
+    #[test]
+    fn junit_xml_groups_by_class_and_escapes() {
+        let xml = parse(RAW).to_junit_xml("com.foo.test");
+        assert!(xml.contains("<testsuites name=\"com.foo.test\" tests=\"3\" failures=\"1\""));
+        assert!(xml.contains("<testsuite name=\"com.foo.LoginTest\" tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("<testsuite name=\"com.foo.CartTest\" tests=\"1\""));
+        assert!(xml.contains("<failure message=\"java.lang.AssertionError: expected &lt;error&gt; but was &lt;home&gt;\">"));
+        assert!(xml.contains("<skipped/>"));
+    }
+
+    #[test]
+    fn parses_instrumentation_list() {
+        let list = parse_instrumentation_list(
+            "instrumentation:com.foo.test/androidx.test.runner.AndroidJUnitRunner (target=com.foo)\n",
+        );
+        assert_eq!(
+            list,
+            vec![Instrumentation {
+                package: "com.foo.test".into(),
+                runner: "androidx.test.runner.AndroidJUnitRunner".into(),
+                target: Some("com.foo".into()),
+            }]
+        );
+    }
+}
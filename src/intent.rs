@@ -0,0 +1,460 @@
+//! Intents for `am start`, `am broadcast` and `am startservice`: extras
+//! written as `KEY:TYPE=VALUE`, flags by name, and the checks that turn `am`'s
+//! printed errors into failures (it exits 0 either way).
+
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// What an intent is sent to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    Activity,
+    Broadcast,
+    Service,
+    ForegroundService,
+}
+
+impl Target {
+    /// The `am` subcommand that sends to this target.
+    pub fn am_command(self) -> &'static str {
+        match self {
+            Target::Activity => "start",
+            Target::Broadcast => "broadcast",
+            Target::Service => "startservice",
+            Target::ForegroundService => "start-foreground-service",
+        }
+    }
+}
+
+/// The type of an intent extra, named as in `--extra KEY:TYPE=VALUE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ExtraType {
+    #[serde(rename = "string")]
+    String,
+    #[serde(rename = "int")]
+    Int,
+    #[serde(rename = "long")]
+    Long,
+    #[serde(rename = "float")]
+    Float,
+    #[serde(rename = "double")]
+    Double,
+    #[serde(rename = "bool")]
+    Bool,
+    #[serde(rename = "uri")]
+    Uri,
+    #[serde(rename = "component")]
+    Component,
+    #[serde(rename = "int[]")]
+    IntArray,
+    #[serde(rename = "long[]")]
+    LongArray,
+    #[serde(rename = "float[]")]
+    FloatArray,
+    #[serde(rename = "string[]")]
+    StringArray,
+    #[serde(rename = "null")]
+    Null,
+}
+
+impl ExtraType {
+    const ALL: [ExtraType; 13] = [
+        ExtraType::String,
+        ExtraType::Int,
+        ExtraType::Long,
+        ExtraType::Float,
+        ExtraType::Double,
+        ExtraType::Bool,
+        ExtraType::Uri,
+        ExtraType::Component,
+        ExtraType::IntArray,
+        ExtraType::LongArray,
+        ExtraType::FloatArray,
+        ExtraType::StringArray,
+        ExtraType::Null,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExtraType::String => "string",
+            ExtraType::Int => "int",
+            ExtraType::Long => "long",
+            ExtraType::Float => "float",
+            ExtraType::Double => "double",
+            ExtraType::Bool => "bool",
+            ExtraType::Uri => "uri",
+            ExtraType::Component => "component",
+            ExtraType::IntArray => "int[]",
+            ExtraType::LongArray => "long[]",
+            ExtraType::FloatArray => "float[]",
+            ExtraType::StringArray => "string[]",
+            ExtraType::Null => "null",
+        }
+    }
+
+    fn am_option(self) -> &'static str {
+        match self {
+            ExtraType::String => "--es",
+            ExtraType::Int => "--ei",
+            ExtraType::Long => "--el",
+            ExtraType::Float => "--ef",
+            ExtraType::Double => "--ed",
+            ExtraType::Bool => "--ez",
+            ExtraType::Uri => "--eu",
+            ExtraType::Component => "--ecn",
+            ExtraType::IntArray => "--eia",
+            ExtraType::LongArray => "--ela",
+            ExtraType::FloatArray => "--efa",
+            ExtraType::StringArray => "--esa",
+            ExtraType::Null => "--esn",
+        }
+    }
+
+    /// Check `value` parses as this type; arrays are comma-separated.
+    fn check(self, value: &str) -> bool {
+        fn all<T: FromStr>(value: &str) -> bool {
+            value.split(',').all(|v| v.trim().parse::<T>().is_ok())
+        }
+        match self {
+            ExtraType::Int => value.parse::<i32>().is_ok(),
+            ExtraType::Long => value.parse::<i64>().is_ok(),
+            ExtraType::Float => value.parse::<f32>().is_ok(),
+            ExtraType::Double => value.parse::<f64>().is_ok(),
+            ExtraType::Bool => value.parse::<bool>().is_ok(),
+            ExtraType::Component => value.contains('/'),
+            ExtraType::IntArray => all::<i32>(value),
+            ExtraType::LongArray => all::<i64>(value),
+            ExtraType::FloatArray => all::<f32>(value),
+            ExtraType::String | ExtraType::Uri | ExtraType::StringArray | ExtraType::Null => true,
+        }
+    }
+}
+
+/// One `--extra`: `KEY:TYPE=VALUE`, `KEY=VALUE` for a string, or `KEY:null`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Extra {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub kind: ExtraType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+impl FromStr for Extra {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (s, None),
+        };
+        let (key, kind) = match name.split_once(':') {
+            Some((key, kind)) => {
+                let kind = ExtraType::ALL
+                    .into_iter()
+                    .find(|t| t.name() == kind)
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = ExtraType::ALL.iter().map(|t| t.name()).collect();
+                        format!("unknown extra type `{}` (use {})", kind, names.join(", "))
+                    })?;
+                (key, kind)
+            }
+            None => (name, ExtraType::String),
+        };
+        if key.is_empty() {
+            return Err(format!("expected KEY:TYPE=VALUE, got `{}`", s));
+        }
+        match (kind, value) {
+            (ExtraType::Null, None) => {}
+            (ExtraType::Null, Some(_)) => {
+                return Err(format!("`{}:null` takes no value", key));
+            }
+            (_, None) => return Err(format!("expected KEY:TYPE=VALUE, got `{}`", s)),
+            (kind, Some(value)) if !kind.check(value) => {
+                return Err(format!("`{}` is not a valid {}", value, kind.name()));
+            }
+            _ => {}
+        }
+        Ok(Extra {
+            key: key.to_string(),
+            kind,
+            value: value.map(str::to_string),
+        })
+    }
+}
+
+/// `Intent.FLAG_*` values `--flags` accepts by name.
+const FLAGS: [(&str, u32); 12] = [
+    ("new-task", 0x1000_0000),
+    ("clear-task", 0x0000_8000),
+    ("clear-top", 0x0400_0000),
+    ("single-top", 0x2000_0000),
+    ("no-history", 0x4000_0000),
+    ("no-animation", 0x0001_0000),
+    ("reorder-to-front", 0x0002_0000),
+    ("multiple-task", 0x0800_0000),
+    ("exclude-from-recents", 0x0080_0000),
+    ("previous-is-top", 0x0100_0000),
+    ("include-stopped-packages", 0x0000_0020),
+    ("receiver-foreground", 0x1000_0000),
+];
+
+/// An intent flag, by name (`clear-task`) or as a number (`0x8000`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntentFlag {
+    pub name: String,
+    pub value: u32,
+}
+
+impl FromStr for IntentFlag {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let name = s.trim().to_lowercase().replace('_', "-");
+        let value = match name.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => FLAGS
+                .iter()
+                .find(|(flag, _)| *flag == name)
+                .map(|(_, value)| *value),
+        };
+        value
+            .map(|value| IntentFlag { name, value })
+            .ok_or_else(|| {
+                let names: Vec<&str> = FLAGS.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "unknown intent flag `{}` (use {}, or a hex value)",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl fmt::Display for IntentFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl Serialize for IntentFlag {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// An intent as `am` takes it. Unset parts are left out of the command.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Intent {
+    /// `package/class`, for an explicit intent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub component: Option<String>,
+    /// Limits an implicit intent to one package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extras: Vec<Extra>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<IntentFlag>,
+}
+
+impl Intent {
+    /// The `am` arguments after the subcommand, each quoted for the device
+    /// shell (`adb shell` joins its arguments without escaping them).
+    pub fn am_args(&self) -> Vec<String> {
+        let mut args: Vec<&str> = Vec::new();
+        if let Some(action) = &self.action {
+            args.extend(["-a", action]);
+        }
+        if let Some(data) = &self.data {
+            args.extend(["-d", data]);
+        }
+        if let Some(mime_type) = &self.mime_type {
+            args.extend(["-t", mime_type]);
+        }
+        for category in &self.categories {
+            args.extend(["-c", category]);
+        }
+        let flags = self.flags.iter().fold(0, |all, f| all | f.value);
+        let flags = format!("0x{:x}", flags);
+        if !self.flags.is_empty() {
+            args.extend(["-f", &flags]);
+        }
+        for extra in &self.extras {
+            args.extend([extra.kind.am_option(), &extra.key]);
+            if let Some(value) = &extra.value {
+                args.push(value);
+            }
+        }
+        if let Some(component) = &self.component {
+            args.extend(["-n", component]);
+        } else if let Some(package) = &self.package {
+            args.extend(["-p", package]);
+        }
+        args.into_iter().map(shell_quote).collect()
+    }
+}
+
+/// `value` quoted for `sh` when it holds anything beyond plain characters.
+pub fn shell_quote(value: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:=,@%+".contains(c);
+    if !value.is_empty() && value.chars().all(plain) {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// `pkg/class` for `class` given as `.Relative`, a full class name, or a
+/// complete component.
+pub fn component(package: &str, class: &str) -> String {
+    if class.contains('/') {
+        class.to_string()
+    } else if class.contains('.') {
+        format!("{}/{}", package, class)
+    } else {
+        format!("{}/.{}", package, class)
+    }
+}
+
+/// What `am broadcast` reports the receivers returned.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BroadcastResult {
+    pub result_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_data: Option<String>,
+}
+
+/// The error `am` printed, if any. `am` exits 0 even when nothing started.
+pub fn am_error(output: &str) -> Option<String> {
+    let lines: Vec<&str> = output.lines().map(str::trim).collect();
+    lines
+        .iter()
+        .find(|l| l.starts_with("Error:"))
+        .or_else(|| {
+            lines.iter().find(|l| {
+                l.starts_with("Error type")
+                    || l.starts_with("Exception occurred")
+                    || l.starts_with("Security exception")
+                    || l.starts_with("java.lang.")
+            })
+        })
+        .map(|l| l.trim_start_matches("Error: ").to_string())
+}
+
+/// Parse `Broadcast completed: result=-1, data="ok"`.
+pub fn parse_broadcast_result(output: &str) -> Option<BroadcastResult> {
+    let line = output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Broadcast completed: "))?;
+    let code = line.strip_prefix("result=")?;
+    let (code, rest) = code.split_once(',').unwrap_or((code, ""));
+    let result_data = rest
+        .trim()
+        .strip_prefix("data=")
+        .map(|d| d.trim_matches('"').to_string());
+    Some(BroadcastResult {
+        result_code: code.trim().parse().ok()?,
+        result_data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_typed_extras() {
+        let extra: Extra = "n:int=3".parse().unwrap();
+        assert_eq!(
+            (extra.kind, extra.value.as_deref()),
+            (ExtraType::Int, Some("3"))
+        );
+        let extra: Extra = "title=Hello = world".parse().unwrap();
+        assert_eq!(
+            (extra.kind, extra.value.as_deref()),
+            (ExtraType::String, Some("Hello = world"))
+        );
+        let extra: Extra = "ids:long[]=1,2,3".parse().unwrap();
+        assert_eq!(extra.kind, ExtraType::LongArray);
+        let extra: Extra = "token:null".parse().unwrap();
+        assert_eq!((extra.kind, extra.value), (ExtraType::Null, None));
+
+        assert!("n:int=three".parse::<Extra>().is_err());
+        assert!("n:short=3".parse::<Extra>().is_err());
+        assert!("flag:bool".parse::<Extra>().is_err());
+        assert!(":int=3".parse::<Extra>().is_err());
+    }
+
+    #[test]
+    fn parses_flags_by_name_or_value() {
+        let flag: IntentFlag = "clear-task".parse().unwrap();
+        assert_eq!(flag.value, 0x8000);
+        assert_eq!("NEW_TASK".parse::<IntentFlag>().unwrap().value, 0x1000_0000);
+        assert_eq!("0x20".parse::<IntentFlag>().unwrap().value, 0x20);
+        assert!("clear-everything".parse::<IntentFlag>().is_err());
+    }
+
+    #[test]
+    fn builds_quoted_am_arguments() {
+        let intent = Intent {
+            component: Some(component("com.example.app", ".ui.DebugActivity")),
+            action: Some("com.example.DEBUG".into()),
+            categories: vec!["android.intent.category.DEFAULT".into()],
+            extras: vec![
+                "key:string=it's here".parse().unwrap(),
+                "n:int=3".parse().unwrap(),
+            ],
+            flags: vec!["clear-task".parse().unwrap(), "new-task".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(
+            intent.am_args().join(" "),
+            r"-a com.example.DEBUG -c android.intent.category.DEFAULT -f 0x10008000 --es key 'it'\''s here' --ei n 3 -n com.example.app/.ui.DebugActivity"
+        );
+        assert_eq!(
+            component("com.example.app", "Main"),
+            "com.example.app/.Main"
+        );
+        assert_eq!(
+            component("com.example.app", "com.other.Main"),
+            "com.example.app/com.other.Main"
+        );
+    }
+
+    #[test]
+    fn reads_am_errors_and_broadcast_results() {
+        let missing = "Starting: Intent { cmp=com.example.app/.Nope }\nError type 3\nError: Activity class {com.example.app/com.example.app.Nope} does not exist.\n";
+        assert_eq!(
+            am_error(missing).as_deref(),
+            Some("Activity class {com.example.app/com.example.app.Nope} does not exist.")
+        );
+        assert_eq!(
+            am_error("Starting: Intent { cmp=com.example.app/.Main }\n"),
+            None
+        );
+
+        let done = "Broadcasting: Intent { act=com.example.PING flg=0x400000 }\nBroadcast completed: result=-1, data=\"pong\"\n";
+        assert_eq!(
+            parse_broadcast_result(done),
+            Some(BroadcastResult {
+                result_code: -1,
+                result_data: Some("pong".into())
+            })
+        );
+        assert_eq!(
+            parse_broadcast_result("Broadcast completed: result=0").map(|r| r.result_code),
+            Some(0)
+        );
+    }
+}
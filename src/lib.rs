@@ -0,0 +1,39 @@
+//! dab as a library: typed access to Android devices over ADB.
+//!
+//! [`AdbClient`] queries devices and performs actions without printing
+//! anything. Queries return the typed results in [`model`] (and the parsers'
+//! own types, such as [`crash::Incident`] or [`perf::Sample`]); the `dab`
+//! binary renders those same values as colored text or serializes them for
+//! `--json`.
+//!
+//! ```no_run
+//! let adb = dab_cli::AdbClient::new()?;
+//! for device in adb.get_devices()?.iter().filter(|d| d.is_ready()) {
+//!     let health = adb.get_device_health(&device.serial)?;
+//!     println!("{}: battery {:?}", device.serial, health.battery.level);
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub mod activity;
+pub mod adb_client;
+pub mod backend;
+pub mod crash;
+pub mod deeplink;
+pub mod device;
+pub mod error;
+pub mod fuzzy;
+pub mod gesture;
+pub mod health;
+pub mod input;
+pub mod instrumentation;
+pub mod intent;
+pub mod logcat;
+pub mod model;
+pub mod perf;
+pub mod provision;
+pub mod startup;
+pub mod ui;
+
+pub use adb_client::AdbClient;
+pub use error::{DabError, ErrorCode};
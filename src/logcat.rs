@@ -0,0 +1,233 @@
+//! Parsing and filtering for `adb logcat -v threadtime` output.
+//!
+//! `AdbClient::stream_logcat` owns the adb process and the PID tracking; this
+//! module only turns lines into [`LogEntry`] values, decides which ones to
+//! keep, and formats them for `--output` files.
+
+use regex::Regex;
+use serde::Serialize;
+use std::str::FromStr;
+
+/// Logcat priority, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum LogLevel {
+    #[serde(rename = "V")]
+    Verbose,
+    #[serde(rename = "D")]
+    Debug,
+    #[serde(rename = "I")]
+    Info,
+    #[serde(rename = "W")]
+    Warn,
+    #[serde(rename = "E")]
+    Error,
+    #[serde(rename = "F")]
+    Fatal,
+}
+
+impl LogLevel {
+    fn from_letter(letter: &str) -> Option<Self> {
+        Some(match letter {
+            "V" => LogLevel::Verbose,
+            "D" => LogLevel::Debug,
+            "I" => LogLevel::Info,
+            "W" => LogLevel::Warn,
+            "E" => LogLevel::Error,
+            "F" | "A" => LogLevel::Fatal,
+            _ => return None,
+        })
+    }
+
+    /// The single-letter priority logcat prints.
+    pub fn letter(self) -> &'static str {
+        match self {
+            LogLevel::Verbose => "V",
+            LogLevel::Debug => "D",
+            LogLevel::Info => "I",
+            LogLevel::Warn => "W",
+            LogLevel::Error => "E",
+            LogLevel::Fatal => "F",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    /// A level by name (`warn`) or by its logcat letter (`w`), in any case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.to_ascii_lowercase().as_str() {
+            "verbose" | "v" => LogLevel::Verbose,
+            "debug" | "d" => LogLevel::Debug,
+            "info" | "i" => LogLevel::Info,
+            "warn" | "w" => LogLevel::Warn,
+            "error" | "e" => LogLevel::Error,
+            "fatal" | "f" => LogLevel::Fatal,
+            _ => return Err(format!("unknown log level `{}`", s)),
+        })
+    }
+}
+
+/// One parsed logcat line; serialized as-is for `--json` NDJSON output.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub time: String,
+    pub pid: u32,
+    pub tid: u32,
+    pub level: LogLevel,
+    pub tag: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Parse a `threadtime` line such as
+    /// `10-16 12:34:56.789  1234  1250 I ActivityManager: Start proc ...`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut rest = line.trim_end();
+        let mut fields = [""; 5];
+        for field in fields.iter_mut() {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace)?;
+            *field = &rest[..end];
+            rest = &rest[end..];
+        }
+        let [date, time, pid, tid, level] = fields;
+        let (tag, message) = rest
+            .trim_start()
+            .split_once(": ")
+            .or_else(|| rest.trim_start().split_once(':'))?;
+        Some(LogEntry {
+            time: format!("{} {}", date, time),
+            pid: pid.parse().ok()?,
+            tid: tid.parse().ok()?,
+            level: LogLevel::from_letter(level)?,
+            tag: tag.trim().to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    /// Plain single-line form, used for `--output` files.
+    pub fn plain(&self) -> String {
+        format!(
+            "{} {:>5} {:>5} {} {}: {}",
+            self.time,
+            self.pid,
+            self.tid,
+            self.level.letter(),
+            self.tag,
+            self.message
+        )
+    }
+}
+
+/// Which entries to keep: minimum level, tag allow-list and message regex.
+#[derive(Default)]
+pub struct LogFilter {
+    pub min_level: Option<LogLevel>,
+    pub tags: Vec<String>,
+    pub pattern: Option<Regex>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.min_level.is_none_or(|min| entry.level >= min)
+            && (self.tags.is_empty() || self.tags.iter().any(|t| t == &entry.tag))
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|re| re.is_match(&entry.message) || re.is_match(&entry.tag))
+    }
+}
+
+/// The PID announced by ActivityManager's
+/// `Start proc 4321:com.example.app/u0a123 for activity ...` line, when it
+/// belongs to `package`. Lets the stream pick up a restarted app immediately
+/// instead of waiting for the next `pidof` poll.
+pub fn started_pid(entry: &LogEntry, package: &str) -> Option<u32> {
+    let rest = entry.message.strip_prefix("Start proc ")?;
+    let (pid, rest) = rest.split_once(':')?;
+    let name = rest.split(['/', ' ']).next()?;
+    (name == package).then(|| pid.parse().ok()).flatten()
+}
+
+/// PIDs from `pidof` output, which lists every process of the package.
+pub fn parse_pids(pidof: &str) -> Vec<u32> {
+    pidof
+        .split_whitespace()
+        .filter_map(|p| p.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: LogLevel, tag: &str, message: &str) -> LogEntry {
+        LogEntry {
+            time: "10-16 12:00:00.000".into(),
+            pid: 1,
+            tid: 1,
+            level,
+            tag: tag.into(),
+            message: message.into(),
+        }
+    }
+
+    #[test]
+    fn parses_threadtime_lines() {
+        let e = LogEntry::parse(
+            "10-16 12:34:56.789  1234  1250 I ActivityManager: Start proc 4321:com.foo/u0a1",
+        )
+        .unwrap();
+        assert_eq!(e.time, "10-16 12:34:56.789");
+        assert_eq!((e.pid, e.tid), (1234, 1250));
+        assert_eq!(e.level, LogLevel::Info);
+        assert_eq!(e.tag, "ActivityManager");
+        assert_eq!(e.message, "Start proc 4321:com.foo/u0a1");
+
+        let padded = LogEntry::parse("10-16 12:34:56.789  99  99 E chromium  : boom: bad").unwrap();
+        assert_eq!(padded.tag, "chromium");
+        assert_eq!(padded.message, "boom: bad");
+
+        assert!(LogEntry::parse("--------- beginning of main").is_none());
+    }
+
+    #[test]
+    fn filters_by_level_tag_and_regex() {
+        let filter = LogFilter {
+            min_level: Some(LogLevel::Warn),
+            tags: vec!["OkHttp".into()],
+            pattern: Some(Regex::new("timeout|refused").unwrap()),
+        };
+        assert!(filter.matches(&entry(LogLevel::Error, "OkHttp", "connect timeout")));
+        assert!(!filter.matches(&entry(LogLevel::Info, "OkHttp", "connect timeout")));
+        assert!(!filter.matches(&entry(LogLevel::Error, "Other", "connect timeout")));
+        assert!(!filter.matches(&entry(LogLevel::Error, "OkHttp", "200 OK")));
+        assert!(LogFilter::default().matches(&entry(LogLevel::Verbose, "x", "y")));
+    }
+
+    #[test]
+    fn detects_app_restarts() {
+        let e = entry(
+            LogLevel::Info,
+            "ActivityManager",
+            "Start proc 4321:com.foo/u0a123 for activity {com.foo/.Main}",
+        );
+        assert_eq!(started_pid(&e, "com.foo"), Some(4321));
+        assert_eq!(started_pid(&e, "com.foo.bar"), None);
+        assert_eq!(parse_pids("123 456\n"), vec![123, 456]);
+    }
+
+    #[test]
+    fn serializes_level_as_letter() {
+        let v = serde_json::to_value(entry(LogLevel::Warn, "t", "m")).unwrap();
+        assert_eq!(v["level"], "W");
+    }
+
+    #[test]
+    fn parses_level_names_and_letters() {
+        assert_eq!("warn".parse(), Ok(LogLevel::Warn));
+        assert_eq!("E".parse(), Ok(LogLevel::Error));
+        assert!("loud".parse::<LogLevel>().is_err());
+    }
+}
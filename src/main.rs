@@ -1,464 +1,2005 @@
-mod adb_client;
-mod app;
 mod cli;
+mod fanout;
+mod mcp;
+mod prompt;
+mod render;
+mod repl;
+mod scenario;
+mod server;
+mod tui;
 
-use adb_client::AdbClient;
+use adb_client::{AdbClient, LogEvent};
 use anyhow::Result;
+use backend::{AdbBackend, RealAdb, Recorder, Replayer};
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, InputCommand, MacroCommand, UiCommand};
 use colored::*;
-use inquire::{MultiSelect, Select};
+use dab_cli::{
+    adb_client, backend, deeplink, device, error, fuzzy, gesture, health, input, instrumentation,
+    intent, logcat, perf, provision, startup, ui,
+};
+use error::{DabError, ErrorCode};
+use fuzzy::Resolution;
+use gesture::Macro;
+use instrumentation::TestStatus;
+use intent::{Intent, Target};
+use logcat::LogFilter;
+use perf::{FrameStats, Sample};
+use prompt::Prompter;
+use provision::Profile;
+use render::Render;
+use scenario::Scenario;
+use serde_json::{json, Value};
+use startup::{StartMode, Stats};
+use std::fs::File;
+use std::io::{IsTerminal, LineWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-fn real_main() -> Result<()> {
-    let cli = Cli::parse();
-    let adb_client = AdbClient::new()?;
-    let json = cli.json;
+/// How long `dab open` waits to report which activity came up.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(5);
 
-    // ── Commands that don't need a connected device ──────────────────────────
+/// Set by Ctrl+C once [`stop_on_ctrl_c`] has run. Every fan-out thread reads
+/// the same flag, so one Ctrl+C stops them all.
+static STOP: AtomicBool = AtomicBool::new(false);
 
-    // `dab devices` — list connected devices
-    if matches!(&cli.command, Some(Commands::Devices)) {
-        if json {
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&adb_client.get_device_list_json())?
-            );
-        } else {
-            let devices = adb_client.get_device_list()?;
-            println!("{}", "Connected devices:".bold().yellow());
-            for d in devices {
-                println!("  {}", d.green());
+/// Make Ctrl+C set [`STOP`] instead of exiting, for commands that wrap up
+/// when interrupted. The handler is installed once per process.
+fn stop_on_ctrl_c() {
+    static INSTALL: Once = Once::new();
+    // Only fails if shell-mode already installed its own; Ctrl+C then
+    // behaves as it does there.
+    INSTALL.call_once(|| {
+        let _ = ctrlc::set_handler(|| STOP.store(true, Ordering::Relaxed));
+    });
+}
+
+const ANDROID_PERMISSIONS: [&str; 23] = [
+    "android.permission.CAMERA",
+    "android.permission.RECORD_AUDIO",
+    "android.permission.READ_CONTACTS",
+    "android.permission.WRITE_CONTACTS",
+    "android.permission.GET_ACCOUNTS",
+    "android.permission.ACCESS_FINE_LOCATION",
+    "android.permission.ACCESS_COARSE_LOCATION",
+    "android.permission.ACCESS_BACKGROUND_LOCATION",
+    "android.permission.READ_PHONE_STATE",
+    "android.permission.CALL_PHONE",
+    "android.permission.READ_CALL_LOG",
+    "android.permission.WRITE_CALL_LOG",
+    "android.permission.ADD_VOICEMAIL",
+    "android.permission.USE_SIP",
+    "android.permission.BODY_SENSORS",
+    "android.permission.SEND_SMS",
+    "android.permission.RECEIVE_SMS",
+    "android.permission.READ_SMS",
+    "android.permission.RECEIVE_WAP_PUSH",
+    "android.permission.RECEIVE_MMS",
+    "android.permission.READ_EXTERNAL_STORAGE",
+    "android.permission.WRITE_EXTERNAL_STORAGE",
+    "android.permission.INTERNET",
+];
+
+/// Resolve a full, partial or fuzzy `--package` value to an installed package.
+fn resolve_package(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    query: &str,
+    json: bool,
+) -> Result<String> {
+    let packages = adb_client.get_installed_apps(device)?.apps;
+    resolve_among(query, &packages, json)
+}
+
+/// Resolve `query` against a list of candidate packages.
+fn resolve_among(query: &str, packages: &[String], json: bool) -> Result<String> {
+    match fuzzy::resolve(query, packages) {
+        Resolution::Found(pkg) => {
+            if !json && pkg != query {
+                println!("{} {} → {}", "Matched".green(), query, pkg.cyan());
             }
+            Ok(pkg)
         }
-        return Ok(());
+        Resolution::Ambiguous(candidates) => Err(DabError::new(
+            ErrorCode::AmbiguousPackage,
+            format!(
+                "'{}' matches several packages: {}",
+                query,
+                candidates.join(", ")
+            ),
+        )
+        .with_detail("query", query)
+        .with_detail("candidates", candidates)
+        .into()),
+        Resolution::NotFound(candidates) if candidates.is_empty() => Err(DabError::new(
+            ErrorCode::PackageNotFound,
+            format!("No installed package matches '{}'", query),
+        )
+        .with_detail("query", query)
+        .into()),
+        Resolution::NotFound(candidates) => Err(DabError::new(
+            ErrorCode::PackageNotFound,
+            format!(
+                "No installed package is named '{}'; close matches: {}",
+                query,
+                candidates.join(", ")
+            ),
+        )
+        .with_hint("Pass the full --package name, or a whole segment of it such as `youtube`.")
+        .with_detail("query", query)
+        .with_detail("candidates", candidates)
+        .into()),
     }
+}
 
-    // `dab info <file>` — analyze local APK/XAPK/APKM
-    if let Some(Commands::Info { file }) = &cli.command {
-        if json {
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&adb_client.analyze_local_file_json(file))?
-            );
-        } else {
-            println!("{} {}", "Analyzing file:".yellow(), file.display());
-            adb_client.analyze_local_file(file)?;
+/// The `--package` value of an app command, if one was given.
+fn package_flag(command: &Commands) -> Option<&str> {
+    match command {
+        Commands::Open { package }
+        | Commands::Uninstall { package }
+        | Commands::Clear { package }
+        | Commands::ForceKill { package }
+        | Commands::Download { package, .. }
+        | Commands::AppInfo { package, .. }
+        | Commands::Logcat { package, .. }
+        | Commands::Crashes { package, .. }
+        | Commands::Startup { package, .. }
+        | Commands::Perf { package, .. }
+        | Commands::Grant { package, .. }
+        | Commands::Revoke { package, .. }
+        | Commands::Deeplinks { package, .. } => package.as_deref(),
+        Commands::Start { intent, .. }
+        | Commands::Broadcast { intent, .. }
+        | Commands::StartService { intent, .. } => intent.package.as_deref(),
+        _ => None,
+    }
+}
+
+/// Resolve the target package: `--package` flag, or the app picker.
+fn select_package(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+    json: bool,
+) -> Result<String> {
+    if let Some(pkg) = package_flag(command) {
+        return resolve_package(adb_client, device, pkg, json);
+    }
+    prompter.require("app", "--package")?;
+    if !json {
+        println!("{}", "Loading installed apps...".yellow());
+    }
+    let apps = adb_client.get_installed_apps(device)?.apps;
+    if apps.is_empty() {
+        return Err(DabError::new(ErrorCode::PackageNotFound, "No installed apps found").into());
+    }
+    prompter.app(apps)
+}
+
+/// Parse `--permissions`, or let the user multi-select them.
+fn select_permissions(
+    permissions: &Option<String>,
+    prompter: &Prompter,
+    verb: &str,
+) -> Result<Vec<String>> {
+    match permissions {
+        Some(p) => Ok(p
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect()),
+        None => prompter.permissions(
+            &format!(
+                "Select permissions to {} (space to select, enter to apply):",
+                verb
+            ),
+            ANDROID_PERMISSIONS.to_vec(),
+        ),
+    }
+}
+
+/// `dab test`: install the given APKs, pick the instrumentation, run it and
+/// write `junit.xml` and `summary.json` into the output directory. Returns
+/// the summary document, or a `TESTS_FAILED` error carrying its counts.
+fn run_tests(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+    json: bool,
+) -> Result<Value> {
+    let Commands::Test {
+        package,
+        runner,
+        class,
+        arg,
+        install,
+        output,
+    } = command
+    else {
+        unreachable!("run_tests is only called for `dab test`")
+    };
+    for apk in install {
+        if !json {
+            println!("{} {}", "Installing file:".yellow(), apk.display());
         }
-        return Ok(());
+        adb_client.install_file(device, apk)?;
     }
 
-    // ── Select (or pin) device ───────────────────────────────────────────────
+    let instrumentations = adb_client.list_instrumentations(device)?;
+    if instrumentations.is_empty() {
+        return Err(DabError::new(
+            ErrorCode::PackageNotFound,
+            "No instrumentation test packages are installed",
+        )
+        .with_hint("Install the test APK, e.g. with --install app-debug-androidTest.apk.")
+        .into());
+    }
+    let test_packages: Vec<String> = instrumentations.iter().map(|i| i.package.clone()).collect();
+    let test_package = match package {
+        Some(query) => resolve_among(query, &test_packages, json)?,
+        None => prompter.app(test_packages)?,
+    };
+    let runner = match runner {
+        Some(r) => r.clone(),
+        None => instrumentations
+            .iter()
+            .find(|i| i.package == test_package)
+            .map(|i| i.runner.clone())
+            .unwrap_or_default(),
+    };
 
-    let device: String = if let Some(serial) = &cli.device {
-        serial.clone()
-    } else {
-        let devices = adb_client.get_device_list()?;
-        if devices.len() > 1 {
-            Select::new("Select device:", devices).prompt()?
-        } else {
-            devices.into_iter().next().unwrap()
+    let mut extras = arg.clone();
+    if !class.is_empty() {
+        extras.push(("class".to_string(), class.join(",")));
+    }
+    if !json {
+        println!(
+            "{} {}/{}",
+            "Running".green(),
+            test_package.cyan(),
+            runner.dimmed()
+        );
+    }
+    let run = adb_client.run_instrumentation(device, &test_package, &runner, &extras, |t| {
+        if !json {
+            println!("{}", t.render());
         }
+    })?;
+
+    std::fs::create_dir_all(output)?;
+    let junit_path = output.join("junit.xml");
+    let summary_path = output.join("summary.json");
+    std::fs::write(&junit_path, run.to_junit_xml(&test_package))?;
+    let mut summary = run.summary_json(&test_package, &runner);
+    summary["device"] = json!(device);
+    summary["junit"] = json!(junit_path.to_string_lossy());
+    std::fs::write(&summary_path, serde_json::to_string_pretty(&summary)?)?;
+
+    if !json {
+        println!("{}", run.render());
+        println!("{} {}", "JUnit report:".bold(), junit_path.display());
+        println!("{} {}", "Summary:".bold(), summary_path.display());
+    }
+    if !run.passed() {
+        let failed = run.count(TestStatus::Failed);
+        let message = match &run.run_error {
+            Some(err) => format!("Instrumentation run failed: {}", err),
+            None => format!("{} of {} tests failed", failed, run.tests.len()),
+        };
+        return Err(DabError::new(ErrorCode::TestsFailed, message)
+            .with_detail("failed", failed)
+            .with_detail("total", run.tests.len())
+            .with_detail("junit", junit_path.to_string_lossy())
+            .with_detail("summary", summary_path.to_string_lossy())
+            .into());
+    }
+    Ok(summary)
+}
+
+/// `dab startup`: launch the app `runs` times in the chosen mode, collect the
+/// `am start -W` timings and compare the median against `--baseline`.
+fn run_startup(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    package: &str,
+    command: &Commands,
+    json: bool,
+) -> Result<Value> {
+    let Commands::Startup {
+        runs,
+        warm,
+        hot,
+        baseline,
+        threshold,
+        ..
+    } = command
+    else {
+        unreachable!("run_startup is only called for `dab startup`")
+    };
+    let mode = if *warm {
+        StartMode::Warm
+    } else if *hot {
+        StartMode::Hot
+    } else {
+        StartMode::Cold
+    };
+    // Read the baseline first so a bad file fails before a long benchmark.
+    let baseline_median = match baseline {
+        Some(path) => Some(read_startup_baseline(path)?),
+        None => None,
     };
 
-    // ── Commands that need a device but not an app ───────────────────────────
+    let component = adb_client.launcher_activity(device, package)?;
+    let settle = || std::thread::sleep(std::time::Duration::from_secs(1));
+    if !json {
+        println!(
+            "{} {} ({:?} start, {} runs)",
+            "Measuring".green(),
+            component.cyan(),
+            mode,
+            runs
+        );
+    }
+    if mode != StartMode::Cold {
+        // Warm and hot starts need the process up before the first run.
+        adb_client.force_kill_app(device, package)?;
+        adb_client.start_activity_timed(device, &component)?;
+        settle();
+    }
+    let mut samples = Vec::new();
+    for run in 1..=*runs {
+        match mode {
+            StartMode::Cold => adb_client.force_kill_app(device, package)?,
+            StartMode::Warm => adb_client.press_key(device, "KEYCODE_BACK")?,
+            StartMode::Hot => adb_client.press_key(device, "KEYCODE_HOME")?,
+        }
+        settle();
+        let timing = adb_client.start_activity_timed(device, &component)?;
+        if !json {
+            println!(
+                "  Run {:>2}/{}: TotalTime {} ms, WaitTime {} ms",
+                run,
+                runs,
+                timing.total_ms.to_string().bold(),
+                timing.wait_ms
+            );
+        }
+        samples.push(timing);
+    }
+    let totals: Vec<u64> = samples.iter().map(|t| t.total_ms).collect();
+    let waits: Vec<u64> = samples.iter().map(|t| t.wait_ms).collect();
+    let total_time = Stats::of(&totals).expect("runs is at least 1");
+    let wait_time = Stats::of(&waits).expect("runs is at least 1");
+    if !json {
+        println!(
+            "\n{:<10} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "", "min", "median", "p90", "max", "mean", "stddev"
+        );
+        for (label, s) in [("TotalTime", &total_time), ("WaitTime", &wait_time)] {
+            println!(
+                "{:<10} {:>8} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                label.cyan(),
+                s.min,
+                s.median,
+                s.p90,
+                s.max,
+                s.mean,
+                s.stddev
+            );
+        }
+    }
+    let mut result = json!({
+        "device": device,
+        "package": package,
+        "activity": component,
+        "mode": mode,
+        "runs": runs,
+        "samples": samples,
+        "total_time": total_time,
+        "wait_time": wait_time,
+    });
 
-    match &cli.command {
-        Some(Commands::Apps) => {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&adb_client.get_installed_apps_json(&device))?
-                );
-            } else {
-                let apps = adb_client.get_installed_apps(&device)?;
-                println!("{}", "Installed apps:".bold().yellow());
-                for app in apps {
-                    println!("  {}", app.package_name);
+    if let (Some(path), Some(base)) = (baseline, baseline_median) {
+        let change = startup::change_percent(base, total_time.median);
+        let regressed = change > *threshold;
+        result["baseline"] = json!({
+            "file": path.to_string_lossy(),
+            "median_ms": base,
+            "change_percent": change,
+            "threshold_percent": threshold,
+            "regressed": regressed,
+        });
+        if !json {
+            let change_text = format!("{:+}%", change);
+            println!(
+                "\n{} median {} ms → {} ms ({})",
+                "Baseline:".bold(),
+                base,
+                total_time.median,
+                if regressed {
+                    change_text.red()
+                } else {
+                    change_text.green()
                 }
-            }
-            return Ok(());
+            );
         }
-        Some(Commands::Device) => {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&adb_client.get_device_info_json(&device))?
-                );
-            } else {
-                println!("{}", "Fetching device info...".yellow());
-                adb_client.get_device_info(&device)?;
-            }
-            return Ok(());
+        if regressed {
+            return Err(DabError::new(
+                ErrorCode::StartupRegression,
+                format!(
+                    "Median TotalTime regressed {:+}% ({} ms → {} ms), over the {}% threshold",
+                    change, base, total_time.median, threshold
+                ),
+            )
+            .with_detail("baseline_ms", base)
+            .with_detail("median_ms", total_time.median)
+            .with_detail("change_percent", change)
+            .with_detail("threshold_percent", *threshold)
+            .into());
         }
-        Some(Commands::Network) => {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&adb_client.get_network_info_json(&device))?
-                );
-            } else {
-                println!("{}", "Fetching network info...".yellow());
-                adb_client.get_network_info(&device)?;
-            }
-            return Ok(());
+    }
+    Ok(result)
+}
+
+/// `dab deeplinks`: list the app's deep links, checking where each resolves
+/// with `--verify`, or with `--open` let the user pick one, fill in its
+/// placeholders and launch it.
+fn run_deeplinks(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+    json: bool,
+) -> Result<Value> {
+    let Commands::Deeplinks {
+        file, open, verify, ..
+    } = command
+    else {
+        unreachable!("run_deeplinks only runs `deeplinks`")
+    };
+    let mut deeplinks = match file {
+        Some(file) => adb_client.get_file_deeplinks(file)?,
+        None => {
+            let package = select_package(adb_client, device, command, prompter, json)?;
+            adb_client.get_deeplinks(device, &package)?
         }
-        Some(Commands::Health) => {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&adb_client.get_device_health_json(&device))?
-                );
-            } else {
-                println!("{}", "Checking device health...".yellow());
-                adb_client.get_device_health(&device)?;
+    };
+
+    if *open {
+        if deeplinks.links.is_empty() {
+            if !json {
+                println!("{}", deeplinks.render());
             }
-            return Ok(());
+            return Ok(serde_json::to_value(&deeplinks)?);
         }
-        Some(Commands::Screenshot { output }) => {
-            let path = adb_client.take_screenshot(&device, output.clone())?;
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "output": path.to_string_lossy() })
-                );
+        let labels: Vec<String> = deeplinks
+            .links
+            .iter()
+            .map(|l| format!("{}  {}", l.url, l.activity.dimmed()))
+            .collect();
+        let link = &deeplinks.links[prompter.deep_link(labels)?];
+        let values = link
+            .placeholders
+            .iter()
+            .enumerate()
+            .map(|(i, name)| prompter.placeholder(name, &link.url, link.sample(i)))
+            .collect::<Result<Vec<String>>>()?;
+        let url = link.fill(&values);
+        let mut result = json!({ "success": true, "url": url, "activity": link.activity });
+        if *verify {
+            let verification = adb_client.verify_deeplink(device, link, &url)?;
+            if !verification.verified {
+                return Err(deeplink_mismatch(&[verification]));
             }
-            return Ok(());
+            result["verification"] = json!(verification);
         }
-        Some(Commands::Record { output }) => {
-            println!("{}", "Recording screen...".yellow());
-            let path = adb_client.record_screen(&device, output.clone())?;
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "output": path.to_string_lossy() })
-                );
-            }
-            return Ok(());
+        if !json {
+            println!("{} {}", "Launching:".green(), url.cyan());
         }
-        Some(Commands::Wifi) => {
-            println!("{}", "Setting up ADB over Wi-Fi...".yellow());
-            adb_client.enable_wifi(&device)?;
-            if json {
-                println!("{}", serde_json::json!({ "success": true }));
-            }
-            return Ok(());
+        adb_client.launch_url(device, &url)?;
+        return Ok(result);
+    }
+
+    if *verify {
+        for link in &mut deeplinks.links {
+            let url = link.sample_url();
+            link.verification = Some(adb_client.verify_deeplink(device, link, &url)?);
         }
-        Some(Commands::Usb) => {
-            println!("{}", "Switching ADB to USB mode...".yellow());
-            adb_client.enable_usb(&device)?;
-            if json {
-                println!("{}", serde_json::json!({ "success": true }));
-            }
-            return Ok(());
+    }
+    if !json {
+        println!("{}", deeplinks.render());
+    }
+    let failed: Vec<deeplink::Verification> = deeplinks
+        .links
+        .iter()
+        .filter_map(|l| l.verification.clone().filter(|v| !v.verified))
+        .collect();
+    if !failed.is_empty() {
+        return Err(deeplink_mismatch(&failed));
+    }
+    Ok(serde_json::to_value(&deeplinks)?)
+}
+
+/// The `DEEPLINK_MISMATCH` error for links that don't open their activity.
+fn deeplink_mismatch(failed: &[deeplink::Verification]) -> anyhow::Error {
+    let message = match failed {
+        [one] => format!(
+            "{} doesn't open {}: {}",
+            one.url,
+            one.expected,
+            one.problem().unwrap_or_default()
+        ),
+        many => format!("{} deep links don't open their own activity", many.len()),
+    };
+    DabError::new(ErrorCode::DeeplinkMismatch, message)
+        .with_detail("failed", json!(failed))
+        .into()
+}
+
+/// `dab start`, `broadcast` and `start-service`: build the intent from the
+/// flags and send it through `am`. The package is resolved (or picked) when
+/// the intent targets a class, or has no action or data to route it by; a
+/// bare `start --package` opens the launcher activity with the extras.
+fn send_intent(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+    json: bool,
+) -> Result<Value> {
+    let (target, class, args) = match command {
+        Commands::Start { activity, intent } => (Target::Activity, activity.as_deref(), intent),
+        Commands::Broadcast { receiver, intent } => {
+            (Target::Broadcast, receiver.as_deref(), intent)
         }
-        Some(Commands::Launch { url }) => {
-            if !json {
-                println!("{} {}", "Launching:".green(), url.cyan());
-            }
-            adb_client.launch_url(&device, url)?;
-            if json {
-                println!("{}", serde_json::json!({ "success": true, "url": url }));
-            }
-            return Ok(());
+        Commands::StartService {
+            service,
+            foreground,
+            intent,
+        } => {
+            let target = if *foreground {
+                Target::ForegroundService
+            } else {
+                Target::Service
+            };
+            (target, Some(service.as_str()), intent)
+        }
+        _ => unreachable!("send_intent only runs intent commands"),
+    };
+    if target == Target::Broadcast && class.is_none() && args.action.is_none() {
+        return Err(
+            DabError::new(ErrorCode::Usage, "`broadcast` needs --action or --receiver").into(),
+        );
+    }
+    let package = if args.package.is_some() || class.is_some() || !args.implicit() {
+        Some(select_package(adb_client, device, command, prompter, json)?)
+    } else {
+        None
+    };
+    let component = match (&package, class) {
+        (Some(package), Some(class)) => Some(intent::component(package, class)),
+        (Some(package), None) if target == Target::Activity && !args.implicit() => {
+            Some(adb_client.launcher_activity(device, package)?)
         }
-        Some(Commands::Install { file }) => {
-            if !json {
-                println!("{} {}", "Installing file:".yellow(), file.display());
-            }
-            adb_client.install_file(&device, file)?;
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "success": true, "file": file.to_string_lossy() })
-                );
-            }
-            return Ok(());
-        }
-        _ => {}
-    }
-
-    // ── Commands that need a device AND an app ───────────────────────────────
-
-    let android_permissions = vec![
-        "android.permission.CAMERA",
-        "android.permission.RECORD_AUDIO",
-        "android.permission.READ_CONTACTS",
-        "android.permission.WRITE_CONTACTS",
-        "android.permission.GET_ACCOUNTS",
-        "android.permission.ACCESS_FINE_LOCATION",
-        "android.permission.ACCESS_COARSE_LOCATION",
-        "android.permission.ACCESS_BACKGROUND_LOCATION",
-        "android.permission.READ_PHONE_STATE",
-        "android.permission.CALL_PHONE",
-        "android.permission.READ_CALL_LOG",
-        "android.permission.WRITE_CALL_LOG",
-        "android.permission.ADD_VOICEMAIL",
-        "android.permission.USE_SIP",
-        "android.permission.BODY_SENSORS",
-        "android.permission.SEND_SMS",
-        "android.permission.RECEIVE_SMS",
-        "android.permission.READ_SMS",
-        "android.permission.RECEIVE_WAP_PUSH",
-        "android.permission.RECEIVE_MMS",
-        "android.permission.READ_EXTERNAL_STORAGE",
-        "android.permission.WRITE_EXTERNAL_STORAGE",
-        "android.permission.INTERNET",
-    ];
-
-    // Extract --package from the command, if provided
-    let package_flag: Option<&str> = match &cli.command {
-        Some(Commands::Open { package }) => package.as_deref(),
-        Some(Commands::Uninstall { package }) => package.as_deref(),
-        Some(Commands::Clear { package }) => package.as_deref(),
-        Some(Commands::ForceKill { package }) => package.as_deref(),
-        Some(Commands::Download { package, .. }) => package.as_deref(),
-        Some(Commands::AppInfo { package, .. }) => package.as_deref(),
-        Some(Commands::Grant { package, .. }) => package.as_deref(),
-        Some(Commands::Revoke { package, .. }) => package.as_deref(),
         _ => None,
     };
-
-    // Resolve the target package: flag → interactive picker → full interactive UI
-    let selected_package: String = if let Some(pkg) = package_flag {
-        pkg.to_string()
-    } else if cli.command.is_some() {
-        // A subcommand was given but no --package: show app picker
+    let intent = Intent {
+        component,
+        package,
+        action: args.action.clone(),
+        data: args.data.clone(),
+        mime_type: args.mime_type.clone(),
+        categories: args.categories.clone(),
+        extras: args.extras.clone(),
+        flags: args.flags.clone(),
+    };
+    if !json {
+        let label = intent
+            .component
+            .as_deref()
+            .or(intent.action.as_deref())
+            .or(intent.data.as_deref())
+            .unwrap_or_default();
+        let verb = match target {
+            Target::Activity => "Starting:",
+            Target::Broadcast => "Broadcasting:",
+            Target::Service | Target::ForegroundService => "Starting service:",
+        };
+        println!("{} {}", verb.green(), label.cyan());
+    }
+    let result = adb_client.send_intent(device, target, &intent)?;
+    let mut doc = json!({ "success": true, "target": target, "intent": intent });
+    if let Some(result) = result {
         if !json {
-            println!("{}", "Loading installed apps...".yellow());
+            let data = result
+                .result_data
+                .as_deref()
+                .map(|d| format!(", data \"{}\"", d))
+                .unwrap_or_default();
+            println!("Broadcast completed: result {}{}", result.result_code, data);
         }
-        let apps = adb_client.get_installed_apps(&device)?;
-        if apps.is_empty() {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "error": "No installed apps found" })
-                );
+        doc["result_code"] = json!(result.result_code);
+        if let Some(data) = result.result_data {
+            doc["result_data"] = json!(data);
+        }
+    }
+    Ok(doc)
+}
+
+/// `dab ui dump`, `find` and `tap`. Finding nothing is a `UI_NODE_NOT_FOUND`
+/// error, so scripts can branch on the exit code.
+fn run_ui(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &UiCommand,
+    json: bool,
+) -> Result<Value> {
+    let (node_query, index) = match command {
+        UiCommand::Dump { xml, output } => {
+            let (contents, hierarchy) = if *xml {
+                (adb_client.dump_ui_xml(device)?, None)
             } else {
-                println!("{}", "No installed apps found.".yellow());
+                let hierarchy = adb_client.get_ui(device)?;
+                (serde_json::to_string_pretty(&hierarchy)?, Some(hierarchy))
+            };
+            if let Some(path) = output {
+                std::fs::write(path, contents + "\n")?;
+                if !json {
+                    println!("UI hierarchy saved to {}", path.display());
+                }
+                return Ok(json!({ "output": path.to_string_lossy() }));
             }
-            return Ok(());
+            return Ok(match hierarchy {
+                Some(hierarchy) => {
+                    if !json {
+                        println!("{}", hierarchy.render());
+                    }
+                    serde_json::to_value(hierarchy)?
+                }
+                None => {
+                    if !json {
+                        println!("{}", contents.trim_end());
+                    }
+                    json!({ "xml": contents })
+                }
+            });
         }
-        let app_strings: Vec<String> = apps.iter().map(|a| a.package_name.clone()).collect();
-        Select::new("Select app:", app_strings)
-            .with_page_size(15)
-            .prompt()?
-    } else {
-        // No subcommand at all: full interactive UI
-        println!("{}", "Loading installed apps...".yellow());
-        let apps = adb_client.get_installed_apps(&device)?;
-        if apps.is_empty() {
-            println!("{}", "No installed apps found.".yellow());
-            return Ok(());
-        }
-        let app_strings: Vec<String> = apps.iter().map(|a| a.package_name.clone()).collect();
-        Select::new("Select app:", app_strings)
-            .with_page_size(15)
-            .prompt()?
+        UiCommand::Find { query } => (query, None),
+        UiCommand::Tap { query, index } => (query, Some(*index)),
     };
 
-    // When no subcommand was given, show the action menu
-    let effective_command: Commands = match cli.command {
-        Some(command) => command,
-        None => {
-            let options = vec![
-                "Open",
-                "App Info",
-                "Uninstall",
-                "Clear App Data",
-                "Force Kill",
-                "Download APK",
-                "Grant Permissions",
-                "Revoke Permissions",
-            ];
-            let selection = Select::new("Select action:", options).prompt()?;
-            match selection {
-                "Open" => Commands::Open { package: None },
-                "App Info" => Commands::AppInfo {
-                    package: None,
-                    all: false,
-                },
-                "Uninstall" => Commands::Uninstall { package: None },
-                "Clear App Data" => Commands::Clear { package: None },
-                "Force Kill" => Commands::ForceKill { package: None },
-                "Download APK" => Commands::Download {
-                    package: None,
-                    output: None,
-                },
-                "Grant Permissions" => Commands::Grant {
-                    package: None,
-                    permissions: None,
-                },
-                "Revoke Permissions" => Commands::Revoke {
-                    package: None,
-                    permissions: None,
-                },
-                _ => unreachable!(),
+    let query = node_query.query();
+    if query.text.is_none() && query.id.is_none() && query.desc.is_none() {
+        return Err(DabError::new(ErrorCode::Usage, "Pass --text, --id or --desc").into());
+    }
+    let wait = node_query.wait.unwrap_or_default();
+    let found = adb_client.find_ui(device, &query, wait)?;
+    let not_found = |message: String| -> anyhow::Error {
+        DabError::new(ErrorCode::UiNodeNotFound, message)
+            .with_detail("query", query.to_string())
+            .with_detail("matches", found.len())
+            .into()
+    };
+    if found.is_empty() {
+        return Err(not_found(format!("No node on screen has {}", query)));
+    }
+    let Some(index) = index else {
+        if !json {
+            for node in &found {
+                println!("{}", node.render());
             }
         }
+        return Ok(json!({ "matches": found }));
     };
 
-    match &effective_command {
-        Commands::Open { .. } => {
-            if !json {
-                println!("{} {}", "Opening".green(), selected_package);
-            }
-            adb_client.open_app(&device, &selected_package)?;
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "success": true, "package": selected_package })
-                );
-            }
+    let target = match index {
+        Some(i) => found
+            .get(i)
+            .filter(|m| !m.node.bounds.is_empty())
+            .ok_or_else(|| {
+                not_found(format!(
+                    "Match {} of the {} with {} isn't on screen",
+                    i,
+                    found.len(),
+                    query
+                ))
+            })?,
+        None => found
+            .iter()
+            .find(|m| !m.node.bounds.is_empty())
+            .ok_or_else(|| not_found(format!("Every node with {} is off screen", query)))?,
+    };
+    if !json {
+        println!("{} {}", "Tapping:".green(), target.render());
+    }
+    adb_client.tap(device, target.center.x, target.center.y)?;
+    Ok(json!({
+        "success": true,
+        "x": target.center.x,
+        "y": target.center.y,
+        "node": target,
+        "matches": found.len(),
+    }))
+}
+
+/// `dab input`: inject one gesture, text or key event and acknowledge it.
+fn run_input(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &InputCommand,
+    json: bool,
+) -> Result<Value> {
+    let (message, mut doc) = match command {
+        InputCommand::Tap { x, y } => {
+            adb_client.tap(device, *x, *y)?;
+            (
+                format!("Tapped {},{}", x, y),
+                json!({ "action": "tap", "x": x, "y": y }),
+            )
         }
-        Commands::Uninstall { .. } => {
-            if !json {
-                println!("{} {}", "Uninstalling".red(), selected_package);
-            }
-            adb_client.uninstall_app(&device, &selected_package)?;
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "success": true, "package": selected_package })
-                );
-            }
+        InputCommand::LongPress { x, y, duration } => {
+            adb_client.long_press(device, *x, *y, *duration)?;
+            (
+                format!("Long-pressed {},{} for {:?}", x, y, duration),
+                json!({ "action": "long_press", "x": x, "y": y, "duration_ms": duration.as_millis() }),
+            )
         }
-        Commands::Clear { .. } => {
-            if !json {
-                println!("{} data for {}", "Clearing".blue(), selected_package);
-            }
-            adb_client.clear_app_data(&device, &selected_package)?;
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({ "success": true, "package": selected_package })
-                );
+        InputCommand::Swipe {
+            x1,
+            y1,
+            x2,
+            y2,
+            duration,
+        } => {
+            adb_client.swipe(device, (*x1, *y1), (*x2, *y2), *duration)?;
+            (
+                format!("Swiped {},{} → {},{} in {:?}", x1, y1, x2, y2, duration),
+                json!({
+                    "action": "swipe",
+                    "from": { "x": x1, "y": y1 },
+                    "to": { "x": x2, "y": y2 },
+                    "duration_ms": duration.as_millis(),
+                }),
+            )
+        }
+        InputCommand::Text { text } => {
+            adb_client.type_text(device, text)?;
+            (
+                format!("Typed {:?}", text),
+                json!({ "action": "text", "text": text }),
+            )
+        }
+        InputCommand::Key { key, long } => {
+            let keycode = input::keycode(key).map_err(|e| DabError::new(ErrorCode::Usage, e))?;
+            if *long {
+                adb_client.long_press_key(device, &keycode)?;
+            } else {
+                adb_client.press_key(device, &keycode)?;
             }
+            (
+                format!("Pressed {}", keycode),
+                json!({ "action": "key", "key": keycode, "long": long }),
+            )
         }
-        Commands::ForceKill { .. } => {
+    };
+    if !json {
+        println!("{}", message.green());
+    }
+    doc["success"] = json!(true);
+    Ok(doc)
+}
+
+/// `dab macro record` and `play`.
+fn run_macro(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &MacroCommand,
+    json: bool,
+) -> Result<Value> {
+    match command {
+        MacroCommand::Record { file } => {
+            // Ctrl+C stops adb's getevent, which ends the recording; dab
+            // itself keeps running to save it.
+            let _ = ctrlc::set_handler(|| {});
             if !json {
-                println!("{} {}", "Force killing".red(), selected_package);
-            }
-            adb_client.force_kill_app(&device, &selected_package)?;
-            if json {
                 println!(
-                    "{}",
-                    serde_json::json!({ "success": true, "package": selected_package })
+                    "Recording touches and keys on {}... Press Ctrl+C to stop.",
+                    device.cyan()
                 );
             }
-        }
-        Commands::Download { output, .. } => {
+            let recorded = adb_client.record_gestures(device, |step| {
+                if !json {
+                    println!("{:>8.2}s  {}", step.t, step.gesture);
+                }
+                Ok(())
+            })?;
+            std::fs::write(file, serde_json::to_string_pretty(&recorded)? + "\n")?;
             if !json {
-                println!("{} APK for {}", "Downloading".cyan(), selected_package);
-            }
-            let output_path =
-                adb_client.download_apk(&device, &selected_package, output.clone())?;
-            if json {
                 println!(
-                    "{}",
-                    serde_json::json!({ "success": true, "package": selected_package, "output": output_path.to_string_lossy() })
+                    "{} {} step(s) to {}",
+                    "Saved".green(),
+                    recorded.steps.len(),
+                    file.display()
                 );
-            } else {
-                println!("APK downloaded to {}", output_path.display());
             }
+            Ok(json!({
+                "output": file.to_string_lossy(),
+                "steps": recorded.steps.len(),
+                "duration_secs": recorded.duration_secs(),
+            }))
         }
-        Commands::AppInfo { all, .. } => {
-            if json {
+        MacroCommand::Play { file, speed } => {
+            let recorded = Macro::load(file)?;
+            let screen = adb_client.screen_size(device)?;
+            let sx = screen.width as f64 / recorded.screen.width as f64;
+            let sy = screen.height as f64 / recorded.screen.height as f64;
+            let total = recorded.steps.len();
+            if !json {
                 println!(
-                    "{}",
-                    serde_json::to_string_pretty(&adb_client.get_app_info_json(
-                        &device,
-                        &selected_package,
-                        *all
-                    ))?
+                    "Playing {} step(s) from {} at {}x on {}...",
+                    total,
+                    file.display(),
+                    speed,
+                    device.cyan()
                 );
-            } else {
-                println!("{} {}", "Fetching info for".yellow(), selected_package);
-                adb_client.get_app_info(&device, &selected_package, *all)?;
-            }
-        }
-        Commands::Grant { permissions, .. } => {
-            let perms_to_grant: Vec<String> = if let Some(p) = permissions {
-                p.split(',')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect()
-            } else {
-                let selected = MultiSelect::new(
-                    "Select permissions to grant (space to select, enter to apply):",
-                    android_permissions.clone(),
-                )
-                .with_page_size(15)
-                .prompt()?;
-                selected.iter().map(|s| s.to_string()).collect()
-            };
-            if perms_to_grant.is_empty() {
-                if json {
-                    println!(
-                        "{}",
-                        serde_json::json!({ "success": false, "reason": "no permissions selected" })
-                    );
-                } else {
-                    println!("No permissions selected.");
-                }
-            } else {
-                let perm_refs: Vec<&str> = perms_to_grant.iter().map(|s| s.as_str()).collect();
-                adb_client.grant_permissions(&device, &selected_package, &perm_refs)?;
-                if json {
+                if screen != recorded.screen {
                     println!(
-                        "{}",
-                        serde_json::json!({ "success": true, "package": selected_package, "granted": perms_to_grant })
+                        "Scaling from {}x{} to {}x{}",
+                        recorded.screen.width, recorded.screen.height, screen.width, screen.height
                     );
-                } else {
-                    println!("Permissions granted successfully.");
                 }
             }
-        }
-        Commands::Revoke { permissions, .. } => {
-            let perms_to_revoke: Vec<String> = if let Some(p) = permissions {
-                p.split(',')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect()
-            } else {
-                let selected = MultiSelect::new(
-                    "Select permissions to revoke (space to select, enter to apply):",
-                    android_permissions.clone(),
-                )
-                .with_page_size(15)
-                .prompt()?;
-                selected.iter().map(|s| s.to_string()).collect()
-            };
-            if perms_to_revoke.is_empty() {
-                if json {
-                    println!(
-                        "{}",
-                        serde_json::json!({ "success": false, "reason": "no permissions selected" })
-                    );
-                } else {
-                    println!("No permissions selected.");
+            // Steps are timed from the start rather than from each other, so
+            // slow adb calls don't add up.
+            let start = Instant::now();
+            for (i, step) in recorded.steps.iter().enumerate() {
+                let due = Duration::try_from_secs_f64(step.t / speed).map_err(|_| {
+                    DabError::new(
+                        ErrorCode::InvalidMacro,
+                        format!(
+                            "Step {} is due too far in at {}x: {}s",
+                            i + 1,
+                            speed,
+                            step.t
+                        ),
+                    )
+                })?;
+                if let Some(wait) = due.checked_sub(start.elapsed()) {
+                    std::thread::sleep(wait);
                 }
-            } else {
-                let perm_refs: Vec<&str> = perms_to_revoke.iter().map(|s| s.as_str()).collect();
-                adb_client.revoke_permissions(&device, &selected_package, &perm_refs)?;
-                if json {
-                    println!(
-                        "{}",
-                        serde_json::json!({ "success": true, "package": selected_package, "revoked": perms_to_revoke })
-                    );
-                } else {
-                    println!("Permissions revoked successfully.");
+                let gesture = step.gesture.scaled(sx, sy, *speed);
+                if !json {
+                    println!("[{}/{}] {}", i + 1, total, gesture);
                 }
+                adb_client.perform_gesture(device, &gesture)?;
+            }
+            if !json {
+                println!("{}", "Macro finished".green());
             }
+            Ok(json!({
+                "success": true,
+                "steps": total,
+                "duration_secs": start.elapsed().as_secs_f64(),
+                "scale": { "x": sx, "y": sy },
+            }))
         }
-        // All remaining commands are handled above and should never reach here
-        _ => {}
     }
+}
 
-    Ok(())
+/// `dab provision`: plan the profile against the device, then apply the
+/// changes in order unless `dry_run`. Returns the plan with `dry_run` set.
+fn run_provision(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    profile: &Path,
+    dry_run: bool,
+    json: bool,
+) -> Result<Value> {
+    let profile = Profile::load(profile)?;
+    let plan = provision::plan(adb_client, device, &profile)?;
+    if !json {
+        if dry_run {
+            println!("{}", plan.render());
+        } else {
+            println!("{} {}", "Provisioning".green(), device.cyan());
+        }
+    }
+    if !dry_run {
+        for step in &plan.changes {
+            step.apply(adb_client, device)?;
+            if !json {
+                println!("  {} {}", "✔".green(), step.render());
+            }
+        }
+    }
+    if !json {
+        println!(
+            "\n{} {} change(s), {} already in place",
+            if dry_run { "Planned" } else { "Applied" }.bold(),
+            plan.changes.len(),
+            plan.unchanged.len()
+        );
+    }
+    let mut doc = serde_json::to_value(&plan)?;
+    doc["dry_run"] = json!(dry_run);
+    Ok(doc)
 }
 
-fn main() {
-    match real_main() {
+/// `dab run`: load the scenario and run its steps, each CLI step through
+/// [`execute_json`] without prompting.
+fn run_scenario(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    path: &Path,
+    vars: &[(String, String)],
+    json: bool,
+) -> Result<Value> {
+    let loaded = Scenario::load(path, vars)?;
+    if !json {
+        println!(
+            "{} {} on {}",
+            "Running".green(),
+            path.display(),
+            device.cyan()
+        );
+    }
+    let serial = device.to_string();
+    scenario::run(adb_client, device, &loaded, json, move |client, command| {
+        execute_json(client, &serial, command, &Prompter::disabled())
+    })
+}
+
+/// `dab perf`: sample the app every `interval` for `duration` (or until
+/// Ctrl+C), printing each sample live and writing the series to `--output`.
+fn run_perf(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    package: &str,
+    command: &Commands,
+    json: bool,
+) -> Result<Value> {
+    let Commands::Perf {
+        interval,
+        duration,
+        output,
+        ..
+    } = command
+    else {
+        unreachable!("run_perf is only called for `dab perf`")
+    };
+    stop_on_ctrl_c();
+    let mut prev_frames = adb_client.app_frame_stats(device, package, true)?;
+    if !json {
+        println!(
+            "{} {} every {:?} for {:?}... Press Ctrl+C to stop early.",
+            "Sampling".green(),
+            package.cyan(),
+            interval,
+            duration
+        );
+    }
+
+    let started = Instant::now();
+    let mut prev_stat = None;
+    let mut waiting_shown = false;
+    let mut samples: Vec<Sample> = Vec::new();
+    while started.elapsed() < *duration && !STOP.load(Ordering::Relaxed) {
+        let tick = Instant::now();
+        let sampled = (|| -> Result<Option<Sample>> {
+            let Some(pid) = adb_client.app_pid(device, package)? else {
+                return Ok(None);
+            };
+            let memory = adb_client.app_meminfo(device, package)?.unwrap_or_default();
+            let stat = adb_client.app_proc_stat(device, pid)?;
+            let now = Instant::now();
+            let cpu_percent = match (prev_stat, stat) {
+                (Some((prev_pid, before, at)), Some(after)) if prev_pid == pid => Some(
+                    perf::cpu_percent(before, after, now.duration_since(at).as_secs_f64()),
+                ),
+                _ => None,
+            };
+            prev_stat = stat.map(|s| (pid, s, now));
+            let frames = adb_client.app_frame_stats(device, package, false)?;
+            let mut sample = Sample {
+                t: perf::round1(started.elapsed().as_secs_f64()),
+                pid,
+                memory,
+                cpu_percent,
+                threads: stat.map(|s| s.threads),
+                frames: 0,
+                janky_frames: 0,
+                jank_percent: 0.0,
+                frame_p90_ms: None,
+            };
+            sample.set_frames(prev_frames, frames);
+            prev_frames = frames;
+            Ok(Some(sample))
+        })();
+        match sampled {
+            Ok(Some(sample)) => {
+                if !json {
+                    println!("{}", sample.render());
+                }
+                samples.push(sample);
+            }
+            Ok(None) => {
+                prev_stat = None;
+                prev_frames = FrameStats::default();
+                if !json && !waiting_shown {
+                    println!("{} {} to start...", "Waiting for".yellow(), package);
+                    waiting_shown = true;
+                }
+            }
+            // adb calls interrupted by Ctrl+C just end the run.
+            Err(_) if STOP.load(Ordering::Relaxed) => break,
+            Err(e) => return Err(e),
+        }
+        while tick.elapsed() < *interval && !STOP.load(Ordering::Relaxed) {
+            std::thread::sleep(Duration::from_millis(50).min(*interval));
+        }
+    }
+
+    let peak_pss_kb = samples.iter().map(|s| s.memory.pss_kb).max();
+    let cpu: Vec<f64> = samples.iter().filter_map(|s| s.cpu_percent).collect();
+    let avg_cpu =
+        (!cpu.is_empty()).then(|| perf::round1(cpu.iter().sum::<f64>() / cpu.len() as f64));
+    let frames: u64 = samples.iter().map(|s| s.frames).sum();
+    let janky: u64 = samples.iter().map(|s| s.janky_frames).sum();
+    let jank_percent = if frames == 0 {
+        0.0
+    } else {
+        perf::round1(janky as f64 / frames as f64 * 100.0)
+    };
+    let mut result = json!({
+        "device": device,
+        "package": package,
+        "interval_secs": interval.as_secs_f64(),
+        "duration_secs": perf::round1(started.elapsed().as_secs_f64()),
+        "summary": {
+            "samples": samples.len(),
+            "peak_pss_kb": peak_pss_kb,
+            "avg_cpu_percent": avg_cpu,
+            "frames": frames,
+            "janky_frames": janky,
+            "jank_percent": jank_percent,
+        },
+        "samples": samples,
+    });
+    if let Some(path) = output {
+        let is_csv = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+        let contents = if is_csv {
+            perf::to_csv(&samples)
+        } else {
+            serde_json::to_string_pretty(&result)?
+        };
+        std::fs::write(path, contents)?;
+        result["output"] = json!(path.to_string_lossy());
+    }
+    if !json {
+        println!(
+            "\n{} {} samples · peak PSS {} · avg CPU {} · {} frames, {}% janky",
+            "Summary:".bold(),
+            samples.len(),
+            peak_pss_kb.map_or("-".to_string(), render::format_kb),
+            avg_cpu.map_or("-".to_string(), |c| format!("{}%", c)),
+            frames,
+            jank_percent
+        );
+        if let Some(path) = output {
+            println!("{} {}", "Saved to".green(), path.display());
+        }
+    }
+    Ok(result)
+}
+
+/// Median TotalTime of a saved `dab startup --json` result.
+fn read_startup_baseline(path: &std::path::Path) -> Result<f64> {
+    if !path.exists() {
+        return Err(DabError::new(
+            ErrorCode::FileNotFound,
+            format!("Baseline file does not exist: {}", path.display()),
+        )
+        .into());
+    }
+    let doc: Value = serde_json::from_str(&std::fs::read_to_string(path)?).unwrap_or(Value::Null);
+    startup::baseline_median(&doc).ok_or_else(|| {
+        DabError::new(
+            ErrorCode::Usage,
+            format!(
+                "{} is not a `dab startup --json` result (no total_time.median)",
+                path.display()
+            ),
+        )
+        .with_hint("Save a baseline with `dab startup --package <pkg> --json > baseline.json`.")
+        .into()
+    })
+}
+
+/// `dab health --watch`: take a sample every `interval`, redrawing the
+/// snapshot in place, or printing one NDJSON line per sample under `--json`.
+/// A sample adb fails to take is reported on stderr and retried at the next
+/// interval.
+fn watch_health(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    interval: Duration,
+    json: bool,
+) -> Result<()> {
+    let Commands::Health {
+        fail_if,
+        warn_if,
+        log,
+        ..
+    } = command
+    else {
+        unreachable!("watch_health is only called for `dab health --watch`")
+    };
+    for sample in 1.. {
+        let started = Instant::now();
+        let health = match adb_client.get_device_health(device) {
+            Ok(health) => health,
+            Err(e) => {
+                let err = DabError::classify(&e);
+                if json {
+                    let mut value = err.to_json();
+                    value["time"] = json!(unix_time());
+                    eprintln!("{}", serde_json::to_string(&value)?);
+                } else {
+                    eprintln!(
+                        "{} sample {} failed, retrying in {:?}: {}",
+                        "Warning:".yellow().bold(),
+                        sample,
+                        interval,
+                        err.message
+                    );
+                }
+                std::thread::sleep(interval.saturating_sub(started.elapsed()));
+                continue;
+            }
+        };
+        let warnings = health::breaches(&health, warn_if);
+        if json {
+            let mut value = serde_json::to_value(&health)?;
+            value["time"] = json!(unix_time());
+            if !warn_if.is_empty() {
+                value["warnings"] = json!(warnings);
+            }
+            writeln!(std::io::stdout(), "{}", serde_json::to_string(&value)?)?;
+        } else {
+            // Clear the screen and redraw from the top left.
+            print!("\x1b[2J\x1b[H");
+            println!("{}", health.render());
+            print_warnings(&warnings);
+            println!(
+                "\n{}",
+                format!("Sample {} · every {:?} · Ctrl+C to stop", sample, interval).dimmed()
+            );
+        }
+        record_health(&health, fail_if, log.as_deref())?;
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+    Ok(())
+}
+
+fn print_warnings(warnings: &[health::Breach]) {
+    for warning in warnings {
+        println!("{} {}", "Warning:".yellow().bold(), warning);
+    }
+}
+
+/// Open `--log` for appending, starting a new or empty file with the CSV
+/// header. Called once before fanning out, so devices sharing the log never
+/// race to write the header.
+fn open_health_log(path: &Path) -> Result<std::fs::File> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if file.metadata()?.len() == 0 {
+        file.write_all(format!("{}\n", health::CSV_HEADER).as_bytes())?;
+    }
+    Ok(file)
+}
+
+/// Append a health sample to `--log`, then fail if it crosses a `--fail-if`
+/// threshold.
+fn record_health(
+    health: &dab_cli::model::DeviceHealth,
+    fail_if: &[health::Threshold],
+    log: Option<&Path>,
+) -> Result<()> {
+    if let Some(path) = log {
+        let row = format!("{}\n", health::csv_row(unix_time(), health));
+        // One write per sample, so parallel devices can share a log.
+        open_health_log(path)?.write_all(row.as_bytes())?;
+    }
+    let breaches = health::breaches(health, fail_if);
+    if breaches.is_empty() {
+        return Ok(());
+    }
+    let list: Vec<String> = breaches.iter().map(|b| b.to_string()).collect();
+    Err(DabError::new(
+        ErrorCode::HealthCheckFailed,
+        format!(
+            "Health check failed on {}: {}",
+            health.device,
+            list.join(", ")
+        ),
+    )
+    .with_detail("device", health.device.as_str())
+    .with_detail("breaches", serde_json::to_value(&breaches)?)
+    .with_detail("health", serde_json::to_value(health)?)
+    .into())
+}
+
+/// Seconds since the Unix epoch.
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// `dab top --watch`: print the activity stack whenever the resumed
+/// activity, its fragments or the back stacks change, until Ctrl+C. JSON
+/// prints one line per change.
+fn watch_top(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    interval: Duration,
+    json: bool,
+) -> Result<()> {
+    if !json {
+        println!(
+            "{}",
+            format!("Watching {} every {:?} · Ctrl+C to stop", device, interval).dimmed()
+        );
+    }
+    let mut last = None;
+    loop {
+        let started = Instant::now();
+        let stack = adb_client.get_activity_stack(device)?;
+        if last.as_ref() != Some(&stack) {
+            if json {
+                let mut value = serde_json::to_value(&stack)?;
+                value["time"] = json!(unix_time());
+                writeln!(std::io::stdout(), "{}", serde_json::to_string(&value)?)?;
+            } else {
+                println!("\n{}", stack.render());
+            }
+            last = Some(stack);
+        }
+        std::thread::sleep(interval.saturating_sub(started.elapsed()));
+    }
+}
+
+/// `dab crashes --watch`: report incidents as they happen, until Ctrl+C.
+fn watch_crashes(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+    json: bool,
+) -> Result<()> {
+    let Commands::Crashes { save_dir, .. } = command else {
+        unreachable!("watch_crashes is only called for `dab crashes --watch`")
+    };
+    let package = select_package(adb_client, device, command, prompter, json)?;
+    if !json {
+        println!(
+            "Watching {} for crashes and ANRs... Press Ctrl+C to stop.",
+            package.cyan()
+        );
+    }
+    adb_client.watch_crashes(device, &package, save_dir.as_deref(), |incident| {
+        if json {
+            writeln!(std::io::stdout(), "{}", serde_json::to_string(&incident)?)?;
+        } else {
+            println!("{}", incident.render());
+        }
+        Ok(())
+    })
+}
+
+/// `dab logcat`: print the app's entries colored, or as NDJSON under `--json`,
+/// mirroring them to `--output` when given.
+fn stream_logs(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+    json: bool,
+) -> Result<()> {
+    let Commands::Logcat {
+        level,
+        tag,
+        grep,
+        output,
+        ..
+    } = command
+    else {
+        unreachable!("stream_logs is only called for `dab logcat`")
+    };
+    let pattern = grep
+        .as_deref()
+        .map(regex::Regex::new)
+        .transpose()
+        .map_err(|e| DabError::new(ErrorCode::Usage, format!("Invalid --grep regex: {}", e)))?;
+    let filter = LogFilter {
+        min_level: *level,
+        tags: tag.clone(),
+        pattern,
+    };
+    let package = select_package(adb_client, device, command, prompter, json)?;
+    let file = match output {
+        Some(path) => Some(Mutex::new(LineWriter::new(File::create(path)?))),
+        None => None,
+    };
+    if !json {
+        println!("{}", "Streaming logs... Press Ctrl+C to stop.".yellow());
+    }
+    let streamed = adb_client.stream_logcat(device, &package, &filter, |event| {
+        match event {
+            LogEvent::Waiting if !json => {
+                println!("Waiting for {} to start...", package.cyan());
+            }
+            LogEvent::Following(pid) if !json => println!(
+                "{} {} {}",
+                "──".dimmed(),
+                package.cyan(),
+                format!("running as pid {}", pid).dimmed()
+            ),
+            LogEvent::Entry(entry) => {
+                let rendered = if json {
+                    serde_json::to_string(entry)?
+                } else {
+                    entry.render()
+                };
+                writeln!(std::io::stdout(), "{}", rendered)?;
+                if let Some(file) = &file {
+                    let line = if json { rendered } else { entry.plain() };
+                    writeln!(file.lock().unwrap(), "{}", line)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    });
+    // A closed pipe (`dab logcat | head`) just ends the stream.
+    match streamed {
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        other => other,
+    }
+}
+
+/// Run one command against one device and return the document `--json` prints.
+fn execute_json(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+) -> Result<Value> {
+    Ok(match command {
+        Commands::Apps => serde_json::to_value(adb_client.get_installed_apps(device)?)?,
+        Commands::Device => serde_json::to_value(adb_client.get_device_info(device)?)?,
+        Commands::Network => serde_json::to_value(adb_client.get_network_info(device)?)?,
+        Commands::Health {
+            fail_if,
+            warn_if,
+            log,
+            ..
+        } => {
+            let health = adb_client.get_device_health(device)?;
+            record_health(&health, fail_if, log.as_deref())?;
+            let mut value = serde_json::to_value(&health)?;
+            if !warn_if.is_empty() {
+                value["warnings"] = json!(health::breaches(&health, warn_if));
+            }
+            value
+        }
+        Commands::Screenshot { output } => {
+            let path = adb_client.take_screenshot(device, output.clone())?;
+            json!({ "output": path.to_string_lossy() })
+        }
+        Commands::Record { output } => {
+            let path = adb_client.record_screen(device, output.clone())?;
+            json!({ "output": path.to_string_lossy() })
+        }
+        Commands::Wifi => {
+            adb_client.enable_wifi(device)?;
+            json!({ "success": true })
+        }
+        Commands::Usb => {
+            adb_client.enable_usb(device)?;
+            json!({ "success": true })
+        }
+        Commands::Launch { url } => {
+            adb_client.launch_url(device, url)?;
+            json!({ "success": true, "url": url })
+        }
+        Commands::Ui { command } => run_ui(adb_client, device, command, true)?,
+        Commands::Input { command } => run_input(adb_client, device, command, true)?,
+        Commands::Macro { command } => run_macro(adb_client, device, command, true)?,
+        Commands::Top { .. } => serde_json::to_value(adb_client.get_activity_stack(device)?)?,
+        Commands::Deeplinks { .. } => run_deeplinks(adb_client, device, command, prompter, true)?,
+        Commands::Start { .. } | Commands::Broadcast { .. } | Commands::StartService { .. } => {
+            send_intent(adb_client, device, command, prompter, true)?
+        }
+        Commands::Install { file } => {
+            adb_client.install_file(device, file)?;
+            json!({ "success": true, "file": file.to_string_lossy() })
+        }
+        Commands::Test { .. } => run_tests(adb_client, device, command, prompter, true)?,
+        Commands::Provision { profile, dry_run } => {
+            run_provision(adb_client, device, profile, *dry_run, true)?
+        }
+        Commands::Run { scenario, var } => run_scenario(adb_client, device, scenario, var, true)?,
+        Commands::Devices | Commands::Info { .. } | Commands::Mcp | Commands::Serve { .. } => {
+            unreachable!("device-independent commands are handled before device selection")
+        }
+        Commands::Logcat { .. } => unreachable!("logcat streams from real_main"),
+        Commands::ShellMode | Commands::Tui => {
+            unreachable!("interactive modes run from real_main")
+        }
+        app_command => {
+            let package = select_package(adb_client, device, app_command, prompter, true)?;
+            match app_command {
+                Commands::Open { .. } => {
+                    adb_client.open_app(device, &package)?;
+                    let activity =
+                        adb_client.wait_for_foreground(device, &package, OPEN_TIMEOUT)?;
+                    json!({ "success": true, "package": package, "activity": activity })
+                }
+                Commands::Uninstall { .. } => {
+                    adb_client.uninstall_app(device, &package)?;
+                    json!({ "success": true, "package": package })
+                }
+                Commands::Clear { .. } => {
+                    adb_client.clear_app_data(device, &package)?;
+                    json!({ "success": true, "package": package })
+                }
+                Commands::ForceKill { .. } => {
+                    adb_client.force_kill_app(device, &package)?;
+                    json!({ "success": true, "package": package })
+                }
+                Commands::Download { output, .. } => {
+                    let output_path = adb_client.download_apk(device, &package, output.clone())?;
+                    json!({ "success": true, "package": package, "output": output_path.to_string_lossy() })
+                }
+                Commands::AppInfo { all, .. } => {
+                    serde_json::to_value(adb_client.get_app_info(device, &package, *all)?)?
+                }
+                Commands::Crashes { save_dir, .. } => serde_json::to_value(
+                    adb_client.get_crashes(device, &package, save_dir.as_deref())?,
+                )?,
+                Commands::Startup { .. } => {
+                    run_startup(adb_client, device, &package, app_command, true)?
+                }
+                Commands::Perf { .. } => run_perf(adb_client, device, &package, app_command, true)?,
+                Commands::Grant { permissions, .. } => {
+                    let perms = select_permissions(permissions, prompter, "grant")?;
+                    if perms.is_empty() {
+                        json!({ "success": false, "reason": "no permissions selected" })
+                    } else {
+                        let perm_refs: Vec<&str> = perms.iter().map(|s| s.as_str()).collect();
+                        adb_client.grant_permissions(device, &package, &perm_refs)?;
+                        json!({ "success": true, "package": package, "granted": perms })
+                    }
+                }
+                Commands::Revoke { permissions, .. } => {
+                    let perms = select_permissions(permissions, prompter, "revoke")?;
+                    if perms.is_empty() {
+                        json!({ "success": false, "reason": "no permissions selected" })
+                    } else {
+                        let perm_refs: Vec<&str> = perms.iter().map(|s| s.as_str()).collect();
+                        adb_client.revoke_permissions(device, &package, &perm_refs)?;
+                        json!({ "success": true, "package": package, "revoked": perms })
+                    }
+                }
+                _ => unreachable!("all device commands are matched above"),
+            }
+        }
+    })
+}
+
+/// Run one command against one device with human-readable output.
+fn execute_text(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    command: &Commands,
+    prompter: &Prompter,
+) -> Result<()> {
+    match command {
+        Commands::Apps => {
+            println!("{}", adb_client.get_installed_apps(device)?.render());
+        }
+        Commands::Device => {
+            println!("{}", "Fetching device info...".yellow());
+            println!("{}", adb_client.get_device_info(device)?.render());
+        }
+        Commands::Network => {
+            println!("{}", "Fetching network info...".yellow());
+            println!("{}", adb_client.get_network_info(device)?.render());
+        }
+        Commands::Health {
+            fail_if,
+            warn_if,
+            log,
+            ..
+        } => {
+            println!("{}", "Checking device health...".yellow());
+            let health = adb_client.get_device_health(device)?;
+            println!("{}", health.render());
+            print_warnings(&health::breaches(&health, warn_if));
+            record_health(&health, fail_if, log.as_deref())?;
+        }
+        Commands::Screenshot { output } => {
+            let path = adb_client.take_screenshot(device, output.clone())?;
+            println!("Screenshot saved to {}", path.display());
+        }
+        Commands::Record { output } => {
+            println!("{}", "Recording screen...".yellow());
+            println!("Recording... Press Ctrl+C to stop.");
+            let path = adb_client.record_screen(device, output.clone())?;
+            println!("Screen recording saved to {}", path.display());
+        }
+        Commands::Wifi => {
+            println!("{}", "Setting up ADB over Wi-Fi...".yellow());
+            println!("Enabling ADB over Wi-Fi (TCP/IP 5555)...");
+            let addr = adb_client.enable_wifi(device)?;
+            println!("Connected to {}", addr.green());
+            println!("\nYou can now disconnect the USB cable and use ADB over Wi-Fi!");
+        }
+        Commands::Usb => {
+            println!("{}", "Switching ADB to USB mode...".yellow());
+            adb_client.enable_usb(device)?;
+            println!("ADB is now in USB mode.");
+        }
+        Commands::Launch { url } => {
+            println!("{} {}", "Launching:".green(), url.cyan());
+            adb_client.launch_url(device, url)?;
+        }
+        Commands::Ui { command } => {
+            run_ui(adb_client, device, command, false)?;
+        }
+        Commands::Input { command } => {
+            run_input(adb_client, device, command, false)?;
+        }
+        Commands::Macro { command } => {
+            run_macro(adb_client, device, command, false)?;
+        }
+        Commands::Top { .. } => {
+            println!("{}", adb_client.get_activity_stack(device)?.render());
+        }
+        Commands::Deeplinks { .. } => {
+            run_deeplinks(adb_client, device, command, prompter, false)?;
+        }
+        Commands::Start { .. } | Commands::Broadcast { .. } | Commands::StartService { .. } => {
+            send_intent(adb_client, device, command, prompter, false)?;
+        }
+        Commands::Install { file } => {
+            println!("{} {}", "Installing file:".yellow(), file.display());
+            match adb_client.install_file(device, file)? {
+                1 => println!("{}", "APK installed successfully!".green()),
+                n => println!(
+                    "{} ({} APK files)",
+                    "Bundle installed successfully!".green(),
+                    n
+                ),
+            }
+        }
+        Commands::Test { .. } => {
+            run_tests(adb_client, device, command, prompter, false)?;
+        }
+        Commands::Provision { profile, dry_run } => {
+            run_provision(adb_client, device, profile, *dry_run, false)?;
+        }
+        Commands::Run { scenario, var } => {
+            run_scenario(adb_client, device, scenario, var, false)?;
+        }
+        Commands::Devices | Commands::Info { .. } | Commands::Mcp | Commands::Serve { .. } => {
+            unreachable!("device-independent commands are handled before device selection")
+        }
+        Commands::Logcat { .. } => unreachable!("logcat streams from real_main"),
+        Commands::ShellMode | Commands::Tui => {
+            unreachable!("interactive modes run from real_main")
+        }
+        app_command => {
+            let package = select_package(adb_client, device, app_command, prompter, false)?;
+            match app_command {
+                Commands::Open { .. } => {
+                    println!("{} {}", "Opening".green(), package);
+                    adb_client.open_app(device, &package)?;
+                    match adb_client.wait_for_foreground(device, &package, OPEN_TIMEOUT)? {
+                        Some(activity) => println!("{} {}", "Resumed".green(), activity.cyan()),
+                        None => println!(
+                            "{}",
+                            format!(
+                                "{} didn't reach the foreground within {:?}",
+                                package, OPEN_TIMEOUT
+                            )
+                            .yellow()
+                        ),
+                    }
+                }
+                Commands::Uninstall { .. } => {
+                    println!("{} {}", "Uninstalling".red(), package);
+                    adb_client.uninstall_app(device, &package)?;
+                }
+                Commands::Clear { .. } => {
+                    println!("{} data for {}", "Clearing".blue(), package);
+                    adb_client.clear_app_data(device, &package)?;
+                }
+                Commands::ForceKill { .. } => {
+                    println!("{} {}", "Force killing".red(), package);
+                    adb_client.force_kill_app(device, &package)?;
+                }
+                Commands::Download { output, .. } => {
+                    println!("{} APK for {}", "Downloading".cyan(), package);
+                    let output_path = adb_client.download_apk(device, &package, output.clone())?;
+                    println!("APK downloaded to {}", output_path.display());
+                }
+                Commands::AppInfo { all, .. } => {
+                    println!("{} {}", "Fetching info for".yellow(), package);
+                    println!(
+                        "{}",
+                        adb_client.get_app_info(device, &package, *all)?.render()
+                    );
+                }
+                Commands::Crashes { save_dir, .. } => {
+                    println!("{} {}", "Looking for crashes and ANRs of".yellow(), package);
+                    let report = adb_client.get_crashes(device, &package, save_dir.as_deref())?;
+                    println!("{}", report.render());
+                }
+                Commands::Startup { .. } => {
+                    run_startup(adb_client, device, &package, app_command, false)?;
+                }
+                Commands::Perf { .. } => {
+                    run_perf(adb_client, device, &package, app_command, false)?;
+                }
+                Commands::Grant { permissions, .. } => {
+                    let perms = select_permissions(permissions, prompter, "grant")?;
+                    if perms.is_empty() {
+                        println!("No permissions selected.");
+                    } else {
+                        let perm_refs: Vec<&str> = perms.iter().map(|s| s.as_str()).collect();
+                        adb_client.grant_permissions(device, &package, &perm_refs)?;
+                        println!("Permissions granted successfully.");
+                    }
+                }
+                Commands::Revoke { permissions, .. } => {
+                    let perms = select_permissions(permissions, prompter, "revoke")?;
+                    if perms.is_empty() {
+                        println!("No permissions selected.");
+                    } else {
+                        let perm_refs: Vec<&str> = perms.iter().map(|s| s.as_str()).collect();
+                        adb_client.revoke_permissions(device, &package, &perm_refs)?;
+                        println!("Permissions revoked successfully.");
+                    }
+                }
+                _ => unreachable!("all device commands are matched above"),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The no-subcommand flow: pick an app, then an action to run on it.
+fn interactive_command(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    prompter: &Prompter,
+) -> Result<Commands> {
+    println!("{}", "Loading installed apps...".yellow());
+    let apps = adb_client.get_installed_apps(device)?.apps;
+    if apps.is_empty() {
+        return Err(DabError::new(ErrorCode::PackageNotFound, "No installed apps found").into());
+    }
+    let package = Some(prompter.app(apps)?);
+    let options = vec![
+        "Open",
+        "App Info",
+        "Uninstall",
+        "Clear App Data",
+        "Force Kill",
+        "Download APK",
+        "Stream Logs",
+        "Show Crashes",
+        "Open Deep Link",
+        "Grant Permissions",
+        "Revoke Permissions",
+    ];
+    Ok(match prompter.action(options)? {
+        "Open" => Commands::Open { package },
+        "App Info" => Commands::AppInfo {
+            package,
+            all: false,
+        },
+        "Uninstall" => Commands::Uninstall { package },
+        "Clear App Data" => Commands::Clear { package },
+        "Force Kill" => Commands::ForceKill { package },
+        "Download APK" => Commands::Download {
+            package,
+            output: None,
+        },
+        "Stream Logs" => Commands::Logcat {
+            package,
+            level: None,
+            tag: Vec::new(),
+            grep: None,
+            output: None,
+        },
+        "Show Crashes" => Commands::Crashes {
+            package,
+            watch: false,
+            save_dir: None,
+        },
+        "Open Deep Link" => Commands::Deeplinks {
+            package,
+            file: None,
+            open: true,
+            verify: false,
+        },
+        "Grant Permissions" => Commands::Grant {
+            package,
+            permissions: None,
+        },
+        "Revoke Permissions" => Commands::Revoke {
+            package,
+            permissions: None,
+        },
+        _ => unreachable!(),
+    })
+}
+
+fn real_main() -> Result<()> {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Usage errors join the JSON envelope; help and version print as usual.
+        Err(e) if e.use_stderr() && json_requested() => {
+            let message = e.to_string();
+            let first_line = message.lines().next().unwrap_or_default();
+            return Err(
+                DabError::new(ErrorCode::Usage, first_line.trim_start_matches("error: ")).into(),
+            );
+        }
+        Err(e) => e.exit(),
+    };
+    // Record every adb exchange to a transcript, or answer from one instead
+    // of a device, for testing command flows without hardware.
+    if let Some(path) = std::env::var_os("DAB_REPLAY") {
+        return run(
+            cli,
+            &AdbClient::with_backend(Replayer::load(path.as_ref())?),
+        );
+    }
+    if let Some(path) = std::env::var_os("DAB_RECORD") {
+        let adb_client = AdbClient::with_backend(Recorder::new(RealAdb::new()?));
+        let result = run(cli, &adb_client);
+        adb_client.backend().transcript().save(path.as_ref())?;
+        return result;
+    }
+    run(cli, &AdbClient::new()?)
+}
+
+fn run(cli: Cli, adb_client: &AdbClient<impl AdbBackend>) -> Result<()> {
+    let json = cli.json;
+    let prompter = Prompter::new(cli.no_input);
+
+    // The full interactive UI is nothing but prompts; refuse it up front.
+    if cli.command.is_none() {
+        prompter.require("command", "a subcommand")?;
+    }
+
+    // ── Commands that don't need a connected device ──────────────────────────
+
+    // `dab devices` — list connected devices
+    if matches!(&cli.command, Some(Commands::Devices)) {
+        let devices = adb_client.get_device_list()?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&devices)?);
+        } else {
+            println!("{}", devices.render());
+        }
+        return Ok(());
+    }
+
+    // `dab info <file>` — analyze local APK/XAPK/APKM
+    if let Some(Commands::Info { file }) = &cli.command {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&adb_client.analyze_local_file(file)?)?
+            );
+        } else {
+            println!("{} {}", "Analyzing file:".yellow(), file.display());
+            println!("{}", adb_client.analyze_local_file(file)?.render());
+        }
+        return Ok(());
+    }
+
+    // `dab deeplinks --file <apk>` — list a local file's deep links
+    if let Some(Commands::Deeplinks {
+        file: Some(file),
+        open: false,
+        verify: false,
+        ..
+    }) = &cli.command
+    {
+        let deeplinks = adb_client.get_file_deeplinks(file)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&deeplinks)?);
+        } else {
+            println!("{}", deeplinks.render());
+        }
+        return Ok(());
+    }
+
+    // `dab mcp` — serve agent tool calls over stdio until the client disconnects
+    if matches!(&cli.command, Some(Commands::Mcp)) {
+        let unattended = Prompter::disabled();
+        return mcp::serve(adb_client, |device, command| {
+            execute_json(adb_client, device, command, &unattended)
+        });
+    }
+
+    // `dab serve` — answer HTTP requests until stopped
+    if let Some(Commands::Serve { port, host, token }) = &cli.command {
+        let unattended = Prompter::disabled();
+        return server::serve(
+            adb_client,
+            host,
+            *port,
+            token.as_deref(),
+            |device, command| execute_json(adb_client, device, command, &unattended),
+        );
+    }
+
+    // ── Fan out over several devices ─────────────────────────────────────────
+
+    if let Some(targets) =
+        fanout::targets(adb_client, cli.device.as_deref(), cli.device_tag.as_deref())?
+    {
+        let Some(command) = &cli.command else {
+            return Err(DabError::new(
+                ErrorCode::Usage,
+                "Pick a subcommand when targeting several devices",
+            )
+            .into());
+        };
+        fanout::preflight(command)?;
+        if let Commands::Health {
+            log: Some(path), ..
+        } = command
+        {
+            open_health_log(path)?;
+        }
+        let unattended = Prompter::disabled();
+        let results = fanout::run(&targets, |serial| {
+            adb_client.check_device(serial)?;
+            execute_json(
+                adb_client,
+                serial,
+                &fanout::for_device(command, serial),
+                &unattended,
+            )
+        });
+        return fanout::report(&results, json);
+    }
+
+    // ── Select (or pin) device ───────────────────────────────────────────────
+
+    let device: String = if let Some(serial) = &cli.device {
+        serial.clone()
+    } else {
+        let devices = adb_client.get_devices()?;
+        if devices.len() > 1 {
+            prompter.device(adb_client.get_devices_detailed()?)?
+        } else {
+            devices.into_iter().next().unwrap().serial
+        }
+    };
+    adb_client.check_device(&device)?;
+
+    // When no subcommand was given, pick an app and show the action menu
+    let command = match cli.command {
+        Some(command) => command,
+        None => interactive_command(adb_client, &device, &prompter)?,
+    };
+
+    // `dab shell-mode` keeps the device and app in context across commands
+    if let Commands::ShellMode = &command {
+        return repl::run(
+            adb_client,
+            device,
+            &prompter,
+            |device, command| match command {
+                Commands::Logcat { .. } => {
+                    stream_logs(adb_client, device, command, &prompter, false)
+                }
+                Commands::Crashes { watch: true, .. } => {
+                    watch_crashes(adb_client, device, command, &prompter, false)
+                }
+                _ => execute_text(adb_client, device, command, &prompter),
+            },
+        );
+    }
+
+    // `dab tui` takes over the terminal until the user quits
+    if let Commands::Tui = &command {
+        if !std::io::stdout().is_terminal() {
+            return Err(
+                DabError::new(ErrorCode::Usage, "`tui` needs an interactive terminal").into(),
+            );
+        }
+        return tui::run(adb_client, &device);
+    }
+
+    // `dab logcat` streams until Ctrl+C instead of returning one document
+    if let Commands::Logcat { .. } = &command {
+        return stream_logs(adb_client, &device, &command, &prompter, json);
+    }
+
+    // `dab health --watch` refreshes until Ctrl+C or a --fail-if breach
+    if let Commands::Health {
+        watch: Some(interval),
+        ..
+    } = &command
+    {
+        return watch_health(adb_client, &device, &command, *interval, json);
+    }
+
+    // `dab top --watch` reports each change of screen
+    if let Commands::Top {
+        watch: Some(interval),
+    } = &command
+    {
+        return watch_top(adb_client, &device, *interval, json);
+    }
+
+    // `dab crashes --watch` reports incidents as they happen
+    if let Commands::Crashes { watch: true, .. } = &command {
+        return watch_crashes(adb_client, &device, &command, &prompter, json);
+    }
+
+    if json {
+        let result = execute_json(adb_client, &device, &command, &prompter)?;
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else {
+        execute_text(adb_client, &device, &command, &prompter)?;
+    }
+
+    Ok(())
+}
+
+/// Whether `--json` was requested, even when argument parsing itself failed.
+fn json_requested() -> bool {
+    std::env::args().any(|a| a == "--json")
+}
+
+/// Print an error and its hint in color.
+fn print_error(e: &anyhow::Error) {
+    let err = DabError::classify(e);
+    eprintln!("{} {}", "Error:".red().bold(), err);
+    if let Some(hint) = &err.hint {
+        eprintln!("{} {}", "Hint:".yellow(), hint);
+    }
+}
+
+fn main() {
+    match real_main() {
         Ok(()) => {}
         Err(e) => {
             if let Some(inquire_err) = e.downcast_ref::<inquire::InquireError>() {
@@ -466,14 +2007,521 @@ fn main() {
                     std::process::exit(0);
                 }
             }
-            // Emit JSON errors when --json was requested
-            let args: Vec<String> = std::env::args().collect();
-            if args.iter().any(|a| a == "--json") {
-                eprintln!("{}", serde_json::json!({ "error": e.to_string() }));
+            let err = DabError::classify(&e);
+            if json_requested() {
+                eprintln!("{}", err.to_json());
             } else {
-                eprintln!("Error: {}", e);
+                print_error(&e);
             }
-            std::process::exit(1);
+            std::process::exit(err.exit_code());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::Transcript;
+
+    /// A client that answers from `exchanges` instead of a device.
+    fn replay(exchanges: Value) -> AdbClient<Replayer> {
+        let transcript: Transcript =
+            serde_json::from_value(json!({ "exchanges": exchanges })).unwrap();
+        AdbClient::with_backend(Replayer::new(transcript))
+    }
+
+    fn execute(adb_client: &AdbClient<Replayer>, command: Commands) -> Result<Value> {
+        let result = execute_json(adb_client, "emu", &command, &Prompter::disabled());
+        assert!(adb_client.backend().unused().is_empty());
+        result
+    }
+
+    fn packages() -> Value {
+        json!({ "call": "list_packages", "args": ["emu"], "ok": ["com.example.app", "com.android.chrome"] })
+    }
+
+    #[test]
+    fn app_info_resolves_a_partial_package_and_parses_pm_dump() {
+        let adb_client = replay(json!([
+            packages(),
+            { "call": "run", "args": ["-s", "emu", "shell", "pm", "dump", "com.example.app"],
+              "ok": { "success": true, "stderr": "", "stdout": "\
+    versionCode=42 minSdk=21 targetSdk=34
+    versionName=2.1.0
+      android.permission.CAMERA: granted=true
+      android.permission.INTERNET: granted=true" } },
+        ]));
+        let result = execute(
+            &adb_client,
+            Commands::AppInfo {
+                package: Some("example".into()),
+                all: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            json!({
+                "package_name": "com.example.app",
+                "version_code": "42",
+                "version_name": "2.1.0",
+                "granted_permissions": ["android.permission.CAMERA", "android.permission.INTERNET"],
+            })
+        );
+    }
+
+    #[test]
+    fn grant_passes_every_listed_permission() {
+        let adb_client = replay(json!([
+            packages(),
+            { "call": "grant",
+              "args": ["emu", "com.android.chrome", "android.permission.CAMERA", "android.permission.RECORD_AUDIO"],
+              "ok": null },
+        ]));
+        let result = execute(
+            &adb_client,
+            Commands::Grant {
+                package: Some("chrome".into()),
+                permissions: Some(
+                    "android.permission.CAMERA, android.permission.RECORD_AUDIO".into(),
+                ),
+            },
+        )
+        .unwrap();
+        assert_eq!(result["granted"].as_array().unwrap().len(), 2);
+        assert_eq!(result["package"], "com.android.chrome");
+    }
+
+    #[test]
+    fn install_failures_keep_their_error_code() {
+        let apk = std::env::temp_dir().join(format!("dab-replay-{}.apk", std::process::id()));
+        std::fs::write(&apk, b"").unwrap();
+        let adb_client = replay(json!([
+            { "call": "install_apk", "args": ["emu", apk.display().to_string()], "ok": null },
+            { "call": "install_apk", "args": ["emu", apk.display().to_string()],
+              "error": "Failed to install APK: Failure [INSTALL_FAILED_VERSION_DOWNGRADE]" },
+        ]));
+        let install = || Commands::Install { file: apk.clone() };
+        let installed = execute_json(&adb_client, "emu", &install(), &Prompter::disabled());
+        let failed = execute(&adb_client, install());
+        std::fs::remove_file(&apk).unwrap();
+
+        assert_eq!(installed.unwrap()["success"], true);
+        assert_eq!(
+            DabError::classify(&failed.unwrap_err()).code,
+            ErrorCode::InstallFailedVersionDowngrade
+        );
+    }
+
+    #[test]
+    fn fanned_out_bundle_installs_extract_separately() {
+        let bundle = std::env::temp_dir().join(format!("dab-replay-{}.xapk", std::process::id()));
+        let mut zip = zip::ZipWriter::new(File::create(&bundle).unwrap());
+        zip.start_file("base.apk", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(&[0; 256 * 1024]).unwrap();
+        zip.finish().unwrap();
+
+        let serials: Vec<String> = (0..8).map(|i| format!("emu-{}", i)).collect();
+        let adb_client = replay(json!(serials
+            .iter()
+            .map(|serial| json!({ "call": "install_multiple",
+                "args": [serial, "base.apk"], "ok": null }))
+            .collect::<Vec<_>>()));
+        let install = Commands::Install {
+            file: bundle.clone(),
+        };
+        let results = fanout::run(&serials, |serial| {
+            execute_json(&adb_client, serial, &install, &Prompter::disabled())
+        });
+        std::fs::remove_file(&bundle).unwrap();
+
+        for result in &results {
+            assert!(result.result.is_ok(), "{}", result.device);
         }
+        assert!(adb_client.backend().unused().is_empty());
+    }
+
+    #[test]
+    fn deeplinks_verify_flags_links_another_app_claims() {
+        let resolve = |url: &str| {
+            json!([
+                "-s",
+                "emu",
+                "shell",
+                "cmd",
+                "package",
+                "resolve-activity",
+                "--brief",
+                "-a",
+                "android.intent.action.VIEW",
+                "-c",
+                "android.intent.category.DEFAULT",
+                "-d",
+                url
+            ])
+        };
+        let adb_client = replay(json!([
+            packages(),
+            { "call": "run", "args": ["-s", "emu", "shell", "dumpsys", "package", "com.example.app"],
+              "ok": { "success": true, "stderr": "", "stdout": "\
+Activity Resolver Table:
+  Schemes:
+      myapp:
+        e2ccd3a com.example.app/.LinkActivity filter 1d2e3f4
+          Action: \"android.intent.action.VIEW\"
+          Scheme: \"myapp\"
+          Authority: \"item\": -1
+          Path: \"PatternMatcher{PREFIX: /}\"
+      https:
+        e2ccd3a com.example.app/.LinkActivity filter 7c7b8f5
+          Action: \"android.intent.action.VIEW\"
+          Scheme: \"https\"
+          Authority: \"example.com\": -1
+" } },
+            { "call": "run", "args": resolve("myapp://item/"),
+              "ok": { "success": true, "stderr": "", "stdout": "priority=0 preferredOrder=0\ncom.example.app/com.example.app.LinkActivity\n" } },
+            { "call": "run", "args": resolve("https://example.com"),
+              "ok": { "success": true, "stderr": "", "stdout": "priority=0 preferredOrder=0\ncom.android.chrome/com.google.android.apps.chrome.IntentDispatcher\n" } },
+        ]));
+        let err = execute(
+            &adb_client,
+            Commands::Deeplinks {
+                package: Some("example".into()),
+                file: None,
+                open: false,
+                verify: true,
+            },
+        )
+        .unwrap_err();
+        let err = DabError::classify(&err);
+        assert_eq!(err.code, ErrorCode::DeeplinkMismatch);
+        assert_eq!(
+            err.message,
+            "https://example.com doesn't open com.example.app/.LinkActivity: it opens com.android.chrome/com.google.android.apps.chrome.IntentDispatcher"
+        );
+    }
+
+    #[test]
+    fn logcat_streams_only_new_entries_of_the_app() {
+        let adb_client = replay(json!([
+            { "call": "pid_of", "args": ["emu", "com.example.app"], "ok": "1000" },
+            { "call": "stream", "args": ["-s", "emu", "logcat", "-v", "threadtime", "-T", "1"], "ok": [
+                "10-16 12:00:00.000  1000  1001 I App     : hello",
+                "10-16 12:00:00.100  3000  3001 I Other   : not mine",
+                "10-16 12:00:01.000   300   310 I ActivityManager: Start proc 2000:com.example.app/u0a12 for activity",
+                "10-16 12:00:01.100  2000  2001 E App     : restarted",
+            ] },
+        ]));
+        let events = Mutex::new(Vec::new());
+        adb_client
+            .stream_logcat("emu", "com.example.app", &LogFilter::default(), |event| {
+                events.lock().unwrap().push(match event {
+                    LogEvent::Waiting => "waiting".to_string(),
+                    LogEvent::Following(pid) => format!("following {}", pid),
+                    LogEvent::Entry(entry) => entry.message.clone(),
+                });
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            events.into_inner().unwrap(),
+            ["following 1000", "hello", "following 2000", "restarted"]
+        );
+        assert!(adb_client.backend().unused().is_empty());
+    }
+
+    #[test]
+    fn deeplinks_resolve_urls_quoted_for_the_device_shell() {
+        let adb_client = replay(json!([
+            { "call": "run", "args": ["-s", "emu", "shell", "cmd", "package", "resolve-activity",
+                "--brief", "-a", "android.intent.action.VIEW", "-c", "android.intent.category.DEFAULT",
+                "-d", "'https://example.com/search?q=a&page=2;x=*'"],
+              "ok": { "success": true, "stderr": "", "stdout": "No activity found\n" } },
+        ]));
+        let resolved = adb_client
+            .resolve_url("emu", "https://example.com/search?q=a&page=2;x=*")
+            .unwrap();
+        assert_eq!(resolved, None);
+        assert!(adb_client.backend().unused().is_empty());
+    }
+
+    #[test]
+    fn start_sends_an_explicit_intent_and_reports_am_errors() {
+        let am_start = json!([
+            "-s",
+            "emu",
+            "shell",
+            "am",
+            "start",
+            "-f",
+            "0x10008000",
+            "--es",
+            "user",
+            "'Jane Doe'",
+            "--ei",
+            "n",
+            "3",
+            "-n",
+            "com.example.app/.ui.DebugActivity"
+        ]);
+        let adb_client = replay(json!([
+            packages(),
+            { "call": "run", "args": am_start,
+              "ok": { "success": true, "stderr": "", "stdout": "Starting: Intent { flg=0x10008000 cmp=com.example.app/.ui.DebugActivity (has extras) }\n" } },
+            packages(),
+            { "call": "run", "args": am_start,
+              "ok": { "success": true, "stderr": "", "stdout": "Starting: Intent { cmp=com.example.app/.ui.DebugActivity }\nError type 3\nError: Activity class {com.example.app/com.example.app.ui.DebugActivity} does not exist.\n" } },
+        ]));
+        let start = || Commands::Start {
+            activity: Some(".ui.DebugActivity".into()),
+            intent: cli::IntentArgs {
+                package: Some("example".into()),
+                action: None,
+                data: None,
+                mime_type: None,
+                categories: Vec::new(),
+                extras: vec!["user=Jane Doe".parse().unwrap(), "n:int=3".parse().unwrap()],
+                flags: vec!["clear-task".parse().unwrap(), "new-task".parse().unwrap()],
+            },
+        };
+        let started = execute_json(&adb_client, "emu", &start(), &Prompter::disabled()).unwrap();
+        assert_eq!(
+            started["intent"]["component"],
+            "com.example.app/.ui.DebugActivity"
+        );
+        assert_eq!(
+            started["intent"]["flags"],
+            json!(["clear-task", "new-task"])
+        );
+
+        let failed = execute(&adb_client, start()).unwrap_err();
+        assert_eq!(DabError::classify(&failed).code, ErrorCode::IntentFailed);
+    }
+
+    #[test]
+    fn open_reports_the_activity_that_came_up() {
+        let activities = json!(["-s", "emu", "shell", "dumpsys", "activity", "activities"]);
+        let adb_client = replay(json!([
+            packages(),
+            { "call": "launch_package", "args": ["emu", "com.example.app"], "ok": null },
+            { "call": "run", "args": activities,
+              "ok": { "success": true, "stderr": "", "stdout": "  mResumedActivity: ActivityRecord{1 u0 com.android.launcher/.Home t1}\n" } },
+            { "call": "run", "args": activities,
+              "ok": { "success": true, "stderr": "", "stdout": "  topResumedActivity=ActivityRecord{2 u0 com.example.app/.MainActivity t31}\n" } },
+        ]));
+        let result = execute(
+            &adb_client,
+            Commands::Open {
+                package: Some("example".into()),
+            },
+        )
+        .unwrap();
+        assert_eq!(result["activity"], "com.example.app/.MainActivity");
+    }
+
+    #[test]
+    fn ui_tap_finds_the_node_by_text_and_taps_its_center() {
+        let dump = json!([
+            "-s",
+            "emu",
+            "shell",
+            "uiautomator",
+            "dump",
+            "/data/local/tmp/dab_ui.xml"
+        ]);
+        let cat = json!(["-s", "emu", "shell", "cat", "/data/local/tmp/dab_ui.xml"]);
+        let rm = json!([
+            "-s",
+            "emu",
+            "shell",
+            "rm",
+            "-f",
+            "/data/local/tmp/dab_ui.xml"
+        ]);
+        let xml = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?><hierarchy rotation="0"><node text="" class="android.widget.FrameLayout" bounds="[0,0][1080,2400]"><node text="Sign in" resource-id="com.example.app:id/login" class="android.widget.Button" clickable="true" enabled="true" bounds="[48,1800][1032,1944]" /></node></hierarchy>"#;
+        let adb_client = replay(json!([
+            { "call": "run", "args": dump,
+              "ok": { "success": true, "stderr": "", "stdout": "UI hierchary dumped to: /data/local/tmp/dab_ui.xml\n" } },
+            { "call": "run", "args": cat, "ok": { "success": true, "stderr": "", "stdout": xml } },
+            { "call": "run", "args": rm, "ok": { "success": true, "stderr": "", "stdout": "" } },
+            { "call": "run", "args": ["-s", "emu", "shell", "input", "tap", "540", "1872"],
+              "ok": { "success": true, "stderr": "", "stdout": "" } },
+            { "call": "run", "args": dump,
+              "ok": { "success": true, "stderr": "", "stdout": "ERROR: could not get idle state.\n" } },
+        ]));
+        let tap = |text: &str| Commands::Ui {
+            command: UiCommand::Tap {
+                query: cli::NodeQuery {
+                    text: Some(text.into()),
+                    id: None,
+                    desc: None,
+                    exact: false,
+                    wait: None,
+                },
+                index: None,
+            },
+        };
+        let tapped =
+            execute_json(&adb_client, "emu", &tap("sign in"), &Prompter::disabled()).unwrap();
+        assert_eq!(
+            (tapped["x"].clone(), tapped["y"].clone()),
+            (json!(540), json!(1872))
+        );
+        assert_eq!(tapped["node"]["resource_id"], "com.example.app:id/login");
+
+        let failed = execute(&adb_client, tap("sign in")).unwrap_err();
+        assert_eq!(DabError::classify(&failed).code, ErrorCode::UiDumpFailed);
+    }
+
+    #[test]
+    fn input_text_types_spaces_and_symbols_and_reports_input_errors() {
+        let input = |args: &[&str]| {
+            let mut all = vec!["-s", "emu", "shell", "input"];
+            all.extend_from_slice(args);
+            json!(all)
+        };
+        let adb_client = replay(json!([
+            { "call": "run", "args": input(&["text", "'Jane%sO'\\''Brien%s&%sco'"]),
+              "ok": { "success": true, "stderr": "", "stdout": "" } },
+            { "call": "run", "args": input(&["keyevent", "KEYCODE_ENTER"]),
+              "ok": { "success": true, "stderr": "", "stdout": "" } },
+            { "call": "run", "args": input(&["text", "'¿qué?'"]),
+              "ok": { "success": true, "stderr": "", "stdout": "java.lang.RuntimeException: Failed to inject key event\n" } },
+        ]));
+        let text = |text: &str| Commands::Input {
+            command: InputCommand::Text { text: text.into() },
+        };
+        let typed = execute_json(
+            &adb_client,
+            "emu",
+            &text("Jane O'Brien & co\n"),
+            &Prompter::disabled(),
+        )
+        .unwrap();
+        assert_eq!(
+            typed,
+            json!({ "action": "text", "text": "Jane O'Brien & co\n", "success": true })
+        );
+
+        let failed = execute(&adb_client, text("¿qué?")).unwrap_err();
+        assert_eq!(DabError::classify(&failed).code, ErrorCode::InputFailed);
+    }
+
+    #[test]
+    fn macro_play_scales_gestures_to_the_target_screen() {
+        let dir = std::env::temp_dir().join(format!("dab-macro-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("login.json");
+        std::fs::write(
+            &file,
+            json!({
+                "screen": { "width": 1080, "height": 2400 },
+                "steps": [
+                    { "t": 0.0, "action": "tap", "x": 540, "y": 1200 },
+                    { "t": 0.02, "action": "swipe", "x1": 540, "y1": 1800, "x2": 540, "y2": 600, "duration_ms": 300 },
+                    { "t": 0.04, "action": "key", "key": "KEYCODE_BACK" },
+                ],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let ok = json!({ "success": true, "stderr": "", "stdout": "" });
+        let adb_client = replay(json!([
+            { "call": "run", "args": ["-s", "emu", "shell", "wm", "size"],
+              "ok": { "success": true, "stderr": "", "stdout": "Physical size: 720x1600\n" } },
+            { "call": "run", "args": ["-s", "emu", "shell", "dumpsys", "input"],
+              "ok": { "success": true, "stderr": "", "stdout": "    SurfaceOrientation: 0\n" } },
+            { "call": "run", "args": ["-s", "emu", "shell", "input", "tap", "360", "800"], "ok": ok },
+            { "call": "run", "args": ["-s", "emu", "shell", "input", "swipe", "360", "1200", "360", "400", "150"], "ok": ok },
+            { "call": "run", "args": ["-s", "emu", "shell", "input", "keyevent", "KEYCODE_BACK"], "ok": ok },
+        ]));
+        let played = execute(
+            &adb_client,
+            Commands::Macro {
+                command: MacroCommand::Play {
+                    file: file.clone(),
+                    speed: 2.0,
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(played["steps"], 3);
+        assert_eq!(played["scale"]["x"].as_f64().unwrap(), 720.0 / 1080.0);
+
+        let play = || Commands::Macro {
+            command: MacroCommand::Play {
+                file: file.clone(),
+                speed: 1.0,
+            },
+        };
+        std::fs::write(&file, "{\"steps\": []}").unwrap();
+        let invalid = execute(&adb_client, play()).unwrap_err();
+        assert_eq!(DabError::classify(&invalid).code, ErrorCode::InvalidMacro);
+
+        std::fs::write(
+            &file,
+            json!({
+                "screen": { "width": 1080, "height": 2400 },
+                "steps": [{ "t": 0.0, "action": "key", "key": "KEYCODE_HOME; rm -rf /sdcard/*" }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let injected = DabError::classify(&execute(&adb_client, play()).unwrap_err());
+        assert_eq!(injected.code, ErrorCode::InvalidMacro);
+        assert!(injected.message.contains("step 1"), "{}", injected.message);
+
+        let write_t = |t: f64| {
+            let steps = json!([{ "t": t, "action": "tap", "x": 540, "y": 1200 }]);
+            let recorded = json!({ "screen": { "width": 1080, "height": 2400 }, "steps": steps });
+            std::fs::write(&file, recorded.to_string()).unwrap();
+        };
+        write_t(-1.0);
+        let negative = DabError::classify(&execute(&adb_client, play()).unwrap_err());
+        assert_eq!(negative.code, ErrorCode::InvalidMacro);
+        write_t(1e300);
+        let adb_client = replay(json!([
+            { "call": "run", "args": ["-s", "emu", "shell", "wm", "size"],
+              "ok": { "success": true, "stderr": "", "stdout": "Physical size: 1080x2400\n" } },
+            { "call": "run", "args": ["-s", "emu", "shell", "dumpsys", "input"],
+              "ok": { "success": true, "stderr": "", "stdout": "" } },
+        ]));
+        let distant = DabError::classify(&execute(&adb_client, play()).unwrap_err());
+        assert_eq!(distant.code, ErrorCode::InvalidMacro);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn health_watch_retries_a_failed_sample_and_logs_one_header() {
+        let mut low = androkit::model::DeviceHealth::default();
+        low.battery.level = Some("5".into());
+        let adb_client = replay(json!([
+            { "call": "device_health", "args": ["emu"], "error": "device offline" },
+            { "call": "device_health", "args": ["emu"], "ok": low },
+        ]));
+        let dir = std::env::temp_dir().join(format!("dab-health-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("health.csv");
+        open_health_log(&log).unwrap();
+        let command = Commands::Health {
+            watch: Some(Duration::from_millis(10)),
+            fail_if: vec!["battery<20".parse().unwrap()],
+            warn_if: Vec::new(),
+            log: Some(log.clone()),
+        };
+        let err = watch_health(
+            &adb_client,
+            "emu",
+            &command,
+            Duration::from_millis(10),
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(DabError::classify(&err).code, ErrorCode::HealthCheckFailed);
+        let csv = std::fs::read_to_string(&log).unwrap();
+        assert_eq!(csv.lines().count(), 2, "{csv}");
+        assert_eq!(csv.lines().next(), Some(health::CSV_HEADER));
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
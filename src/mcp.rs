@@ -0,0 +1,1097 @@
+//! `dab mcp` — a Model Context Protocol server over stdio.
+//!
+//! Speaks newline-delimited JSON-RPC 2.0. Every tool maps onto a [`Commands`]
+//! variant and runs through the same `--json` code path as the CLI, with
+//! prompts disabled, so a tool result is exactly what `dab <cmd> --json`
+//! prints. Failures come back as tool results flagged `isError`, carrying the
+//! usual error envelope; arguments of the wrong type or range are rejected as
+//! invalid params instead. Screenshots are returned as PNG image content.
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::cli::{
+    parse_duration, Commands, InputCommand, IntentArgs, MacroCommand, NodeQuery, UiCommand,
+};
+use super::error::{DabError, ErrorCode};
+use anyhow::Result;
+use base64::Engine;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const SCHEMA_HINT: &str = "See the tool's inputSchema in `tools/list` for its arguments.";
+
+/// CLI commands with no tool, and why. Sent as the server's `instructions`.
+const NOT_TOOLS: [(&str, &str); 9] = [
+    ("record", "records until stopped"),
+    ("logcat", "streams until stopped"),
+    ("macro-record", "records touches until stopped"),
+    (
+        "wifi",
+        "switches the adb transport, dropping the device mid-session",
+    ),
+    (
+        "usb",
+        "switches the adb transport, dropping the device mid-session",
+    ),
+    ("shell-mode", "interactive"),
+    ("tui", "interactive"),
+    ("mcp", "this server"),
+    ("serve", "a server of its own"),
+];
+
+/// What a client should know beyond `tools/list`: the commands left out.
+fn instructions() -> String {
+    let left_out: Vec<String> = NOT_TOOLS
+        .iter()
+        .map(|(name, why)| format!("`{}` ({})", name, why))
+        .collect();
+    format!(
+        "Every dab command is a tool except {}. The watch modes of `health`, `crashes` and \
+         `top` are left out too, since they never return, and so is `deeplinks --open`, \
+         since it prompts; open a listed link with `launch`.",
+        left_out.join(", ")
+    )
+}
+
+/// Runs one tool call: its content, or `None` when no tool has that name.
+type CallTool<'a> = dyn FnMut(&str, &Value) -> Option<Result<Vec<Value>, DabError>> + 'a;
+
+/// Serve requests from stdin until it closes. `execute` runs one device
+/// command and returns its `--json` document.
+pub fn serve(
+    adb_client: &AdbClient<impl AdbBackend>,
+    execute: impl Fn(&str, &Commands) -> Result<Value>,
+) -> Result<()> {
+    let mut call_tool = |name: &str, args: &Value| -> Option<Result<Vec<Value>, DabError>> {
+        let tool = tools().into_iter().find(|t| t["name"] == name)?;
+        let absent = tool["inputSchema"]["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .find(|key| args.get(*key).is_none_or(Value::is_null));
+        let result = match (absent, name) {
+            (Some(key), _) => Err(DabError::new(
+                ErrorCode::Usage,
+                format!("Missing required argument `{}`", key),
+            )
+            .with_hint(SCHEMA_HINT)
+            .with_detail("argument", key)
+            .into()),
+            (None, "devices") => adb_client.get_device_list().map(|v| vec![text(&json!(v))]),
+            (None, "info") => Args(args)
+                .required("file")
+                .map_err(Into::into)
+                .and_then(|file| {
+                    adb_client
+                        .analyze_local_file(&PathBuf::from(file))
+                        .map(|v| vec![text(&json!(v))])
+                }),
+            _ => match command_for(name, args)? {
+                Ok(command) => run_device_tool(adb_client, &execute, args, &command),
+                Err(err) => Err(err.into()),
+            },
+        };
+        Some(result.map_err(|e| DabError::classify(&e)))
+    };
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => handle(&request, &mut call_tool),
+            Err(e) => Some(rpc_error(
+                Value::Null,
+                -32700,
+                &format!("Parse error: {}", e),
+            )),
+        };
+        if let Some(response) = response {
+            writeln!(stdout, "{}", response)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Pick the device, then run the command on it. Screenshots also return the
+/// image itself.
+fn run_device_tool(
+    adb_client: &AdbClient<impl AdbBackend>,
+    execute: &impl Fn(&str, &Commands) -> Result<Value>,
+    args: &Value,
+    command: &Commands,
+) -> Result<Vec<Value>> {
+    let device = pick_device(adb_client, Args(args).string("device")?.as_deref())?;
+    if let Commands::Screenshot { output } = command {
+        let path = output.clone().unwrap_or_else(|| {
+            std::env::temp_dir().join(format!("dab-mcp-{}.png", std::process::id()))
+        });
+        let result = execute(
+            &device,
+            &Commands::Screenshot {
+                output: Some(path.clone()),
+            },
+        )?;
+        let png = std::fs::read(&path)?;
+        if output.is_none() {
+            let _ = std::fs::remove_file(&path);
+        }
+        return Ok(vec![
+            json!({
+                "type": "image",
+                "data": base64::engine::general_purpose::STANDARD.encode(png),
+                "mimeType": "image/png",
+            }),
+            text(&result),
+        ]);
+    }
+    Ok(vec![text(&execute(&device, command)?)])
+}
+
+/// The `device` argument, or the only ready device. Never prompts.
+fn pick_device(adb_client: &AdbClient<impl AdbBackend>, device: Option<&str>) -> Result<String> {
+    if let Some(serial) = device {
+        adb_client.check_device(serial)?;
+        return Ok(serial.to_string());
+    }
+    let ready: Vec<String> = adb_client
+        .get_devices()?
+        .into_iter()
+        .filter(|d| d.is_ready())
+        .map(|d| d.serial)
+        .collect();
+    match ready.as_slice() {
+        [only] => Ok(only.clone()),
+        [] => Err(DabError::new(ErrorCode::NoDevices, "No ready devices").into()),
+        _ => Err(DabError::new(
+            ErrorCode::InputRequired,
+            "Several devices are connected; pass `device`",
+        )
+        .with_hint("Call the `devices` tool and pass one serial as `device`.")
+        .with_detail("candidates", ready)
+        .into()),
+    }
+}
+
+/// Answer one JSON-RPC message; `None` for notifications.
+/// `call_tool` returns `None` for an unknown tool name.
+fn handle(request: &Value, call_tool: &mut CallTool) -> Option<Value> {
+    let id = request.get("id")?.clone();
+    let method = request["method"].as_str().unwrap_or_default();
+    let params = &request["params"];
+    let result = match method {
+        "initialize" => {
+            let requested = params["protocolVersion"].as_str().unwrap_or_default();
+            let version = PROTOCOL_VERSIONS
+                .into_iter()
+                .find(|v| *v == requested)
+                .unwrap_or(PROTOCOL_VERSIONS[0]);
+            json!({
+                "protocolVersion": version,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "dab", "version": env!("CARGO_PKG_VERSION") },
+                "instructions": instructions(),
+            })
+        }
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": tools() }),
+        "tools/call" => {
+            let name = params["name"].as_str().unwrap_or_default();
+            let empty = json!({});
+            let args = params.get("arguments").unwrap_or(&empty);
+            match call_tool(name, args) {
+                Some(Ok(content)) => json!({ "content": content, "isError": false }),
+                Some(Err(err)) if err.code == ErrorCode::Usage => {
+                    let mut response = rpc_error(id, -32602, &err.message);
+                    response["error"]["data"] = err.to_json();
+                    return Some(response);
+                }
+                Some(Err(err)) => json!({ "content": [text(&err.to_json())], "isError": true }),
+                None => return Some(rpc_error(id, -32602, &format!("Unknown tool: {}", name))),
+            }
+        }
+        _ => {
+            return Some(rpc_error(
+                id,
+                -32601,
+                &format!("Method not found: {}", method),
+            ))
+        }
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn text(value: &Value) -> Value {
+    json!({ "type": "text", "text": serde_json::to_string_pretty(value).unwrap_or_default() })
+}
+
+/// A tool call's `arguments`, read as the types its schema declares. A
+/// wrong type or an out-of-range number is a USAGE error rather than a
+/// default, so a malformed call never runs a different action.
+struct Args<'a>(&'a Value);
+
+impl Args<'_> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key).filter(|v| !v.is_null())
+    }
+
+    fn invalid(key: &str, expected: &str) -> DabError {
+        DabError::new(
+            ErrorCode::Usage,
+            format!("Argument `{}` must be {}", key, expected),
+        )
+        .with_hint(SCHEMA_HINT)
+        .with_detail("argument", key)
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, DabError> {
+        self.get(key)
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| Self::invalid(key, "a string"))
+            })
+            .transpose()
+    }
+
+    /// A string the schema lists as required; its presence is checked before
+    /// the tool runs.
+    fn required(&self, key: &str) -> Result<String, DabError> {
+        self.string(key)?.ok_or_else(|| Self::invalid(key, "given"))
+    }
+
+    fn path(&self, key: &str) -> Result<Option<PathBuf>, DabError> {
+        Ok(self.string(key)?.map(PathBuf::from))
+    }
+
+    fn flag(&self, key: &str) -> Result<bool, DabError> {
+        self.get(key)
+            .map(|v| v.as_bool().ok_or_else(|| Self::invalid(key, "a boolean")))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    fn count(&self, key: &str) -> Result<Option<u64>, DabError> {
+        self.get(key)
+            .map(|v| {
+                v.as_u64()
+                    .ok_or_else(|| Self::invalid(key, "a non-negative integer"))
+            })
+            .transpose()
+    }
+
+    /// A screen coordinate: a required non-negative integer that fits `input`.
+    fn pixel(&self, key: &str) -> Result<u32, DabError> {
+        self.count(key)?
+            .and_then(|n| u32::try_from(n).ok())
+            .filter(|n| *n <= i32::MAX as u32)
+            .ok_or_else(|| Self::invalid(key, "a pixel coordinate from 0 to 2147483647"))
+    }
+
+    /// A duration as a number of seconds or a string such as `500ms`.
+    fn duration(&self, key: &str) -> Result<Option<Duration>, DabError> {
+        let text = match self.get(key) {
+            None => return Ok(None),
+            Some(Value::Number(secs)) => secs.to_string(),
+            Some(Value::String(text)) => text.clone(),
+            Some(_) => return Err(Self::invalid(key, "a duration such as `5s`")),
+        };
+        parse_duration(&text)
+            .map(Some)
+            .map_err(|e| Self::invalid(key, &format!("a duration such as `5s` ({})", e)))
+    }
+
+    /// An object of string values, as `KEY=VALUE` pairs.
+    fn pairs(&self, key: &str) -> Result<Vec<(String, String)>, DabError> {
+        let Some(value) = self.get(key) else {
+            return Ok(Vec::new());
+        };
+        value
+            .as_object()
+            .and_then(|map| {
+                map.iter()
+                    .map(|(k, v)| v.as_str().map(|v| (k.clone(), v.to_string())))
+                    .collect()
+            })
+            .ok_or_else(|| Self::invalid(key, "an object of string values"))
+    }
+
+    fn number(&self, key: &str) -> Result<Option<f64>, DabError> {
+        self.get(key)
+            .map(|v| {
+                v.as_f64()
+                    .filter(|n| n.is_finite())
+                    .ok_or_else(|| Self::invalid(key, "a number"))
+            })
+            .transpose()
+    }
+
+    /// An array of strings, each parsed as `T`.
+    fn parsed<T: FromStr<Err = String>>(
+        &self,
+        key: &str,
+        expected: &str,
+    ) -> Result<Vec<T>, DabError> {
+        self.strings(key)?
+            .into_iter()
+            .flatten()
+            .map(|item| {
+                item.parse()
+                    .map_err(|e| Self::invalid(key, &format!("{} ({})", expected, e)))
+            })
+            .collect()
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, DabError> {
+        self.get(key)
+            .map(|v| {
+                v.as_array()
+                    .and_then(|list| {
+                        list.iter()
+                            .map(|item| item.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .ok_or_else(|| Self::invalid(key, "an array of strings"))
+            })
+            .transpose()
+    }
+}
+
+/// Paths on this machine, from an array argument.
+fn paths(list: Option<Vec<String>>) -> Vec<PathBuf> {
+    list.into_iter().flatten().map(PathBuf::from).collect()
+}
+
+/// The `Commands` a device tool runs; `None` for unknown tools. Required
+/// arguments are checked against the tool's schema before this is called.
+fn command_for(name: &str, args: &Value) -> Option<Result<Commands, DabError>> {
+    command_from(name, &Args(args)).transpose()
+}
+
+fn command_from(name: &str, args: &Args) -> Result<Option<Commands>, DabError> {
+    let package = args.string("package")?;
+    let intent = || -> Result<IntentArgs, DabError> {
+        Ok(IntentArgs {
+            package: package.clone(),
+            action: args.string("action")?,
+            data: args.string("data")?,
+            mime_type: args.string("type")?,
+            categories: args.strings("categories")?.unwrap_or_default(),
+            extras: args.parsed("extras", "an array of `KEY:TYPE=VALUE` extras")?,
+            flags: args.parsed("flags", "an array of intent flag names")?,
+        })
+    };
+    let permissions = || -> Result<Option<String>, DabError> {
+        Ok(args.strings("permissions")?.map(|list| list.join(",")))
+    };
+    Ok(Some(match name {
+        "apps" => Commands::Apps,
+        "device" => Commands::Device,
+        "network" => Commands::Network,
+        "health" => Commands::Health {
+            watch: None,
+            fail_if: Vec::new(),
+            warn_if: Vec::new(),
+            log: None,
+        },
+        "app-info" => Commands::AppInfo {
+            package,
+            all: args.flag("all")?,
+        },
+        "open" => Commands::Open { package },
+        "uninstall" => Commands::Uninstall { package },
+        "clear" => Commands::Clear { package },
+        "force-kill" => Commands::ForceKill { package },
+        "launch" => Commands::Launch {
+            url: args.required("url")?,
+        },
+        "install" => Commands::Install {
+            file: PathBuf::from(args.required("file")?),
+        },
+        "screenshot" => Commands::Screenshot {
+            output: args.path("output")?,
+        },
+        "grant" => Commands::Grant {
+            package,
+            permissions: permissions()?,
+        },
+        "revoke" => Commands::Revoke {
+            package,
+            permissions: permissions()?,
+        },
+        "crashes" => Commands::Crashes {
+            package,
+            watch: false,
+            save_dir: args.path("save_dir")?,
+        },
+        "ui-dump" => Commands::Ui {
+            command: UiCommand::Dump {
+                xml: false,
+                output: None,
+            },
+        },
+        "ui-find" | "ui-tap" => {
+            let query = NodeQuery {
+                text: args.string("text")?,
+                id: args.string("id")?,
+                desc: args.string("desc")?,
+                exact: args.flag("exact")?,
+                wait: args.duration("wait")?,
+            };
+            Commands::Ui {
+                command: match name {
+                    "ui-find" => UiCommand::Find { query },
+                    _ => UiCommand::Tap {
+                        query,
+                        index: args.count("index")?.map(|i| i as usize),
+                    },
+                },
+            }
+        }
+        "input-tap" => Commands::Input {
+            command: InputCommand::Tap {
+                x: args.pixel("x")?,
+                y: args.pixel("y")?,
+            },
+        },
+        "input-long-press" => Commands::Input {
+            command: InputCommand::LongPress {
+                x: args.pixel("x")?,
+                y: args.pixel("y")?,
+                duration: Duration::from_millis(args.count("duration_ms")?.unwrap_or(1000)),
+            },
+        },
+        "input-swipe" => Commands::Input {
+            command: InputCommand::Swipe {
+                x1: args.pixel("x1")?,
+                y1: args.pixel("y1")?,
+                x2: args.pixel("x2")?,
+                y2: args.pixel("y2")?,
+                duration: Duration::from_millis(args.count("duration_ms")?.unwrap_or(300)),
+            },
+        },
+        "input-text" => Commands::Input {
+            command: InputCommand::Text {
+                text: args.required("text")?,
+            },
+        },
+        "input-key" => Commands::Input {
+            command: InputCommand::Key {
+                key: args.required("key")?,
+                long: args.flag("long")?,
+            },
+        },
+        "macro-play" => Commands::Macro {
+            command: MacroCommand::Play {
+                file: PathBuf::from(args.required("file")?),
+                speed: match args.get("speed") {
+                    None => 1.0,
+                    Some(speed) => speed
+                        .as_f64()
+                        .filter(|s| *s > 0.0 && s.is_finite())
+                        .ok_or_else(|| Args::invalid("speed", "a number greater than 0"))?,
+                },
+            },
+        },
+        "test" => Commands::Test {
+            package,
+            runner: args.string("runner")?,
+            class: args.strings("class")?.unwrap_or_default(),
+            arg: args.pairs("args")?,
+            install: paths(args.strings("install")?),
+            output: args
+                .path("output")?
+                .unwrap_or_else(|| PathBuf::from("test-results")),
+        },
+        "startup" => {
+            let mode = args.string("mode")?;
+            let (cold, warm, hot) = match mode.as_deref() {
+                None | Some("cold") => (true, false, false),
+                Some("warm") => (false, true, false),
+                Some("hot") => (false, false, true),
+                Some(_) => return Err(Args::invalid("mode", "`cold`, `warm` or `hot`")),
+            };
+            Commands::Startup {
+                package,
+                runs: match args.count("runs")? {
+                    None => 10,
+                    Some(runs) => u32::try_from(runs)
+                        .ok()
+                        .filter(|r| *r >= 1)
+                        .ok_or_else(|| Args::invalid("runs", "at least 1"))?,
+                },
+                cold,
+                warm,
+                hot,
+                baseline: args.path("baseline")?,
+                threshold: args.number("threshold")?.unwrap_or(10.0),
+            }
+        }
+        "perf" => Commands::Perf {
+            package,
+            interval: args.duration("interval")?.unwrap_or(Duration::from_secs(1)),
+            duration: args
+                .duration("duration")?
+                .unwrap_or(Duration::from_secs(60)),
+            output: args.path("output")?,
+        },
+        "provision" => Commands::Provision {
+            profile: PathBuf::from(args.required("profile")?),
+            dry_run: args.flag("dry_run")?,
+        },
+        "run" => Commands::Run {
+            scenario: PathBuf::from(args.required("scenario")?),
+            var: args.pairs("vars")?,
+        },
+        "deeplinks" => {
+            let file = args.path("file")?;
+            if package.is_some() && file.is_some() {
+                return Err(Args::invalid("package", "omitted when `file` is given"));
+            }
+            Commands::Deeplinks {
+                package,
+                file,
+                open: false,
+                verify: args.flag("verify")?,
+            }
+        }
+        "start" => Commands::Start {
+            activity: args.string("activity")?,
+            intent: intent()?,
+        },
+        "broadcast" => Commands::Broadcast {
+            receiver: args.string("receiver")?,
+            intent: intent()?,
+        },
+        "start-service" => Commands::StartService {
+            service: args.required("service")?,
+            foreground: args.flag("foreground")?,
+            intent: intent()?,
+        },
+        "top" => Commands::Top { watch: None },
+        "download" => Commands::Download {
+            package,
+            output: args.path("output")?,
+        },
+        _ => return Ok(None),
+    }))
+}
+
+/// Tool definitions with their input JSON schemas.
+fn tools() -> Vec<Value> {
+    let device = json!({
+        "type": "string",
+        "description": "Device serial from the `devices` tool. Optional when exactly one device is ready.",
+    });
+    let package = json!({
+        "type": "string",
+        "description": "Package name, full or partial (e.g. `youtube`). Ambiguous names fail with the candidates.",
+    });
+    let permissions = json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": "Full permission names, e.g. `android.permission.CAMERA`.",
+    });
+    let tool = |name: &str, description: &str, properties: Value, required: &[&str]| {
+        json!({
+            "name": name,
+            "description": description,
+            "inputSchema": { "type": "object", "properties": properties, "required": required },
+        })
+    };
+    let node = || {
+        json!({
+            "device": device,
+            "text": { "type": "string", "description": "Text the node shows; a case-insensitive substring unless `exact`." },
+            "id": { "type": "string", "description": "Resource id, full (`com.app:id/login`) or just the name (`login`)." },
+            "desc": { "type": "string", "description": "Content description; a case-insensitive substring unless `exact`." },
+            "exact": { "type": "boolean" },
+            "wait": { "type": "string", "description": "Keep re-reading the screen this long (e.g. `5s`) until a node matches." },
+        })
+    };
+    let intent = || {
+        json!({
+            "device": device,
+            "package": package,
+            "action": { "type": "string", "description": "Intent action, e.g. `com.example.action.DEBUG`." },
+            "data": { "type": "string", "description": "Data URI." },
+            "type": { "type": "string", "description": "MIME type." },
+            "categories": { "type": "array", "items": { "type": "string" } },
+            "extras": { "type": "array", "items": { "type": "string" }, "description": "Extras as `KEY:TYPE=VALUE`, e.g. `n:int=3`; `KEY=VALUE` is a string and `KEY:null` a null string." },
+            "flags": { "type": "array", "items": { "type": "string" }, "description": "Intent flags by name, e.g. `clear-task`, or as a hex value." },
+        })
+    };
+    let device_only = || json!({ "device": device });
+    let app = || json!({ "device": device, "package": package });
+    vec![
+        tool(
+            "devices",
+            "List connected devices with state, model, transport, Android version and battery.",
+            json!({}),
+            &[],
+        ),
+        tool("apps", "List installed package names.", device_only(), &[]),
+        tool(
+            "device",
+            "Device properties: model, manufacturer, Android version, SDK, ABI.",
+            device_only(),
+            &[],
+        ),
+        tool("network", "IP addresses and Wi-Fi SSID.", device_only(), &[]),
+        tool(
+            "health",
+            "Battery, storage, RAM and network health.",
+            device_only(),
+            &[],
+        ),
+        tool(
+            "app-info",
+            "Version of an installed app, and its granted permissions with `all`.",
+            json!({ "device": device, "package": package, "all": { "type": "boolean" } }),
+            &["package"],
+        ),
+        tool("open", "Launch an app.", app(), &["package"]),
+        tool("uninstall", "Uninstall an app.", app(), &["package"]),
+        tool("clear", "Clear an app's data.", app(), &["package"]),
+        tool("force-kill", "Force-stop an app.", app(), &["package"]),
+        tool(
+            "launch",
+            "Open a URL or deep link.",
+            json!({ "device": device, "url": { "type": "string" } }),
+            &["url"],
+        ),
+        tool(
+            "install",
+            "Install a local APK, XAPK or APKM file.",
+            json!({ "device": device, "file": { "type": "string", "description": "Path on this machine." } }),
+            &["file"],
+        ),
+        tool(
+            "screenshot",
+            "Capture the screen and return it as a PNG image.",
+            json!({ "device": device, "output": { "type": "string", "description": "Also keep the PNG at this path." } }),
+            &[],
+        ),
+        tool(
+            "ui-dump",
+            "The on-screen view hierarchy: every node's text, resource id, description, class, bounds and flags.",
+            device_only(),
+            &[],
+        ),
+        tool(
+            "ui-find",
+            "Find on-screen nodes by text, resource id or content description (at least one), with their bounds and center.",
+            node(),
+            &[],
+        ),
+        tool(
+            "ui-tap",
+            "Tap the center of the first on-screen node matching text, resource id or content description (at least one).",
+            {
+                let mut properties = node();
+                properties["index"] = json!({ "type": "integer", "description": "Tap this match instead, counting from 0 in `ui-find` order." });
+                properties
+            },
+            &[],
+        ),
+        tool(
+            "input-tap",
+            "Tap at a pixel position.",
+            json!({ "device": device, "x": { "type": "integer" }, "y": { "type": "integer" } }),
+            &["x", "y"],
+        ),
+        tool(
+            "input-long-press",
+            "Press and hold at a pixel position, e.g. to open a context menu.",
+            json!({
+                "device": device,
+                "x": { "type": "integer" }, "y": { "type": "integer" },
+                "duration_ms": { "type": "integer", "description": "Defaults to 1000." },
+            }),
+            &["x", "y"],
+        ),
+        tool(
+            "input-swipe",
+            "Swipe between two pixel positions, e.g. to scroll.",
+            json!({
+                "device": device,
+                "x1": { "type": "integer" }, "y1": { "type": "integer" },
+                "x2": { "type": "integer" }, "y2": { "type": "integer" },
+                "duration_ms": { "type": "integer", "description": "Defaults to 300." },
+            }),
+            &["x1", "y1", "x2", "y2"],
+        ),
+        tool(
+            "input-text",
+            "Type text into the focused field; spaces, symbols and newlines arrive as written.",
+            json!({ "device": device, "text": { "type": "string" } }),
+            &["text"],
+        ),
+        tool(
+            "input-key",
+            "Press a key: BACK, HOME, ENTER, a KEYCODE_ constant or a number; `long` holds it.",
+            json!({ "device": device, "key": { "type": "string" }, "long": { "type": "boolean" } }),
+            &["key"],
+        ),
+        tool(
+            "macro-play",
+            "Replay a macro recorded with `dab macro record`, scaled to the device's screen; `speed` 2 plays it twice as fast.",
+            json!({ "device": device, "file": { "type": "string" }, "speed": { "type": "number" } }),
+            &["file"],
+        ),
+        tool(
+            "grant",
+            "Grant runtime permissions to an app.",
+            json!({ "device": device, "package": package, "permissions": permissions }),
+            &["package", "permissions"],
+        ),
+        tool(
+            "revoke",
+            "Revoke runtime permissions from an app.",
+            json!({ "device": device, "package": package, "permissions": permissions }),
+            &["package", "permissions"],
+        ),
+        tool(
+            "crashes",
+            "Crashes, native crashes and ANRs of an app still in the log buffers, with stack traces.",
+            json!({ "device": device, "package": package, "save_dir": { "type": "string" } }),
+            &["package"],
+        ),
+        tool(
+            "test",
+            "Run on-device instrumentation tests, write junit.xml and summary.json to `output`, and return the summary with every test's status.",
+            json!({
+                "device": device,
+                "package": { "type": "string", "description": "Test package, full or partial (e.g. `myapp.test`)." },
+                "runner": { "type": "string", "description": "Defaults to the runner the test APK declares." },
+                "class": { "type": "array", "items": { "type": "string" }, "description": "Only run these classes, or `Class#method`." },
+                "args": { "type": "object", "additionalProperties": { "type": "string" }, "description": "Instrumentation arguments, passed as `-e KEY VALUE`." },
+                "install": { "type": "array", "items": { "type": "string" }, "description": "APKs on this machine to install first, e.g. the app and its test APK." },
+                "output": { "type": "string", "description": "Directory for the reports. Defaults to `test-results`." },
+            }),
+            &["package"],
+        ),
+        tool(
+            "startup",
+            "Benchmark an app's start time over several launches: min, median, p90, max and stddev, compared against `baseline` when given.",
+            json!({
+                "device": device,
+                "package": package,
+                "runs": { "type": "integer", "minimum": 1, "description": "Measured launches. Defaults to 10." },
+                "mode": { "type": "string", "enum": ["cold", "warm", "hot"], "description": "Defaults to `cold`." },
+                "baseline": { "type": "string", "description": "A previous `startup` result (JSON file) to compare against." },
+                "threshold": { "type": "number", "description": "Fail when the median regresses by more than this percentage. Defaults to 10." },
+            }),
+            &["package"],
+        ),
+        tool(
+            "perf",
+            "Sample an app's memory, CPU, threads and jank every `interval` for `duration`, then return the samples and their summary. The call lasts `duration`.",
+            json!({
+                "device": device,
+                "package": package,
+                "interval": { "type": "string", "description": "Time between samples, e.g. `500ms`. Defaults to `1s`." },
+                "duration": { "type": "string", "description": "How long to sample, e.g. `30s`. Defaults to `60s`." },
+                "output": { "type": "string", "description": "Also write the samples here: CSV for `.csv`, JSON otherwise." },
+            }),
+            &["package"],
+        ),
+        tool(
+            "provision",
+            "Apply a YAML or TOML device profile (installs, permissions, settings and more), skipping what is already in place.",
+            json!({
+                "device": device,
+                "profile": { "type": "string", "description": "Path on this machine (`.yaml`, `.yml` or `.toml`)." },
+                "dry_run": { "type": "boolean", "description": "Return the plan without changing the device." },
+            }),
+            &["profile"],
+        ),
+        tool(
+            "run",
+            "Run the steps of a YAML scenario against the device and return the step-by-step report; the first failing step stops the run.",
+            json!({
+                "device": device,
+                "scenario": { "type": "string", "description": "Path on this machine." },
+                "vars": { "type": "object", "additionalProperties": { "type": "string" }, "description": "Set or override scenario variables, used as `${KEY}`." },
+            }),
+            &["scenario"],
+        ),
+        tool(
+            "deeplinks",
+            "The deep links an app declares (scheme, host, path pattern and activity), from the installed app or a local APK `file`. Open one with `launch`.",
+            json!({
+                "device": device,
+                "package": package,
+                "file": { "type": "string", "description": "Read the links from this APK, XAPK or APKM instead (needs aapt or aapt2)." },
+                "verify": { "type": "boolean", "description": "Check that each link opens its own activity rather than a chooser or browser." },
+            }),
+            &[],
+        ),
+        tool(
+            "start",
+            "Start an activity with an explicit intent: extras, flags, action and categories.",
+            {
+                let mut properties = intent();
+                properties["activity"] = json!({ "type": "string", "description": "Activity class, `.Relative` to the package or fully qualified. Defaults to the launcher activity." });
+                properties
+            },
+            &[],
+        ),
+        tool(
+            "broadcast",
+            "Send a broadcast, with the same intent arguments as `start`.",
+            {
+                let mut properties = intent();
+                properties["receiver"] = json!({ "type": "string", "description": "Receiver class, `.Relative` to the package or fully qualified." });
+                properties
+            },
+            &[],
+        ),
+        tool(
+            "start-service",
+            "Start a service, with the same intent arguments as `start`.",
+            {
+                let mut properties = intent();
+                properties["service"] = json!({ "type": "string", "description": "Service class, `.Relative` to the package or fully qualified." });
+                properties["foreground"] = json!({ "type": "boolean", "description": "Start it as a foreground service." });
+                properties
+            },
+            &["service"],
+        ),
+        tool(
+            "top",
+            "The resumed activity with its task and fragments, and every task's back stack.",
+            device_only(),
+            &[],
+        ),
+        tool(
+            "download",
+            "Pull an app's APK to this machine.",
+            json!({
+                "device": device,
+                "package": package,
+                "output": { "type": "string", "description": "Where to save it, relative to the server's working directory." },
+            }),
+            &[],
+        ),
+        tool(
+            "info",
+            "Analyze a local APK, XAPK or APKM file. No device needed.",
+            json!({ "file": { "type": "string" } }),
+            &["file"],
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Cli;
+    use clap::CommandFactory;
+
+    fn no_tools(_: &str, _: &Value) -> Option<Result<Vec<Value>, DabError>> {
+        None
+    }
+
+    #[test]
+    fn initialize_negotiates_protocol_version() {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize",
+            "params": { "protocolVersion": "2024-11-05" } });
+        let response = handle(&request, &mut no_tools).unwrap();
+        assert_eq!(response["id"], 1);
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "dab");
+    }
+
+    #[test]
+    fn notifications_get_no_response() {
+        let note = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(handle(&note, &mut no_tools).is_none());
+    }
+
+    #[test]
+    fn lists_every_tool_with_a_schema() {
+        let response = handle(
+            &json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" }),
+            &mut no_tools,
+        )
+        .unwrap();
+        let tools = response["result"]["tools"].as_array().unwrap();
+        for tool in tools {
+            let name = tool["name"].as_str().unwrap();
+            assert_eq!(tool["inputSchema"]["type"], "object", "{name}");
+            if !matches!(name, "devices" | "info") {
+                assert!(command_for(name, &json!({})).is_some(), "{name}");
+            }
+        }
+        assert!(tools.iter().any(|t| t["name"] == "screenshot"));
+    }
+
+    #[test]
+    fn every_command_is_a_tool_or_left_out_with_a_reason() {
+        let tools: Vec<String> = tools()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap().to_string())
+            .collect();
+        let mut names = Vec::new();
+        for command in Cli::command().get_subcommands() {
+            let name = command.get_name();
+            if command.has_subcommands() {
+                names.extend(
+                    command
+                        .get_subcommands()
+                        .map(|sub| format!("{}-{}", name, sub.get_name())),
+                );
+            } else {
+                names.push(name.to_string());
+            }
+        }
+        for name in &names {
+            let left_out = NOT_TOOLS.iter().any(|(n, _)| n == name);
+            assert!(
+                tools.contains(name) != left_out,
+                "`{name}` must be exactly one of a tool or in NOT_TOOLS"
+            );
+        }
+        for (name, _) in NOT_TOOLS {
+            assert!(names.iter().any(|n| n == name), "`{name}` is not a command");
+        }
+        assert!(instructions().contains("`wifi` (switches the adb transport"));
+    }
+
+    #[test]
+    fn tool_errors_are_flagged_results() {
+        let mut failing =
+            |_: &str, _: &Value| Some(Err(DabError::new(ErrorCode::PackageNotFound, "nope")));
+        let request = json!({ "jsonrpc": "2.0", "id": 3, "method": "tools/call",
+            "params": { "name": "open", "arguments": { "package": "x" } } });
+        let response = handle(&request, &mut failing).unwrap();
+        assert_eq!(response["result"]["isError"], true);
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains("PACKAGE_NOT_FOUND"));
+
+        let unknown = json!({ "jsonrpc": "2.0", "id": 4, "method": "tools/call",
+            "params": { "name": "nope" } });
+        assert_eq!(
+            handle(&unknown, &mut no_tools).unwrap()["error"]["code"],
+            -32602
+        );
+    }
+
+    #[test]
+    fn maps_permission_arrays() {
+        let command = command_for(
+            "grant",
+            &json!({ "package": "com.foo", "permissions": ["a", "b"] }),
+        );
+        match command {
+            Some(Ok(Commands::Grant { permissions, .. })) => {
+                assert_eq!(permissions.as_deref(), Some("a,b"))
+            }
+            _ => panic!("expected grant"),
+        }
+    }
+
+    #[test]
+    fn maps_test_arguments() {
+        let command = command_for(
+            "test",
+            &json!({ "package": "myapp.test", "class": ["com.foo.LoginTest"], "args": { "size": "large" } }),
+        );
+        match command {
+            Some(Ok(Commands::Test {
+                class, arg, output, ..
+            })) => {
+                assert_eq!(class, ["com.foo.LoginTest"]);
+                assert_eq!(arg, [("size".to_string(), "large".to_string())]);
+                assert_eq!(output, PathBuf::from("test-results"));
+            }
+            _ => panic!("expected test"),
+        }
+        assert!(
+            command_for("test", &json!({ "package": "t", "args": { "n": 1 } }))
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn maps_intent_arguments() {
+        let args = json!({ "package": "com.app", "action": "com.app.PING",
+            "extras": ["n:int=3", "name=dab"], "flags": ["clear-task"] });
+        let Some(Ok(Commands::Broadcast { receiver, intent })) = command_for("broadcast", &args)
+        else {
+            panic!("expected a broadcast");
+        };
+        assert_eq!(receiver, None);
+        assert_eq!(intent.package.as_deref(), Some("com.app"));
+        assert_eq!(intent.extras.len(), 2);
+        assert_eq!(intent.flags[0].name, "clear-task");
+
+        let err = command_for("start", &json!({ "extras": ["n:int=three"] }))
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(err.code, ErrorCode::Usage);
+        assert_eq!(err.details["argument"], "extras");
+    }
+
+    #[test]
+    fn rejects_arguments_of_the_wrong_type_or_range() {
+        let usage = |name: &str, args: Value| {
+            let err = command_for(name, &args).unwrap().err().unwrap();
+            assert_eq!(err.code, ErrorCode::Usage, "{name} {args}");
+            err.details["argument"].clone()
+        };
+        assert_eq!(usage("input-tap", json!({ "x": -5, "y": 10 })), "x");
+        assert_eq!(usage("input-tap", json!({ "x": "10", "y": 10 })), "x");
+        assert_eq!(
+            usage("input-tap", json!({ "x": 10, "y": 4294967306u64 })),
+            "y"
+        );
+        assert_eq!(
+            usage("ui-find", json!({ "text": "OK", "wait": "soon" })),
+            "wait"
+        );
+        assert_eq!(
+            usage("grant", json!({ "package": "a", "permissions": "CAMERA" })),
+            "permissions"
+        );
+        assert_eq!(
+            usage("macro-play", json!({ "file": "m.json", "speed": 0 })),
+            "speed"
+        );
+        assert_eq!(
+            usage("deeplinks", json!({ "package": "a", "file": "a.apk" })),
+            "package"
+        );
+        assert!(matches!(
+            command_for("input-tap", &json!({ "x": 10, "y": 20 })),
+            Some(Ok(Commands::Input {
+                command: InputCommand::Tap { x: 10, y: 20 }
+            }))
+        ));
+
+        let mut failing = |name: &str, args: &Value| {
+            command_for(name, args).map(|command| command.map(|_| Vec::new()))
+        };
+        let request = json!({ "jsonrpc": "2.0", "id": 5, "method": "tools/call",
+            "params": { "name": "input-tap", "arguments": { "x": -1, "y": 0 } } });
+        let response = handle(&request, &mut failing).unwrap();
+        assert_eq!(response["error"]["code"], -32602);
+        assert_eq!(response["error"]["data"]["code"], "USAGE");
+    }
+}
@@ -0,0 +1,149 @@
+//! Typed results returned by [`AdbClient`](crate::adb_client::AdbClient).
+//!
+//! Each type serializes to exactly the document `dab <cmd> --json` prints, so
+//! the JSON contract is pinned here rather than in `json!` literals. The `dab`
+//! binary renders the same values as colored text.
+
+use super::crash::Incident;
+use super::device::Device;
+use serde::Serialize;
+use std::path::PathBuf;
+
+pub use androkit::model::{ApkInfo, DeviceInfo, Ram, Storage};
+
+/// `dab devices`.
+#[derive(Debug, Serialize)]
+pub struct DeviceList {
+    pub devices: Vec<Device>,
+}
+
+/// `dab apps`.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstalledApps {
+    pub device: String,
+    pub apps: Vec<String>,
+}
+
+/// `dab app-info`; `granted_permissions` only with `--all`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppInfo {
+    pub package_name: String,
+    pub version_code: String,
+    pub version_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub granted_permissions: Option<Vec<String>>,
+}
+
+/// `dab network`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkInfo {
+    pub device: String,
+    pub ip_addresses: Vec<String>,
+    pub ssid: Option<String>,
+}
+
+impl From<androkit::model::NetworkInfo> for NetworkInfo {
+    fn from(info: androkit::model::NetworkInfo) -> Self {
+        NetworkInfo {
+            device: info.device,
+            ip_addresses: info.ip_addresses,
+            ssid: info.ssid,
+        }
+    }
+}
+
+/// `dab health`. Unlike androkit's type, missing values serialize as `null`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceHealth {
+    pub device: String,
+    pub battery: Battery,
+    pub storage: Option<Storage>,
+    pub ram: Ram,
+    pub network: NetworkStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Battery {
+    pub level: Option<String>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkStatus {
+    pub ip: Option<String>,
+    pub ssid: Option<String>,
+}
+
+impl From<androkit::model::DeviceHealth> for DeviceHealth {
+    fn from(h: androkit::model::DeviceHealth) -> Self {
+        DeviceHealth {
+            device: h.device,
+            battery: Battery {
+                level: h.battery.level,
+                status: h.battery.status,
+            },
+            storage: h.storage,
+            ram: h.ram,
+            network: NetworkStatus {
+                ip: h.network.ip,
+                ssid: h.network.ssid,
+            },
+        }
+    }
+}
+
+/// `dab crashes`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashReport {
+    pub device: String,
+    pub package: String,
+    pub incidents: Vec<SavedIncident>,
+}
+
+/// An incident, with where `--save-dir` wrote it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SavedIncident {
+    #[serde(flatten)]
+    pub incident: Incident,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn health_serializes_missing_values_as_null() {
+        let health = DeviceHealth::from(androkit::model::DeviceHealth {
+            device: "emulator-5554".into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            serde_json::to_value(&health).unwrap(),
+            json!({
+                "device": "emulator-5554",
+                "battery": { "level": null, "status": null },
+                "storage": null,
+                "ram": { "total_gb": 0.0, "free_gb": 0.0 },
+                "network": { "ip": null, "ssid": null },
+            })
+        );
+    }
+
+    #[test]
+    fn app_info_omits_permissions_unless_requested() {
+        let mut info = AppInfo {
+            package_name: "com.foo".into(),
+            version_code: "42".into(),
+            version_name: "2.1.0".into(),
+            granted_permissions: None,
+        };
+        let doc = serde_json::to_value(&info).unwrap();
+        assert!(doc.get("granted_permissions").is_none());
+        info.granted_permissions = Some(vec![]);
+        let doc = serde_json::to_value(&info).unwrap();
+        assert_eq!(doc["granted_permissions"], json!([]));
+    }
+}
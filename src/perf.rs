@@ -0,0 +1,284 @@
+//! Per-app performance sampling for `dab perf`: parsers for `dumpsys meminfo`,
+//! `/proc/<pid>/stat` and `dumpsys gfxinfo`, the [`Sample`] they combine
+//! into, and CSV output of the resulting time series.
+
+use serde::Serialize;
+
+/// Kernel clock ticks per second (`USER_HZ`), fixed at 100 on Android.
+const CLOCK_TICKS: f64 = 100.0;
+
+/// The App Summary section of `dumpsys meminfo <package>`, in KB.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MemInfo {
+    pub pss_kb: u64,
+    pub rss_kb: Option<u64>,
+    pub java_heap_kb: u64,
+    pub native_heap_kb: u64,
+    pub code_kb: u64,
+    pub stack_kb: u64,
+    pub graphics_kb: u64,
+    pub private_other_kb: u64,
+    pub system_kb: u64,
+}
+
+pub fn parse_meminfo(dumpsys: &str) -> Option<MemInfo> {
+    let mut mem = MemInfo::default();
+    let mut found = false;
+    for line in dumpsys.lines().map(str::trim) {
+        let first_number = |rest: &str| {
+            rest.split_whitespace()
+                .next()
+                .and_then(|n| n.parse::<u64>().ok())
+        };
+        if let Some(rest) = line.strip_prefix("TOTAL PSS:") {
+            // `TOTAL PSS:  123456   TOTAL RSS:  234567   TOTAL SWAP PSS: 12`
+            mem.pss_kb = first_number(rest)?;
+            mem.rss_kb = rest
+                .split_once("TOTAL RSS:")
+                .and_then(|(_, r)| first_number(r));
+            found = true;
+            continue;
+        }
+        let Some((label, rest)) = line.split_once(':') else {
+            continue;
+        };
+        let slot = match label {
+            "Java Heap" => &mut mem.java_heap_kb,
+            "Native Heap" => &mut mem.native_heap_kb,
+            "Code" => &mut mem.code_kb,
+            "Stack" => &mut mem.stack_kb,
+            "Graphics" => &mut mem.graphics_kb,
+            "Private Other" => &mut mem.private_other_kb,
+            "System" => &mut mem.system_kb,
+            // Older releases print `TOTAL: 123456` instead of `TOTAL PSS:`.
+            "TOTAL" if !found => {
+                mem.pss_kb = first_number(rest)?;
+                found = true;
+                continue;
+            }
+            _ => continue,
+        };
+        if let Some(kb) = first_number(rest) {
+            *slot = kb;
+        }
+    }
+    found.then_some(mem)
+}
+
+/// CPU time and thread count from `/proc/<pid>/stat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcStat {
+    /// utime + stime, in clock ticks.
+    pub cpu_ticks: u64,
+    pub threads: u64,
+}
+
+pub fn parse_proc_stat(stat: &str) -> Option<ProcStat> {
+    // The command name (field 2) may contain spaces; fields resume after `)`.
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |i: usize| fields.get(i)?.parse::<u64>().ok();
+    Some(ProcStat {
+        cpu_ticks: field(11)? + field(12)?,
+        threads: field(17)?,
+    })
+}
+
+/// CPU percent of one core between two `/proc/<pid>/stat` readings
+/// `elapsed_secs` apart, like `top` (can exceed 100 on several cores).
+pub fn cpu_percent(before: ProcStat, after: ProcStat, elapsed_secs: f64) -> f64 {
+    if elapsed_secs <= 0.0 {
+        return 0.0;
+    }
+    let ticks = after.cpu_ticks.saturating_sub(before.cpu_ticks) as f64;
+    round1(ticks / CLOCK_TICKS / elapsed_secs * 100.0)
+}
+
+/// Cumulative frame counters from `dumpsys gfxinfo <package>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    pub frames: u64,
+    pub janky: u64,
+    pub p90_ms: Option<f64>,
+}
+
+pub fn parse_gfxinfo(dumpsys: &str) -> FrameStats {
+    let mut stats = FrameStats::default();
+    for line in dumpsys.lines().map(str::trim) {
+        let number = |rest: &str| {
+            rest.split_whitespace()
+                .next()
+                .map(|n| n.trim_end_matches("ms").to_string())
+        };
+        if let Some(rest) = line.strip_prefix("Total frames rendered:") {
+            stats.frames = number(rest).and_then(|n| n.parse().ok()).unwrap_or(0);
+        } else if let Some(rest) = line.strip_prefix("Janky frames:") {
+            stats.janky = number(rest).and_then(|n| n.parse().ok()).unwrap_or(0);
+        } else if let Some(rest) = line.strip_prefix("90th percentile:") {
+            stats.p90_ms = number(rest).and_then(|n| n.parse().ok());
+        }
+    }
+    stats
+}
+
+/// One point of the time series.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sample {
+    /// Seconds since sampling started.
+    pub t: f64,
+    pub pid: u32,
+    #[serde(flatten)]
+    pub memory: MemInfo,
+    pub cpu_percent: Option<f64>,
+    pub threads: Option<u64>,
+    /// Frames rendered since the previous sample.
+    pub frames: u64,
+    pub janky_frames: u64,
+    pub jank_percent: f64,
+    /// 90th percentile frame time since sampling started.
+    pub frame_p90_ms: Option<f64>,
+}
+
+impl Sample {
+    /// Frame counters for the interval between two cumulative readings.
+    pub fn set_frames(&mut self, before: FrameStats, after: FrameStats) {
+        // gfxinfo starts over when the app restarts.
+        let before = if after.frames < before.frames {
+            FrameStats::default()
+        } else {
+            before
+        };
+        self.frames = after.frames - before.frames;
+        self.janky_frames = after.janky.saturating_sub(before.janky);
+        self.jank_percent = if self.frames == 0 {
+            0.0
+        } else {
+            round1(self.janky_frames as f64 / self.frames as f64 * 100.0)
+        };
+        self.frame_p90_ms = after.p90_ms;
+    }
+}
+
+const CSV_HEADER: &str = "t,pid,pss_kb,rss_kb,java_heap_kb,native_heap_kb,code_kb,stack_kb,graphics_kb,private_other_kb,system_kb,cpu_percent,threads,frames,janky_frames,jank_percent,frame_p90_ms";
+
+pub fn to_csv(samples: &[Sample]) -> String {
+    fn opt<T: ToString>(v: Option<T>) -> String {
+        v.map(|v| v.to_string()).unwrap_or_default()
+    }
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for s in samples {
+        let m = &s.memory;
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            s.t,
+            s.pid,
+            m.pss_kb,
+            opt(m.rss_kb),
+            m.java_heap_kb,
+            m.native_heap_kb,
+            m.code_kb,
+            m.stack_kb,
+            m.graphics_kb,
+            m.private_other_kb,
+            m.system_kb,
+            opt(s.cpu_percent),
+            opt(s.threads),
+            s.frames,
+            s.janky_frames,
+            s.jank_percent,
+            opt(s.frame_p90_ms)
+        ));
+    }
+    csv
+}
+
+pub fn round1(v: f64) -> f64 {
+    (v * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &str = "\
+Applications Memory Usage (in Kilobytes):
+Uptime: 123 Realtime: 123
+
+** MEMINFO in pid 1234 [com.foo] **
+                   Pss  Private  Private  SwapPss      Rss     Heap     Heap     Heap
+                 Total    Dirty    Clean    Dirty    Total     Size    Alloc     Free
+  Native Heap    10240    10200        0        0    12000    16384    12000     4384
+        TOTAL    60000    50000     5000        0    90000
+
+ App Summary
+                       Pss(KB)                        Rss(KB)
+                        ------                         ------
+           Java Heap:     8000                          20000
+         Native Heap:    10200                          12000
+                Code:     5000                          30000
+               Stack:      600                            610
+            Graphics:    12000                          12000
+       Private Other:     3000
+              System:    21200
+             Unknown:                                    1500
+
+           TOTAL PSS:    60000            TOTAL RSS:    90000       TOTAL SWAP PSS:        0
+";
+
+    #[test]
+    fn parses_meminfo_app_summary() {
+        let mem = parse_meminfo(MEMINFO).unwrap();
+        assert_eq!(mem.pss_kb, 60000);
+        assert_eq!(mem.rss_kb, Some(90000));
+        assert_eq!(mem.java_heap_kb, 8000);
+        assert_eq!(mem.native_heap_kb, 10200);
+        assert_eq!(mem.graphics_kb, 12000);
+        assert_eq!(mem.system_kb, 21200);
+        assert!(parse_meminfo("No process found for: com.foo").is_none());
+    }
+
+    #[test]
+    fn parses_proc_stat_and_cpu() {
+        let stat = "1234 (com.foo bar) S 100 100 0 0 -1 1077952832 5000 0 0 0 150 50 0 0 10 -10 42 0 12345";
+        let before = parse_proc_stat(stat).unwrap();
+        assert_eq!(before.cpu_ticks, 200);
+        assert_eq!(before.threads, 42);
+        let after = ProcStat {
+            cpu_ticks: 250,
+            threads: 42,
+        };
+        assert_eq!(cpu_percent(before, after, 2.0), 25.0);
+    }
+
+    #[test]
+    fn parses_gfxinfo_and_frame_deltas() {
+        let gfx = "Stats since: 123ns\nTotal frames rendered: 300\nJanky frames: 30 (10.00%)\n50th percentile: 8ms\n90th percentile: 17ms\n";
+        let after = parse_gfxinfo(gfx);
+        assert_eq!(after.frames, 300);
+        assert_eq!(after.janky, 30);
+        assert_eq!(after.p90_ms, Some(17.0));
+        let mut sample = Sample {
+            t: 1.0,
+            pid: 1,
+            memory: MemInfo::default(),
+            cpu_percent: None,
+            threads: None,
+            frames: 0,
+            janky_frames: 0,
+            jank_percent: 0.0,
+            frame_p90_ms: None,
+        };
+        let before = FrameStats {
+            frames: 200,
+            janky: 25,
+            p90_ms: None,
+        };
+        sample.set_frames(before, after);
+        assert_eq!((sample.frames, sample.janky_frames), (100, 5));
+        assert_eq!(sample.jank_percent, 5.0);
+
+        let csv = to_csv(&[sample]);
+        assert!(csv.starts_with("t,pid,pss_kb"));
+        assert_eq!(csv.lines().nth(1).unwrap().split(',').count(), 17);
+    }
+}
@@ -0,0 +1,106 @@
+//! Interactive prompts, and the guard that keeps them closed under `--no-input`.
+use super::device::Device;
+use super::error::{DabError, ErrorCode};
+use super::fuzzy;
+use super::render::Render;
+use anyhow::Result;
+use inquire::{MultiSelect, Select, Text};
+use std::io::IsTerminal;
+
+/// The `INPUT_REQUIRED` error for a prompt that `flag` would have answered.
+pub fn input_required(prompt: &'static str, flag: &'static str) -> DabError {
+    DabError::new(
+        ErrorCode::InputRequired,
+        format!(
+            "Missing {flag}: cannot prompt for {prompt} selection under --no-input or without a TTY"
+        ),
+    )
+    .with_hint(format!("Pass {flag} explicitly."))
+    .with_detail("prompt", prompt)
+    .with_detail("flag", flag)
+}
+
+/// Opens `inquire` prompts only when a human can answer them.
+pub struct Prompter {
+    interactive: bool,
+}
+
+impl Prompter {
+    /// Prompts are disabled by `--no-input` or when stdin is not a TTY.
+    pub fn new(no_input: bool) -> Self {
+        Self {
+            interactive: !no_input && std::io::stdin().is_terminal(),
+        }
+    }
+
+    /// A prompter that never prompts, for unattended runs such as fan-out.
+    pub fn disabled() -> Self {
+        Self { interactive: false }
+    }
+
+    /// Fail fast with [`input_required`] when prompts are disabled.
+    pub fn require(&self, prompt: &'static str, flag: &'static str) -> Result<()> {
+        if self.interactive {
+            Ok(())
+        } else {
+            Err(input_required(prompt, flag).into())
+        }
+    }
+
+    /// Device picker showing each device's summary; returns the serial.
+    pub fn device(&self, devices: Vec<Device>) -> Result<String> {
+        self.require("device", "--device")?;
+        let labels: Vec<String> = devices.iter().map(Device::render).collect();
+        let choice = Select::new("Select device:", labels).raw_prompt()?;
+        Ok(devices[choice.index].serial.clone())
+    }
+
+    /// App picker, ranked by the same scorer as `--package`.
+    pub fn app(&self, packages: Vec<String>) -> Result<String> {
+        self.require("app", "--package")?;
+        Ok(Select::new("Select app:", packages)
+            .with_scorer(&|input, _, value, _| fuzzy::score(input, value))
+            .with_page_size(15)
+            .prompt()?)
+    }
+
+    pub fn permissions(&self, message: &str, options: Vec<&'static str>) -> Result<Vec<String>> {
+        self.require("permission", "--permissions")?;
+        let selected = MultiSelect::new(message, options)
+            .with_page_size(15)
+            .prompt()?;
+        Ok(selected.iter().map(|s| s.to_string()).collect())
+    }
+
+    pub fn action(&self, options: Vec<&'static str>) -> Result<&'static str> {
+        self.require("command", "a subcommand")?;
+        Ok(Select::new("Select action:", options).prompt()?)
+    }
+
+    /// Deep link picker; returns the index of the chosen label.
+    pub fn deep_link(&self, labels: Vec<String>) -> Result<usize> {
+        if !self.interactive {
+            return Err(DabError::new(
+                ErrorCode::InputRequired,
+                "`deeplinks --open` prompts for the link to open, which needs a TTY and no --no-input",
+            )
+            .with_hint("List the links with `dab deeplinks`, then open one with `dab launch <url>`.")
+            .into());
+        }
+        let choice = Select::new("Select deep link:", labels)
+            .with_page_size(15)
+            .raw_prompt()?;
+        Ok(choice.index)
+    }
+
+    /// Ask for the value of a deep link's `{name}` placeholder, offering
+    /// `sample` as the default. Only asked after [`Self::deep_link`].
+    pub fn placeholder(&self, name: &str, url: &str, sample: &str) -> Result<String> {
+        let message = format!("{{{}}} in {}:", name, url);
+        let mut prompt = Text::new(&message);
+        if !sample.is_empty() {
+            prompt = prompt.with_default(sample);
+        }
+        Ok(prompt.prompt()?)
+    }
+}
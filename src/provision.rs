@@ -0,0 +1,492 @@
+//! Declarative device setup for `dab provision`: the profile format and the
+//! plan that brings a device in line with it.
+//!
+//! Planning reads the device first — installed packages and versions,
+//! granted permissions, current settings — so applying a plan only touches
+//! what differs, and running the same profile twice changes nothing the
+//! second time.
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::error::{DabError, ErrorCode};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A `dab provision` profile, in YAML or TOML. Every section is optional.
+///
+/// ```yaml
+/// uninstall: [com.example.legacy]
+/// disable: [com.android.chrome]
+/// install:
+///   - builds/app-debug.apk
+/// permissions:
+///   com.example.app: [android.permission.CAMERA]
+/// settings:
+///   global:
+///     animator_duration_scale: 0
+/// open: com.example.app
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Packages to remove.
+    pub uninstall: Vec<String>,
+    /// Packages to disable for the default user.
+    pub disable: Vec<String>,
+    /// APK, XAPK or APKM files; relative paths are relative to the profile.
+    pub install: Vec<PathBuf>,
+    /// Runtime permissions to grant, per package.
+    pub permissions: BTreeMap<String, Vec<String>>,
+    /// `settings put` values by namespace.
+    #[serde(deserialize_with = "scalar_settings")]
+    pub settings: BTreeMap<Namespace, BTreeMap<String, String>>,
+    /// Package to open once everything else is applied.
+    pub open: Option<String>,
+    /// URL or deep link to launch once everything else is applied.
+    pub launch: Option<String>,
+}
+
+/// A `settings` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Namespace {
+    Global,
+    Secure,
+    System,
+}
+
+impl Namespace {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Namespace::Global => "global",
+            Namespace::Secure => "secure",
+            Namespace::System => "system",
+        }
+    }
+}
+
+/// Settings values as `settings put` takes them, so `animator_duration_scale:
+/// 0` needn't be quoted.
+fn scalar_settings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<BTreeMap<Namespace, BTreeMap<String, String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scalar {
+        Bool(bool),
+        Int(i64),
+        Float(f64),
+        Text(String),
+    }
+    let raw = BTreeMap::<Namespace, BTreeMap<String, Scalar>>::deserialize(deserializer)?;
+    Ok(raw
+        .into_iter()
+        .map(|(namespace, values)| {
+            let values = values
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Scalar::Bool(b) => b.to_string(),
+                        Scalar::Int(i) => i.to_string(),
+                        Scalar::Float(f) => f.to_string(),
+                        Scalar::Text(s) => s,
+                    };
+                    (key, value)
+                })
+                .collect();
+            (namespace, values)
+        })
+        .collect())
+}
+
+impl Profile {
+    /// Read a profile, as TOML for `.toml` files and YAML otherwise.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Err(anyhow!("File does not exist: {}", path.display()));
+        }
+        let text = std::fs::read_to_string(path)?;
+        let is_toml = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("toml"));
+        let mut profile = if is_toml {
+            Self::from_toml(&text)
+        } else {
+            Self::from_yaml(&text)
+        }
+        .map_err(|e| {
+            DabError::new(
+                ErrorCode::InvalidProfile,
+                format!("Invalid profile {}: {}", path.display(), e.message),
+            )
+        })?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        for file in &mut profile.install {
+            if file.is_relative() {
+                *file = base.join(&*file);
+            }
+        }
+        Ok(profile)
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Self, DabError> {
+        // An empty file is an empty profile, not a parse error.
+        if text.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(text).map_err(invalid)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, DabError> {
+        toml::from_str(text).map_err(invalid)
+    }
+}
+
+fn invalid(err: impl std::fmt::Display) -> DabError {
+    DabError::new(
+        ErrorCode::InvalidProfile,
+        err.to_string().trim().to_string(),
+    )
+}
+
+/// One change `dab provision` can make.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Step {
+    Uninstall {
+        package: String,
+    },
+    Disable {
+        package: String,
+    },
+    Install {
+        file: PathBuf,
+        package: String,
+        version_code: String,
+        /// What's on the device now; `None` when it isn't installed.
+        installed_version_code: Option<String>,
+    },
+    Grant {
+        package: String,
+        permissions: Vec<String>,
+    },
+    Setting {
+        namespace: Namespace,
+        key: String,
+        value: String,
+        current: Option<String>,
+    },
+    Open {
+        package: String,
+    },
+    Launch {
+        url: String,
+    },
+}
+
+impl Step {
+    pub fn apply(&self, adb_client: &AdbClient<impl AdbBackend>, device: &str) -> Result<()> {
+        match self {
+            Step::Uninstall { package } => adb_client.uninstall_app(device, package),
+            Step::Disable { package } => adb_client.disable_app(device, package),
+            Step::Install { file, .. } => adb_client.install_file(device, file).map(drop),
+            Step::Grant {
+                package,
+                permissions,
+            } => {
+                let perm_refs: Vec<&str> = permissions.iter().map(|s| s.as_str()).collect();
+                adb_client.grant_permissions(device, package, &perm_refs)
+            }
+            Step::Setting {
+                namespace,
+                key,
+                value,
+                ..
+            } => adb_client.put_setting(device, namespace.as_str(), key, value),
+            Step::Open { package } => adb_client.open_app(device, package),
+            Step::Launch { url } => adb_client.launch_url(device, url),
+        }
+    }
+}
+
+/// What applying a profile to one device would do.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    pub device: String,
+    /// Steps that change the device, in the order they are applied:
+    /// uninstall, disable, install, grant, settings, then open and launch.
+    pub changes: Vec<Step>,
+    /// Profile entries the device already satisfies.
+    pub unchanged: Vec<Step>,
+}
+
+impl Plan {
+    fn add(&mut self, needed: bool, step: Step) {
+        if needed {
+            self.changes.push(step);
+        } else {
+            self.unchanged.push(step);
+        }
+    }
+}
+
+/// Compare `profile` against the device and plan the difference. Nothing on
+/// the device changes.
+pub fn plan(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    profile: &Profile,
+) -> Result<Plan> {
+    let installed: HashSet<String> = adb_client
+        .get_installed_apps(device)?
+        .apps
+        .into_iter()
+        .collect();
+    // Installed and staying that way, so its current state carries over.
+    let kept = |package: &str| {
+        installed.contains(package) && !profile.uninstall.iter().any(|p| p == package)
+    };
+    let mut plan = Plan {
+        device: device.to_string(),
+        changes: Vec::new(),
+        unchanged: Vec::new(),
+    };
+
+    for package in &profile.uninstall {
+        plan.add(
+            installed.contains(package),
+            Step::Uninstall {
+                package: package.clone(),
+            },
+        );
+    }
+
+    if !profile.disable.is_empty() {
+        let disabled: HashSet<String> = adb_client
+            .get_disabled_packages(device)?
+            .into_iter()
+            .collect();
+        for package in &profile.disable {
+            plan.add(
+                kept(package) && !disabled.contains(package),
+                Step::Disable {
+                    package: package.clone(),
+                },
+            );
+        }
+    }
+
+    let mut incoming = HashSet::new();
+    for file in &profile.install {
+        let apk = adb_client.analyze_local_file(file)?;
+        let installed_version_code = if kept(&apk.package_name) {
+            Some(
+                adb_client
+                    .get_app_info(device, &apk.package_name, false)?
+                    .version_code,
+            )
+        } else {
+            None
+        };
+        incoming.insert(apk.package_name.clone());
+        plan.add(
+            installed_version_code.as_deref() != Some(apk.version_code.as_str()),
+            Step::Install {
+                file: file.clone(),
+                package: apk.package_name,
+                version_code: apk.version_code,
+                installed_version_code,
+            },
+        );
+    }
+
+    let available = |package: &str| -> Result<()> {
+        if kept(package) || incoming.contains(package) {
+            return Ok(());
+        }
+        Err(DabError::new(
+            ErrorCode::InvalidProfile,
+            format!(
+                "{} is not installed on {} and not in the profile's `install` list",
+                package, device
+            ),
+        )
+        .with_detail("package", package)
+        .into())
+    };
+
+    for (package, permissions) in &profile.permissions {
+        available(package)?;
+        let granted = if kept(package) {
+            adb_client
+                .get_app_info(device, package, true)?
+                .granted_permissions
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let (have, missing): (Vec<String>, Vec<String>) = permissions
+            .iter()
+            .cloned()
+            .partition(|p| granted.contains(p));
+        if !have.is_empty() {
+            plan.add(
+                false,
+                Step::Grant {
+                    package: package.clone(),
+                    permissions: have,
+                },
+            );
+        }
+        if !missing.is_empty() {
+            plan.add(
+                true,
+                Step::Grant {
+                    package: package.clone(),
+                    permissions: missing,
+                },
+            );
+        }
+    }
+
+    for (namespace, values) in &profile.settings {
+        for (key, value) in values {
+            let current = adb_client.get_setting(device, namespace.as_str(), key)?;
+            plan.add(
+                current.as_ref() != Some(value),
+                Step::Setting {
+                    namespace: *namespace,
+                    key: key.clone(),
+                    value: value.clone(),
+                    current,
+                },
+            );
+        }
+    }
+
+    if let Some(package) = &profile.open {
+        available(package)?;
+        plan.add(
+            true,
+            Step::Open {
+                package: package.clone(),
+            },
+        );
+    }
+    if let Some(url) = &profile.launch {
+        plan.add(true, Step::Launch { url: url.clone() });
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Replayer, Transcript};
+    use serde_json::{json, Value};
+
+    fn run_ok(args: &[&str], stdout: &str) -> Value {
+        json!({ "call": "run", "args": args, "ok": { "success": true, "stdout": stdout, "stderr": "" } })
+    }
+
+    #[test]
+    fn plans_only_what_differs() {
+        let transcript: Transcript = serde_json::from_value(json!({ "exchanges": [
+            { "call": "list_packages", "args": ["emu"], "ok": ["com.foo", "com.bloat", "com.old"] },
+            run_ok(&["-s", "emu", "shell", "pm", "list", "packages", "-d"], "package:com.bloat\n"),
+            run_ok(
+                &["-s", "emu", "shell", "pm", "dump", "com.foo"],
+                "versionCode=7\n  android.permission.CAMERA: granted=true\n",
+            ),
+            run_ok(&["-s", "emu", "shell", "settings", "get", "global", "animator_duration_scale"], "0\n"),
+            run_ok(&["-s", "emu", "shell", "settings", "get", "global", "stay_on_while_plugged_in"], "null\n"),
+        ]}))
+        .unwrap();
+        let adb_client = AdbClient::with_backend(Replayer::new(transcript));
+        let profile = Profile::from_yaml(
+            "\
+uninstall: [com.old, com.gone]
+disable: [com.bloat]
+permissions:
+  com.foo: [android.permission.CAMERA, android.permission.RECORD_AUDIO]
+settings:
+  global:
+    animator_duration_scale: 0
+    stay_on_while_plugged_in: 3
+",
+        )
+        .unwrap();
+        let plan = plan(&adb_client, "emu", &profile).unwrap();
+        assert!(adb_client.backend().unused().is_empty());
+        assert_eq!(
+            serde_json::to_value(&plan.changes).unwrap(),
+            json!([
+                { "action": "uninstall", "package": "com.old" },
+                { "action": "grant", "package": "com.foo", "permissions": ["android.permission.RECORD_AUDIO"] },
+                { "action": "setting", "namespace": "global", "key": "stay_on_while_plugged_in",
+                  "value": "3", "current": null },
+            ])
+        );
+        assert_eq!(plan.unchanged.len(), 4);
+    }
+
+    #[test]
+    fn yaml_and_toml_profiles_agree() {
+        let yaml = Profile::from_yaml(
+            "\
+install: [app.apk]
+permissions:
+  com.foo: [android.permission.CAMERA]
+settings:
+  global:
+    animator_duration_scale: 0.5
+    stay_on_while_plugged_in: 3
+  secure:
+    location_mode: off
+open: com.foo
+",
+        )
+        .unwrap();
+        let toml = Profile::from_toml(
+            r#"
+install = ["app.apk"]
+open = "com.foo"
+
+[permissions]
+"com.foo" = ["android.permission.CAMERA"]
+
+[settings.global]
+animator_duration_scale = 0.5
+stay_on_while_plugged_in = 3
+
+[settings.secure]
+location_mode = "off"
+"#,
+        )
+        .unwrap();
+        for profile in [yaml, toml] {
+            assert_eq!(profile.install, [PathBuf::from("app.apk")]);
+            assert_eq!(profile.open.as_deref(), Some("com.foo"));
+            assert_eq!(
+                profile.settings[&Namespace::Global]["animator_duration_scale"],
+                "0.5"
+            );
+            assert_eq!(
+                profile.settings[&Namespace::Global]["stay_on_while_plugged_in"],
+                "3"
+            );
+            assert_eq!(profile.settings[&Namespace::Secure]["location_mode"], "off");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_sections_and_namespaces() {
+        let err = Profile::from_yaml("instal: [app.apk]").unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidProfile);
+        assert!(err.message.contains("instal"));
+        assert!(Profile::from_toml("[settings.vendor]\nfoo = 1").is_err());
+        assert!(Profile::from_yaml("").unwrap().install.is_empty());
+    }
+}
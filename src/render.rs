@@ -0,0 +1,570 @@
+//! Colored terminal rendering of the typed results `dab_cli` returns.
+//!
+//! This is the text counterpart of `--json`: both render the same value, one
+//! through [`Render`], the other through `Serialize`, so the two outputs
+//! can't disagree about what a command found.
+
+use colored::*;
+use dab_cli::activity::ActivityStack;
+use dab_cli::crash::Incident;
+use dab_cli::deeplink::DeepLinks;
+use dab_cli::device::Device;
+use dab_cli::instrumentation::{TestCase, TestRun, TestStatus};
+use dab_cli::logcat::{LogEntry, LogLevel};
+use dab_cli::model::{
+    ApkInfo, AppInfo, CrashReport, DeviceHealth, DeviceInfo, DeviceList, InstalledApps,
+    NetworkInfo, SavedIncident,
+};
+use dab_cli::perf::Sample;
+use dab_cli::provision::{Plan, Step};
+use dab_cli::ui::{Hierarchy, Match, Node};
+
+/// Human-readable form of a result, without a trailing newline.
+pub trait Render {
+    fn render(&self) -> String;
+}
+
+fn or_na(value: &Option<String>) -> ColoredString {
+    value.as_deref().unwrap_or("N/A").green()
+}
+
+/// `Granted Permissions:` style list, with `None` in red when empty.
+fn list(out: &mut Vec<String>, label: &str, items: &[String]) {
+    out.push(format!("{}:", label.cyan()));
+    if items.is_empty() {
+        out.push(format!("  {}", "None".red()));
+    }
+    for item in items {
+        out.push(format!("  {}", item.blue()));
+    }
+}
+
+impl Render for Device {
+    /// One line for listings and the device picker.
+    fn render(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if let Some(model) = self.display_model() {
+            parts.push(model.bold().to_string());
+        }
+        if let Some(product) = &self.product {
+            parts.push(format!("({})", product).dimmed().to_string());
+        }
+        parts.push(self.transport.to_string().cyan().to_string());
+        if let Some(version) = &self.android_version {
+            parts.push(format!("Android {}", version));
+        }
+        if let Some(level) = self.battery_level {
+            let battery = format!("{}%", level);
+            parts.push(if level < 20 {
+                battery.red().to_string()
+            } else {
+                battery.green().to_string()
+            });
+        }
+        let state = if self.is_ready() {
+            self.state.green()
+        } else {
+            self.state.red().bold()
+        };
+        format!("{}  {}  {}", self.serial, state, parts.join(" · "))
+    }
+}
+
+impl Render for DeviceList {
+    fn render(&self) -> String {
+        let mut out = vec!["Connected devices:".bold().yellow().to_string()];
+        for d in &self.devices {
+            out.push(format!("  {}", d.render()));
+            if let Some(hint) = d.hint {
+                out.push(format!("    {} {}", "Hint:".yellow(), hint));
+            }
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for InstalledApps {
+    fn render(&self) -> String {
+        let mut out = vec!["Installed apps:".bold().yellow().to_string()];
+        out.extend(self.apps.iter().map(|app| format!("  {}", app)));
+        out.join("\n")
+    }
+}
+
+impl Render for AppInfo {
+    fn render(&self) -> String {
+        let mut out = vec![
+            "\nApp Info".bold().underline().yellow().to_string(),
+            format!("{}: {}", "Package Name".cyan(), self.package_name.green()),
+            format!("{}: {}", "Version Code".cyan(), self.version_code.green()),
+            format!("{}: {}", "Version Name".cyan(), self.version_name.green()),
+        ];
+        if let Some(granted) = &self.granted_permissions {
+            list(&mut out, "Granted Permissions", granted);
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for DeviceInfo {
+    fn render(&self) -> String {
+        let rows: [(&str, &Option<String>); 13] = [
+            ("Model", &self.model),
+            ("Manufacturer", &self.manufacturer),
+            ("Brand", &self.brand),
+            ("Device", &self.device),
+            ("Name", &self.name),
+            ("Android Version", &self.android_version),
+            ("SDK", &self.sdk),
+            ("Codename", &self.codename),
+            ("Board", &self.board),
+            ("CPU ABI", &self.cpu_abi),
+            ("Locale", &self.locale),
+            ("Build ID", &self.build_id),
+            ("Security Patch", &self.security_patch),
+        ];
+        let mut out = vec![format!("\n{}", "Device Info".bold().underline().yellow())];
+        for (label, value) in rows {
+            if let Some(v) = value {
+                out.push(format!("{:<18}: {}", label.cyan(), v.green()));
+            }
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for NetworkInfo {
+    fn render(&self) -> String {
+        let mut out = vec![format!(
+            "\n{}",
+            "Network Interfaces (IP addresses)"
+                .bold()
+                .underline()
+                .yellow()
+        )];
+        for ip in &self.ip_addresses {
+            out.push(format!("{} {}", "IP Address:".cyan(), ip.green()));
+        }
+        out.push(format!("\n{}", "WiFi Info".bold().underline().yellow()));
+        out.push(format!("{} {}", "SSID:".cyan(), or_na(&self.ssid)));
+        out.join("\n")
+    }
+}
+
+impl Render for DeviceHealth {
+    fn render(&self) -> String {
+        let mut out = vec![
+            format!("\n{}", "Device Health Check".bold().underline().yellow()),
+            format!(
+                "{} {}% (Status: {})",
+                "Battery:".cyan(),
+                or_na(&self.battery.level),
+                or_na(&self.battery.status)
+            ),
+        ];
+        if let Some(s) = &self.storage {
+            out.push(format!(
+                "{} Used: {:.2} GB ({:.1}%) / Total: {:.2} GB | Free: {:.2} GB",
+                "Storage:".cyan(),
+                s.used_gb,
+                s.percent_used,
+                s.total_gb,
+                s.free_gb
+            ));
+        }
+        out.push(format!(
+            "{} {:.2} GB free / {:.2} GB total",
+            "RAM:".cyan(),
+            self.ram.free_gb,
+            self.ram.total_gb
+        ));
+        out.push(format!(
+            "{} {} (SSID: {})",
+            "Network:".cyan(),
+            or_na(&self.network.ip),
+            or_na(&self.network.ssid)
+        ));
+        out.join("\n")
+    }
+}
+
+impl Render for ApkInfo {
+    fn render(&self) -> String {
+        let mut out = vec![
+            "\nAPK File Analysis"
+                .bold()
+                .underline()
+                .yellow()
+                .to_string(),
+            format!("{}: {}", "Package Name".cyan(), self.package_name.green()),
+            format!("{}: {}", "App Name".cyan(), self.app_name.green()),
+            format!("{}: {}", "Version Code".cyan(), self.version_code.green()),
+            format!("{}: {}", "Version Name".cyan(), self.version_name.green()),
+        ];
+        list(&mut out, "Permissions Requested", &self.permissions);
+        out.join("\n")
+    }
+}
+
+impl Render for Incident {
+    fn render(&self) -> String {
+        let mut out = vec![format!(
+            "{} {} {} {}",
+            self.kind.label().red().bold(),
+            self.package.cyan(),
+            self.pid.map(|p| format!("(pid {})", p)).unwrap_or_default(),
+            self.time.dimmed()
+        )];
+        if !self.summary.is_empty() {
+            out.push(format!("  {}", self.summary.yellow()));
+        }
+        for line in &self.trace {
+            out.push(format!("    {}", line.dimmed()));
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for SavedIncident {
+    fn render(&self) -> String {
+        match &self.saved {
+            Some(path) => format!(
+                "{}\n{} {}\n",
+                self.incident.render(),
+                "Saved to".green(),
+                path.display()
+            ),
+            None => format!("{}\n", self.incident.render()),
+        }
+    }
+}
+
+impl Render for CrashReport {
+    fn render(&self) -> String {
+        if self.incidents.is_empty() {
+            return format!(
+                "No crashes or ANRs for {} in the log buffers.",
+                self.package.green()
+            );
+        }
+        let incidents: Vec<String> = self.incidents.iter().map(Render::render).collect();
+        incidents.join("\n")
+    }
+}
+
+fn paint(level: LogLevel, text: &str) -> ColoredString {
+    match level {
+        LogLevel::Verbose => text.dimmed(),
+        LogLevel::Debug => text.blue(),
+        LogLevel::Info => text.green(),
+        LogLevel::Warn => text.yellow(),
+        LogLevel::Error => text.red(),
+        LogLevel::Fatal => text.red().bold(),
+    }
+}
+
+impl Render for LogEntry {
+    fn render(&self) -> String {
+        let time = self.time.split_once(' ').map_or(&*self.time, |(_, t)| t);
+        format!(
+            "{} {} {} {}",
+            time.dimmed(),
+            paint(self.level, self.level.letter()).bold(),
+            paint(self.level, &self.tag),
+            self.message
+        )
+    }
+}
+
+impl Render for TestCase {
+    fn render(&self) -> String {
+        let name = format!("{}#{}", self.class, self.name);
+        let duration = format!("({:.2}s)", self.duration_secs).dimmed();
+        match self.status {
+            TestStatus::Passed => format!("  {} {} {}", "✔".green(), name, duration),
+            TestStatus::Ignored => format!("  {} {} {}", "↷".yellow(), name, "ignored".yellow()),
+            TestStatus::AssumptionFailed => {
+                let reason = self.stack.as_deref().and_then(|s| s.lines().next());
+                format!(
+                    "  {} {} {} {}",
+                    "↷".yellow(),
+                    name,
+                    "assumption failed".yellow(),
+                    reason.unwrap_or_default().dimmed()
+                )
+            }
+            TestStatus::Failed => {
+                let mut out = vec![format!("  {} {} {}", "✘".red(), name.red(), duration)];
+                for line in self.stack.iter().flat_map(|s| s.lines()) {
+                    out.push(format!("      {}", line.dimmed()));
+                }
+                out.join("\n")
+            }
+        }
+    }
+}
+
+impl Render for TestRun {
+    /// The totals line, plus the run error if the run broke.
+    fn render(&self) -> String {
+        let mut out = format!(
+            "\n{} {} total, {} passed, {} failed, {} skipped in {:.1}s",
+            "Tests:".bold(),
+            self.tests.len(),
+            self.count(TestStatus::Passed).to_string().green(),
+            self.count(TestStatus::Failed).to_string().red(),
+            self.skipped().to_string().yellow(),
+            self.duration_secs
+        );
+        if let Some(err) = &self.run_error {
+            out.push_str(&format!("\n{} {}", "Run error:".red().bold(), err));
+        }
+        out
+    }
+}
+
+impl Render for Sample {
+    fn render(&self) -> String {
+        format!(
+            "  {:>6}  PSS {:>9}  RSS {:>9}  CPU {:>6}  threads {:>3}  frames {:>4}  jank {}",
+            format!("{}s", self.t).dimmed(),
+            format_kb(self.memory.pss_kb).bold(),
+            self.memory.rss_kb.map_or("-".to_string(), format_kb),
+            self.cpu_percent
+                .map_or("-".to_string(), |c| format!("{}%", c)),
+            self.threads.map_or("-".to_string(), |t| t.to_string()),
+            self.frames,
+            if self.jank_percent > 10.0 {
+                format!("{}%", self.jank_percent).red()
+            } else {
+                format!("{}%", self.jank_percent).normal()
+            }
+        )
+    }
+}
+
+impl Render for Step {
+    fn render(&self) -> String {
+        match self {
+            Step::Uninstall { package } => format!("uninstall {}", package.cyan()),
+            Step::Disable { package } => format!("disable {}", package.cyan()),
+            Step::Install {
+                file,
+                package,
+                version_code,
+                installed_version_code,
+            } => {
+                let version = match installed_version_code {
+                    Some(installed) if installed != version_code => {
+                        format!("{} → {}", installed, version_code)
+                    }
+                    _ => version_code.clone(),
+                };
+                format!(
+                    "install {} ({} {})",
+                    file.display(),
+                    package.cyan(),
+                    version
+                )
+            }
+            Step::Grant {
+                package,
+                permissions,
+            } => format!("grant {}: {}", package.cyan(), permissions.join(", ")),
+            Step::Setting {
+                namespace,
+                key,
+                value,
+                current,
+            } => {
+                let mut out = format!("set {} {} = {}", namespace.as_str(), key, value.green());
+                if let Some(current) = current.as_ref().filter(|c| *c != value) {
+                    out.push_str(&format!(" (was {})", current));
+                }
+                out
+            }
+            Step::Open { package } => format!("open {}", package.cyan()),
+            Step::Launch { url } => format!("launch {}", url.cyan()),
+        }
+    }
+}
+
+impl Render for Plan {
+    /// Changes marked `+`, entries already in place dimmed and marked `=`.
+    fn render(&self) -> String {
+        let mut out = vec![format!(
+            "{} {}",
+            "Provisioning plan for".bold().yellow(),
+            self.device.cyan()
+        )];
+        for step in &self.changes {
+            out.push(format!("  {} {}", "+".green(), step.render()));
+        }
+        for step in &self.unchanged {
+            out.push(format!("  {}", format!("= {}", step.render()).dimmed()));
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for ActivityStack {
+    /// The resumed activity and its fragments, then each task's back stack
+    /// with the resumed activity marked `▶`.
+    fn render(&self) -> String {
+        let mut out = Vec::new();
+        match &self.resumed {
+            Some(resumed) => {
+                let task = self
+                    .task_id
+                    .map(|id| format!(" (task {})", id))
+                    .unwrap_or_default();
+                out.push(format!(
+                    "{} {}{}",
+                    "Resumed:".bold().yellow(),
+                    resumed.green(),
+                    task
+                ));
+            }
+            None => out.push(format!(
+                "{} {}",
+                "Resumed:".bold().yellow(),
+                "none (screen off or locked?)".red()
+            )),
+        }
+        if !self.fragments.is_empty() {
+            out.push(format!(
+                "{} {}",
+                "Fragments:".cyan(),
+                self.fragments.join(" › ")
+            ));
+        }
+        if !self.tasks.is_empty() {
+            out.push(format!("{}", "Back stack:".cyan()));
+        }
+        for task in &self.tasks {
+            out.push(format!("  {}", format!("Task {}", task.id).bold()));
+            for activity in &task.activities {
+                if Some(activity) == self.resumed.as_ref() {
+                    out.push(format!("    {} {}", "▶".green(), activity.green()));
+                } else {
+                    out.push(format!("      {}", activity));
+                }
+            }
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for DeepLinks {
+    /// Links grouped by activity, with `--verify` results marked ✔ or ✘.
+    fn render(&self) -> String {
+        if self.links.is_empty() {
+            return format!("{} declares no deep links", self.package.cyan());
+        }
+        let mut out = vec![format!(
+            "{} {} ({})",
+            "Deep links for".bold().yellow(),
+            self.package.cyan(),
+            self.links.len()
+        )];
+        let mut activities: Vec<&str> = Vec::new();
+        for link in &self.links {
+            if !activities.contains(&link.activity.as_str()) {
+                activities.push(&link.activity);
+            }
+        }
+        for activity in activities {
+            out.push(format!("  {}", activity.bold()));
+            for link in self.links.iter().filter(|l| l.activity == activity) {
+                let mut line = format!("    {}", link.url.green());
+                if link.auto_verify {
+                    line.push_str(&format!(" {}", "(autoVerify)".dimmed()));
+                }
+                if let Some(verification) = &link.verification {
+                    match verification.problem() {
+                        None => line.push_str(&format!(" {}", "✔".green())),
+                        Some(problem) => line.push_str(&format!(
+                            " {} {}: {}",
+                            "✘".red(),
+                            verification.url,
+                            problem.red()
+                        )),
+                    }
+                }
+                out.push(line);
+            }
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for Node {
+    /// One line: class, text, id, description, bounds and the interesting
+    /// flags, without the children.
+    fn render(&self) -> String {
+        let mut out = vec![self.short_class().bold().to_string()];
+        if !self.text.is_empty() {
+            out.push(format!("\"{}\"", self.text).green().to_string());
+        }
+        if !self.resource_id.is_empty() {
+            let id = self
+                .resource_id
+                .split_once(":id/")
+                .map_or(self.resource_id.as_str(), |(_, name)| name);
+            out.push(format!("#{}", id).cyan().to_string());
+        }
+        if !self.content_desc.is_empty() {
+            out.push(format!("({})", self.content_desc).yellow().to_string());
+        }
+        let b = &self.bounds;
+        out.push(
+            format!("[{},{}][{},{}]", b.left, b.top, b.right, b.bottom)
+                .dimmed()
+                .to_string(),
+        );
+        let flags = [
+            (self.clickable, "clickable"),
+            (self.scrollable, "scrollable"),
+            (self.checked, "checked"),
+            (self.focused, "focused"),
+            (self.selected, "selected"),
+            (!self.enabled, "disabled"),
+        ];
+        for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+            out.push(flag.dimmed().to_string());
+        }
+        out.join(" ")
+    }
+}
+
+impl Render for Hierarchy {
+    /// Every node, indented under its parent.
+    fn render(&self) -> String {
+        fn walk(node: &Node, depth: usize, out: &mut Vec<String>) {
+            out.push(format!("{}{}", "  ".repeat(depth), node.render()));
+            for child in &node.children {
+                walk(child, depth + 1, out);
+            }
+        }
+        let mut out = Vec::new();
+        for node in &self.nodes {
+            walk(node, 0, &mut out);
+        }
+        out.join("\n")
+    }
+}
+
+impl Render for Match {
+    fn render(&self) -> String {
+        format!(
+            "{} {}",
+            self.node.render(),
+            format!("→ {},{}", self.center.x, self.center.y).cyan()
+        )
+    }
+}
+
+/// `123.4 MB` for a size in KB.
+pub fn format_kb(kb: u64) -> String {
+    format!("{:.1} MB", kb as f64 / 1024.0)
+}
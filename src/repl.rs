@@ -0,0 +1,529 @@
+//! `dab shell-mode`: a REPL that keeps the device and app in context.
+//!
+//! Every line is a dab command without the `dab` prefix (`open`, `clear`,
+//! `logcat --level warn`, ...). Commands that take `--package` default to the
+//! app picked with `use`, and all of them run on the device picked with
+//! `device`, so neither has to be chosen again for every action.
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::cli::{Cli, Commands};
+use super::error::{DabError, ErrorCode};
+use super::prompt::Prompter;
+use super::render::Render;
+use anyhow::Result;
+use clap::{CommandFactory, Parser};
+use colored::*;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::PathBuf;
+
+/// Shell-only commands, completed alongside dab's own subcommands.
+const BUILTINS: &[&str] = &["use", "device", "help", "exit", "quit"];
+
+/// Shorthands for the everyday actions, expanded before parsing.
+const SHORTHANDS: &[(&str, &[&str])] = &[
+    ("info", &["app-info"]),
+    ("perms", &["app-info", "--all"]),
+    ("logs", &["logcat"]),
+    ("kill", &["force-kill"]),
+];
+
+/// Subcommands that can't run inside the shell.
+const UNSUPPORTED: &[&str] = &["shell-mode", "tui", "mcp", "serve", "record", "perf"];
+
+/// One parsed input line.
+enum Line {
+    Empty,
+    Exit,
+    Help,
+    /// `use [query]`: switch app, picking from a list without a query.
+    Use(Option<String>),
+    /// `device <serial>`: switch device.
+    Device(String),
+    Command(Commands),
+}
+
+/// Run the shell on `device` until `exit` or Ctrl+D. `execute` runs one
+/// command in text mode, exactly as `dab <command> --device <serial>` would.
+pub fn run(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: String,
+    prompter: &Prompter,
+    execute: impl Fn(&str, &Commands) -> Result<()>,
+) -> Result<()> {
+    // Ctrl+C while a command runs stops its adb child (ending `logcat` or
+    // `crashes --watch`) instead of the whole shell.
+    let _ = ctrlc::set_handler(|| {});
+
+    let mut session = Session {
+        device,
+        package: None,
+    };
+    let mut editor: Editor<Completions, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(Completions {
+        packages: adb_client.get_installed_apps(&session.device)?.apps,
+        serials: serials(adb_client),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+
+    println!(
+        "{} on {}. Type {} for commands, {} to leave.",
+        "dab shell".bold().yellow(),
+        session.device.cyan(),
+        "help".bold(),
+        "exit".bold()
+    );
+    loop {
+        let input = match editor.readline(&session.prompt()) {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if !input.trim().is_empty() {
+            let _ = editor.add_history_entry(input.as_str());
+        }
+        let completions = editor.helper_mut().expect("helper is set");
+        match session.handle(adb_client, prompter, completions, &input, &execute) {
+            Ok(true) => {}
+            Ok(false) => break,
+            // Leaving a picker with Esc or Ctrl+C just returns to the prompt.
+            Err(e)
+                if e.downcast_ref::<inquire::InquireError>().is_some_and(|e| {
+                    matches!(
+                        e,
+                        inquire::InquireError::OperationCanceled
+                            | inquire::InquireError::OperationInterrupted
+                    )
+                }) => {}
+            Err(e) => super::print_error(&e),
+        }
+    }
+    if let Some(path) = &history {
+        let _ = editor.save_history(path);
+    }
+    Ok(())
+}
+
+/// The device and app every command targets.
+struct Session {
+    device: String,
+    package: Option<String>,
+}
+
+impl Session {
+    fn prompt(&self) -> String {
+        match &self.package {
+            Some(package) => format!("{} {}> ", self.device, package),
+            None => format!("{}> ", self.device),
+        }
+    }
+
+    /// Handle one line; `false` ends the shell.
+    fn handle(
+        &mut self,
+        adb_client: &AdbClient<impl AdbBackend>,
+        prompter: &Prompter,
+        completions: &mut Completions,
+        input: &str,
+        execute: impl Fn(&str, &Commands) -> Result<()>,
+    ) -> Result<bool> {
+        match parse_line(input, self.package.as_deref())? {
+            Line::Empty => {}
+            Line::Exit => return Ok(false),
+            Line::Help => print_help(),
+            Line::Use(query) => {
+                let packages = adb_client.get_installed_apps(&self.device)?.apps;
+                let package = match query {
+                    Some(query) => super::resolve_among(&query, &packages, false)?,
+                    None => prompter.app(packages.clone())?,
+                };
+                println!("{} {}", "Using".green(), package.cyan());
+                self.package = Some(package);
+                completions.packages = packages;
+            }
+            Line::Device(serial) => {
+                adb_client.check_device(&serial)?;
+                let packages = adb_client.get_installed_apps(&serial)?.apps;
+                println!("{} {}", "Switched to".green(), serial.cyan());
+                self.device = serial;
+                self.forget_missing_package(&packages);
+                completions.packages = packages;
+                completions.serials = serials(adb_client);
+            }
+            Line::Command(Commands::Devices) => {
+                println!("{}", adb_client.get_device_list()?.render());
+            }
+            Line::Command(Commands::Info { file }) => {
+                println!("{}", adb_client.analyze_local_file(&file)?.render());
+            }
+            Line::Command(command) => {
+                let changes_apps = matches!(
+                    command,
+                    Commands::Install { .. } | Commands::Uninstall { .. }
+                );
+                execute(&self.device, &command)?;
+                if changes_apps {
+                    let packages = adb_client.get_installed_apps(&self.device)?.apps;
+                    self.forget_missing_package(&packages);
+                    completions.packages = packages;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Drop the app from context once it's no longer installed.
+    fn forget_missing_package(&mut self, packages: &[String]) {
+        if let Some(package) = &self.package {
+            if !packages.contains(package) {
+                println!("{} is not installed; no app selected", package.cyan());
+                self.package = None;
+            }
+        }
+    }
+}
+
+/// Parse a line into a shell command, defaulting `--package` to `package`.
+fn parse_line(input: &str, package: Option<&str>) -> Result<Line> {
+    let words = split_words(input)?;
+    let Some(first) = words.first() else {
+        return Ok(Line::Empty);
+    };
+    match first.as_str() {
+        "exit" | "quit" => return Ok(Line::Exit),
+        "help" => return Ok(Line::Help),
+        "use" => return Ok(Line::Use(words.get(1).cloned())),
+        // `device` alone still shows the device info, as `dab device` does.
+        "device" if words.len() > 1 => return Ok(Line::Device(words[1].clone())),
+        name if UNSUPPORTED.contains(&name) => {
+            return Err(usage(format!(
+                "`{}` can't run inside shell-mode; run `dab {}` instead",
+                name, name
+            ))
+            .into())
+        }
+        _ => {}
+    }
+    let mut args = vec!["dab".to_string()];
+    match SHORTHANDS.iter().find(|(name, _)| *name == first) {
+        Some((_, expansion)) => args.extend(expansion.iter().map(|s| s.to_string())),
+        None => args.push(first.clone()),
+    }
+    args.extend(words.into_iter().skip(1));
+
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        // `open --help` and friends print as usual.
+        Err(e) if !e.use_stderr() => {
+            e.print()?;
+            return Ok(Line::Empty);
+        }
+        Err(e) => {
+            let message = e.to_string();
+            let first_line = message.lines().next().unwrap_or_default();
+            return Err(usage(first_line.trim_start_matches("error: ")).into());
+        }
+    };
+    if cli.device.is_some() || cli.device_tag.is_some() {
+        return Err(
+            usage("--device doesn't apply in shell-mode; switch with `device <serial>`").into(),
+        );
+    }
+    if cli.json {
+        return Err(usage("shell-mode prints text; drop --json").into());
+    }
+    let mut command = cli.command.expect("a subcommand was given");
+    match command {
+        Commands::Health { watch: Some(_), .. } => {
+            return Err(usage(
+                "`health --watch` runs until Ctrl+C; run `dab health --watch` instead",
+            )
+            .into())
+        }
+        Commands::Top { watch: Some(_) } => {
+            return Err(
+                usage("`top --watch` runs until Ctrl+C; run `dab top --watch` instead").into(),
+            )
+        }
+        _ => {}
+    }
+    if let Some(package) = package {
+        default_package(&mut command, package);
+    }
+    Ok(Line::Command(command))
+}
+
+/// Fill in `--package` on app commands that were given none.
+fn default_package(command: &mut Commands, selected: &str) {
+    match command {
+        Commands::Open { package }
+        | Commands::Uninstall { package }
+        | Commands::Clear { package }
+        | Commands::ForceKill { package }
+        | Commands::Download { package, .. }
+        | Commands::AppInfo { package, .. }
+        | Commands::Logcat { package, .. }
+        | Commands::Crashes { package, .. }
+        | Commands::Startup { package, .. }
+        | Commands::Grant { package, .. }
+        | Commands::Revoke { package, .. } => {
+            package.get_or_insert_with(|| selected.to_string());
+        }
+        Commands::Deeplinks {
+            package,
+            file: None,
+            ..
+        } => {
+            package.get_or_insert_with(|| selected.to_string());
+        }
+        Commands::Start { activity, intent } if activity.is_some() || !intent.implicit() => {
+            intent.package.get_or_insert_with(|| selected.to_string());
+        }
+        Commands::Broadcast {
+            receiver: Some(_),
+            intent,
+        }
+        | Commands::StartService { intent, .. } => {
+            intent.package.get_or_insert_with(|| selected.to_string());
+        }
+        _ => {}
+    }
+}
+
+/// Split a line into words, honoring single and double quotes.
+fn split_words(input: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in input.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(usage(format!("Unclosed {} quote", q)).into());
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// A usage error pointing at the shell's own help.
+fn usage(message: impl Into<String>) -> DabError {
+    DabError::new(ErrorCode::Usage, message).with_hint("Type `help` for the shell's commands.")
+}
+
+fn print_help() {
+    let rows = [
+        (
+            "use [app]",
+            "Select the app, full or partial name (picker without one)",
+        ),
+        (
+            "device <serial>",
+            "Switch device (`device` alone shows its info)",
+        ),
+        (
+            "open, clear, kill",
+            "Open, clear or force-kill the selected app",
+        ),
+        (
+            "info, perms",
+            "Show the app's version, or its granted permissions too",
+        ),
+        ("logs", "Stream the app's logs until Ctrl+C"),
+        (
+            "<command>",
+            "Any dab command, e.g. `grant`, `crashes`, `install app.apk`",
+        ),
+        ("exit", "Leave the shell (or Ctrl+D)"),
+    ];
+    for (command, description) in rows {
+        println!("  {:<20} {}", command.bold(), description);
+    }
+}
+
+/// `~/.dab_history`, where the shell keeps its history between sessions.
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".dab_history"))
+}
+
+/// Serials of the connected devices, for completing `device`.
+fn serials(adb_client: &AdbClient<impl AdbBackend>) -> Vec<String> {
+    adb_client
+        .get_devices()
+        .map(|devices| devices.into_iter().map(|d| d.serial).collect())
+        .unwrap_or_default()
+}
+
+/// Tab completion of commands, package names and device serials.
+struct Completions {
+    packages: Vec<String>,
+    serials: Vec<String>,
+}
+
+impl Completions {
+    /// Candidates for the word `partial`, given the words before it.
+    fn candidates(&self, before: &[&str], partial: &str) -> Vec<String> {
+        let commands = || {
+            let subcommands: Vec<String> = Cli::command()
+                .get_subcommands()
+                .map(|c| c.get_name().to_string())
+                .collect();
+            BUILTINS
+                .iter()
+                .chain(SHORTHANDS.iter().map(|(name, _)| name))
+                .map(|s| s.to_string())
+                .chain(subcommands)
+                .filter(|name| !UNSUPPORTED.contains(&name.as_str()))
+                .collect::<Vec<_>>()
+        };
+        let (options, by_substring) = match before {
+            [] => (commands(), false),
+            ["use"] => (self.packages.clone(), true),
+            [.., "--package"] => (self.packages.clone(), true),
+            ["device"] => (self.serials.clone(), false),
+            _ => return Vec::new(),
+        };
+        let mut matches: Vec<String> = options
+            .into_iter()
+            .filter(|o| {
+                if by_substring {
+                    o.contains(partial)
+                } else {
+                    o.starts_with(partial)
+                }
+            })
+            .collect();
+        matches.sort();
+        matches.dedup();
+        matches
+    }
+}
+
+impl Completer for Completions {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let before: Vec<&str> = line[..start].split_whitespace().collect();
+        let candidates = self
+            .candidates(&before, &line[start..])
+            .into_iter()
+            .map(|c| Pair {
+                display: c.clone(),
+                replacement: c,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorthands_expand_and_default_to_the_selected_app() {
+        let Line::Command(Commands::AppInfo { package, all }) =
+            parse_line("perms", Some("com.example.app")).unwrap()
+        else {
+            panic!("expected app-info");
+        };
+        assert_eq!(package.as_deref(), Some("com.example.app"));
+        assert!(all);
+
+        let Line::Command(Commands::Logcat { package, level, .. }) =
+            parse_line("logs --package chrome -l w", Some("com.example.app")).unwrap()
+        else {
+            panic!("expected logcat");
+        };
+        assert_eq!(package.as_deref(), Some("chrome"));
+        assert!(level.is_some());
+
+        assert!(matches!(
+            parse_line("device", None).unwrap(),
+            Line::Command(Commands::Device)
+        ));
+        assert!(matches!(
+            parse_line("device emu-1", None).unwrap(),
+            Line::Device(_)
+        ));
+        assert!(matches!(parse_line("  ", None).unwrap(), Line::Empty));
+    }
+
+    #[test]
+    fn rejects_what_the_shell_cannot_run() {
+        assert!(parse_line("record", None).is_err());
+        assert!(parse_line("open --device emu-2", None).is_err());
+        assert!(parse_line("health --json", None).is_err());
+        assert!(parse_line("nonsense", None).is_err());
+    }
+
+    #[test]
+    fn splits_quoted_words() {
+        assert_eq!(
+            split_words(r#"logs --grep "time out|refused" -t 'A B'"#).unwrap(),
+            vec!["logs", "--grep", "time out|refused", "-t", "A B"]
+        );
+        assert_eq!(split_words(r#"launch """#).unwrap(), vec!["launch", ""]);
+        assert!(split_words("launch 'oops").is_err());
+    }
+
+    #[test]
+    fn completes_commands_packages_and_serials() {
+        let completions = Completions {
+            packages: vec![
+                "com.google.android.youtube".into(),
+                "com.example.app".into(),
+            ],
+            serials: vec!["emulator-5554".into(), "R38M".into()],
+        };
+        assert_eq!(completions.candidates(&[], "per"), vec!["perms"]);
+        assert_eq!(
+            completions.candidates(&["use"], "tube"),
+            vec!["com.google.android.youtube"]
+        );
+        assert_eq!(
+            completions.candidates(&["open", "--package"], "example"),
+            vec!["com.example.app"]
+        );
+        assert_eq!(
+            completions.candidates(&["device"], "emu"),
+            vec!["emulator-5554"]
+        );
+        assert!(completions.candidates(&[], "").iter().all(|c| c != "mcp"));
+    }
+}
@@ -0,0 +1,770 @@
+//! `dab run`: the steps of a scenario file, executed in order against one
+//! device.
+//!
+//! Device actions (`install`, `clear`, `grant`, `open`, `launch`,
+//! `screenshot`, `swipe`, `text`, `key`, and `tap` on a node) run as the same
+//! [`Commands`] the CLI dispatches, so a step's `result` is the document that
+//! command's `--json` prints. `wait`, `tap` at a point and `assert_foreground`
+//! are handled here. The first failing step stops the run and the rest are
+//! reported as skipped. A step with a `max_duration` runs on its own thread
+//! and fails as soon as the limit passes; a hung adb call is left behind
+//! rather than waited for.
+//!
+//! ```yaml
+//! vars:
+//!   app: com.example.app
+//! steps:
+//!   - install: build/app-debug.apk
+//!     max_duration: 2m
+//!   - clear: ${app}
+//!   - grant: { package: "${app}", permissions: [android.permission.CAMERA] }
+//!   - open: ${app}
+//!   - assert_foreground: ${app}
+//!     timeout: 10s
+//!   - tap: { text: Sign in }
+//!     timeout: 10s
+//!   - tap: { x: 540, y: 1200 }
+//!   - text: jane@example.com
+//!   - key: enter
+//!   - swipe: { x1: 540, y1: 1800, x2: 540, y2: 600 }
+//!   - screenshot: shots/home.png
+//! ```
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::cli::{parse_duration, Commands, InputCommand, NodeQuery, UiCommand};
+use super::error::{DabError, ErrorCode};
+use anyhow::{anyhow, Result};
+use colored::*;
+use dab_cli::activity::component_package;
+use dab_cli::input;
+use dab_cli::ui::Query;
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use serde_yaml::Value as Yaml;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long `assert_foreground` keeps checking when the step sets no
+/// `timeout`.
+const FOREGROUND_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a `tap` on a node keeps looking for it when the step sets no
+/// `timeout`.
+const NODE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A scenario with its variables substituted.
+#[derive(Debug)]
+pub struct Scenario {
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    #[serde(flatten)]
+    pub action: Action,
+    /// Shown in the report instead of the action.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// For `assert_foreground` and `tap` on a node, how long to keep
+    /// checking before the step fails.
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub timeout: Option<Duration>,
+    /// Fail the step once it has run this long, without waiting for it to
+    /// finish.
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub max_duration: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// APK, XAPK or APKM; relative paths are relative to the scenario.
+    Install(PathBuf),
+    Clear(String),
+    Grant {
+        package: String,
+        permissions: Vec<String>,
+    },
+    Open(String),
+    Launch(String),
+    Wait(#[serde(deserialize_with = "duration")] Duration),
+    Tap(TapTarget),
+    Swipe {
+        #[serde(deserialize_with = "coordinate")]
+        x1: u32,
+        #[serde(deserialize_with = "coordinate")]
+        y1: u32,
+        #[serde(deserialize_with = "coordinate")]
+        x2: u32,
+        #[serde(deserialize_with = "coordinate")]
+        y2: u32,
+        #[serde(default = "swipe_duration", deserialize_with = "duration")]
+        duration: Duration,
+    },
+    Text(String),
+    /// A key name, `KEYCODE_` constant or number, as `dab input key` takes.
+    Key(String),
+    Screenshot(Option<PathBuf>),
+    AssertForeground(String),
+}
+
+/// The keys of a step's action, one of which each step has.
+const ACTIONS: [&str; 12] = [
+    "install",
+    "clear",
+    "grant",
+    "open",
+    "launch",
+    "wait",
+    "tap",
+    "swipe",
+    "text",
+    "key",
+    "screenshot",
+    "assert_foreground",
+];
+/// The keys a step may have besides its action.
+const STEP_OPTIONS: [&str; 3] = ["name", "timeout", "max_duration"];
+
+/// Where a `tap` lands: a pixel position, or the center of the node with
+/// this text, id or description (as `dab ui tap` finds it).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, expecting = "`tap` needs x and y, or text, id or desc")]
+pub enum TapTarget {
+    Point {
+        #[serde(deserialize_with = "coordinate")]
+        x: u32,
+        #[serde(deserialize_with = "coordinate")]
+        y: u32,
+    },
+    Node(Query),
+}
+
+/// A duration as `2s`, `500ms` or a bare number of seconds.
+fn duration<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Duration, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Secs(f64),
+        Text(String),
+    }
+    let text = match Raw::deserialize(deserializer)? {
+        Raw::Secs(secs) => secs.to_string(),
+        Raw::Text(text) => text,
+    };
+    parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+fn swipe_duration() -> Duration {
+    Duration::from_millis(300)
+}
+
+fn optional_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<Duration>, D::Error> {
+    duration(deserializer).map(Some)
+}
+
+/// A pixel coordinate; also accepted as a string, since variables are.
+fn coordinate<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u32),
+        Text(String),
+    }
+    match Raw::deserialize(deserializer)? {
+        Raw::Number(n) => Ok(n),
+        Raw::Text(text) => text.trim().parse().map_err(|_| {
+            serde::de::Error::custom(format!("expected a pixel coordinate, got `{}`", text))
+        }),
+    }
+}
+
+impl Action {
+    /// Whether the step keeps checking the screen until its `timeout`.
+    fn polls(&self) -> bool {
+        matches!(
+            self,
+            Action::AssertForeground(_) | Action::Tap(TapTarget::Node(_))
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Install(_) => "install",
+            Action::Clear(_) => "clear",
+            Action::Grant { .. } => "grant",
+            Action::Open(_) => "open",
+            Action::Launch(_) => "launch",
+            Action::Wait(_) => "wait",
+            Action::Tap(_) => "tap",
+            Action::Swipe { .. } => "swipe",
+            Action::Text(_) => "text",
+            Action::Key(_) => "key",
+            Action::Screenshot(_) => "screenshot",
+            Action::AssertForeground(_) => "assert_foreground",
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Action::Install(file) => format!("install {}", file.display()),
+            Action::Clear(package) => format!("clear {}", package),
+            Action::Grant {
+                package,
+                permissions,
+            } => format!("grant {}: {}", package, permissions.join(", ")),
+            Action::Open(package) => format!("open {}", package),
+            Action::Launch(url) => format!("launch {}", url),
+            Action::Wait(duration) => format!("wait {:?}", duration),
+            Action::Tap(TapTarget::Point { x, y }) => format!("tap {},{}", x, y),
+            Action::Tap(TapTarget::Node(query)) => format!("tap {}", query),
+            Action::Swipe { x1, y1, x2, y2, .. } => {
+                format!("swipe {},{} → {},{}", x1, y1, x2, y2)
+            }
+            Action::Text(text) => format!("text {:?}", text),
+            Action::Key(key) => format!("key {}", key),
+            Action::Screenshot(Some(path)) => format!("screenshot {}", path.display()),
+            Action::Screenshot(None) => "screenshot".to_string(),
+            Action::AssertForeground(package) => format!("assert_foreground {}", package),
+        }
+    }
+
+    /// The CLI command this step runs, for the actions that have one.
+    fn command(&self) -> Option<Commands> {
+        Some(match self {
+            Action::Install(file) => Commands::Install { file: file.clone() },
+            Action::Clear(package) => Commands::Clear {
+                package: Some(package.clone()),
+            },
+            Action::Grant {
+                package,
+                permissions,
+            } => Commands::Grant {
+                package: Some(package.clone()),
+                permissions: Some(permissions.join(",")),
+            },
+            Action::Open(package) => Commands::Open {
+                package: Some(package.clone()),
+            },
+            Action::Launch(url) => Commands::Launch { url: url.clone() },
+            Action::Screenshot(output) => Commands::Screenshot {
+                output: output.clone(),
+            },
+            Action::Swipe {
+                x1,
+                y1,
+                x2,
+                y2,
+                duration,
+            } => Commands::Input {
+                command: InputCommand::Swipe {
+                    x1: *x1,
+                    y1: *y1,
+                    x2: *x2,
+                    y2: *y2,
+                    duration: *duration,
+                },
+            },
+            Action::Text(text) => Commands::Input {
+                command: InputCommand::Text { text: text.clone() },
+            },
+            Action::Key(key) => Commands::Input {
+                command: InputCommand::Key {
+                    key: key.clone(),
+                    long: false,
+                },
+            },
+            Action::Wait(_) | Action::Tap(_) | Action::AssertForeground(_) => return None,
+        })
+    }
+}
+
+impl Step {
+    fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.action.describe())
+    }
+}
+
+impl Scenario {
+    /// Read a YAML scenario; `overrides` (from `--var`) win over its `vars`.
+    pub fn load(path: &Path, overrides: &[(String, String)]) -> Result<Self> {
+        if !path.exists() {
+            return Err(anyhow!("File does not exist: {}", path.display()));
+        }
+        let text = std::fs::read_to_string(path)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, overrides, base).map_err(|e| {
+            DabError::new(
+                ErrorCode::InvalidScenario,
+                format!("Invalid scenario {}: {}", path.display(), e.message),
+            )
+            .into()
+        })
+    }
+
+    fn parse(text: &str, overrides: &[(String, String)], base: &Path) -> Result<Self, DabError> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct File {
+            #[serde(default)]
+            vars: BTreeMap<String, Yaml>,
+            steps: Vec<Yaml>,
+        }
+        let file: File = serde_yaml::from_str(text).map_err(invalid)?;
+        let mut vars = BTreeMap::new();
+        for (name, value) in file.vars {
+            let value = match value {
+                Yaml::String(s) => s,
+                Yaml::Number(n) => n.to_string(),
+                Yaml::Bool(b) => b.to_string(),
+                _ => {
+                    return Err(invalid(format!(
+                        "variable `{}` must be a string or number",
+                        name
+                    )))
+                }
+            };
+            vars.insert(name, value);
+        }
+        vars.extend(overrides.iter().cloned());
+
+        let mut steps = Vec::new();
+        for (i, mut raw) in file.steps.into_iter().enumerate() {
+            let at_step = |e: DabError| invalid(format!("step {}: {}", i + 1, e.message));
+            substitute(&mut raw, &vars).map_err(at_step)?;
+            check_keys(&raw).map_err(at_step)?;
+            let mut step: Step = serde_yaml::from_value(raw).map_err(|e| at_step(invalid(e)))?;
+            match &mut step.action {
+                Action::Install(file) if file.is_relative() => *file = base.join(&*file),
+                Action::Key(key) => *key = input::keycode(key).map_err(|e| at_step(invalid(e)))?,
+                Action::Tap(TapTarget::Node(query))
+                    if query.text.is_none() && query.id.is_none() && query.desc.is_none() =>
+                {
+                    return Err(at_step(invalid("`tap` needs x and y, or text, id or desc")));
+                }
+                _ => {}
+            }
+            if step.timeout.is_some() && !step.action.polls() {
+                return Err(at_step(invalid(
+                    "`timeout` is how long `assert_foreground` and `tap` on a node keep checking; use `max_duration` to fail a slow step",
+                )));
+            }
+            steps.push(step);
+        }
+        Ok(Scenario { steps })
+    }
+}
+
+fn invalid(err: impl std::fmt::Display) -> DabError {
+    DabError::new(
+        ErrorCode::InvalidScenario,
+        err.to_string().trim().to_string(),
+    )
+}
+
+/// Check that a step has exactly one action and nothing besides
+/// [`STEP_OPTIONS`]; `#[serde(flatten)]` would silently ignore anything else.
+fn check_keys(raw: &Yaml) -> Result<(), DabError> {
+    let Yaml::Mapping(map) = raw else {
+        return Ok(());
+    };
+    let mut actions = Vec::new();
+    for key in map.keys() {
+        let key = key
+            .as_str()
+            .ok_or_else(|| invalid(format!("expected a key name, got {:?}", key)))?;
+        if ACTIONS.contains(&key) {
+            actions.push(key);
+        } else if !STEP_OPTIONS.contains(&key) {
+            return Err(invalid(format!(
+                "unknown key `{}`; a step has one action plus `name`, `timeout` and `max_duration`",
+                key
+            )));
+        }
+    }
+    match actions[..] {
+        [_] => Ok(()),
+        [] => Err(invalid(format!(
+            "no action; expected one of {}",
+            ACTIONS.join(", ")
+        ))),
+        _ => Err(invalid(format!(
+            "one action per step, got `{}`",
+            actions.join("`, `")
+        ))),
+    }
+}
+
+/// Replace `${name}` in every string of `value`.
+fn substitute(value: &mut Yaml, vars: &BTreeMap<String, String>) -> Result<(), DabError> {
+    match value {
+        Yaml::String(text) => *text = expand(text, vars)?,
+        Yaml::Sequence(items) => {
+            for item in items {
+                substitute(item, vars)?;
+            }
+        }
+        Yaml::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                substitute(item, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand(text: &str, vars: &BTreeMap<String, String>) -> Result<String, DabError> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| invalid(format!("unclosed `${{` in `{}`", text)))?;
+        let name = &after[..end];
+        let value = vars
+            .get(name)
+            .ok_or_else(|| invalid(format!("undefined variable `{}`", name)))?;
+        out.push_str(value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Run every step, printing each as it finishes unless `json`. `execute`
+/// runs a CLI command on the device and returns its `--json` document.
+///
+/// Returns the report, or a `SCENARIO_FAILED` error carrying it.
+pub fn run<B: AdbBackend>(
+    adb_client: &AdbClient<B>,
+    device: &str,
+    scenario: &Scenario,
+    json: bool,
+    execute: impl Fn(&AdbClient<B>, &Commands) -> Result<Value> + Send + Sync + 'static,
+) -> Result<Value> {
+    let execute = Arc::new(execute);
+    let started = Instant::now();
+    let total = scenario.steps.len();
+    let mut reports = Vec::new();
+    let mut failure: Option<(usize, String, String)> = None;
+    for (i, step) in scenario.steps.iter().enumerate() {
+        let label = step.label();
+        let mut report = json!({ "step": i + 1, "action": step.action.name(), "label": label });
+        if failure.is_some() {
+            report["status"] = json!("skipped");
+            if !json {
+                println!(
+                    "  {} [{}/{}] {}",
+                    "↷".yellow(),
+                    i + 1,
+                    total,
+                    label.dimmed()
+                );
+            }
+            reports.push(report);
+            continue;
+        }
+        let step_started = Instant::now();
+        let outcome = match step.max_duration {
+            None => perform(adb_client, device, step, &*execute),
+            Some(limit) => {
+                let (tx, rx) = mpsc::channel();
+                let (client, serial, owned, execute) = (
+                    adb_client.clone(),
+                    device.to_string(),
+                    step.clone(),
+                    Arc::clone(&execute),
+                );
+                thread::spawn(move || {
+                    let _ = tx.send(perform(&client, &serial, &owned, &*execute));
+                });
+                match rx.recv_timeout(limit) {
+                    Ok(outcome) => outcome,
+                    Err(RecvTimeoutError::Timeout) => Err(anyhow!(
+                        "Still running after its {:?} max_duration; stopped waiting for it",
+                        limit
+                    )),
+                    Err(RecvTimeoutError::Disconnected) => Err(anyhow!("The step panicked")),
+                }
+            }
+        };
+        let elapsed = step_started.elapsed().as_secs_f64();
+        report["duration_secs"] = json!((elapsed * 100.0).round() / 100.0);
+        match outcome {
+            Ok(result) => {
+                report["status"] = json!("passed");
+                report["result"] = result;
+                if !json {
+                    println!(
+                        "  {} [{}/{}] {} {}",
+                        "✔".green(),
+                        i + 1,
+                        total,
+                        label,
+                        format!("({:.2}s)", elapsed).dimmed()
+                    );
+                }
+            }
+            Err(e) => {
+                let err = DabError::classify(&e);
+                report["status"] = json!("failed");
+                report["error"] = err.to_json();
+                if !json {
+                    println!(
+                        "  {} [{}/{}] {} {}",
+                        "✘".red(),
+                        i + 1,
+                        total,
+                        label.red(),
+                        format!("({:.2}s)", elapsed).dimmed()
+                    );
+                    println!("      {}", err.message.dimmed());
+                }
+                failure = Some((i + 1, label, err.message));
+            }
+        }
+        reports.push(report);
+    }
+
+    let duration_secs = (started.elapsed().as_secs_f64() * 10.0).round() / 10.0;
+    if let Some((step, label, message)) = failure {
+        if !json {
+            println!(
+                "\n{} failed at step {} of {} in {:.1}s",
+                "Scenario".red().bold(),
+                step,
+                total,
+                duration_secs
+            );
+        }
+        return Err(DabError::new(
+            ErrorCode::ScenarioFailed,
+            format!("Step {} ({}) failed: {}", step, label, message),
+        )
+        .with_detail("device", device)
+        .with_detail("failed_step", step)
+        .with_detail("steps", reports)
+        .into());
+    }
+    if !json {
+        println!(
+            "\n{} {} steps passed in {:.1}s",
+            "Scenario passed:".green().bold(),
+            total,
+            duration_secs
+        );
+    }
+    Ok(json!({
+        "device": device,
+        "passed": true,
+        "duration_secs": duration_secs,
+        "steps": reports,
+    }))
+}
+
+fn perform<B: AdbBackend>(
+    adb_client: &AdbClient<B>,
+    device: &str,
+    step: &Step,
+    execute: &impl Fn(&AdbClient<B>, &Commands) -> Result<Value>,
+) -> Result<Value> {
+    if let Some(command) = step.action.command() {
+        return execute(adb_client, &command);
+    }
+    match &step.action {
+        Action::Wait(duration) => {
+            std::thread::sleep(*duration);
+            Ok(json!({ "waited_secs": duration.as_secs_f64() }))
+        }
+        Action::Tap(TapTarget::Point { x, y }) => {
+            adb_client.tap(device, *x, *y)?;
+            Ok(json!({ "success": true, "x": x, "y": y }))
+        }
+        Action::Tap(TapTarget::Node(query)) => execute(
+            adb_client,
+            &Commands::Ui {
+                command: UiCommand::Tap {
+                    query: NodeQuery {
+                        text: query.text.clone(),
+                        id: query.id.clone(),
+                        desc: query.desc.clone(),
+                        exact: query.exact,
+                        wait: Some(step.timeout.unwrap_or(NODE_TIMEOUT)),
+                    },
+                    index: None,
+                },
+            },
+        ),
+        Action::AssertForeground(package) => {
+            let deadline = Instant::now() + step.timeout.unwrap_or(FOREGROUND_TIMEOUT);
+            loop {
+                let activity = adb_client.get_foreground_activity(device)?;
+                if activity
+                    .as_deref()
+                    .is_some_and(|a| component_package(a) == package)
+                {
+                    return Ok(json!({ "package": package, "activity": activity }));
+                }
+                if Instant::now() >= deadline {
+                    return Err(anyhow!(
+                        "{} is not in the foreground (resumed activity: {})",
+                        package,
+                        activity.as_deref().unwrap_or("none")
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(500));
+            }
+        }
+        _ => unreachable!("steps without a CLI command are matched above"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str, overrides: &[(&str, &str)]) -> Result<Scenario, DabError> {
+        let overrides: Vec<(String, String)> = overrides
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Scenario::parse(text, &overrides, Path::new("smoke"))
+    }
+
+    #[test]
+    fn substitutes_variables_with_overrides_winning() {
+        let scenario = parse(
+            "\
+vars:
+  app: com.example.app
+  x: 540
+steps:
+  - install: build/${app}.apk
+  - assert_foreground: ${app}
+    timeout: 10s
+    name: App is up
+  - tap: { x: '${x}', y: 1200 }
+  - wait: 1.5
+  - tap: { id: '${app}:id/login' }
+  - key: back
+",
+            &[("app", "com.example.debug")],
+        )
+        .unwrap();
+        let steps = &scenario.steps;
+        assert!(matches!(&steps[0].action,
+            Action::Install(file) if file == Path::new("smoke/build/com.example.debug.apk")));
+        assert!(matches!(&steps[1].action,
+            Action::AssertForeground(p) if p == "com.example.debug"));
+        assert_eq!(steps[1].timeout, Some(Duration::from_secs(10)));
+        assert_eq!(steps[1].label(), "App is up");
+        assert!(matches!(
+            steps[2].action,
+            Action::Tap(TapTarget::Point { x: 540, y: 1200 })
+        ));
+        assert!(matches!(steps[3].action, Action::Wait(d) if d == Duration::from_millis(1500)));
+        assert_eq!(steps[4].label(), r#"tap id "com.example.debug:id/login""#);
+        assert!(matches!(&steps[5].action, Action::Key(k) if k == "KEYCODE_BACK"));
+    }
+
+    #[test]
+    fn fails_steps_that_run_past_their_max_duration() {
+        use crate::backend::{Replayer, Transcript};
+        let adb_client = AdbClient::with_backend(Replayer::new(Transcript::default()));
+        let scenario = parse(
+            "steps:\n  - wait: 50ms\n    max_duration: 10ms\n  - wait: 10ms\n",
+            &[],
+        )
+        .unwrap();
+        let err = run(&adb_client, "emu", &scenario, true, |_, _| {
+            unreachable!("`wait` runs no command")
+        })
+        .unwrap_err();
+        let err = DabError::classify(&err);
+        assert_eq!(err.code, ErrorCode::ScenarioFailed);
+        let steps = &err.details["steps"];
+        assert_eq!(steps[0]["status"], "failed");
+        assert!(steps[0]["error"]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Still running after its 10ms max_duration"));
+        assert_eq!(steps[1]["status"], "skipped");
+    }
+
+    #[test]
+    fn stops_waiting_for_a_hung_step_at_its_max_duration() {
+        use crate::backend::{Replayer, Transcript};
+        use std::sync::Mutex;
+        let adb_client = AdbClient::with_backend(Replayer::new(Transcript::default()));
+        let scenario = parse(
+            "steps:\n  - open: a\n    max_duration: 50ms\n  - open: b\n",
+            &[],
+        )
+        .unwrap();
+        // The command never answers until the test lets it go.
+        let (release, hung) = mpsc::channel::<()>();
+        let hung = Mutex::new(hung);
+        let started = Instant::now();
+        let err = run(&adb_client, "emu", &scenario, true, move |_, _| {
+            let _ = hung.lock().unwrap().recv();
+            Ok(json!({}))
+        })
+        .unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let err = DabError::classify(&err);
+        assert_eq!(err.details["failed_step"], 1);
+        assert_eq!(err.details["steps"][0]["status"], "failed");
+        assert_eq!(err.details["steps"][1]["status"], "skipped");
+        drop(release);
+    }
+
+    #[test]
+    fn reports_bad_steps_by_number() {
+        let err = parse("steps:\n  - open: a\n  - open: ${missing}\n", &[]).unwrap_err();
+        assert_eq!(err.code, ErrorCode::InvalidScenario);
+        assert_eq!(err.message, "step 2: undefined variable `missing`");
+        assert!(parse("steps:\n  - swipe: up\n", &[])
+            .unwrap_err()
+            .message
+            .starts_with("step 1:"));
+        assert_eq!(
+            parse("steps:\n  - tap: {}\n", &[]).unwrap_err().message,
+            "step 1: `tap` needs x and y, or text, id or desc"
+        );
+        assert!(parse("steps:\n  - tap: { x: 1, z: 2 }\n", &[]).is_err());
+        assert_eq!(
+            parse("steps:\n  - wait: 1e30\n", &[]).unwrap_err().code,
+            ErrorCode::InvalidScenario
+        );
+        assert_eq!(
+            parse("steps:\n  - open: a\n    timout: 1s\n", &[])
+                .unwrap_err()
+                .message,
+            "step 1: unknown key `timout`; a step has one action plus `name`, `timeout` and `max_duration`"
+        );
+        assert_eq!(
+            parse("steps:\n  - open: a\n    clear: b\n", &[])
+                .unwrap_err()
+                .message,
+            "step 1: one action per step, got `open`, `clear`"
+        );
+        assert!(parse("steps:\n  - open: a\n    timeout: 5s\n", &[])
+            .unwrap_err()
+            .message
+            .starts_with("step 1: `timeout` is how long"));
+        assert!(parse("steps:\n  - name: nothing\n", &[])
+            .unwrap_err()
+            .message
+            .starts_with("step 1: no action"));
+        assert!(parse("step: []\n", &[]).is_err());
+    }
+}
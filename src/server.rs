@@ -0,0 +1,600 @@
+//! `dab serve` — a local HTTP/JSON API for device control.
+//!
+//! Every route maps onto a [`Commands`] variant and runs through the same
+//! `--json` code path as the CLI, with prompts disabled, so a response body is
+//! exactly what `dab <cmd> --json` prints. Failures return the usual error
+//! envelope with an HTTP status derived from its code. Uploaded APKs and
+//! screenshots pass through temporary files that are removed afterwards.
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::cli::Commands;
+use super::error::{DabError, ErrorCode};
+use anyhow::{Context, Result};
+use colored::*;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Requests served in parallel; each one holds an adb call at most.
+const WORKERS: usize = 4;
+/// The largest request body read into memory: room for big XAPK bundles, but
+/// bounded for every worker at once.
+const MAX_BODY: u64 = 512 * 1024 * 1024;
+
+/// What a route asks for, before any device is touched.
+enum Route {
+    Devices,
+    Run { serial: String, command: Commands },
+    Install { serial: String },
+    Screenshot { serial: String },
+}
+
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(status: u16, value: &Value) -> Self {
+        Reply {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec_pretty(value).unwrap_or_default(),
+        }
+    }
+
+    fn error(err: &anyhow::Error) -> Self {
+        let err = DabError::classify(err);
+        Reply::json(http_status(err.code), &err.to_json())
+    }
+}
+
+/// Listen on `host:port` until the process is stopped. `execute` runs one
+/// device command and returns its `--json` document. When `token` is set,
+/// every request must carry `Authorization: Bearer <token>`.
+pub fn serve(
+    adb_client: &AdbClient<impl AdbBackend>,
+    host: &str,
+    port: u16,
+    token: Option<&str>,
+    execute: impl Fn(&str, &Commands) -> Result<Value> + Sync,
+) -> Result<()> {
+    let address = format!("{}:{}", host, port);
+    let server = tiny_http::Server::http(&address)
+        .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", address, e))?;
+    println!(
+        "{} http://{}",
+        "Listening on".bold().green(),
+        server.server_addr()
+    );
+    if token.is_none() && !is_loopback(host) {
+        println!(
+            "{} serving on {} without --token; anyone on the network can control your devices",
+            "Warning:".yellow().bold(),
+            host
+        );
+    }
+    let uploads = AtomicU64::new(0);
+    std::thread::scope(|scope| {
+        for _ in 0..WORKERS {
+            scope.spawn(|| {
+                for mut request in server.incoming_requests() {
+                    let started = Instant::now();
+                    let reply = if authorized(&request, token) {
+                        respond(adb_client, &execute, &mut request, &uploads)
+                    } else {
+                        Reply::error(
+                            &DabError::new(ErrorCode::Unauthorized, "Missing or wrong token")
+                                .into(),
+                        )
+                    };
+                    println!(
+                        "{} {} {} {}",
+                        request.method().as_str().cyan(),
+                        request.url(),
+                        status_label(reply.status),
+                        format!("{}ms", started.elapsed().as_millis()).dimmed()
+                    );
+                    let header = tiny_http::Header::from_bytes(
+                        &b"Content-Type"[..],
+                        reply.content_type.as_bytes(),
+                    )
+                    .expect("static header is valid");
+                    let response = tiny_http::Response::from_data(reply.body)
+                        .with_status_code(reply.status)
+                        .with_header(header);
+                    let _ = request.respond(response);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+fn respond(
+    adb_client: &AdbClient<impl AdbBackend>,
+    execute: &impl Fn(&str, &Commands) -> Result<Value>,
+    request: &mut tiny_http::Request,
+    uploads: &AtomicU64,
+) -> Reply {
+    let method = request.method().as_str().to_string();
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let length = request.body_length();
+    let body = match read_body(request.as_reader(), length, MAX_BODY) {
+        Ok(body) => body,
+        Err(e) => return Reply::error(&e),
+    };
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Content-Type"))
+        .map(|h| h.value.as_str().to_string())
+        .unwrap_or_default();
+    let result = route(&method, &path, &body).and_then(|route| match route {
+        Route::Devices => Ok(Reply::json(200, &json!(adb_client.get_device_list()?))),
+        Route::Run { serial, command } => {
+            adb_client.check_device(&serial)?;
+            Ok(Reply::json(200, &execute(&serial, &command)?))
+        }
+        Route::Install { serial } => {
+            adb_client.check_device(&serial)?;
+            let (name, apk) = uploaded_file(&content_type, &body)?;
+            let result = install_upload(execute, uploads, &serial, &name, apk)?;
+            Ok(Reply::json(200, &result))
+        }
+        Route::Screenshot { serial } => {
+            adb_client.check_device(&serial)?;
+            let temp = temp_path(uploads, "png");
+            let result = execute(
+                &serial,
+                &Commands::Screenshot {
+                    output: Some(temp.clone()),
+                },
+            )
+            .and_then(|_| Ok(std::fs::read(&temp)?));
+            let _ = std::fs::remove_file(&temp);
+            Ok(Reply {
+                status: 200,
+                content_type: "image/png",
+                body: result?,
+            })
+        }
+    });
+    result.unwrap_or_else(|e| Reply::error(&e))
+}
+
+/// Install an uploaded APK, XAPK or APKM named `name`. Workers store
+/// uploads in separate files, and `install_file` extracts bundles into
+/// separate directories, so concurrent uploads don't touch each other.
+fn install_upload(
+    execute: &impl Fn(&str, &Commands) -> Result<Value>,
+    uploads: &AtomicU64,
+    serial: &str,
+    name: &str,
+    data: &[u8],
+) -> Result<Value> {
+    let extension = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "apk".to_string());
+    let temp = temp_path(uploads, &extension);
+    std::fs::write(&temp, data).context("Failed to store the uploaded file")?;
+    let result = execute(serial, &Commands::Install { file: temp.clone() });
+    let _ = std::fs::remove_file(&temp);
+    let mut result = result?;
+    result["file"] = json!(name);
+    Ok(result)
+}
+
+/// Map a method and path onto a [`Route`]. `body` is the JSON payload for
+/// `launch` and the permission routes.
+fn route(method: &str, path: &str, body: &[u8]) -> Result<Route> {
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(percent_decode)
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    let payload = || -> Result<Value> {
+        if body.is_empty() {
+            return Ok(json!({}));
+        }
+        serde_json::from_slice(body).map_err(|e| {
+            DabError::new(ErrorCode::Usage, format!("Invalid JSON body: {}", e)).into()
+        })
+    };
+    let permissions = || -> Result<Option<String>> {
+        Ok(payload()?["permissions"].as_array().map(|list| {
+            list.iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(",")
+        }))
+    };
+    let run = |serial: &str, command: Commands| Route::Run {
+        serial: serial.to_string(),
+        command,
+    };
+    let package = |p: &str| Some(p.to_string());
+    Ok(match (method, segments.as_slice()) {
+        ("GET", ["devices"]) => Route::Devices,
+        ("GET", ["devices", s]) => run(s, Commands::Device),
+        ("GET", ["devices", s, "health"]) => run(
+            s,
+            Commands::Health {
+                watch: None,
+                fail_if: Vec::new(),
+                warn_if: Vec::new(),
+                log: None,
+            },
+        ),
+        ("GET", ["devices", s, "network"]) => run(s, Commands::Network),
+        ("GET", ["devices", s, "screenshot.png"]) => Route::Screenshot {
+            serial: s.to_string(),
+        },
+        ("POST", ["devices", s, "install"]) => Route::Install {
+            serial: s.to_string(),
+        },
+        ("POST", ["devices", s, "launch"]) => match payload()?["url"].as_str() {
+            Some(url) => run(s, Commands::Launch { url: url.into() }),
+            None => {
+                return Err(
+                    DabError::new(ErrorCode::Usage, "Body must be {\"url\": \"...\"}").into(),
+                )
+            }
+        },
+        ("GET", ["devices", s, "apps"]) => run(s, Commands::Apps),
+        ("GET", ["devices", s, "apps", p]) => run(
+            s,
+            Commands::AppInfo {
+                package: package(p),
+                all: true,
+            },
+        ),
+        ("DELETE", ["devices", s, "apps", p]) => run(
+            s,
+            Commands::Uninstall {
+                package: package(p),
+            },
+        ),
+        ("POST", ["devices", s, "apps", p, "open"]) => run(
+            s,
+            Commands::Open {
+                package: package(p),
+            },
+        ),
+        ("POST", ["devices", s, "apps", p, "clear"]) => run(
+            s,
+            Commands::Clear {
+                package: package(p),
+            },
+        ),
+        ("POST", ["devices", s, "apps", p, "force-kill"]) => run(
+            s,
+            Commands::ForceKill {
+                package: package(p),
+            },
+        ),
+        ("GET", ["devices", s, "apps", p, "crashes"]) => run(
+            s,
+            Commands::Crashes {
+                package: package(p),
+                watch: false,
+                save_dir: None,
+            },
+        ),
+        ("POST", ["devices", s, "apps", p, "permissions"]) => run(
+            s,
+            Commands::Grant {
+                package: package(p),
+                permissions: permissions()?,
+            },
+        ),
+        ("DELETE", ["devices", s, "apps", p, "permissions"]) => run(
+            s,
+            Commands::Revoke {
+                package: package(p),
+                permissions: permissions()?,
+            },
+        ),
+        _ => {
+            return Err(DabError::new(
+                ErrorCode::RouteNotFound,
+                format!("No route for {} {}", method, path),
+            )
+            .into())
+        }
+    })
+}
+
+/// The file in an install request: the first file part of a
+/// `multipart/form-data` body, or the raw body itself.
+fn uploaded_file<'a>(content_type: &str, body: &'a [u8]) -> Result<(String, &'a [u8])> {
+    let missing = || DabError::new(ErrorCode::Usage, "No APK in the request body");
+    if !content_type.starts_with("multipart/form-data") {
+        if body.is_empty() {
+            return Err(missing().into());
+        }
+        return Ok(("upload.apk".to_string(), body));
+    }
+    let boundary = content_type
+        .split(';')
+        .find_map(|p| p.trim().strip_prefix("boundary="))
+        .map(|b| b.trim_matches('"'))
+        .ok_or_else(|| DabError::new(ErrorCode::Usage, "Multipart body without a boundary"))?;
+    multipart_file(boundary, body)
+        .map(|(name, data)| (name.to_string(), data))
+        .ok_or_else(|| missing().into())
+}
+
+fn multipart_file<'a>(boundary: &str, body: &'a [u8]) -> Option<(&'a str, &'a [u8])> {
+    let delimiter = format!("--{}", boundary);
+    let mut rest = body;
+    loop {
+        let start = find(rest, delimiter.as_bytes())? + delimiter.len();
+        rest = &rest[start..];
+        if rest.starts_with(b"--") {
+            return None;
+        }
+        let headers_end = find(rest, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).ok()?;
+        let data = &rest[headers_end + 4..];
+        let end = find(data, format!("\r\n{}", delimiter).as_bytes())?;
+        let file_name = headers.lines().find_map(|line| {
+            let (_, value) = line.split_once("filename=\"")?;
+            value.split_once('"').map(|(name, _)| name)
+        });
+        if let Some(name) = file_name {
+            return Some((name, &data[..end]));
+        }
+        rest = &data[end..];
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn percent_decode(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The request body, refused as `PAYLOAD_TOO_LARGE` once it passes `limit`:
+/// up front from `Content-Length`, or while reading a chunked body.
+fn read_body(reader: impl Read, length: Option<usize>, limit: u64) -> Result<Vec<u8>> {
+    let too_large = || {
+        DabError::new(
+            ErrorCode::PayloadTooLarge,
+            format!("Request body is over the {} MiB limit", limit >> 20),
+        )
+        .with_detail("limit_bytes", limit)
+        .into()
+    };
+    if length.is_some_and(|n| n as u64 > limit) {
+        return Err(too_large());
+    }
+    let mut body = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > limit {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+fn authorized(request: &tiny_http::Request, token: Option<&str>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .is_some_and(|given| same_token(given.trim(), token))
+}
+
+/// Compare tokens in time that depends only on their length, so response
+/// timing doesn't reveal how much of a guess was right.
+fn same_token(given: &str, token: &str) -> bool {
+    let (given, token) = (given.as_bytes(), token.as_bytes());
+    let difference = given.iter().zip(token).fold(0, |acc, (a, b)| acc | (a ^ b));
+    std::hint::black_box(difference) == 0 && given.len() == token.len()
+}
+
+fn is_loopback(host: &str) -> bool {
+    host == "localhost"
+        || host
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+fn temp_path(uploads: &AtomicU64, extension: &str) -> PathBuf {
+    let n = uploads.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!(
+        "dab-serve-{}-{}.{}",
+        std::process::id(),
+        n,
+        extension
+    ))
+}
+
+/// HTTP status for an error envelope.
+fn http_status(code: ErrorCode) -> u16 {
+    match code {
+        ErrorCode::Usage
+        | ErrorCode::AmbiguousPackage
+        | ErrorCode::InputRequired
+        | ErrorCode::UnsupportedFile => 400,
+        ErrorCode::Unauthorized => 401,
+        ErrorCode::DeviceNotFound
+        | ErrorCode::PackageNotFound
+        | ErrorCode::FileNotFound
+        | ErrorCode::RouteNotFound => 404,
+        ErrorCode::DeviceUnauthorized | ErrorCode::DeviceOffline => 409,
+        ErrorCode::PayloadTooLarge => 413,
+        ErrorCode::InstallFailed
+        | ErrorCode::InstallFailedVersionDowngrade
+        | ErrorCode::InstallFailedInsufficientStorage => 422,
+        ErrorCode::AdbMissing | ErrorCode::NoDevices => 503,
+        _ => 500,
+    }
+}
+
+fn status_label(status: u16) -> ColoredString {
+    match status {
+        200..=299 => status.to_string().green(),
+        400..=499 => status.to_string().yellow(),
+        _ => status.to_string().red(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_rest_paths_to_commands() {
+        assert!(matches!(
+            route("GET", "/devices", b"").unwrap(),
+            Route::Devices
+        ));
+        match route("POST", "/devices/192.168.1.5%3A5555/apps/com.foo/open", b"").unwrap() {
+            Route::Run {
+                serial,
+                command: Commands::Open { package },
+            } => {
+                assert_eq!(serial, "192.168.1.5:5555");
+                assert_eq!(package.as_deref(), Some("com.foo"));
+            }
+            _ => panic!("unexpected route"),
+        }
+        let body =
+            br#"{"permissions": ["android.permission.CAMERA", "android.permission.RECORD_AUDIO"]}"#;
+        match route("DELETE", "/devices/x/apps/com.foo/permissions", body).unwrap() {
+            Route::Run {
+                command: Commands::Revoke { permissions, .. },
+                ..
+            } => assert_eq!(
+                permissions.as_deref(),
+                Some("android.permission.CAMERA,android.permission.RECORD_AUDIO")
+            ),
+            _ => panic!("unexpected route"),
+        }
+        assert!(matches!(
+            route("GET", "/devices/x/screenshot.png", b"").unwrap(),
+            Route::Screenshot { .. }
+        ));
+    }
+
+    #[test]
+    fn unknown_routes_and_bad_bodies_are_errors() {
+        let err = DabError::classify(&route("GET", "/nope", b"").err().unwrap());
+        assert_eq!(err.code, ErrorCode::RouteNotFound);
+        assert_eq!(http_status(err.code), 404);
+        let err = DabError::classify(&route("POST", "/devices/x/launch", b"{}").err().unwrap());
+        assert_eq!(http_status(err.code), 400);
+    }
+
+    #[test]
+    fn bodies_over_the_limit_are_refused() {
+        let body = [7u8; 64];
+        assert_eq!(read_body(&body[..], Some(64), 64).unwrap().len(), 64);
+        let err = DabError::classify(&read_body(&body[..], Some(1 << 40), 64).unwrap_err());
+        assert_eq!(err.code, ErrorCode::PayloadTooLarge);
+        assert_eq!(http_status(err.code), 413);
+        // A chunked body has no length to check up front.
+        let err = DabError::classify(&read_body(&body[..], None, 63).unwrap_err());
+        assert_eq!(err.code, ErrorCode::PayloadTooLarge);
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(same_token("s3cret", "s3cret"));
+        assert!(!same_token("s3cre", "s3cret"));
+        assert!(!same_token("s3cret!", "s3cret"));
+        assert!(!same_token("S3cret", "s3cret"));
+        assert!(!same_token("", "s3cret"));
+    }
+
+    #[test]
+    fn extracts_file_from_multipart_body() {
+        let body = b"--XyZ\r\nContent-Disposition: form-data; name=\"note\"\r\n\r\nhello\r\n--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"app.xapk\"\r\nContent-Type: application/octet-stream\r\n\r\nPK\x03\x04data\r\n--XyZ--\r\n";
+        let (name, data) = uploaded_file("multipart/form-data; boundary=XyZ", body).unwrap();
+        assert_eq!(name, "app.xapk");
+        assert_eq!(data, b"PK\x03\x04data");
+        let (name, data) = uploaded_file("application/octet-stream", b"PK").unwrap();
+        assert_eq!((name.as_str(), data), ("upload.apk", &b"PK"[..]));
+        assert!(uploaded_file("multipart/form-data; boundary=XyZ", b"--XyZ--\r\n").is_err());
+    }
+
+    #[test]
+    fn concurrent_bundle_uploads_install_independently() {
+        use crate::backend::{Replayer, Transcript};
+        use crate::execute_json;
+        use crate::prompt::Prompter;
+        use std::io::Write;
+
+        let mut bundle = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        bundle
+            .start_file("base.apk", zip::write::FileOptions::default())
+            .unwrap();
+        bundle.write_all(&[0; 256 * 1024]).unwrap();
+        let bundle = bundle.finish().unwrap().into_inner();
+
+        let serials: Vec<String> = (0..WORKERS).map(|i| format!("emu-{}", i)).collect();
+        let exchanges: Vec<Value> = serials
+            .iter()
+            .map(|serial| json!({ "call": "install_multiple", "args": [serial, "base.apk"], "ok": null }))
+            .collect();
+        let transcript: Transcript =
+            serde_json::from_value(json!({ "exchanges": exchanges })).unwrap();
+        let adb_client = AdbClient::with_backend(Replayer::new(transcript));
+        let execute = |serial: &str, command: &Commands| {
+            execute_json(&adb_client, serial, command, &Prompter::disabled())
+        };
+        let uploads = AtomicU64::new(0);
+        let results: Vec<Result<Value>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = serials
+                .iter()
+                .map(|serial| {
+                    let (execute, uploads, bundle) = (&execute, &uploads, &bundle);
+                    scope
+                        .spawn(move || install_upload(execute, uploads, serial, "app.xapk", bundle))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for result in results {
+            assert_eq!(result.unwrap()["file"], "app.xapk");
+        }
+        assert!(adb_client.backend().unused().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! App start timing: parsing `am start -W`, summary statistics and the
+//! `--baseline` regression check for `dab startup`.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// How warm the app is when each measured launch happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StartMode {
+    /// Process killed before every launch
+    Cold,
+    /// Process alive, activity finished with Back before every launch
+    Warm,
+    /// Activity sent to the background with Home before every launch
+    Hot,
+}
+
+/// What `am start -W` reports for one launch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StartTiming {
+    pub total_ms: u64,
+    pub wait_ms: u64,
+    pub launch_state: Option<String>,
+}
+
+/// Parse `am start -W` output; `None` when it has no `TotalTime` (the
+/// activity failed to start).
+pub fn parse_start_timing(output: &str) -> Option<StartTiming> {
+    let field = |name: &str| {
+        output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix(name)?.trim().parse::<u64>().ok())
+    };
+    let total_ms = field("TotalTime:")?;
+    Some(StartTiming {
+        total_ms,
+        wait_ms: field("WaitTime:").unwrap_or(total_ms),
+        launch_state: output
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("LaunchState:"))
+            .map(|s| s.trim().to_string()),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    pub min: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Stats {
+    /// Summary of `samples`; `None` when there are none.
+    pub fn of(samples: &[u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = samples.iter().map(|&s| s as f64).collect();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len();
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+        // Nearest-rank percentile.
+        let p90 = sorted[((0.9 * n as f64).ceil() as usize).clamp(1, n) - 1];
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let variance = sorted.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n as f64;
+        Some(Stats {
+            min: sorted[0],
+            median,
+            p90,
+            max: sorted[n - 1],
+            mean: round1(mean),
+            stddev: round1(variance.sqrt()),
+        })
+    }
+}
+
+fn round1(v: f64) -> f64 {
+    (v * 10.0).round() / 10.0
+}
+
+/// Median TotalTime from a previous `dab startup --json` document.
+pub fn baseline_median(baseline: &Value) -> Option<f64> {
+    baseline.get("total_time")?.get("median")?.as_f64()
+}
+
+/// Relative change from `baseline` to `current`, in percent.
+pub fn change_percent(baseline: f64, current: f64) -> f64 {
+    if baseline == 0.0 {
+        return 0.0;
+    }
+    round1((current - baseline) / baseline * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_am_start_w_output() {
+        let out = "Starting: Intent { cmp=com.foo/.Main }\nStatus: ok\nLaunchState: COLD\nActivity: com.foo/.Main\nTotalTime: 512\nWaitTime: 530\nComplete\n";
+        assert_eq!(
+            parse_start_timing(out),
+            Some(StartTiming {
+                total_ms: 512,
+                wait_ms: 530,
+                launch_state: Some("COLD".into()),
+            })
+        );
+        assert_eq!(
+            parse_start_timing("Error: Activity class {com.foo/.Nope} does not exist."),
+            None
+        );
+    }
+
+    #[test]
+    fn computes_stats() {
+        let stats = Stats::of(&[100, 300, 200, 400, 1000]).unwrap();
+        assert_eq!(stats.min, 100.0);
+        assert_eq!(stats.median, 300.0);
+        assert_eq!(stats.p90, 1000.0);
+        assert_eq!(stats.max, 1000.0);
+        assert_eq!(stats.mean, 400.0);
+        assert_eq!(stats.stddev, 316.2);
+        assert_eq!(Stats::of(&[10, 20]).unwrap().median, 15.0);
+        assert!(Stats::of(&[]).is_none());
+    }
+
+    #[test]
+    fn reads_baseline_and_change() {
+        let baseline = json!({ "total_time": { "median": 400.0 } });
+        assert_eq!(baseline_median(&baseline), Some(400.0));
+        assert_eq!(change_percent(400.0, 460.0), 15.0);
+        assert_eq!(baseline_median(&json!({})), None);
+    }
+}
@@ -0,0 +1,755 @@
+//! `dab tui`: a full-screen dashboard for one device.
+//!
+//! A poller thread fetches health, the foreground activity and new logcat
+//! entries and sends them to the UI thread, which owns the terminal and runs
+//! app actions as keys are pressed. Logs are dumped with `logcat -d` on every
+//! tick rather than streamed, so the poller stops as soon as the UI quits.
+
+use super::adb_client::AdbClient;
+use super::backend::AdbBackend;
+use super::fuzzy;
+use anyhow::Result;
+use dab_cli::logcat::{LogEntry, LogLevel};
+use dab_cli::model::DeviceHealth;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, LineGauge, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often the poller checks the device.
+const TICK: Duration = Duration::from_secs(1);
+/// Health is slower to collect, so it's fetched every this many ticks.
+const HEALTH_EVERY: u64 = 5;
+/// Entries dumped on the first tick, and kept in the log panel.
+const LOG_BACKLOG: usize = 500;
+
+/// Run the dashboard on `device` until `q` or Ctrl+C.
+pub fn run(adb_client: &AdbClient<impl AdbBackend>, device: &str) -> Result<()> {
+    let mut dashboard = Dashboard::new(device, adb_client.get_installed_apps(device)?.apps);
+    let followed = Mutex::new(None);
+    let stop = AtomicBool::new(false);
+    let (updates, received) = mpsc::channel();
+
+    let mut terminal = ratatui::init();
+    let result = std::thread::scope(|scope| {
+        scope.spawn(|| poll(adb_client, device, &followed, &stop, updates));
+        let result = ui(
+            adb_client,
+            &mut terminal,
+            &mut dashboard,
+            &followed,
+            &received,
+        );
+        stop.store(true, Ordering::Relaxed);
+        result
+    });
+    ratatui::restore();
+    result
+}
+
+/// What the poller learned about the device.
+enum Update {
+    Health(std::result::Result<DeviceHealth, String>),
+    Foreground(Option<String>),
+    Logs(Vec<LogEntry>),
+    /// The followed app is running as this pid.
+    Pid(String, u32),
+}
+
+fn poll(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    followed: &Mutex<Option<String>>,
+    stop: &AtomicBool,
+    updates: Sender<Update>,
+) {
+    let mut cursor = LogCursor::default();
+    let mut tick = 0;
+    while !stop.load(Ordering::Relaxed) {
+        let mut batch = Vec::new();
+        if tick % HEALTH_EVERY == 0 {
+            let health = adb_client.get_device_health(device);
+            batch.push(Update::Health(health.map_err(|e| e.to_string())));
+        }
+        if let Ok(foreground) = adb_client.get_foreground_activity(device) {
+            batch.push(Update::Foreground(foreground));
+        }
+        if let Ok(entries) = adb_client.dump_logcat(device, cursor.since(), LOG_BACKLOG) {
+            batch.push(Update::Logs(cursor.fresh(entries)));
+        }
+        let package = followed.lock().unwrap().clone();
+        if let Some(package) = package {
+            if let Ok(Some(pid)) = adb_client.app_pid(device, &package) {
+                batch.push(Update::Pid(package, pid));
+            }
+        }
+        if batch
+            .into_iter()
+            .any(|update| updates.send(update).is_err())
+        {
+            return;
+        }
+        tick += 1;
+        // Sleep in short steps so quitting doesn't wait out a whole tick.
+        for _ in 0..10 {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(TICK / 10);
+        }
+    }
+}
+
+/// Tracks where the last dump ended, so each `logcat -d -t <time>` only
+/// yields entries not seen before.
+#[derive(Default)]
+struct LogCursor {
+    time: Option<String>,
+    /// Entries logged at exactly `time`, which the next dump repeats.
+    seen: HashSet<String>,
+}
+
+impl LogCursor {
+    fn since(&self) -> Option<&str> {
+        self.time.as_deref()
+    }
+
+    fn fresh(&mut self, entries: Vec<LogEntry>) -> Vec<LogEntry> {
+        let fresh: Vec<LogEntry> = entries
+            .into_iter()
+            .filter(|e| match &self.time {
+                Some(time) => {
+                    e.time > *time || (e.time == *time && !self.seen.contains(&e.plain()))
+                }
+                None => true,
+            })
+            .collect();
+        if let Some(last) = fresh.last() {
+            if self.time.as_ref() != Some(&last.time) {
+                self.time = Some(last.time.clone());
+                self.seen.clear();
+            }
+            let time = &last.time;
+            self.seen.extend(
+                fresh
+                    .iter()
+                    .filter(|e| e.time == *time)
+                    .map(LogEntry::plain),
+            );
+        }
+        fresh
+    }
+}
+
+/// Which panel the arrow keys move.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Apps,
+    Logs,
+}
+
+/// An app action picked by a key; run by the UI loop.
+#[derive(Debug, PartialEq)]
+enum Action {
+    Quit,
+    Open(String),
+    Clear(String),
+    Kill(String),
+    Uninstall(String),
+    Info(String),
+    RefreshApps,
+}
+
+/// Everything the dashboard shows.
+struct Dashboard {
+    device: String,
+    health: Option<std::result::Result<DeviceHealth, String>>,
+    foreground: Option<String>,
+    apps: Vec<String>,
+    /// `apps` matching `query`, best match first.
+    shown: Vec<String>,
+    query: String,
+    searching: bool,
+    selected: ListState,
+    focus: Focus,
+    logs: VecDeque<LogEntry>,
+    /// Lines scrolled up from the newest entry; 0 follows the tail.
+    scroll: usize,
+    /// The app whose logs the log panel is limited to, and its pids so far.
+    follow: Option<(String, HashSet<u32>)>,
+    /// Package waiting for `y` to confirm its uninstall.
+    confirm_uninstall: Option<String>,
+    status: Option<(String, bool)>,
+}
+
+impl Dashboard {
+    fn new(device: &str, apps: Vec<String>) -> Self {
+        let mut dashboard = Dashboard {
+            device: device.to_string(),
+            health: None,
+            foreground: None,
+            apps,
+            shown: Vec::new(),
+            query: String::new(),
+            searching: false,
+            selected: ListState::default(),
+            focus: Focus::Apps,
+            logs: VecDeque::new(),
+            scroll: 0,
+            follow: None,
+            confirm_uninstall: None,
+            status: None,
+        };
+        dashboard.filter();
+        dashboard
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Health(health) => self.health = Some(health),
+            Update::Foreground(foreground) => self.foreground = foreground,
+            Update::Logs(entries) => {
+                if self.scroll > 0 {
+                    self.scroll += entries.len();
+                }
+                self.logs.extend(entries);
+                while self.logs.len() > LOG_BACKLOG {
+                    self.logs.pop_front();
+                }
+            }
+            Update::Pid(package, pid) => {
+                if let Some((followed, pids)) = &mut self.follow {
+                    if *followed == package {
+                        pids.insert(pid);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_apps(&mut self, apps: Vec<String>) {
+        self.apps = apps;
+        self.filter();
+    }
+
+    /// Re-rank the app list after the query or the apps changed.
+    fn filter(&mut self) {
+        self.shown = fuzzy::rank(&self.query, &self.apps)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let selected = self.selected.selected().unwrap_or(0);
+        self.selected
+            .select((!self.shown.is_empty()).then(|| selected.min(self.shown.len() - 1)));
+    }
+
+    fn selected_app(&self) -> Option<String> {
+        self.selected
+            .selected()
+            .and_then(|i| self.shown.get(i).cloned())
+    }
+
+    fn report(&mut self, message: impl Into<String>, error: bool) {
+        self.status = Some((message.into(), error));
+    }
+
+    /// Log entries the panel shows: all of them, or the followed app's.
+    fn visible_logs(&self) -> Vec<&LogEntry> {
+        self.logs
+            .iter()
+            .filter(|e| {
+                self.follow
+                    .as_ref()
+                    .is_none_or(|(_, pids)| pids.contains(&e.pid))
+            })
+            .collect()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Some(Action::Quit);
+        }
+        if let Some(package) = self.confirm_uninstall.take() {
+            if key.code == KeyCode::Char('y') {
+                return Some(Action::Uninstall(package));
+            }
+            self.report("Uninstall cancelled", false);
+            return None;
+        }
+        if self.searching {
+            match key.code {
+                KeyCode::Char(c) => self.query.push(c),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                }
+                KeyCode::Esc => {
+                    self.query.clear();
+                    self.searching = false;
+                }
+                KeyCode::Enter | KeyCode::Down | KeyCode::Up => self.searching = false,
+                _ => {}
+            }
+            self.selected.select(Some(0));
+            self.filter();
+            return None;
+        }
+        self.status = None;
+        match (key.code, self.focus) {
+            (KeyCode::Char('q'), _) => return Some(Action::Quit),
+            (KeyCode::Tab, Focus::Apps) => self.focus = Focus::Logs,
+            (KeyCode::Tab, Focus::Logs) => self.focus = Focus::Apps,
+            (KeyCode::Char('/'), _) => {
+                self.focus = Focus::Apps;
+                self.searching = true;
+            }
+            (KeyCode::Char('r'), _) => return Some(Action::RefreshApps),
+            (KeyCode::Up, Focus::Apps) => self.selected.select_previous(),
+            (KeyCode::Down, Focus::Apps) => self.selected.select_next(),
+            (KeyCode::PageUp, Focus::Apps) => self.selected.scroll_up_by(10),
+            (KeyCode::PageDown, Focus::Apps) => self.selected.scroll_down_by(10),
+            (KeyCode::Up, Focus::Logs) => self.scroll += 1,
+            (KeyCode::Down, Focus::Logs) => self.scroll = self.scroll.saturating_sub(1),
+            (KeyCode::PageUp, Focus::Logs) => self.scroll += 10,
+            (KeyCode::PageDown, Focus::Logs) => self.scroll = self.scroll.saturating_sub(10),
+            (KeyCode::End, Focus::Logs) => self.scroll = 0,
+            (KeyCode::Char('f'), _) => {
+                self.follow = match (self.follow.take(), self.selected_app()) {
+                    (None, Some(package)) => Some((package, HashSet::new())),
+                    _ => None,
+                };
+                self.scroll = 0;
+            }
+            (code, Focus::Apps) => {
+                let package = self.selected_app()?;
+                return match code {
+                    KeyCode::Enter | KeyCode::Char('o') => Some(Action::Open(package)),
+                    KeyCode::Char('c') => Some(Action::Clear(package)),
+                    KeyCode::Char('k') => Some(Action::Kill(package)),
+                    KeyCode::Char('i') => Some(Action::Info(package)),
+                    KeyCode::Char('u') => {
+                        self.report(format!("Uninstall {}? (y/n)", package), true);
+                        self.confirm_uninstall = Some(package);
+                        None
+                    }
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+fn ui(
+    adb_client: &AdbClient<impl AdbBackend>,
+    terminal: &mut DefaultTerminal,
+    dashboard: &mut Dashboard,
+    followed: &Mutex<Option<String>>,
+    updates: &Receiver<Update>,
+) -> Result<()> {
+    loop {
+        for update in updates.try_iter() {
+            dashboard.apply(update);
+        }
+        terminal.draw(|frame| draw(frame, dashboard))?;
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let action = dashboard.handle_key(key);
+        *followed.lock().unwrap() = dashboard.follow.as_ref().map(|(p, _)| p.clone());
+        let Some(action) = action else {
+            continue;
+        };
+        if action == Action::Quit {
+            return Ok(());
+        }
+        let device = dashboard.device.clone();
+        match perform(adb_client, &device, action, dashboard) {
+            Ok(message) => dashboard.report(message, false),
+            Err(e) => dashboard.report(e.to_string(), true),
+        }
+    }
+}
+
+/// Run an app action, returning the message for the status bar.
+fn perform(
+    adb_client: &AdbClient<impl AdbBackend>,
+    device: &str,
+    action: Action,
+    dashboard: &mut Dashboard,
+) -> Result<String> {
+    Ok(match action {
+        Action::Open(package) => {
+            adb_client.open_app(device, &package)?;
+            format!("Opened {}", package)
+        }
+        Action::Clear(package) => {
+            adb_client.clear_app_data(device, &package)?;
+            format!("Cleared data of {}", package)
+        }
+        Action::Kill(package) => {
+            adb_client.force_kill_app(device, &package)?;
+            format!("Force killed {}", package)
+        }
+        Action::Uninstall(package) => {
+            adb_client.uninstall_app(device, &package)?;
+            dashboard.set_apps(adb_client.get_installed_apps(device)?.apps);
+            format!("Uninstalled {}", package)
+        }
+        Action::Info(package) => {
+            let info = adb_client.get_app_info(device, &package, false)?;
+            format!(
+                "{} {} (version code {})",
+                package, info.version_name, info.version_code
+            )
+        }
+        Action::RefreshApps => {
+            dashboard.set_apps(adb_client.get_installed_apps(device)?.apps);
+            format!("{} apps installed", dashboard.apps.len())
+        }
+        Action::Quit => unreachable!("the UI loop handles quitting"),
+    })
+}
+
+fn draw(frame: &mut Frame, dashboard: &mut Dashboard) {
+    let [top, main, footer] = Layout::vertical([
+        Constraint::Length(5),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [health, activity] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+    let [apps, logs] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)]).areas(main);
+    draw_health(frame, health, dashboard);
+    draw_activity(frame, activity, dashboard);
+    draw_apps(frame, apps, dashboard);
+    draw_logs(frame, logs, dashboard);
+    draw_footer(frame, footer, dashboard);
+}
+
+fn panel(title: &str, focused: bool) -> Block<'_> {
+    let block = Block::bordered().title(format!(" {} ", title));
+    if focused {
+        block.border_style(Style::new().yellow())
+    } else {
+        block
+    }
+}
+
+fn draw_health(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let block = panel("Health", false);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+    let health = match &dashboard.health {
+        None => return frame.render_widget(Paragraph::new("Checking...").dim(), inner),
+        Some(Err(e)) => return frame.render_widget(Paragraph::new(e.as_str()).red(), inner),
+        Some(Ok(health)) => health,
+    };
+    let battery = health
+        .battery
+        .level
+        .as_deref()
+        .and_then(|l| l.parse::<f64>().ok());
+    let storage = health.storage.as_ref();
+    let ram_used = health.ram.total_gb - health.ram.free_gb;
+    let gauges = [
+        gauge(
+            format!(
+                "Battery {}  {}",
+                battery.map_or("n/a".to_string(), |l| format!("{:.0}%", l)),
+                health.battery.status.as_deref().unwrap_or_default()
+            ),
+            battery.map(|l| l / 100.0),
+            // Battery is only in trouble when it runs low.
+            |charge| charge < 0.15,
+            |charge| charge < 0.3,
+        ),
+        gauge(
+            match storage {
+                Some(s) => format!("Storage {:.1}/{:.1} GB", s.used_gb, s.total_gb),
+                None => "Storage n/a".to_string(),
+            },
+            storage.map(|s| s.percent_used / 100.0),
+            |used| used > 0.9,
+            |used| used > 0.75,
+        ),
+        gauge(
+            format!("RAM {:.1}/{:.1} GB", ram_used, health.ram.total_gb),
+            (health.ram.total_gb > 0.0).then(|| ram_used / health.ram.total_gb),
+            |used| used > 0.9,
+            |used| used > 0.75,
+        ),
+    ];
+    let lines = Layout::vertical([Constraint::Length(1); 3]).split(inner);
+    for (gauge, line) in gauges.into_iter().zip(lines.iter()) {
+        frame.render_widget(gauge, *line);
+    }
+}
+
+/// A one-line gauge, red when `critical` and yellow when `warning`.
+fn gauge(
+    label: String,
+    ratio: Option<f64>,
+    critical: impl Fn(f64) -> bool,
+    warning: impl Fn(f64) -> bool,
+) -> LineGauge<'static> {
+    let ratio = ratio.unwrap_or(0.0).clamp(0.0, 1.0);
+    let color = if critical(ratio) {
+        Color::Red
+    } else if warning(ratio) {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+    LineGauge::default()
+        .ratio(ratio)
+        .label(format!("{:<26}", label))
+        .filled_style(Style::new().fg(color))
+        .unfilled_style(Style::new().dark_gray())
+}
+
+fn draw_activity(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let label = |name: &str| Span::styled(format!("{:<12}", name), Style::new().cyan());
+    let network = dashboard
+        .health
+        .as_ref()
+        .and_then(|h| h.as_ref().ok())
+        .map(|h| {
+            format!(
+                "{} {}",
+                h.network.ip.as_deref().unwrap_or("n/a"),
+                h.network
+                    .ssid
+                    .as_deref()
+                    .map(|s| format!("({})", s))
+                    .unwrap_or_default()
+            )
+        });
+    let lines = vec![
+        Line::from(vec![
+            label("Device"),
+            Span::raw(dashboard.device.as_str()).bold(),
+        ]),
+        Line::from(vec![
+            label("Foreground"),
+            match &dashboard.foreground {
+                Some(activity) => Span::raw(activity.as_str()).green(),
+                None => Span::raw("nothing resumed").dim(),
+            },
+        ]),
+        Line::from(vec![
+            label("Network"),
+            Span::raw(network.unwrap_or_default()),
+        ]),
+    ];
+    frame.render_widget(Paragraph::new(lines).block(panel("Device", false)), area);
+}
+
+fn draw_apps(frame: &mut Frame, area: Rect, dashboard: &mut Dashboard) {
+    let title = if dashboard.searching || !dashboard.query.is_empty() {
+        format!("Apps /{}", dashboard.query)
+    } else {
+        format!("Apps ({})", dashboard.apps.len())
+    };
+    let items: Vec<ListItem> = dashboard
+        .shown
+        .iter()
+        .map(|app| ListItem::new(app.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(panel(&title, dashboard.focus == Focus::Apps))
+        .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(list, area, &mut dashboard.selected);
+}
+
+fn draw_logs(frame: &mut Frame, area: Rect, dashboard: &mut Dashboard) {
+    let title = match &dashboard.follow {
+        Some((package, _)) => format!("Logcat: {}", package),
+        None => "Logcat".to_string(),
+    };
+    let height = area.height.saturating_sub(2) as usize;
+    let total = dashboard.visible_logs().len();
+    dashboard.scroll = dashboard.scroll.min(total.saturating_sub(height));
+    let end = total - dashboard.scroll;
+    let lines: Vec<Line> = dashboard.visible_logs()[end.saturating_sub(height)..end]
+        .iter()
+        .map(|entry| log_line(entry))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(panel(&title, dashboard.focus == Focus::Logs)),
+        area,
+    );
+}
+
+fn log_line(entry: &LogEntry) -> Line<'_> {
+    let color = match entry.level {
+        LogLevel::Verbose | LogLevel::Debug => Color::DarkGray,
+        LogLevel::Info => Color::Reset,
+        LogLevel::Warn => Color::Yellow,
+        LogLevel::Error | LogLevel::Fatal => Color::Red,
+    };
+    // Drop the date; the time of day is enough in a live tail.
+    let time = entry
+        .time
+        .split_once(' ')
+        .map_or(entry.time.as_str(), |(_, t)| t);
+    Line::from(vec![
+        Span::raw(time).dim(),
+        Span::raw(format!(" {} ", entry.level.letter())).fg(color),
+        Span::raw(entry.tag.as_str()).cyan(),
+        Span::raw(": "),
+        Span::raw(entry.message.as_str()).fg(color),
+    ])
+}
+
+fn draw_footer(frame: &mut Frame, area: Rect, dashboard: &Dashboard) {
+    let line = match &dashboard.status {
+        Some((message, true)) => Line::from(message.as_str()).red(),
+        Some((message, false)) => Line::from(message.as_str()).green(),
+        None if dashboard.searching => {
+            Line::from("Type to filter · Enter keep · Esc clear").dim()
+        }
+        None => Line::from(
+            "q quit · Tab switch panel · / search · o open · c clear · k kill · u uninstall · i info · f follow logs · r refresh",
+        )
+        .dim(),
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: &str) -> LogEntry {
+        LogEntry::parse(line).unwrap()
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn log_cursor_drops_entries_the_next_dump_repeats() {
+        let mut cursor = LogCursor::default();
+        let first = cursor.fresh(vec![
+            entry("10-16 12:00:00.000  1000  1001 I A: one"),
+            entry("10-16 12:00:01.000  1000  1001 I A: two"),
+        ]);
+        assert_eq!(first.len(), 2);
+        assert_eq!(cursor.since(), Some("10-16 12:00:01.000"));
+
+        let next = cursor.fresh(vec![
+            entry("10-16 12:00:01.000  1000  1001 I A: two"),
+            entry("10-16 12:00:01.000  1000  1001 I A: two and a half"),
+            entry("10-16 12:00:02.000  1000  1001 I A: three"),
+        ]);
+        let messages: Vec<&str> = next.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["two and a half", "three"]);
+        assert!(cursor.fresh(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn keys_search_apps_and_pick_actions() {
+        let mut dashboard = Dashboard::new(
+            "emu",
+            vec![
+                "com.android.chrome".into(),
+                "com.google.android.youtube".into(),
+            ],
+        );
+        assert_eq!(
+            dashboard.selected_app().as_deref(),
+            Some("com.android.chrome")
+        );
+
+        dashboard.handle_key(key(KeyCode::Char('/')));
+        for c in "tube".chars() {
+            dashboard.handle_key(key(KeyCode::Char(c)));
+        }
+        dashboard.handle_key(key(KeyCode::Enter));
+        assert_eq!(dashboard.shown, ["com.google.android.youtube"]);
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('o'))),
+            Some(Action::Open("com.google.android.youtube".into()))
+        );
+
+        assert_eq!(dashboard.handle_key(key(KeyCode::Char('u'))), None);
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('y'))),
+            Some(Action::Uninstall("com.google.android.youtube".into()))
+        );
+        assert_eq!(dashboard.handle_key(key(KeyCode::Char('u'))), None);
+        assert_eq!(dashboard.handle_key(key(KeyCode::Char('n'))), None);
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('q'))),
+            Some(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn following_an_app_limits_the_logs_to_its_pids() {
+        let mut dashboard = Dashboard::new("emu", vec!["com.foo".into()]);
+        dashboard.apply(Update::Logs(vec![
+            entry("10-16 12:00:00.000  1000  1001 I Foo: mine"),
+            entry("10-16 12:00:00.100   500   501 I Bar: not mine"),
+        ]));
+        assert_eq!(dashboard.visible_logs().len(), 2);
+
+        dashboard.handle_key(key(KeyCode::Char('f')));
+        dashboard.apply(Update::Pid("com.foo".into(), 1000));
+        let visible = dashboard.visible_logs();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].message, "mine");
+
+        dashboard.handle_key(key(KeyCode::Char('f')));
+        assert!(dashboard.follow.is_none());
+    }
+
+    #[test]
+    fn draws_every_panel() {
+        let mut dashboard = Dashboard::new("emu", vec!["com.foo".into()]);
+        let mut health = DeviceHealth::from(androkit::model::DeviceHealth::default());
+        health.battery.level = Some("85".into());
+        dashboard.apply(Update::Health(Ok(health)));
+        dashboard.apply(Update::Foreground(Some("com.foo/.MainActivity".into())));
+        dashboard.apply(Update::Logs(vec![entry(
+            "10-16 12:00:00.000  1000  1001 W Foo: careful",
+        )]));
+
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(120, 16)).unwrap();
+        terminal.draw(|frame| draw(frame, &mut dashboard)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        for text in [
+            "Battery 85%",
+            "com.foo/.MainActivity",
+            "> com.foo",
+            "W Foo: careful",
+        ] {
+            assert!(screen.contains(text), "missing {:?}", text);
+        }
+    }
+}
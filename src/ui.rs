@@ -0,0 +1,437 @@
+//! The on-screen view hierarchy, parsed from `uiautomator dump`, and the
+//! queries `dab ui find` / `dab ui tap` match against it.
+//!
+//! A query matches nodes by what they mean rather than where they are: their
+//! text, resource id or content description. Text and descriptions match as
+//! case-insensitive substrings unless the query is exact.
+
+use serde::{Deserialize, Serialize};
+
+/// A node's on-screen rectangle, in pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Bounds {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Bounds {
+    /// Parse uiautomator's `[left,top][right,bottom]`.
+    pub fn parse(s: &str) -> Option<Bounds> {
+        let mut numbers = s
+            .split(['[', ']', ','])
+            .filter(|n| !n.is_empty())
+            .map(|n| n.trim().parse::<i32>());
+        let mut next = || numbers.next()?.ok();
+        Some(Bounds {
+            left: next()?,
+            top: next()?,
+            right: next()?,
+            bottom: next()?,
+        })
+    }
+
+    /// Zero-sized bounds belong to nodes that are off screen or collapsed.
+    pub fn is_empty(&self) -> bool {
+        self.right <= self.left || self.bottom <= self.top
+    }
+
+    /// The point `input tap` should hit.
+    pub fn center(&self) -> Point {
+        Point {
+            x: ((self.left + self.right) / 2).max(0) as u32,
+            y: ((self.top + self.bottom) / 2).max(0) as u32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// One view. Attributes the dump leaves empty are skipped in JSON, as are
+/// the `false` flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Node {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub text: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub resource_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub content_desc: String,
+    pub class: String,
+    pub package: String,
+    pub bounds: Bounds,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub clickable: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub long_clickable: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub scrollable: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub checkable: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub checked: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub focused: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub selected: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub password: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<Node>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl Node {
+    fn from_attributes(attributes: &[(String, String)]) -> Node {
+        let mut node = Node::default();
+        for (name, value) in attributes {
+            let flag = value == "true";
+            match name.as_str() {
+                "text" => node.text = value.clone(),
+                "resource-id" => node.resource_id = value.clone(),
+                "content-desc" => node.content_desc = value.clone(),
+                "class" => node.class = value.clone(),
+                "package" => node.package = value.clone(),
+                "bounds" => node.bounds = Bounds::parse(value).unwrap_or_default(),
+                "enabled" => node.enabled = flag,
+                "clickable" => node.clickable = flag,
+                "long-clickable" => node.long_clickable = flag,
+                "scrollable" => node.scrollable = flag,
+                "checkable" => node.checkable = flag,
+                "checked" => node.checked = flag,
+                "focused" => node.focused = flag,
+                "selected" => node.selected = flag,
+                "password" => node.password = flag,
+                _ => {}
+            }
+        }
+        node
+    }
+
+    /// The class name without its package, e.g. `Button`.
+    pub fn short_class(&self) -> &str {
+        self.class.rsplit('.').next().unwrap_or(&self.class)
+    }
+
+    /// The node and its descendants, depth first in document order.
+    pub fn walk(&self) -> Vec<&Node> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.walk());
+        }
+        nodes
+    }
+}
+
+/// Everything `uiautomator dump` saw: one root node per window.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Hierarchy {
+    pub rotation: u32,
+    pub nodes: Vec<Node>,
+}
+
+impl Hierarchy {
+    /// The nodes `query` matches, in document order (roughly top to bottom).
+    pub fn find(&self, query: &Query) -> Vec<Match> {
+        self.nodes
+            .iter()
+            .flat_map(Node::walk)
+            .filter(|node| query.matches(node))
+            .map(|node| Match {
+                node: Node {
+                    children: Vec::new(),
+                    ..node.clone()
+                },
+                center: node.bounds.center(),
+            })
+            .collect()
+    }
+}
+
+/// A matching node, without its children, and where to tap it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Match {
+    #[serde(flatten)]
+    pub node: Node,
+    pub center: Point,
+}
+
+/// What to look for. Every criterion given must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Query {
+    pub text: Option<String>,
+    /// A full resource id (`com.app:id/login`) or just its name (`login`).
+    pub id: Option<String>,
+    pub desc: Option<String>,
+    /// Match text and descriptions whole and case-sensitively.
+    pub exact: bool,
+}
+
+impl Query {
+    pub fn matches(&self, node: &Node) -> bool {
+        let text = |wanted: &Option<String>, actual: &str| match wanted {
+            None => true,
+            Some(wanted) if self.exact => actual == wanted,
+            Some(wanted) => actual.to_lowercase().contains(&wanted.to_lowercase()),
+        };
+        let id = match &self.id {
+            None => true,
+            Some(id) => {
+                node.resource_id == *id
+                    || node
+                        .resource_id
+                        .split_once(":id/")
+                        .is_some_and(|(_, name)| name == id)
+            }
+        };
+        (self.text.is_some() || self.id.is_some() || self.desc.is_some())
+            && id
+            && text(&self.text, &node.text)
+            && text(&self.desc, &node.content_desc)
+    }
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(text) = &self.text {
+            parts.push(format!("text {:?}", text));
+        }
+        if let Some(id) = &self.id {
+            parts.push(format!("id {:?}", id));
+        }
+        if let Some(desc) = &self.desc {
+            parts.push(format!("description {:?}", desc));
+        }
+        write!(f, "{}", parts.join(" and "))
+    }
+}
+
+/// Parse a `uiautomator dump` document. Anything other than `<hierarchy>`
+/// and `<node>` elements is ignored.
+pub fn parse_hierarchy(xml: &str) -> Result<Hierarchy, String> {
+    let mut hierarchy = Hierarchy::default();
+    let mut open: Vec<Node> = Vec::new();
+    let mut seen_root = false;
+    let mut rest = xml;
+
+    fn attach(node: Node, open: &mut [Node], roots: &mut Vec<Node>) {
+        match open.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+
+    while let Some(start) = rest.find('<') {
+        let tag_len = tag_end(&rest[start + 1..]).ok_or("unterminated tag")?;
+        let tag = &rest[start + 1..start + 1 + tag_len];
+        rest = &rest[start + tag_len + 2..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            if name.trim() == "node" {
+                let node = open.pop().ok_or("unbalanced </node>")?;
+                attach(node, &mut open, &mut hierarchy.nodes);
+            }
+            continue;
+        }
+        let self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/').trim();
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let attributes = parse_attributes(attributes);
+        match name {
+            "hierarchy" => {
+                seen_root = true;
+                hierarchy.rotation = attributes
+                    .iter()
+                    .find(|(name, _)| name == "rotation")
+                    .and_then(|(_, value)| value.parse().ok())
+                    .unwrap_or(0);
+            }
+            "node" => {
+                let node = Node::from_attributes(&attributes);
+                if self_closing {
+                    attach(node, &mut open, &mut hierarchy.nodes);
+                } else {
+                    open.push(node);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !seen_root {
+        return Err("no <hierarchy> element".into());
+    }
+    if !open.is_empty() {
+        return Err("unclosed <node>".into());
+    }
+    Ok(hierarchy)
+}
+
+/// The length of a tag's contents up to its `>`, skipping quoted values.
+fn tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '>') => return Some(i),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_attributes(s: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = s.trim_start();
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim().to_string();
+        let value = rest[eq + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            break;
+        };
+        let Some(close) = value[1..].find(quote) else {
+            break;
+        };
+        attributes.push((name, unescape(&value[1..close + 1])));
+        rest = value[close + 2..].trim_start();
+    }
+    attributes
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').and_then(|semi| {
+            let entity = &rest[1..semi];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = match entity.strip_prefix("#x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => entity.strip_prefix('#')?.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, semi + 1))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes' ?><hierarchy rotation="0"><node index="0" text="" resource-id="" class="android.widget.FrameLayout" package="com.example" content-desc="" checkable="false" checked="false" clickable="false" enabled="true" focusable="false" focused="false" scrollable="false" long-clickable="false" password="false" selected="false" bounds="[0,0][1080,2400]"><node index="0" text="Welcome &amp; hello &lt;you&gt;" resource-id="com.example:id/title" class="android.widget.TextView" package="com.example" content-desc="" checkable="false" checked="false" clickable="false" enabled="true" focusable="false" focused="false" scrollable="false" long-clickable="false" password="false" selected="false" bounds="[48,200][1032,280]" /><node index="1" text="Sign in" resource-id="com.example:id/login" class="android.widget.Button" package="com.example" content-desc="" checkable="false" checked="false" clickable="true" enabled="true" focusable="true" focused="false" scrollable="false" long-clickable="false" password="false" selected="false" bounds="[48,1800][1032,1944]" /><node index="2" text="" resource-id="" class="android.widget.ImageButton" package="com.example" content-desc="Sign in with Google" checkable="false" checked="false" clickable="true" enabled="true" focusable="true" focused="false" scrollable="false" long-clickable="false" password="false" selected="false" bounds="[0,0][0,0]" /></node></hierarchy>"#;
+
+    #[test]
+    fn parses_a_uiautomator_dump() {
+        let hierarchy = parse_hierarchy(DUMP).unwrap();
+        assert_eq!(hierarchy.nodes.len(), 1);
+        let root = &hierarchy.nodes[0];
+        assert_eq!(root.short_class(), "FrameLayout");
+        assert_eq!(root.children.len(), 3);
+        assert_eq!(root.children[0].text, "Welcome & hello <you>");
+        let login = &root.children[1];
+        assert!(login.clickable && login.enabled && !login.checked);
+        assert_eq!(
+            login.bounds,
+            Bounds {
+                left: 48,
+                top: 1800,
+                right: 1032,
+                bottom: 1944
+            }
+        );
+        assert_eq!(login.bounds.center(), Point { x: 540, y: 1872 });
+        assert!(root.children[2].bounds.is_empty());
+        assert_eq!(root.walk().len(), 4);
+
+        assert!(parse_hierarchy("ERROR: null root node returned").is_err());
+        assert!(parse_hierarchy(r#"<hierarchy><node text="a">"#).is_err());
+    }
+
+    #[test]
+    fn queries_match_text_ids_and_descriptions() {
+        let hierarchy = parse_hierarchy(DUMP).unwrap();
+        let find = |query: Query| -> Vec<String> {
+            hierarchy
+                .find(&query)
+                .into_iter()
+                .map(|m| m.node.short_class().to_string())
+                .collect()
+        };
+        let text = |t: &str| Query {
+            text: Some(t.into()),
+            ..Default::default()
+        };
+        assert_eq!(find(text("sign IN")), ["Button"]);
+        assert!(find(Query {
+            exact: true,
+            ..text("sign in")
+        })
+        .is_empty());
+        assert_eq!(
+            find(Query {
+                desc: Some("google".into()),
+                ..Default::default()
+            }),
+            ["ImageButton"]
+        );
+        for id in ["login", "com.example:id/login"] {
+            assert_eq!(
+                find(Query {
+                    id: Some(id.into()),
+                    ..Default::default()
+                }),
+                ["Button"]
+            );
+        }
+        assert!(find(Query {
+            id: Some("log".into()),
+            ..Default::default()
+        })
+        .is_empty());
+        assert!(find(Query::default()).is_empty());
+        assert_eq!(
+            Query {
+                id: Some("login".into()),
+                ..text("Sign in")
+            }
+            .to_string(),
+            r#"text "Sign in" and id "login""#
+        );
+    }
+}